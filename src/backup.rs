@@ -0,0 +1,156 @@
+use std::{
+    io::Read,
+    path::{Component, Path},
+};
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    credential::{default_credentials_location, Credential},
+    error::NomosError,
+    job::{default_jobs_location, Job},
+    script::{default_scripts_location, models::Script},
+    settings::SyncSummary,
+    utils::validate_id,
+};
+
+const CREDENTIALS: &str = "credentials";
+const SCRIPTS: &str = "scripts";
+const JOBS: &str = "jobs";
+
+/// Result of an import. Entities failing validation are skipped and reported in `errors`.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ImportSummary {
+    #[serde(flatten)]
+    pub changes: SyncSummary,
+    pub errors: Vec<String>,
+}
+
+/// Archives the `jobs` and `scripts` directories as tar.gz. Credentials are stored in plain text, so they are only
+/// included if `include_credentials` is set.
+pub fn export(include_credentials: bool) -> Result<Vec<u8>, String> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    builder
+        .append_dir_all(JOBS, default_jobs_location()?)
+        .map_err(|e| e.to_string())?;
+    builder
+        .append_dir_all(SCRIPTS, default_scripts_location()?)
+        .map_err(|e| e.to_string())?;
+    if include_credentials {
+        // Signatures are specific to the key of this server, imported credentials are signed again when saved.
+        for entry in std::fs::read_dir(default_credentials_location()?).map_err(|e| e.to_string())? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            let is_credential = path.extension().is_some_and(|extension| extension == "yml");
+            let Some(file_name) = path.file_name().filter(|_| is_credential) else {
+                continue;
+            };
+            builder
+                .append_path_with_name(&path, Path::new(CREDENTIALS).join(file_name))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    let encoder = builder.into_inner().map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())
+}
+
+/// Restores an archive created by `export`. Credentials, scripts and jobs are synced in that order so jobs can
+/// reference the imported scripts. Nothing is deleted.
+///
+/// The whole archive is read and the ids are checked before syncing, so a malformed archive does not change
+/// anything.
+pub async fn import(archive: &[u8]) -> Result<ImportSummary, String> {
+    let mut credentials = Vec::new();
+    let mut scripts = Vec::new();
+    let mut jobs = Vec::new();
+
+    let mut archive = tar::Archive::new(GzDecoder::new(archive));
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+        let path = entry.path().map_err(|e| e.to_string())?.into_owned();
+        let kind = archive_kind(&path).ok_or_else(|| format!("Unexpected entry in archive: {}", path.display()))?;
+
+        let mut content = String::new();
+        entry.read_to_string(&mut content).map_err(|e| e.to_string())?;
+        let parse_error = |e: serde_yaml::Error| format!("Invalid {}: {}", path.display(), e);
+        // Ids become file names, one escaping the data directory rejects the whole archive
+        let id_error = |e: NomosError| format!("Invalid {}: {}", path.display(), e);
+        match kind {
+            CREDENTIALS => {
+                let credential = serde_yaml::from_str::<Credential>(&content).map_err(parse_error)?;
+                validate_id(&credential.id).map_err(id_error)?;
+                credentials.push(credential);
+            }
+            SCRIPTS => {
+                let script = serde_yaml::from_str::<Script>(&content).map_err(parse_error)?;
+                validate_id(&script.id).map_err(id_error)?;
+                scripts.push((script, content));
+            }
+            _ => {
+                let job = serde_yaml::from_str::<Job>(&content).map_err(parse_error)?;
+                validate_id(&job.id).map_err(id_error)?;
+                jobs.push(job);
+            }
+        }
+    }
+
+    let mut summary = ImportSummary::default();
+    for credential in credentials {
+        match credential.sync(&mut None) {
+            Ok(action) => summary.changes.credentials.add(&credential.id, action),
+            Err(e) => summary.errors.push(format!("Credential {:?}: {}", credential.id, e)),
+        }
+    }
+    for (script, source) in scripts {
+        match script.sync_source(&source, None) {
+            Ok(action) => summary.changes.scripts.add(&script.id, action),
+            Err(e) => summary.errors.push(format!("Script {:?}: {}", script.id, e)),
+        }
+    }
+    for job in jobs {
+        match job.sync(None).await {
+            Ok(action) => summary.changes.jobs.add(&job.id, action),
+            Err(e) => summary.errors.push(format!("Job {:?}: {}", job.id, e)),
+        }
+    }
+    Ok(summary)
+}
+
+/// Directory of an archive entry, if it is a `<directory>/<id>.yml` file of a known directory. Anything else,
+/// including paths escaping the archive, is rejected.
+fn archive_kind(path: &Path) -> Option<&'static str> {
+    let mut components = path.components();
+    let (Some(Component::Normal(directory)), Some(Component::Normal(file)), None) =
+        (components.next(), components.next(), components.next())
+    else {
+        return None;
+    };
+    if !file.to_str().is_some_and(|file| file.ends_with(".yml")) {
+        return None;
+    }
+    [CREDENTIALS, SCRIPTS, JOBS]
+        .into_iter()
+        .find(|kind| directory.to_str() == Some(kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_kind() {
+        assert_eq!(archive_kind(Path::new("jobs/build.yml")), Some(JOBS));
+        assert_eq!(archive_kind(Path::new("scripts/build.yml")), Some(SCRIPTS));
+        assert_eq!(archive_kind(Path::new("credentials/key.yml")), Some(CREDENTIALS));
+        assert_eq!(archive_kind(Path::new("jobs/build.txt")), None);
+        assert_eq!(archive_kind(Path::new("jobs/../../etc/passwd.yml")), None);
+        assert_eq!(archive_kind(Path::new("../jobs/build.yml")), None);
+        assert_eq!(archive_kind(Path::new("/jobs/build.yml")), None);
+        assert_eq!(archive_kind(Path::new("jobs/nested/build.yml")), None);
+        assert_eq!(archive_kind(Path::new("job_results/1.yml")), None);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+/// Parsed files keyed by path. An entry is reused while the modification time and size of the file are unchanged,
+/// so edits to the file take effect on the next lookup.
+pub struct FileCache<T> {
+    entries: Mutex<HashMap<PathBuf, CachedFile<T>>>,
+}
+
+struct CachedFile<T> {
+    modified: SystemTime,
+    len: u64,
+    value: T,
+}
+
+impl<T: Clone> FileCache<T> {
+    pub fn new() -> Self {
+        FileCache {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the cached value of `path`, or parses it with `load` if the file changed since the last lookup.
+    /// Files without a readable modification time are never cached.
+    pub fn get_or_load<E>(&self, path: &Path, load: impl FnOnce(&Path) -> Result<T, E>) -> Result<T, E> {
+        let Some((modified, len)) = std::fs::metadata(path)
+            .ok()
+            .and_then(|metadata| Some((metadata.modified().ok()?, metadata.len())))
+        else {
+            self.lock().remove(path);
+            return load(path);
+        };
+
+        if let Some(cached) = self.lock().get(path) {
+            if cached.modified == modified && cached.len == len {
+                return Ok(cached.value.clone());
+            }
+        }
+
+        let value = load(path)?;
+        self.lock().insert(
+            path.to_path_buf(),
+            CachedFile {
+                modified,
+                len,
+                value: value.clone(),
+            },
+        );
+        Ok(value)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, CachedFile<T>>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: Clone> Default for FileCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn test_file_cache() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("script.yml");
+        std::fs::write(&path, "first").unwrap();
+
+        let cache = FileCache::new();
+        let loads = Cell::new(0);
+        let load = |path: &Path| {
+            loads.set(loads.get() + 1);
+            std::fs::read_to_string(path)
+        };
+
+        assert_eq!(cache.get_or_load(&path, load).unwrap(), "first");
+        assert_eq!(cache.get_or_load(&path, load).unwrap(), "first");
+        assert_eq!(loads.get(), 1);
+
+        std::fs::write(&path, "second version").unwrap();
+        assert_eq!(cache.get_or_load(&path, load).unwrap(), "second version");
+        assert_eq!(loads.get(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(cache.get_or_load(&path, load).is_err());
+        assert_eq!(loads.get(), 3);
+    }
+}
@@ -1,57 +1,190 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::script::ScriptExecutionContext;
 
-use crate::{log::LogLevel, utils::execute_command};
+use crate::{
+    log::LogLevel,
+    utils::{execute_command, execute_program, execute_program_with_stdin, is_program_in_path, shell_quote, tmp_dir},
+};
 
-/// docker run -d {..args}
-pub async fn docker_run(image: &str, args: Vec<&str>, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+pub const DOCKER_NOT_FOUND: &str = "Docker is not installed or not in PATH";
+
+/// Fails with `DOCKER_NOT_FOUND` instead of a shell error if the `docker` command can't be found in the `PATH` of the
+/// step. Checked once per run, dry runs don't need docker.
+pub fn ensure_docker(context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+    if context.job_result.dry_run {
+        return Ok(());
+    }
+    let available = *context.job_result.docker_available.get_or_insert_with(|| {
+        let step_path = context.job_result.step_env.iter().rev().find(|(key, _)| key == "PATH");
+        match step_path {
+            Some((_, path)) => is_program_in_path("docker", Some(path.as_ref())),
+            None => is_program_in_path("docker", std::env::var_os("PATH").as_deref()),
+        }
+    });
+    if available {
+        Ok(())
+    } else {
+        Err(DOCKER_NOT_FOUND.to_string())
+    }
+}
+
+/// docker run -d {..args} {image}
+pub fn docker_run_command(image: &str, args: &[&str]) -> String {
     let mut command = vec!["docker", "run", "-d"];
     command.extend(args);
     command.push(image);
+    command.join(" ")
+}
+
+/// --restart {restart} {..-p port} {..-v volume}
+///
+/// The flags are part of the `sh` command of `docker_run`. The restart policy must be one of the policies of docker,
+/// ports and volumes are quoted with `shell_quote`.
+pub fn docker_run_flags(restart: Option<&str>, ports: &[String], volumes: &[String]) -> Result<Vec<String>, String> {
+    let mut flags = Vec::new();
+    if let Some(restart) = restart {
+        let valid = match restart.strip_prefix("on-failure") {
+            Some(retries) => retries.is_empty() || retries.strip_prefix(':').is_some_and(|n| n.parse::<u32>().is_ok()),
+            None => ["no", "always", "unless-stopped"].contains(&restart),
+        };
+        if !valid {
+            return Err(format!("Invalid restart policy: {:?}", restart));
+        }
+        flags.push("--restart".to_string());
+        flags.push(restart.to_string());
+    }
+    for (flag, values) in [("-p", ports), ("-v", volumes)] {
+        for value in values {
+            flags.push(flag.to_string());
+            flags.push(shell_quote(value));
+        }
+    }
+    Ok(flags)
+}
 
+/// docker run -d {..args} {image}
+pub async fn docker_run(image: &str, args: Vec<&str>, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+    ensure_docker(context)?;
     context
         .job_result
         .add_log(LogLevel::Info, format!("command: docker run -d <args> {}", image));
     if !context.job_result.dry_run {
-        execute_command(&command.join(" "), context).await?;
+        execute_command(&docker_run_command(image, &args), context).await?;
     }
     Ok(())
 }
 
-/// docker build -t {image} -f {dockerfile}
-pub async fn docker_build(
+/// A BuildKit secret mounted from a file, e.g. with `RUN --mount=type=secret,id={id}`.
+pub struct DockerBuildSecret {
+    pub id: String,
+    pub src: PathBuf,
+    /// Only used to mask the secret in logs.
+    pub value: String,
+}
+
+/// Stage and platform of a build, the last stage for the platform of the host if unset.
+#[derive(Debug, Default)]
+pub struct DockerBuildTarget {
+    /// Passed as `--target`, e.g. `runtime` of a multi-stage Dockerfile.
+    pub target: Option<String>,
+    /// Passed as `--platform`, e.g. `linux/arm64`.
+    pub platform: Option<String>,
+}
+
+/// docker build {context} -t {image} -f {dockerfile} {--target} {--platform} {..build_args} {..secrets}
+///
+/// The build context defaults to the directory of the Dockerfile.
+pub fn docker_build_args(
     image: &str,
     dockerfile: &Path,
-    context: &mut ScriptExecutionContext<'_>,
-) -> Result<(), String> {
-    let dockerfile_dir = match dockerfile.parent() {
+    build_context: Option<&Path>,
+    build_target: &DockerBuildTarget,
+    build_args: &[(String, String)],
+    secrets: &[DockerBuildSecret],
+) -> Result<Vec<String>, String> {
+    let build_context = match build_context {
         Some(dir) => dir,
-        None => return Err("Dockerfile directory not found".to_string()),
+        None => match dockerfile.parent() {
+            Some(dir) => dir,
+            None => return Err("Dockerfile directory not found".to_string()),
+        },
     };
 
-    let dockerfile_dir_str = match dockerfile_dir.to_str() {
+    let build_context_str = match build_context.to_str() {
         Some(dir_str) => dir_str,
-        None => return Err("Failed to convert Dockerfile directory to string".to_string()),
+        None => return Err("Failed to convert build context directory to string".to_string()),
     };
 
-    let command = format!(
-        "docker build {} -t {} -f {}",
-        dockerfile_dir_str,
-        image,
-        dockerfile.display()
+    let mut args = vec![
+        "docker".to_string(),
+        "build".to_string(),
+        build_context_str.to_string(),
+        "-t".to_string(),
+        image.to_string(),
+        "-f".to_string(),
+        dockerfile.display().to_string(),
+    ];
+    if let Some(target) = &build_target.target {
+        args.extend(["--target".to_string(), target.clone()]);
+    }
+    if let Some(platform) = &build_target.platform {
+        args.extend(["--platform".to_string(), platform.clone()]);
+    }
+    for (key, value) in build_args {
+        args.extend(["--build-arg".to_string(), format!("{}={}", key, value)]);
+    }
+    for secret in secrets {
+        args.extend([
+            "--secret".to_string(),
+            format!("id={},src={}", secret.id, secret.src.display()),
+        ]);
+    }
+    Ok(args)
+}
+
+/// Replaces every occurrence of the secret values with `***`.
+pub fn mask_secrets(text: &str, secrets: &[DockerBuildSecret]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.value.is_empty())
+        .fold(text.to_string(), |text, secret| text.replace(&secret.value, "***"))
+}
+
+/// docker build {context} -t {image} -f {dockerfile} {--target} {--platform} {..build_args} {..secrets}
+pub async fn docker_build(
+    image: &str,
+    dockerfile: &Path,
+    build_context: Option<&Path>,
+    build_target: &DockerBuildTarget,
+    build_args: &[(String, String)],
+    secrets: &[DockerBuildSecret],
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<(), String> {
+    let args = docker_build_args(image, dockerfile, build_context, build_target, build_args, secrets)?;
+    ensure_docker(context)?;
+    context.job_result.add_log(
+        LogLevel::Info,
+        format!("command: {}", mask_secrets(&args.join(" "), secrets)),
     );
-    context
-        .job_result
-        .add_log(LogLevel::Info, format!("command: {}", command));
     if !context.job_result.dry_run {
-        execute_command(&command, context).await?;
+        let env = if secrets.is_empty() && build_target.platform.is_none() {
+            vec![]
+        } else {
+            // Secrets and platforms require BuildKit
+            vec![("DOCKER_BUILDKIT".to_string(), "1".to_string())]
+        };
+        execute_program(args, env, context).await?;
     }
     Ok(())
 }
 
 /// docker stop {container} && docker rm {container}
 pub async fn docker_stop_and_rm(container: &str, context: &mut ScriptExecutionContext<'_>) {
+    if let Err(e) = ensure_docker(context) {
+        context.job_result.add_log(LogLevel::Warning, e);
+        return;
+    }
     context
         .job_result
         .add_log(LogLevel::Info, format!("command: docker stop {}", container));
@@ -65,3 +198,254 @@ pub async fn docker_stop_and_rm(container: &str, context: &mut ScriptExecutionCo
         let _ = execute_command(&format!("docker rm {}", container), context).await;
     }
 }
+
+/// docker exec {..env} {container} sh -c {command}
+pub fn docker_exec_args(container: &str, command: &str, env: &[(String, String)]) -> Vec<String> {
+    let mut args = vec!["docker".to_string(), "exec".to_string()];
+    for (key, value) in env {
+        args.push("--env".to_string());
+        args.push(format!("{}={}", key, value));
+    }
+    args.extend([
+        container.to_string(),
+        "sh".to_string(),
+        "-c".to_string(),
+        command.to_string(),
+    ]);
+    args
+}
+
+/// docker exec {..env} {container} sh -c {command}
+pub async fn docker_exec(
+    container: &str,
+    command: &str,
+    env: Vec<(String, String)>,
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<(), String> {
+    ensure_docker(context)?;
+    context.job_result.add_log(
+        LogLevel::Info,
+        format!("command: docker exec <args> {} {}", container, command),
+    );
+    if !context.job_result.dry_run {
+        execute_program(docker_exec_args(container, command, &env), vec![], context).await?;
+    }
+    Ok(())
+}
+
+/// Registry host of `image`, `None` for Docker Hub. Like docker, the first component of the name is a registry if it
+/// contains `.` or `:` or is `localhost`.
+pub fn image_registry(image: &str) -> Option<&str> {
+    let (first, _) = image.split_once('/')?;
+    (first.contains('.') || first.contains(':') || first == "localhost").then_some(first)
+}
+
+/// docker login {registry} -u {username} --password-stdin
+pub fn docker_login_args(registry: Option<&str>, username: &str) -> Vec<String> {
+    let mut args = vec!["docker".to_string(), "login".to_string()];
+    args.extend(registry.map(str::to_string));
+    args.extend(["-u".to_string(), username.to_string(), "--password-stdin".to_string()]);
+    args
+}
+
+/// Registry login of `docker_push`.
+pub struct DockerLogin {
+    pub username: String,
+    pub password: String,
+}
+
+/// docker login {registry} -u {username} --password-stdin && docker push {image}
+///
+/// The login is stored in a temporary `DOCKER_CONFIG` directory, so it doesn't outlive the push.
+pub async fn docker_push(
+    image: &str,
+    login: Option<DockerLogin>,
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<(), String> {
+    ensure_docker(context)?;
+    let config = tempfile::tempdir_in(tmp_dir()?).map_err(|e| e.to_string())?;
+    let env = vec![("DOCKER_CONFIG".to_string(), config.path().display().to_string())];
+
+    if let Some(login) = login {
+        let args = docker_login_args(image_registry(image), &login.username);
+        context
+            .job_result
+            .add_log(LogLevel::Info, format!("command: {}", args.join(" ")));
+        if !context.job_result.dry_run {
+            execute_program_with_stdin(args, env.clone(), &login.password, context).await?;
+        }
+    }
+
+    context
+        .job_result
+        .add_log(LogLevel::Info, format!("command: docker push {}", image));
+    if !context.job_result.dry_run {
+        let args = vec!["docker".to_string(), "push".to_string(), image.to_string()];
+        execute_program(args, env, context).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_build_args() {
+        let dockerfile = Path::new("/work/repo/docker/app/Dockerfile");
+        assert_eq!(
+            docker_build_args("app", dockerfile, None, &DockerBuildTarget::default(), &[], &[])
+                .unwrap()
+                .join(" "),
+            "docker build /work/repo/docker/app -t app -f /work/repo/docker/app/Dockerfile"
+        );
+        assert_eq!(
+            docker_build_args(
+                "app",
+                dockerfile,
+                Some(Path::new("/work/repo")),
+                &DockerBuildTarget::default(),
+                &[],
+                &[]
+            )
+            .unwrap()
+            .join(" "),
+            "docker build /work/repo -t app -f /work/repo/docker/app/Dockerfile"
+        );
+    }
+
+    #[test]
+    fn test_docker_build_args_with_args_and_secrets() {
+        let dockerfile = Path::new("/work/repo/Dockerfile");
+        let build_args = vec![
+            ("NODE_ENV".to_string(), "production".to_string()),
+            ("MESSAGE".to_string(), "a\"b $(id)".to_string()),
+        ];
+        let secrets = vec![DockerBuildSecret {
+            id: "npm_token".to_string(),
+            src: PathBuf::from("/tmp/npm_token"),
+            value: "s3cr3t".to_string(),
+        }];
+
+        let args = docker_build_args(
+            "app",
+            dockerfile,
+            None,
+            &DockerBuildTarget::default(),
+            &build_args,
+            &secrets,
+        )
+        .unwrap();
+        assert_eq!(
+            args[7..],
+            [
+                "--build-arg",
+                "NODE_ENV=production",
+                "--build-arg",
+                "MESSAGE=a\"b $(id)",
+                "--secret",
+                "id=npm_token,src=/tmp/npm_token"
+            ]
+        );
+        assert!(!args.iter().any(|arg| arg.contains("s3cr3t")));
+    }
+
+    #[test]
+    fn test_docker_build_args_with_target() {
+        let build_target = DockerBuildTarget {
+            target: Some("runtime".to_string()),
+            platform: Some("linux/amd64,linux/arm64".to_string()),
+        };
+        let build_args = vec![("VERSION".to_string(), "1.0".to_string())];
+        assert_eq!(
+            docker_build_args(
+                "app",
+                Path::new("/work/repo/Dockerfile"),
+                None,
+                &build_target,
+                &build_args,
+                &[]
+            )
+            .unwrap()
+            .join(" "),
+            "docker build /work/repo -t app -f /work/repo/Dockerfile --target runtime \
+             --platform linux/amd64,linux/arm64 --build-arg VERSION=1.0"
+        );
+    }
+
+    #[test]
+    fn test_mask_secrets() {
+        let secrets = vec![DockerBuildSecret {
+            id: "npm_token".to_string(),
+            src: PathBuf::from("/tmp/npm_token"),
+            value: "s3cr3t".to_string(),
+        }];
+        assert_eq!(
+            mask_secrets("docker build . --build-arg TOKEN=s3cr3t", &secrets),
+            "docker build . --build-arg TOKEN=***"
+        );
+    }
+
+    #[test]
+    fn test_docker_login_args() {
+        assert_eq!(image_registry("nomos:1.0"), None);
+        assert_eq!(image_registry("requizm/nomos:1.0"), None);
+        assert_eq!(image_registry("ghcr.io/requizm/nomos:1.0"), Some("ghcr.io"));
+        assert_eq!(image_registry("localhost:5000/nomos"), Some("localhost:5000"));
+
+        assert_eq!(
+            docker_login_args(Some("ghcr.io"), "requizm").join(" "),
+            "docker login ghcr.io -u requizm --password-stdin"
+        );
+        assert_eq!(
+            docker_login_args(None, "requizm").join(" "),
+            "docker login -u requizm --password-stdin"
+        );
+    }
+
+    #[test]
+    fn test_docker_run_flags() {
+        let ports = vec!["8080:80".to_string(), "127.0.0.1:9090:9090/udp".to_string()];
+        let volumes = vec!["/srv/app data:/data".to_string(), "/srv/it's:/data;$(id)".to_string()];
+        let flags = docker_run_flags(Some("unless-stopped"), &ports, &volumes).unwrap();
+        let args = flags.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(
+            docker_run_command("nginx", &args),
+            r#"docker run -d --restart unless-stopped -p '8080:80' -p '127.0.0.1:9090:9090/udp' -v '/srv/app data:/data' -v '/srv/it'\''s:/data;$(id)' nginx"#
+        );
+
+        assert!(docker_run_flags(Some("on-failure"), &[], &[]).is_ok());
+        assert!(docker_run_flags(Some("on-failure:3"), &[], &[]).is_ok());
+        assert!(docker_run_flags(Some("on-failure:x"), &[], &[]).is_err());
+        assert!(docker_run_flags(Some("always; rm -rf /"), &[], &[]).is_err());
+        assert!(docker_run_flags(None, &[], &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_docker_exec_args() {
+        assert_eq!(
+            docker_exec_args("app", "php artisan migrate", &[]),
+            ["docker", "exec", "app", "sh", "-c", "php artisan migrate"]
+        );
+
+        let env = vec![
+            ("APP_ENV".to_string(), "production".to_string()),
+            ("QUOTE".to_string(), "a\"b $(id)".to_string()),
+        ];
+        assert_eq!(
+            docker_exec_args("app", "echo 'done'", &env),
+            [
+                "docker",
+                "exec",
+                "--env",
+                "APP_ENV=production",
+                "--env",
+                "QUOTE=a\"b $(id)",
+                "app",
+                "sh",
+                "-c",
+                "echo 'done'"
+            ]
+        );
+    }
+}
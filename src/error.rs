@@ -0,0 +1,42 @@
+use thiserror::Error;
+
+/// Error of the core modules. Unlike a plain `String` it keeps the category, so the API can tell a missing entity
+/// from a failure.
+#[derive(Debug, Error)]
+pub enum NomosError {
+    /// E.g. `Job "deploy"`.
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("{0}")]
+    Validation(String),
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("{0}")]
+    Credential(String),
+    /// E.g. `Credential "deploy-key"`, managed outside of the API.
+    #[error("{0} is read-only")]
+    ReadOnly(String),
+    /// Errors of code still returning `String`.
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<String> for NomosError {
+    fn from(e: String) -> Self {
+        NomosError::Other(e)
+    }
+}
+
+impl From<&str> for NomosError {
+    fn from(e: &str) -> Self {
+        NomosError::Other(e.to_string())
+    }
+}
+
+impl From<NomosError> for String {
+    fn from(e: NomosError) -> Self {
+        e.to_string()
+    }
+}
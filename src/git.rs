@@ -1,3 +1,5 @@
+use std::{path::Path, process::Command};
+
 use tempfile::NamedTempFile;
 
 use crate::script::ScriptExecutionContext;
@@ -5,19 +7,125 @@ use crate::script::ScriptExecutionContext;
 use crate::{
     credential::{Credential, CredentialType},
     log::LogLevel,
-    utils::{execute_command, execute_command_with_env},
+    utils::{execute_command, execute_command_with_env, execute_program, tmp_dir},
 };
 
+/// Options of `git clone` besides the branch. The default is a full clone without submodules.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CloneOptions {
+    /// `--depth N`, a shallow clone of the last N commits.
+    pub depth: Option<u32>,
+    /// `--recurse-submodules`
+    pub submodules: bool,
+}
+
+/// Branch cloned when neither the step nor the job set one.
+const DEFAULT_BRANCH: &str = "main";
+
+/// Branch of a git-clone step without one: the job's default, then `NOMOS_GIT_DEFAULT_BRANCH`, then `main`.
+pub fn default_branch(job_default: Option<&str>) -> String {
+    branch_or_default(job_default, std::env::var("NOMOS_GIT_DEFAULT_BRANCH").ok())
+}
+
+/// `default_branch` with `configured` as the value of `NOMOS_GIT_DEFAULT_BRANCH`.
+fn branch_or_default(job_default: Option<&str>, configured: Option<String>) -> String {
+    job_default
+        .filter(|branch| !branch.is_empty())
+        .map(str::to_string)
+        .or_else(|| configured.filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| DEFAULT_BRANCH.to_string())
+}
+
+/// Checks that `branch` is a plain branch or tag name: letters, digits and `-_./+@`, not starting with `-` and
+/// without `..`.
+pub fn validate_branch(branch: &str) -> Result<(), String> {
+    let valid = !branch.is_empty()
+        && !branch.starts_with('-')
+        && !branch.contains("..")
+        && branch
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./+@".contains(c));
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid git branch {:?}", branch))
+    }
+}
+
+/// Checks that `url` has a form git accepts: `https://` (or `http`, `ssh`, `git`, `file`) URLs, scp-like
+/// `git@host:path` and absolute local paths. Typos otherwise only show up as a git error while cloning.
+pub fn validate_url(url: &str) -> Result<(), String> {
+    let valid = if url.is_empty() || url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        false
+    } else if let Some((scheme, rest)) = url.split_once("://") {
+        match scheme {
+            "file" => !rest.is_empty(),
+            "https" | "http" | "ssh" | "git" => {
+                matches!(rest.split_once('/'), Some((host, path)) if !host.is_empty() && !path.is_empty())
+            }
+            _ => false,
+        }
+    } else if let Some((user_host, path)) = url.split_once(':').filter(|(user_host, _)| user_host.contains('@')) {
+        matches!(user_host.split_once('@'), Some((user, host)) if !user.is_empty() && !host.is_empty())
+            && !user_host.contains('/')
+            && !path.is_empty()
+    } else {
+        Path::new(url).is_absolute()
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid git URL {:?}, expected https://, ssh://, git@host:path or an absolute path",
+            url
+        ))
+    }
+}
+
+/// `git clone` arguments for `url`, run without a shell.
+pub fn clone_args(url: &str, branch: &str, options: &CloneOptions) -> Vec<String> {
+    let mut args = vec![
+        "git".to_string(),
+        "clone".to_string(),
+        "-b".to_string(),
+        branch.to_string(),
+    ];
+    if let Some(depth) = options.depth {
+        args.extend(["--depth".to_string(), depth.to_string()]);
+    }
+    if options.submodules {
+        args.push("--recurse-submodules".to_string());
+    }
+    args.extend(["--".to_string(), url.to_string()]);
+    args
+}
+
+/// Clones `url` into `directory` and returns the checked out commit SHA. `None` in dry run mode.
 pub async fn git_clone(
+    url: &str,
+    branch: &str,
+    directory: &Path,
+    credential_id: Option<&str>,
+    options: &CloneOptions,
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<Option<String>, String> {
+    clone(url, branch, credential_id, options, context).await?;
+    git_head_sha(directory, context)
+}
+
+async fn clone(
     url: &str,
     branch: &str,
     credential_id: Option<&str>,
+    options: &CloneOptions,
     context: &mut ScriptExecutionContext<'_>,
 ) -> Result<(), String> {
+    validate_branch(branch)?;
+    let args = clone_args(url, branch, options);
     if cfg!(target_os = "windows") {
         if !context.job_result.dry_run {
             // Workaround for local
-            execute_command(&format!("git clone -b {} {}", branch, url), context).await?;
+            execute_program(args, vec![], context).await?;
         }
 
         Ok(())
@@ -34,9 +142,9 @@ pub async fn git_clone(
                     .add_log(LogLevel::Info, "command: chmod 400 <private_key_temp_file>".to_string());
                 context
                     .job_result
-                    .add_log(LogLevel::Info, format!("command: git clone -b {} {}", branch, url));
+                    .add_log(LogLevel::Info, format!("command: {}", args.join(" ")));
                 if !context.job_result.dry_run {
-                    let tmp_file = NamedTempFile::new().map_err(|e| e.to_string())?;
+                    let tmp_file = NamedTempFile::new_in(tmp_dir()?).map_err(|e| e.to_string())?;
                     let tmp_path = tmp_file.path();
                     std::fs::write(tmp_path, ssh_credential.private_key).map_err(|e| e.to_string())?;
 
@@ -46,7 +154,7 @@ pub async fn git_clone(
                         "GIT_SSH_COMMAND".to_string(),
                         format!("ssh -i {} -o StrictHostKeyChecking=no", tmp_path.display()),
                     )];
-                    execute_command_with_env(&format!("git clone -b {} {}", branch, url), env, context).await?;
+                    execute_program(args, env, context).await?;
                 }
 
                 Ok(())
@@ -58,11 +166,23 @@ pub async fn git_clone(
     }
 }
 
+/// Pulls `directory` and returns the checked out commit SHA. `None` in dry run mode.
 pub async fn git_pull(
     directory: &str,
     lfs: bool,
     credential_id: Option<&str>,
     context: &mut ScriptExecutionContext<'_>,
+) -> Result<Option<String>, String> {
+    pull(directory, lfs, credential_id, context).await?;
+    let directory = context.directory.join(directory);
+    git_head_sha(&directory, context)
+}
+
+async fn pull(
+    directory: &str,
+    lfs: bool,
+    credential_id: Option<&str>,
+    context: &mut ScriptExecutionContext<'_>,
 ) -> Result<(), String> {
     if cfg!(target_os = "windows") {
         if !context.job_result.dry_run {
@@ -88,9 +208,11 @@ pub async fn git_pull(
                 } else {
                     "git pull".to_string()
                 };
-                context.job_result.add_log(LogLevel::Info, format!("command: {}", log_command));
+                context
+                    .job_result
+                    .add_log(LogLevel::Info, format!("command: {}", log_command));
                 if !context.job_result.dry_run {
-                    let tmp_file = NamedTempFile::new().map_err(|e| e.to_string())?;
+                    let tmp_file = NamedTempFile::new_in(tmp_dir()?).map_err(|e| e.to_string())?;
                     let tmp_path = tmp_file.path();
                     std::fs::write(tmp_path, ssh_credential.private_key).map_err(|e| e.to_string())?;
                     execute_command(&format!("chmod 400 {}", tmp_path.display()), context).await?;
@@ -114,3 +236,119 @@ pub async fn git_pull(
         Err("Credential ID is required".into())
     }
 }
+
+/// git rev-parse HEAD
+fn git_head_sha(directory: &Path, context: &mut ScriptExecutionContext<'_>) -> Result<Option<String>, String> {
+    context
+        .job_result
+        .add_log(LogLevel::Info, "command: git rev-parse HEAD".to_string());
+    if context.job_result.dry_run {
+        return Ok(None);
+    }
+
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(directory)
+        .output()
+        .map_err(|e| format!("Failed to run git rev-parse: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to resolve HEAD in {}: {}",
+            directory.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let sha = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    context.job_result.add_log(LogLevel::Info, format!("HEAD: {}", sha));
+    Ok(Some(sha))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clone_args() {
+        let url = "git@github.com:nomos-cicd/nomos-rust.git";
+        assert_eq!(
+            clone_args(url, "main", &CloneOptions::default()).join(" "),
+            format!("git clone -b main -- {}", url)
+        );
+
+        let options = CloneOptions {
+            depth: Some(1),
+            submodules: true,
+        };
+        assert_eq!(
+            clone_args(url, "dev", &options).join(" "),
+            format!("git clone -b dev --depth 1 --recurse-submodules -- {}", url)
+        );
+
+        // Passed as one argument, never through a shell
+        let url = "https://github.com/nomos-cicd/nomos-rust.git;rm -rf ~";
+        assert_eq!(clone_args(url, "main", &CloneOptions::default()).last().unwrap(), url);
+    }
+
+    #[test]
+    fn test_validate_branch() {
+        for branch in ["main", "release/1.0", "feature-x_y", "v1.2.3"] {
+            assert!(validate_branch(branch).is_ok(), "{}", branch);
+        }
+        for branch in [
+            "",
+            "-x",
+            "--upload-pack=touch",
+            "a b",
+            "a..b",
+            "a:b",
+            "main;id",
+            "$(id)",
+            "`id`",
+        ] {
+            assert!(validate_branch(branch).is_err(), "{}", branch);
+        }
+    }
+
+    #[test]
+    fn test_validate_url() {
+        for url in [
+            "https://github.com/nomos-cicd/nomos-rust.git",
+            "http://git.local/repo",
+            "ssh://git@github.com/nomos-cicd/nomos-rust.git",
+            "ssh://git@github.com:22/nomos-cicd/nomos-rust.git",
+            "git@github.com:nomos-cicd/nomos-rust.git",
+            "file:///srv/git/repo.git",
+            "/srv/git/repo.git",
+        ] {
+            assert!(validate_url(url).is_ok(), "{}", url);
+        }
+        for url in [
+            "",
+            "github.com/nomos-cicd/nomos-rust",
+            "htps://github.com/nomos-cicd/nomos-rust",
+            "https://",
+            "https://github.com",
+            "git@github.com",
+            "git@:nomos-rust.git",
+            "@github.com:nomos-rust.git",
+            "git@github.com:",
+            "https://github.com/nomos-cicd/nomos rust",
+            "relative/path",
+        ] {
+            assert!(validate_url(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_default_branch() {
+        assert_eq!(default_branch(Some("develop")), "develop");
+
+        // NOMOS_GIT_DEFAULT_BRANCH, then main
+        assert_eq!(branch_or_default(None, Some("trunk".to_string())), "trunk");
+        assert_eq!(branch_or_default(Some(""), Some("trunk".to_string())), "trunk");
+        assert_eq!(branch_or_default(Some("develop"), Some("trunk".to_string())), "develop");
+        assert_eq!(branch_or_default(None, Some(String::new())), "main");
+        assert_eq!(branch_or_default(None, None), "main");
+    }
+}
@@ -1,9 +1,82 @@
+pub mod backup;
 pub mod credentials;
 pub mod job_results;
 pub mod jobs;
+pub mod maintenance;
+pub mod openapi;
 pub mod scripts;
+pub mod sync;
 
+pub use backup::*;
 pub use credentials::*;
 pub use job_results::*;
 pub use jobs::*;
+pub use maintenance::*;
+pub use openapi::*;
 pub use scripts::*;
+pub use sync::*;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::error::NomosError;
+
+/// Body of a 400 response for YAML that failed to parse.
+#[derive(Debug, Serialize)]
+pub struct YamlError {
+    pub message: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl From<serde_yaml::Error> for YamlError {
+    fn from(e: serde_yaml::Error) -> Self {
+        let location = e.location();
+        YamlError {
+            message: e.to_string(),
+            line: location.as_ref().map(|l| l.line()),
+            column: location.as_ref().map(|l| l.column()),
+        }
+    }
+}
+
+/// Error of an API handler, answered with the status code matching the `NomosError` category.
+#[derive(Debug)]
+pub struct ApiError(pub NomosError);
+
+impl From<NomosError> for ApiError {
+    fn from(e: NomosError) -> Self {
+        ApiError(e)
+    }
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self.0 {
+            NomosError::NotFound(_) => StatusCode::NOT_FOUND,
+            NomosError::ReadOnly(_) => StatusCode::FORBIDDEN,
+            NomosError::Validation(_) | NomosError::Credential(_) => StatusCode::BAD_REQUEST,
+            // Request bodies are answered by `yaml_error_response`, so these come from stored files
+            NomosError::Yaml(_) | NomosError::Io(_) | NomosError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status == StatusCode::INTERNAL_SERVER_ERROR {
+            eprintln!("Internal error: {}", self.0);
+            return status.into_response();
+        }
+        (status, self.0.to_string()).into_response()
+    }
+}
+
+pub fn yaml_error_response(e: serde_yaml::Error) -> Response {
+    (StatusCode::BAD_REQUEST, Json(YamlError::from(e))).into_response()
+}
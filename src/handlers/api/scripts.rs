@@ -1,15 +1,39 @@
 use axum::{
-    extract::Path,
-    http::{HeaderMap, StatusCode},
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 
-use crate::script::models::Script;
+use serde::Deserialize;
 
-pub async fn get_scripts() -> Response {
+use crate::{
+    error::NomosError,
+    handlers::{yaml_error_response, ApiError},
+    script::models::Script,
+};
+
+#[derive(Deserialize)]
+pub struct ScriptsQuery {
+    /// Case-insensitive substring of the script name.
+    q: Option<String>,
+}
+
+pub async fn get_scripts(Query(query): Query<ScriptsQuery>) -> Response {
     match Script::get_all() {
-        Ok(scripts) => Json(scripts).into_response(),
+        Ok(scripts) => {
+            let filtered_scripts: Vec<Script> = match &query.q {
+                Some(q) => {
+                    let q = q.to_lowercase();
+                    scripts
+                        .into_iter()
+                        .filter(|script| script.name.to_lowercase().contains(&q))
+                        .collect()
+                }
+                None => scripts,
+            };
+            Json(filtered_scripts).into_response()
+        }
         Err(e) => {
             eprintln!("Failed to get scripts: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR.into_response()
@@ -17,15 +41,14 @@ pub async fn get_scripts() -> Response {
     }
 }
 
-pub async fn get_script(Path(id): Path<String>) -> Response {
-    match Script::get(id.as_str()) {
-        Ok(Some(script)) => Json(script).into_response(),
-        Ok(None) => StatusCode::NOT_FOUND.into_response(),
-        Err(e) => {
-            eprintln!("Failed to get script {}: {}", id, e);
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
-    }
+pub async fn get_script(Path(id): Path<String>) -> Result<Json<Script>, ApiError> {
+    Ok(Json(Script::require(&id)?))
+}
+
+/// The script as stored, for editing and posting back to `POST /api/scripts`.
+pub async fn get_script_yaml(Path(id): Path<String>) -> Result<Response, ApiError> {
+    let yaml = Script::get_source(&id)?.ok_or_else(|| NomosError::NotFound(format!("Script {:?}", id)))?;
+    Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml).into_response())
 }
 
 pub async fn create_script(headers: HeaderMap, body: String) -> Response {
@@ -39,33 +62,101 @@ pub async fn create_script(headers: HeaderMap, body: String) -> Response {
     }
 
     match serde_yaml::from_str::<Script>(&body) {
-        Ok(script) => match script.sync(None) {
+        Ok(script) => match script.sync_source(&body, None) {
             Ok(_) => Json(script).into_response(),
             Err(e) => {
                 eprintln!("Failed to sync script: {}", e);
-                StatusCode::BAD_REQUEST.into_response()
+                (StatusCode::BAD_REQUEST, e).into_response()
             }
         },
         Err(e) => {
             eprintln!("Failed to parse script YAML: {}", e);
-            StatusCode::BAD_REQUEST.into_response()
+            yaml_error_response(e)
         }
     }
 }
 
-pub async fn delete_script(Path(id): Path<String>) -> Response {
-    match Script::get(id.as_str()) {
-        Ok(Some(script)) => match script.delete() {
-            Ok(_) => StatusCode::NO_CONTENT.into_response(),
-            Err(e) => {
-                eprintln!("Failed to delete script {}: {}", id, e);
-                StatusCode::INTERNAL_SERVER_ERROR.into_response()
-            }
-        },
-        Ok(None) => StatusCode::NOT_FOUND.into_response(),
-        Err(e) => {
-            eprintln!("Failed to get script for deletion {}: {}", id, e);
-            StatusCode::INTERNAL_SERVER_ERROR.into_response()
-        }
+pub async fn delete_script(Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    Script::require(&id)?.delete()?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_create_script_duplicate_step_names() {
+        let body = r#"
+id: api-duplicate-steps-script
+name: Duplicate Steps
+parameters: []
+steps:
+  - name: Build
+    values:
+      - type: bash
+        code: echo first
+  - name: Build
+    values:
+      - type: bash
+        code: echo second
+"#;
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/yaml"));
+
+        let response = create_script(headers, body.to_string()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(Script::get("api-duplicate-steps-script").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_script_yaml() {
+        let body = r#"# Kept as written
+id: api-yaml-script
+name: Yaml Script
+parameters: []
+steps:
+  - name: Build
+    values:
+      - type: bash
+        code: echo build # inline comment
+"#;
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/yaml"));
+        let response = create_script(headers, body.to_string()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get_script_yaml(Path("api-yaml-script".to_string()))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "application/yaml");
+        let yaml = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(std::str::from_utf8(&yaml).unwrap(), body);
+        let stored: Script = serde_yaml::from_slice(&yaml).unwrap();
+        assert_eq!(stored, serde_yaml::from_str::<Script>(body).unwrap());
+
+        let response = get_script_yaml(Path("api-missing-yaml-script".to_string()))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_create_script_malformed_yaml() {
+        let body = "id: api-malformed-script\nname: Malformed\nparameters: []\nsteps:\n  - name: [Build\n";
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/yaml"));
+
+        let response = create_script(headers, body.to_string()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let error: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(error["line"], 5);
+        assert!(error["column"].as_u64().is_some());
+        assert!(error["message"].as_str().is_some());
     }
 }
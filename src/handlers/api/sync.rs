@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::NomosError,
+    handlers::api::ApiError,
+    job::JobResult,
+    settings::{ConfigRepository, SyncSummary},
+};
+
+#[derive(Deserialize, Default)]
+pub struct SyncRequest {
+    /// Local directory to sync instead of the config repository.
+    directory: Option<String>,
+    /// Credential cloning the config repository, instead of `NOMOS_CONFIG_CREDENTIAL_ID`.
+    credential_id: Option<String>,
+    /// Required with `directory`, which replaces the credentials, scripts and jobs just like the config repository.
+    #[serde(default)]
+    prune: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncResponse {
+    job_result_id: String,
+    summary: Option<SyncSummary>,
+}
+
+/// Syncs the config repository now, without waiting for `NOMOS_CONFIG_SYNC_INTERVAL`. Waits for a sync that is
+/// already running.
+pub async fn sync_configuration(request: Option<Json<SyncRequest>>) -> Result<Json<SyncResponse>, ApiError> {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let job_result_id =
+        match (request.directory, ConfigRepository::from_env()) {
+            (Some(_), _) if !request.prune => return Err(NomosError::Validation(
+                "Syncing a directory deletes every credential, script and job it doesn't contain, set prune to confirm"
+                    .to_string(),
+            )
+            .into()),
+            (Some(directory), _) => ConfigRepository::sync_directory(PathBuf::from(directory)).await?,
+            (None, Some(config_repository)) => {
+                let config_repository = ConfigRepository {
+                    credential_id: request.credential_id.or(config_repository.credential_id),
+                    ..config_repository
+                };
+                config_repository.sync().await?
+            }
+            (None, None) => {
+                return Err(NomosError::Validation(
+                    "No config repository configured, set NOMOS_CONFIG_REPO or provide a directory".to_string(),
+                )
+                .into())
+            }
+        };
+
+    let summary = JobResult::get(&job_result_id)
+        .ok()
+        .flatten()
+        .and_then(|job_result| job_result.sync_summary);
+    Ok(Json(SyncResponse { job_result_id, summary }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        http::StatusCode,
+        response::{IntoResponse, Response},
+    };
+
+    use super::*;
+
+    async fn sync_directory(directory: &std::path::Path, prune: bool) -> Response {
+        sync_configuration(Some(Json(SyncRequest {
+            directory: Some(directory.to_str().unwrap().to_string()),
+            credential_id: None,
+            prune,
+        })))
+        .await
+        .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_sync_missing_directory() {
+        let directory = tempfile::tempdir().unwrap().path().join("missing");
+        let response = sync_directory(&directory, true).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("Directory does not exist"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_directory_requires_prune() {
+        // Empty, syncing it would delete everything
+        let directory = tempfile::tempdir().unwrap();
+        let response = sync_directory(directory.path(), false).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("set prune to confirm"));
+    }
+}
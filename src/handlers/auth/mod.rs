@@ -1,5 +1,7 @@
 mod session;
+mod store;
 mod users;
 
 pub use session::*;
+pub use store::*;
 pub use users::*;
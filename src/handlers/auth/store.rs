@@ -0,0 +1,184 @@
+use std::path::PathBuf;
+
+use axum::async_trait;
+use axum_login::tower_sessions::{
+    session::{Id, Record},
+    session_store::{self, ExpiredDeletion},
+    SessionStore,
+};
+use chrono::Utc;
+
+use crate::utils::data_directory;
+
+pub fn default_sessions_location() -> Result<PathBuf, String> {
+    data_directory("sessions")
+}
+
+/// Sessions stored as one JSON file per session, so logins survive restarts and can be shared by instances with the
+/// same data directory. Selected with `NOMOS_SESSION_STORE=file`, sessions are kept in memory otherwise.
+#[derive(Debug, Clone)]
+pub struct FileSessionStore {
+    path: PathBuf,
+}
+
+impl FileSessionStore {
+    pub fn new(path: PathBuf) -> Self {
+        FileSessionStore { path }
+    }
+
+    /// `None` for the in-memory store.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var("NOMOS_SESSION_STORE").as_deref() {
+            Ok("file") => Ok(Some(Self::new(default_sessions_location()?))),
+            Ok("memory") | Ok("") | Err(_) => Ok(None),
+            Ok(other) => Err(format!("Unknown session store: {}", other)),
+        }
+    }
+
+    fn record_path(&self, id: &Id) -> PathBuf {
+        // Ids are url-safe base64, so they can't escape the directory
+        self.path.join(format!("{}.json", id))
+    }
+
+    fn is_active(record: &Record) -> bool {
+        record.expiry_date.unix_timestamp() > Utc::now().timestamp()
+    }
+
+    fn write(&self, record: &Record) -> session_store::Result<()> {
+        let content = serde_json::to_vec(record).map_err(|e| session_store::Error::Encode(e.to_string()))?;
+        let path = self.record_path(&record.id);
+        let temporary = path.with_extension("json.tmp");
+        std::fs::write(&temporary, content).map_err(|e| session_store::Error::Backend(e.to_string()))?;
+        std::fs::rename(&temporary, &path).map_err(|e| session_store::Error::Backend(e.to_string()))
+    }
+
+    fn read(path: &PathBuf) -> session_store::Result<Option<Record>> {
+        match std::fs::read(path) {
+            Ok(content) => serde_json::from_slice(&content)
+                .map(Some)
+                .map_err(|e| session_store::Error::Decode(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(session_store::Error::Backend(e.to_string())),
+        }
+    }
+
+    fn remove(path: &PathBuf) -> session_store::Result<()> {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(session_store::Error::Backend(e.to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Deletes expired sessions every `period`, sessions that are never loaded again would stay on disk otherwise.
+    pub async fn delete_expired_periodically(self, period: std::time::Duration) {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.delete_expired().await {
+                eprintln!("Failed to delete expired sessions: {}", e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SessionStore for FileSessionStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        while self.record_path(&record.id).exists() {
+            record.id = Id::default();
+        }
+        self.write(record)
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.write(record)
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        let path = self.record_path(session_id);
+        match Self::read(&path)? {
+            Some(record) if Self::is_active(&record) => Ok(Some(record)),
+            Some(_) => {
+                Self::remove(&path)?;
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        Self::remove(&self.record_path(session_id))
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for FileSessionStore {
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        let entries = std::fs::read_dir(&self.path).map_err(|e| session_store::Error::Backend(e.to_string()))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            match Self::read(&path) {
+                Ok(Some(record)) if Self::is_active(&record) => {}
+                // Unreadable files can't be loaded either
+                _ => Self::remove(&path)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use time::{Duration, OffsetDateTime};
+
+    use super::*;
+
+    fn record(expires_in: Duration) -> Record {
+        Record {
+            id: Id::default(),
+            data: HashMap::from([("user".to_string(), serde_json::json!("admin"))]),
+            expiry_date: OffsetDateTime::now_utc() + expires_in,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_survives_reload() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut active = record(Duration::hours(1));
+        FileSessionStore::new(directory.path().to_path_buf())
+            .create(&mut active)
+            .await
+            .unwrap();
+
+        // A new store on the same directory, like after a restart
+        let store = FileSessionStore::new(directory.path().to_path_buf());
+        assert_eq!(store.load(&active.id).await.unwrap(), Some(active.clone()));
+
+        store.delete(&active.id).await.unwrap();
+        assert_eq!(store.load(&active.id).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_expired_sessions() {
+        let directory = tempfile::tempdir().unwrap();
+        let store = FileSessionStore::new(directory.path().to_path_buf());
+        let expired = record(Duration::seconds(-1));
+        let other_expired = record(Duration::seconds(-1));
+        let active = record(Duration::hours(1));
+        for record in [&expired, &other_expired, &active] {
+            store.save(record).await.unwrap();
+        }
+
+        assert_eq!(store.load(&expired.id).await.unwrap(), None);
+        assert!(!store.record_path(&expired.id).exists());
+
+        store.delete_expired().await.unwrap();
+        assert!(!store.record_path(&other_expired.id).exists());
+        assert!(store.load(&active.id).await.unwrap().is_some());
+    }
+}
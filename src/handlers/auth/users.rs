@@ -2,13 +2,19 @@ use std::collections::HashMap;
 
 use axum::async_trait;
 use axum_login::{AuthUser, AuthnBackend, UserId};
+use hmac::{Hmac, Mac};
 use serde::Deserialize;
+use sha2::Sha256;
 
 #[derive(Clone, Deserialize)]
 pub struct User {
     id: i64,
     pub username: String,
+    /// bcrypt hash.
     password: String,
+    /// Derived from `password` by `session_auth_hash`.
+    #[serde(skip)]
+    auth_hash: Vec<u8>,
 }
 
 // Here we've implemented `Debug` manually to avoid accidentally logging the
@@ -31,19 +37,64 @@ impl AuthUser for User {
     }
 
     fn session_auth_hash(&self) -> &[u8] {
-        self.password.as_bytes() // We use the password hash as the auth
-                                 // hash--what this means
-                                 // is when the user changes their password the
-                                 // auth session becomes invalid.
+        &self.auth_hash
     }
 }
 
+/// Sessions are bound to the password hash, so they become invalid when the password changes. The hash itself is
+/// not stored in the sessions.
+fn session_auth_hash(password_hash: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"nomos-session").expect("HMAC accepts keys of any size");
+    mac.update(password_hash.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Users allowed to log in, `NOMOS_USERNAME` with `NOMOS_PASSWORD`. Passwords are only kept as bcrypt hashes.
 #[derive(Clone, Default)]
 pub struct Backend {
-    #[allow(dead_code)]
     users: HashMap<i64, User>,
 }
 
+impl Backend {
+    /// `password_hash` is a bcrypt hash, e.g. from `bcrypt::hash`.
+    pub fn new(username: String, password_hash: String) -> Self {
+        let user = User {
+            id: 1,
+            username,
+            auth_hash: session_auth_hash(&password_hash),
+            password: password_hash,
+        };
+        Backend {
+            users: HashMap::from([(user.id, user)]),
+        }
+    }
+
+    /// Without `NOMOS_USERNAME` and `NOMOS_PASSWORD` nobody can log in, which only debug builds allow.
+    /// `NOMOS_PASSWORD` is either the password or its bcrypt hash. A password is hashed with a random salt on every
+    /// start, so sessions only survive restarts with a hash.
+    pub fn from_env() -> Result<Self, String> {
+        let (Ok(username), Ok(password)) = (std::env::var("NOMOS_USERNAME"), std::env::var("NOMOS_PASSWORD")) else {
+            return Ok(Self::default());
+        };
+        let password_hash = if is_bcrypt_hash(&password) {
+            password
+        } else {
+            hash_password(&password)?
+        };
+        Ok(Self::new(username, password_hash))
+    }
+}
+
+fn is_bcrypt_hash(value: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| value.starts_with(prefix))
+}
+
+fn hash_password(password: &str) -> Result<String, String> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| format!("Failed to hash password: {}", e))
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Credentials {
     pub username: String,
@@ -65,29 +116,93 @@ impl AuthnBackend for Backend {
             next: _,
         }: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
-        let expected_username = std::env::var("NOMOS_USERNAME").unwrap();
-        let expected_password = std::env::var("NOMOS_PASSWORD").unwrap();
-        if username != expected_username || password != expected_password {
+        let Some(user) = self.users.values().find(|user| user.username == username).cloned() else {
             return Ok(None);
-        }
-
-        let user = User {
-            id: 1,
-            username,
-            password,
         };
 
-        return Ok(user.into());
+        // bcrypt is slow on purpose, keep it off the async workers
+        let hash = user.password.clone();
+        let is_valid = tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash))
+            .await
+            .map(|result| {
+                result.unwrap_or_else(|e| {
+                    eprintln!("Failed to verify password of {}: {}", username, e);
+                    false
+                })
+            })
+            .unwrap_or(false);
+
+        Ok(is_valid.then_some(user))
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        let username = std::env::var("NOMOS_USERNAME").unwrap();
-        let password = std::env::var("NOMOS_PASSWORD").unwrap();
+        Ok(self.users.get(user_id).cloned())
+    }
+}
 
-        return Ok(Some(Self::User {
-            id: *user_id,
-            username,
-            password,
-        }));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend() -> Backend {
+        Backend::new("admin".to_string(), bcrypt::hash("secret", 4).unwrap())
+    }
+
+    fn credentials(username: &str, password: &str) -> Credentials {
+        Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+            next: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let backend = backend();
+        let user = backend
+            .authenticate(credentials("admin", "secret"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.username, "admin");
+        assert_eq!(backend.get_user(&user.id).await.unwrap().unwrap().username, "admin");
+
+        assert!(backend
+            .authenticate(credentials("admin", "wrong"))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(backend
+            .authenticate(credentials("other", "secret"))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(backend.get_user(&2).await.unwrap().is_none());
+        assert!(Backend::default()
+            .authenticate(credentials("admin", "secret"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_password_hash() {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        assert!(is_bcrypt_hash(&hash));
+        assert!(!is_bcrypt_hash("secret"));
+    }
+
+    #[test]
+    fn test_session_auth_hash() {
+        let first = Backend::new("admin".to_string(), bcrypt::hash("secret", 4).unwrap());
+        let second = Backend::new("admin".to_string(), bcrypt::hash("secret", 4).unwrap());
+        let first = first.users[&1].clone();
+        let second = second.users[&1].clone();
+
+        // Random salts
+        assert_ne!(first.password, second.password);
+        assert_ne!(first.session_auth_hash(), second.session_auth_hash());
+        assert_ne!(first.session_auth_hash(), first.password.as_bytes());
+        assert_eq!(first.session_auth_hash(), session_auth_hash(&first.password).as_slice());
     }
 }
@@ -1,7 +1,31 @@
 pub mod api;
 mod auth;
+mod rate_limit;
+mod request_log;
 pub mod templates;
+mod webhook_deliveries;
 
 pub use api::*;
 pub use auth::*;
+pub use rate_limit::*;
+pub use request_log::*;
 pub use templates::*;
+pub use webhook_deliveries::*;
+
+use axum::http::HeaderMap;
+
+use crate::AppState;
+
+/// `NOMOS_BASE_URL` if configured, otherwise derived from the request `Host` header.
+pub fn base_url(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    if let Some(base_url) = &state.base_url {
+        return Some(base_url.clone());
+    }
+
+    let host = headers.get("host")?.to_str().ok()?;
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+    Some(format!("{}://{}", scheme, host))
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// Fixed window request counter keyed by client IP.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    /// Peers whose `X-Forwarded-For` header is trusted, e.g. a reverse proxy in front of the server.
+    trusted_proxies: Vec<IpAddr>,
+    windows: Mutex<Windows>,
+}
+
+#[derive(Debug)]
+struct Windows {
+    counts: HashMap<String, (Instant, u32)>,
+    /// Expired windows are dropped at most once per window, not on every request.
+    swept_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        RateLimiter {
+            limit,
+            window,
+            trusted_proxies: vec![],
+            windows: Mutex::new(Windows {
+                counts: HashMap::new(),
+                swept_at: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+
+    /// Reads `NOMOS_WEBHOOK_RATE_LIMIT` (requests, default 60), `NOMOS_WEBHOOK_RATE_WINDOW` (seconds, default 60)
+    /// and the comma separated IPs of `NOMOS_TRUSTED_PROXIES`.
+    pub fn from_env() -> Result<Self, String> {
+        let limit = std::env::var("NOMOS_WEBHOOK_RATE_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+        let window = std::env::var("NOMOS_WEBHOOK_RATE_WINDOW")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+        let trusted_proxies = parse_trusted_proxies(&std::env::var("NOMOS_TRUSTED_PROXIES").unwrap_or_default())?;
+        Ok(Self::new(limit, Duration::from_secs(window)).with_trusted_proxies(trusted_proxies))
+    }
+
+    /// Counts a request for `key`, returning `false` when the limit of the current window is exceeded.
+    pub fn check(&self, key: &str) -> bool {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(windows.swept_at) >= self.window {
+            windows
+                .counts
+                .retain(|_, (started_at, _)| now.duration_since(*started_at) < self.window);
+            windows.swept_at = now;
+        }
+
+        let (started_at, count) = windows.counts.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(*started_at) >= self.window {
+            *started_at = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= self.limit
+    }
+
+    /// The peer address, or with a trusted proxy as peer the last `X-Forwarded-For` entry that isn't a trusted
+    /// proxy. Other clients can't change their key by sending the header.
+    fn client_ip(&self, request: &Request) -> String {
+        let Some(peer) = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+        else {
+            return String::new();
+        };
+        if !self.trusted_proxies.contains(&peer) {
+            return peer.to_string();
+        }
+
+        let forwarded_for = request
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+        forwarded_for
+            .into_iter()
+            .rev()
+            .find(|ip| !self.trusted_proxies.contains(ip))
+            .unwrap_or(peer)
+            .to_string()
+    }
+}
+
+fn parse_trusted_proxies(value: &str) -> Result<Vec<IpAddr>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+        .map(|ip| {
+            ip.parse()
+                .map_err(|_| format!("Invalid IP in NOMOS_TRUSTED_PROXIES: {:?}", ip))
+        })
+        .collect()
+}
+
+pub async fn rate_limit(State(limiter): State<Arc<RateLimiter>>, request: Request, next: Next) -> Response {
+    let ip = limiter.client_ip(&request);
+    if !limiter.check(&ip) {
+        eprintln!("Rate limit exceeded for {}", ip);
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, middleware, routing, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert!(limiter.check("1.1.1.1"));
+        assert!(limiter.check("1.1.1.1"));
+        assert!(!limiter.check("1.1.1.1"));
+        assert!(limiter.check("2.2.2.2"));
+    }
+
+    #[test]
+    fn test_window_expires() {
+        let limiter = RateLimiter::new(1, Duration::ZERO);
+        assert!(limiter.check("1.1.1.1"));
+        assert!(limiter.check("1.1.1.1"));
+    }
+
+    #[test]
+    fn test_parse_trusted_proxies() {
+        assert_eq!(
+            parse_trusted_proxies("10.0.0.2, ::1").unwrap(),
+            vec!["10.0.0.2".parse::<IpAddr>().unwrap(), "::1".parse().unwrap()]
+        );
+        assert!(parse_trusted_proxies("").unwrap().is_empty());
+        assert!(parse_trusted_proxies("10.0.0.0/8").is_err());
+    }
+
+    fn app(limiter: RateLimiter) -> Router {
+        Router::new().route(
+            "/webhook",
+            routing::post(|| async { StatusCode::OK })
+                .layer(middleware::from_fn_with_state(Arc::new(limiter), rate_limit)),
+        )
+    }
+
+    fn request(peer: &str, forwarded_for: &str) -> Request {
+        let mut request = Request::post("/webhook")
+            .header("x-forwarded-for", forwarded_for)
+            .body(Body::empty())
+            .unwrap();
+        let peer = SocketAddr::new(peer.parse().unwrap(), 40000);
+        request.extensions_mut().insert(ConnectInfo(peer));
+        request
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let app = app(RateLimiter::new(1, Duration::from_secs(60)));
+
+        let response = app.clone().oneshot(request("10.0.0.1", "1.1.1.1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Without a trusted proxy the header is ignored
+        let response = app.oneshot(request("10.0.0.1", "2.2.2.2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_trusted_proxy() {
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let app = app(RateLimiter::new(1, Duration::from_secs(60)).with_trusted_proxies(vec![proxy]));
+
+        let response = app.clone().oneshot(request("10.0.0.2", "1.1.1.1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.clone().oneshot(request("10.0.0.2", "2.2.2.2")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Spoofed entries in front of the one the proxy appended don't matter
+        let response = app.oneshot(request("10.0.0.2", "3.3.3.3, 1.1.1.1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+}
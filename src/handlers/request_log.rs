@@ -0,0 +1,143 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::Instrument;
+
+use crate::AppState;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Route pattern, e.g. `/api/jobs/:id`, or the path if no route matched.
+    pub path: String,
+    pub status: u16,
+    pub duration_ms: u64,
+    pub started_at: DateTime<Utc>,
+}
+
+/// The most recent API requests, oldest first, for `GET /api/debug/requests`.
+#[derive(Debug)]
+pub struct RequestLog {
+    capacity: usize,
+    requests: Mutex<VecDeque<RecordedRequest>>,
+}
+
+impl RequestLog {
+    pub fn new(capacity: usize) -> Self {
+        RequestLog {
+            capacity,
+            requests: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, request: RecordedRequest) {
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        if requests.len() == self.capacity {
+            requests.pop_front();
+        }
+        requests.push_back(request);
+    }
+
+    pub fn recent(&self) -> Vec<RecordedRequest> {
+        let requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        requests.iter().cloned().collect()
+    }
+}
+
+impl Default for RequestLog {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+/// API calls only. Logs are polled continuously while a job runs and would drown everything else.
+fn is_recorded(path: &str) -> bool {
+    (path.starts_with("/api/") || path.starts_with("/public/api/")) && !path.contains("/logs")
+}
+
+/// Runs each API request in a `request` span with method, path, status and duration, and keeps it in the
+/// `RequestLog`.
+pub async fn record_requests(State(log): State<Arc<RequestLog>>, request: Request, next: Next) -> Response {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    if !is_recorded(&path) {
+        return next.run(request).await;
+    }
+
+    let method = request.method().to_string();
+    let span = tracing::info_span!(
+        "request",
+        method = %method,
+        path = %path,
+        status = tracing::field::Empty,
+        duration_ms = tracing::field::Empty
+    );
+    let started_at = Utc::now();
+    let start = Instant::now();
+    let response = next.run(request).instrument(span.clone()).await;
+    let duration_ms = start.elapsed().as_millis() as u64;
+    let status = response.status().as_u16();
+
+    span.record("status", status);
+    span.record("duration_ms", duration_ms);
+    span.in_scope(|| tracing::info!("{} {} {} {}ms", method, path, status, duration_ms));
+    log.record(RecordedRequest {
+        method,
+        path,
+        status,
+        duration_ms,
+        started_at,
+    });
+    response
+}
+
+pub async fn get_recent_requests(State(state): State<AppState>) -> Response {
+    Json(state.request_log.recent()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::StatusCode, middleware, routing, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_requests() {
+        let log = Arc::new(RequestLog::new(2));
+        let app = Router::new()
+            .route("/api/jobs/:id", routing::get(|| async { StatusCode::NOT_FOUND }))
+            .route("/api/job-results/:id/logs/raw", routing::get(|| async { "logs" }))
+            .layer(middleware::from_fn_with_state(log.clone(), record_requests));
+
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(get("/api/jobs/deploy")).await.unwrap();
+        app.clone().oneshot(get("/api/job-results/1/logs/raw")).await.unwrap();
+
+        let recent = log.recent();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].method, "GET");
+        assert_eq!(recent[0].path, "/api/jobs/:id");
+        assert_eq!(recent[0].status, 404);
+
+        // Only the latest requests are kept
+        for _ in 0..3 {
+            app.clone().oneshot(get("/api/jobs/build")).await.unwrap();
+        }
+        assert_eq!(log.recent().len(), 2);
+    }
+}
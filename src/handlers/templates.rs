@@ -9,3 +9,50 @@ pub use job_results::*;
 pub use jobs::*;
 pub use login::*;
 pub use scripts::*;
+
+use askama::Template;
+use axum::{
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+
+/// Renders the template as HTML, responding with `500` instead of panicking when rendering fails.
+pub fn render_html<T: Template>(template: &T) -> Response {
+    match template.render() {
+        Ok(html) => Html(html).into_response(),
+        Err(e) => {
+            eprintln!("Failed to render template: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::{Display, Formatter};
+
+    use super::*;
+
+    struct FailingValue;
+
+    impl Display for FailingValue {
+        fn fmt(&self, _f: &mut Formatter<'_>) -> std::fmt::Result {
+            Err(std::fmt::Error)
+        }
+    }
+
+    #[derive(Template)]
+    #[template(source = "<p>{{ value }}</p>", ext = "html")]
+    struct TestTemplate<T: Display> {
+        value: T,
+    }
+
+    #[test]
+    fn test_render_html() {
+        let response = render_html(&TestTemplate { value: "ok" });
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = render_html(&TestTemplate { value: FailingValue });
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
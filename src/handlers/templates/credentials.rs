@@ -4,10 +4,11 @@ use askama::Template;
 use axum::{
     extract::{Path, Query},
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{IntoResponse, Response},
 };
 use serde::Deserialize;
 
+use super::render_html;
 use crate::credential::{Credential, CredentialType};
 
 #[derive(Template)]
@@ -24,7 +25,7 @@ pub async fn template_credentials() -> Response {
                 title: "Credentials",
                 credentials,
             };
-            Html(template.render().unwrap()).into_response()
+            render_html(&template)
         }
         Err(e) => {
             eprintln!("Failed to get all credentials: {}", e);
@@ -75,7 +76,7 @@ pub async fn template_credential(id: Option<Path<String>>, title: &str) -> Respo
         credential: credential.as_ref(),
         credential_value,
     };
-    Html(template.render().unwrap()).into_response()
+    render_html(&template)
 }
 
 #[derive(Template)]
@@ -100,7 +101,7 @@ pub async fn template_credential_value(params: Query<CredentialValueQuery>) -> R
                         let template = CredentialValueTemplate {
                             credential_value: &credential.value,
                         };
-                        return (StatusCode::OK, Html(template.render().unwrap())).into_response();
+                        return render_html(&template);
                     }
                 }
             }
@@ -116,7 +117,7 @@ pub async fn template_credential_value(params: Query<CredentialValueQuery>) -> R
         let template = CredentialValueTemplate {
             credential_value: &credential_type,
         };
-        return Html(template.render().unwrap()).into_response();
+        return render_html(&template);
     }
 
     StatusCode::BAD_REQUEST.into_response()
@@ -1,13 +1,14 @@
 use askama::Template;
 use axum::{
-    extract::{Path, Query},
-    http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
 };
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 
-use crate::{job::JobResult, log::LogLevel};
+use super::render_html;
+use crate::{handlers::base_url, job::JobResult, log::LogLevel, utils::result_url, AppState};
 
 #[derive(Template)]
 #[template(path = "job-results.html")]
@@ -53,6 +54,7 @@ pub struct JobResultsQuery {
 pub struct JobResultHeaderTemplate<'a> {
     result: &'a JobResult,
     now: DateTime<Utc>,
+    result_url: Option<String>,
 }
 
 #[derive(Template)]
@@ -76,7 +78,7 @@ pub async fn template_job_results(query: Query<JobResultsQuery>) -> Response {
                 has_in_progress,
                 job_id_filter: query.job_id.as_deref(),
             };
-            Html(template.render().unwrap()).into_response()
+            render_html(&template)
         }
         Err(e) => {
             eprintln!("Failed to get all job results: {}", e);
@@ -89,7 +91,7 @@ pub async fn template_job_results_table(query: Query<JobResultsQuery>) -> Respon
     match JobResult::get_all(query.job_id.clone()) {
         Ok(results) => {
             let template = JobResultsTableTemplate { results };
-            Html(template.render().unwrap()).into_response()
+            render_html(&template)
         }
         Err(e) => {
             eprintln!("Failed to get all job results for table: {}", e);
@@ -105,7 +107,7 @@ pub async fn template_job_result(Path(id): Path<String>) -> Response {
                 title: "Job Result",
                 result: &result,
             };
-            Html(template.render().unwrap()).into_response()
+            render_html(&template)
         }
         Ok(None) => {
             eprintln!("Job result not found: {}", id);
@@ -134,7 +136,7 @@ pub async fn template_job_result_logs(Path(result_id): Path<String>) -> Response
                             .collect();
 
                         let template = JobResultLogsTemplate { logs: formatted_logs };
-                        Html(template.render().unwrap()).into_response()
+                        render_html(&template)
                     }
                     Err(e) => {
                         eprintln!("Failed to get logs for job result {}: {}", result_id, e);
@@ -157,29 +159,27 @@ pub async fn template_job_result_logs(Path(result_id): Path<String>) -> Response
     }
 }
 
-pub async fn template_job_result_dynamic_content(Path((id, content_type)): Path<(String, String)>) -> Response {
+pub async fn template_job_result_dynamic_content(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((id, content_type)): Path<(String, String)>,
+) -> Response {
     match JobResult::get(&id) {
         Ok(Some(result)) => {
             let now = Utc::now();
-            let template = match content_type.as_str() {
-                "header" => {
-                    let template = JobResultHeaderTemplate { result: &result, now };
-                    template.render().unwrap()
-                }
-                "steps" => {
-                    let template = JobResultStepsTemplate { result: &result };
-                    template.render().unwrap()
-                }
-                "abort-button" => {
-                    let template = JobResultAbortButtonTemplate { result: &result };
-                    template.render().unwrap()
-                }
+            match content_type.as_str() {
+                "header" => render_html(&JobResultHeaderTemplate {
+                    result: &result,
+                    now,
+                    result_url: base_url(&state, &headers).map(|base| result_url(&base, &result.id)),
+                }),
+                "steps" => render_html(&JobResultStepsTemplate { result: &result }),
+                "abort-button" => render_html(&JobResultAbortButtonTemplate { result: &result }),
                 _ => {
                     eprintln!("Invalid content type: {}", content_type);
-                    return StatusCode::BAD_REQUEST.into_response();
+                    StatusCode::BAD_REQUEST.into_response()
                 }
-            };
-            Html(template).into_response()
+            }
         }
         Ok(None) => {
             eprintln!("Job result not found: {}", id);
@@ -2,11 +2,13 @@ use askama::Template;
 use axum::{
     extract::{Path, Query},
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{IntoResponse, Response},
 };
 use serde::Deserialize;
 
+use super::render_html;
 use crate::{
+    handlers::api::jobs::JobsQuery,
     job::{self, Job},
     script::models::Script,
 };
@@ -33,11 +35,13 @@ pub struct JobFormQuery {
     from_job_id: Option<String>,
 }
 
-pub async fn template_jobs() -> Response {
+/// Takes the same filters as `GET /api/jobs`, tags link to `/jobs?tag=<tag>`.
+pub async fn template_jobs(Query(query): Query<JobsQuery>) -> Response {
     match job::Job::get_all() {
         Ok(jobs) => {
+            let jobs = jobs.into_iter().filter(|job| query.matches(job)).collect();
             let template = JobsTemplate { title: "Jobs", jobs };
-            Html(template.render().unwrap()).into_response()
+            render_html(&template)
         }
         Err(e) => {
             eprintln!("Failed to get all jobs: {}", e);
@@ -104,5 +108,5 @@ pub async fn template_job(id: Option<Path<String>>, title: &str, params: Query<J
         title,
         job: job_yaml.as_deref(),
     };
-    Html(template.render().unwrap()).into_response()
+    render_html(&template)
 }
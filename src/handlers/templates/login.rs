@@ -1,11 +1,12 @@
 use askama::Template;
 use axum::{
     extract::Query,
-    response::{Html, IntoResponse, Redirect},
+    response::{IntoResponse, Redirect, Response},
     Form,
 };
 use serde::Deserialize;
 
+use super::render_html;
 use crate::handlers::auth::{self, Credentials};
 
 #[derive(Template)]
@@ -20,12 +21,12 @@ pub struct NextUrl {
     next: Option<String>,
 }
 
-pub async fn template_get_login(Query(NextUrl { next }): Query<NextUrl>) -> Html<String> {
+pub async fn template_get_login(Query(NextUrl { next }): Query<NextUrl>) -> Response {
     let template = LoginTemplate {
         next,
         title: "Login".to_string(),
     };
-    template.render().unwrap().into()
+    render_html(&template)
 }
 
 pub async fn template_post_login(
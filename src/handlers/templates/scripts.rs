@@ -2,9 +2,10 @@ use askama::Template;
 use axum::{
     extract::Path,
     http::StatusCode,
-    response::{Html, IntoResponse, Response},
+    response::{IntoResponse, Response},
 };
 
+use super::render_html;
 use crate::script::models::Script;
 
 #[derive(Template)]
@@ -28,7 +29,7 @@ pub async fn template_scripts() -> Response {
                 title: "Scripts",
                 scripts,
             };
-            Html(template.render().unwrap()).into_response()
+            render_html(&template)
         }
         Err(e) => {
             eprintln!("Failed to get all scripts: {}", e);
@@ -67,5 +68,5 @@ pub async fn template_script(id: Option<Path<String>>, title: &str) -> Response
         title,
         script: script_yaml.as_deref(),
     };
-    Html(template.render().unwrap()).into_response()
+    render_html(&template)
 }
@@ -0,0 +1,60 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How long a delivery id is remembered. GitHub redelivers within seconds when an attempt times out.
+pub const WEBHOOK_DELIVERY_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// `X-GitHub-Delivery` ids of recent webhook requests. Redeliveries of an event arriving while the first delivery is
+/// still being handled are acknowledged without starting runs again.
+#[derive(Debug)]
+pub struct WebhookDeliveries {
+    window: Duration,
+    seen: Mutex<HashMap<String, Instant>>,
+}
+
+impl WebhookDeliveries {
+    pub fn new(window: Duration) -> Self {
+        WebhookDeliveries {
+            window,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records `delivery_id`, `false` if it was already seen within the window.
+    pub fn first_seen(&self, delivery_id: &str) -> bool {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, seen_at| now.duration_since(*seen_at) < self.window);
+        if seen.contains_key(delivery_id) {
+            return false;
+        }
+        seen.insert(delivery_id.to_string(), now);
+        true
+    }
+}
+
+impl Default for WebhookDeliveries {
+    fn default() -> Self {
+        Self::new(WEBHOOK_DELIVERY_WINDOW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_seen() {
+        let deliveries = WebhookDeliveries::default();
+        assert!(deliveries.first_seen("72d3162e-cc78-11e3-81ab-4c9367dc0958"));
+        assert!(!deliveries.first_seen("72d3162e-cc78-11e3-81ab-4c9367dc0958"));
+        assert!(deliveries.first_seen("98f1a4b2-cc78-11e3-81ab-4c9367dc0958"));
+
+        let deliveries = WebhookDeliveries::new(Duration::ZERO);
+        assert!(deliveries.first_seen("72d3162e-cc78-11e3-81ab-4c9367dc0958"));
+        assert!(deliveries.first_seen("72d3162e-cc78-11e3-81ab-4c9367dc0958"));
+    }
+}
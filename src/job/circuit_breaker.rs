@@ -0,0 +1,173 @@
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+use crate::{script::models::ScriptStatus, utils::data_root};
+
+/// Consecutive failures after which webhook triggers of a job are skipped.
+pub const CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+/// How long webhook triggers are skipped once the circuit is open.
+pub const CIRCUIT_BREAKER_COOLDOWN_SECONDS: i64 = 10 * 60;
+
+pub fn default_circuit_breakers_location() -> Result<PathBuf, String> {
+    Ok(data_root()?.join("circuit_breakers.json"))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct CircuitState {
+    pub consecutive_failures: u32,
+    /// Set when `consecutive_failures` reaches the threshold. Another failure after the cooldown opens it again.
+    pub opened_at: Option<DateTime<Utc>>,
+}
+
+/// Tracks consecutive failures per job and skips webhook-triggered runs of jobs failing repeatedly, so a broken job
+/// doesn't run on every push. Manual runs are not affected and a success closes the circuit.
+///
+/// The states are kept in memory and read from the file on first use. The file is only written when a circuit
+/// opens or closes, so failures below the threshold are not counted across restarts.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// Nothing is tracked without a path.
+    path: Option<PathBuf>,
+    threshold: u32,
+    cooldown: Duration,
+    states: Mutex<Option<HashMap<String, CircuitState>>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(path: Option<PathBuf>, threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            path,
+            threshold,
+            cooldown,
+            states: Mutex::new(None),
+        }
+    }
+
+    /// Persisted at `default_circuit_breakers_location()`, with the default threshold and cooldown.
+    pub fn load() -> Self {
+        Self::new(
+            default_circuit_breakers_location().ok(),
+            CIRCUIT_BREAKER_THRESHOLD,
+            Duration::seconds(CIRCUIT_BREAKER_COOLDOWN_SECONDS),
+        )
+    }
+
+    pub fn state(&self, job_id: &str) -> CircuitState {
+        self.with_states(|states| states.get(job_id).cloned().unwrap_or_default())
+    }
+
+    /// Returns when webhook triggers of the job are accepted again, if the circuit is open.
+    pub fn open_until(&self, job_id: &str) -> Option<DateTime<Utc>> {
+        let opened_at = self.state(job_id).opened_at?;
+        let until = opened_at + self.cooldown;
+        (Utc::now() < until).then_some(until)
+    }
+
+    /// Counts a finished run. Aborted runs are ignored.
+    pub fn record(&self, job_id: &str, status: &ScriptStatus) -> Result<(), String> {
+        if self.path.is_none() {
+            return Ok(());
+        }
+        self.with_states(|states| {
+            let opened = match status {
+                ScriptStatus::Success => states.remove(job_id).is_some_and(|state| state.opened_at.is_some()),
+                ScriptStatus::Failed => {
+                    let state = states.entry(job_id.to_string()).or_default();
+                    state.consecutive_failures += 1;
+                    if state.consecutive_failures < self.threshold {
+                        return Ok(());
+                    }
+                    if state.opened_at.is_none() {
+                        eprintln!(
+                            "Job {} failed {} times in a row, skipping webhook triggers",
+                            job_id, state.consecutive_failures
+                        );
+                    }
+                    state.opened_at = Some(Utc::now());
+                    true
+                }
+                ScriptStatus::Aborted | ScriptStatus::Skipped => false,
+            };
+            if opened {
+                self.save(states)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Runs `f` with the states, read from the file on first use.
+    fn with_states<T>(&self, f: impl FnOnce(&mut HashMap<String, CircuitState>) -> T) -> T {
+        let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+        f(states.get_or_insert_with(|| self.read()))
+    }
+
+    fn read(&self) -> HashMap<String, CircuitState> {
+        let Some(Ok(content)) = self.path.as_ref().map(std::fs::read_to_string) else {
+            return HashMap::new();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("Failed to parse circuit breakers: {}", e);
+            HashMap::new()
+        })
+    }
+
+    /// Written to a temporary file first, so a crash can't leave a partial file behind.
+    fn save(&self, states: &HashMap<String, CircuitState>) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let directory = path.parent().ok_or("Circuit breakers file has no directory")?;
+        let mut temporary = NamedTempFile::new_in(directory).map_err(|e| e.to_string())?;
+        serde_json::to_writer(&mut temporary, states).map_err(|e| e.to_string())?;
+        temporary.persist(path).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circuit_breaker(directory: &tempfile::TempDir, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(Some(directory.path().join("circuit_breakers.json")), 3, cooldown)
+    }
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let directory = tempfile::tempdir().unwrap();
+        let breaker = circuit_breaker(&directory, Duration::minutes(10));
+
+        breaker.record("job", &ScriptStatus::Failed).unwrap();
+        breaker.record("job", &ScriptStatus::Aborted).unwrap();
+        breaker.record("job", &ScriptStatus::Failed).unwrap();
+        assert!(breaker.open_until("job").is_none());
+        // Only opening and closing the circuit is persisted
+        assert!(!directory.path().join("circuit_breakers.json").exists());
+        breaker.record("job", &ScriptStatus::Failed).unwrap();
+        assert!(breaker.open_until("job").is_some());
+        assert!(breaker.open_until("other").is_none());
+
+        // Persisted
+        let reloaded = circuit_breaker(&directory, Duration::minutes(10));
+        assert_eq!(reloaded.state("job").consecutive_failures, 3);
+        assert!(reloaded.open_until("job").is_some());
+
+        breaker.record("job", &ScriptStatus::Success).unwrap();
+        assert_eq!(breaker.state("job"), CircuitState::default());
+        assert!(breaker.open_until("job").is_none());
+    }
+
+    #[test]
+    fn test_closes_after_cooldown() {
+        let directory = tempfile::tempdir().unwrap();
+        let breaker = circuit_breaker(&directory, Duration::zero());
+        for _ in 0..3 {
+            breaker.record("job", &ScriptStatus::Failed).unwrap();
+        }
+        assert!(breaker.state("job").opened_at.is_some());
+        assert!(breaker.open_until("job").is_none());
+    }
+}
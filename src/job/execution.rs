@@ -1,26 +1,79 @@
+use chrono::Utc;
+use serde::Serialize;
 use std::{
     collections::HashMap,
     fs,
+    future::Future,
     path::{Path, PathBuf},
+    pin::Pin,
     sync::Arc,
+    time::{Duration, Instant},
 };
-use sysinfo::System;
 use tokio::{
     sync::Mutex,
     task::{self},
 };
 
 use crate::{
-    job::models::{Job, JobResult},
+    error::NomosError,
+    job::{
+        circuit_breaker::CircuitBreaker,
+        idempotency::{IdempotencyKeys, Reservation},
+        models::{compress_results_enabled, CleanupPolicy, ConcurrencyPolicy, Job, JobCancellation, JobResult},
+        queue::{default_queue_location, max_running_jobs, ExecutionQueue, QueuedExecution},
+        secrets::RunSecrets,
+    },
+    notification::notify,
     script::{
-        models::{Script, ScriptStatus},
+        models::{RunningScriptStep, Script, ScriptStatus},
         ScriptExecutionContext, ScriptExecutor, ScriptParameterType,
     },
-    utils::get_process_recursive,
+    utils::dry_run_directory,
 };
+
+/// Kept by `cleanup_directory`. On Windows the log and the step logs are in the working directory too.
+const KEPT_FILES: [&str; 5] = ["result.yml", "result.yml.gz", "log", "log.gz", "steps"];
+
+/// Outcome of a dry run.
+#[derive(Debug, Serialize)]
+pub struct DryRunResult {
+    pub steps: Vec<RunningScriptStep>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct RunningJob {
+    job_id: String,
+    /// Dry runs are ignored by the concurrency policy of the job.
+    dry_run: bool,
+    abort_handle: task::AbortHandle,
+}
+
+/// How `spawn` starts a run.
+#[derive(Default)]
+struct SpawnOptions<'a> {
+    /// Commands are only logged.
+    dry_run: bool,
+    /// Previous result, the step to rerun it from and the secrets it was run with.
+    rerun: Option<(&'a JobResult, &'a str, HashMap<String, ScriptParameterType>)>,
+    /// The run waited in the queue, its result is created under the queued id.
+    queued: Option<QueuedExecution>,
+}
+
 #[derive(Debug, Clone)]
 pub struct JobExecutor {
-    handles: Arc<Mutex<HashMap<String, task::AbortHandle>>>,
+    /// Keyed by job result id.
+    handles: Arc<Mutex<HashMap<String, RunningJob>>>,
+    /// Cancellation details of stopped jobs, applied to the result once the task is aborted.
+    cancellations: Arc<Mutex<HashMap<String, JobCancellation>>>,
+    /// Loaded on first use.
+    idempotency_keys: Arc<Mutex<Option<IdempotencyKeys>>>,
+    pub circuit_breaker: Arc<CircuitBreaker>,
+    /// Runs beyond this many wait in `queue`. Unlimited if `None`.
+    max_running: Option<usize>,
+    queue: Arc<Mutex<ExecutionQueue>>,
+    /// Secret parameters of recent runs, which their results don't keep.
+    secrets: Arc<Mutex<RunSecrets>>,
 }
 
 impl Default for JobExecutor {
@@ -31,21 +84,335 @@ impl Default for JobExecutor {
 
 impl JobExecutor {
     pub fn new() -> Self {
+        Self::with_queue(max_running_jobs(), default_queue_location().ok())
+    }
+
+    /// Executor running at most `max_running` jobs at once, further runs wait in the queue persisted at `queue_path`.
+    pub fn with_queue(max_running: Option<usize>, queue_path: Option<PathBuf>) -> Self {
         JobExecutor {
             handles: Arc::new(Mutex::new(HashMap::new())),
+            cancellations: Arc::new(Mutex::new(HashMap::new())),
+            idempotency_keys: Arc::new(Mutex::new(None)),
+            circuit_breaker: Arc::new(CircuitBreaker::load()),
+            max_running,
+            queue: Arc::new(Mutex::new(ExecutionQueue::new(queue_path))),
+            secrets: Arc::new(Mutex::new(RunSecrets::default())),
         }
     }
 
+    /// Starts the runs queued before a restart, as far as the limit allows.
+    pub async fn resume_queue(&self) {
+        self.start_queued().await
+    }
+
+    /// Executes the job unless `idempotency_key` was already seen for it recently,
+    /// in which case the id of the existing job result is returned. A duplicate arriving while the job is being
+    /// started waits for its job result.
+    pub async fn execute_idempotent(
+        &self,
+        job: &Job,
+        parameters: HashMap<String, ScriptParameterType>,
+        idempotency_key: Option<&str>,
+    ) -> Result<String, String> {
+        let Some(idempotency_key) = idempotency_key else {
+            return job.execute(self, parameters).await;
+        };
+
+        let key = format!("{}:{}", job.id, idempotency_key);
+        // The lock is only held to reserve the key, not while the job starts
+        let reservation = self
+            .idempotency_keys
+            .lock()
+            .await
+            .get_or_insert_with(IdempotencyKeys::load)
+            .reserve(&key);
+        let job_result_id = match reservation {
+            Reservation::Seen(job_result_id) => job_result_id,
+            Reservation::Running(mut receiver) => {
+                let _ = receiver.changed().await;
+                let job_result_id = receiver.borrow().clone();
+                job_result_id
+                    .ok_or_else(|| format!("Execution of job {} for key {} failed", job.id, idempotency_key))?
+            }
+            Reservation::Reserved(sender) => {
+                let result = job.execute(self, parameters).await;
+                self.idempotency_keys
+                    .lock()
+                    .await
+                    .get_or_insert_with(IdempotencyKeys::load)
+                    .release(key, sender, result.as_ref().ok().cloned());
+                return result;
+            }
+        };
+        eprintln!(
+            "Skipping duplicate execution of job {} for key {}, existing result: {}",
+            job.id, idempotency_key, job_result_id
+        );
+        Ok(job_result_id)
+    }
+
     pub async fn execute_with_script(
         &self,
         job: &Job,
         parameters: HashMap<String, ScriptParameterType>,
         script: &Script,
     ) -> Result<String, String> {
+        self.spawn(job, parameters, script, SpawnOptions::default()).await
+    }
+
+    /// Executes the job like `execute_with_script`, but commands are only logged. Unlike `dry_run`, the result is
+    /// persisted so it can be inspected like any other run.
+    pub async fn dry_execute(
+        &self,
+        job: &Job,
+        parameters: HashMap<String, ScriptParameterType>,
+        script: &Script,
+    ) -> Result<String, String> {
+        self.spawn(
+            job,
+            parameters,
+            script,
+            SpawnOptions {
+                dry_run: true,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    /// Starts a new run of the job at `step_name`. The steps before it are skipped, and the parameters, step outputs
+    /// and working directory files of the finished run `previous` are carried forward.
+    pub async fn rerun_from(
+        &self,
+        job: &Job,
+        script: &Script,
+        previous: &JobResult,
+        step_name: &str,
+    ) -> Result<String, NomosError> {
+        if previous.finished_at.is_none() {
+            return Err(NomosError::Validation(format!(
+                "Job result {} is still running",
+                previous.id
+            )));
+        }
+        if !script.steps.iter().any(|step| step.name == step_name) {
+            return Err(NomosError::Validation(format!(
+                "Step {:?} not found in script {:?}",
+                step_name, script.id
+            )));
+        }
+
+        // Secrets are redacted in saved results and only kept in memory
+        let secrets = self.secrets.lock().await.get(&previous.id).cloned();
+        let secrets = match secrets {
+            Some(secrets) => secrets,
+            None if previous.parameters.values().any(|value| value.is_secret()) => {
+                return Err(NomosError::Validation(format!(
+                    "The secret parameters of job result {} are no longer available, run the job again",
+                    previous.id
+                )))
+            }
+            None => HashMap::new(),
+        };
+
+        let options = SpawnOptions {
+            rerun: Some((previous, step_name, secrets)),
+            ..Default::default()
+        };
+        Ok(self.spawn(job, previous.parameters.clone(), script, options).await?)
+    }
+
+    /// Starts queued runs while there are free slots. A run whose job or script was deleted while it waited is
+    /// marked failed.
+    fn start_queued(&self) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        // Boxed, as spawned runs call this again when they finish
+        Box::pin(async move {
+            let Some(max_running) = self.max_running else {
+                return;
+            };
+            while self.running_count().await < max_running {
+                let entry = match self.queue.lock().await.pop_front() {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => return,
+                    Err(e) => {
+                        eprintln!("Failed to read the execution queue: {}", e);
+                        return;
+                    }
+                };
+
+                let job_result_id = entry.job_result_id.clone();
+                let job = match Job::get(&entry.job_id) {
+                    Ok(Some(job)) => job,
+                    Ok(None) => {
+                        let message = format!("Job {} was deleted while the run was queued", entry.job_id);
+                        Self::finish_queued(&job_result_id, ScriptStatus::Failed, message, None);
+                        continue;
+                    }
+                    Err(e) => {
+                        let message = format!("Failed to get job {}: {}", entry.job_id, e);
+                        Self::finish_queued(&job_result_id, ScriptStatus::Failed, message, None);
+                        continue;
+                    }
+                };
+                let script = match Script::get(&job.script_id) {
+                    Ok(Some(script)) => script,
+                    Ok(None) => {
+                        let message = format!("Script {} was deleted while the run was queued", job.script_id);
+                        Self::finish_queued(&job_result_id, ScriptStatus::Failed, message, None);
+                        continue;
+                    }
+                    Err(e) => {
+                        let message = format!("Failed to get script {}: {}", job.script_id, e);
+                        Self::finish_queued(&job_result_id, ScriptStatus::Failed, message, None);
+                        continue;
+                    }
+                };
+
+                let mut parameters = entry.parameters.clone();
+                if !entry.secret_parameters.is_empty() {
+                    match self.secrets.lock().await.get(&job_result_id) {
+                        Some(secrets) => parameters.extend(secrets.clone()),
+                        None => {
+                            let message = "The secret parameters of the run were lost while it was queued, e.g. by a \
+                                           restart. Run the job again"
+                                .to_string();
+                            Self::finish_queued(&job_result_id, ScriptStatus::Failed, message, None);
+                            continue;
+                        }
+                    }
+                }
+                let options = SpawnOptions {
+                    queued: Some(entry),
+                    ..Default::default()
+                };
+                if let Err(e) = self.spawn(&job, parameters, &script, options).await {
+                    Self::finish_queued(&job_result_id, ScriptStatus::Failed, e, None);
+                }
+            }
+        })
+    }
+
+    /// Finishes the result of a run that never left the queue.
+    fn finish_queued(
+        job_result_id: &str,
+        status: ScriptStatus,
+        message: String,
+        cancellation: Option<JobCancellation>,
+    ) {
+        match JobResult::get(job_result_id) {
+            Ok(Some(mut job_result)) => {
+                job_result.add_log(crate::log::LogLevel::Error, message);
+                let now = Utc::now();
+                job_result.queued = false;
+                job_result.status = status;
+                job_result.cancellation = cancellation;
+                job_result.updated_at = now;
+                job_result.finished_at = Some(now);
+                if let Err(e) = job_result.save() {
+                    eprintln!("Failed to save job result {}: {}", job_result_id, e);
+                }
+            }
+            Ok(None) => eprintln!("{}", message),
+            Err(e) => eprintln!("Failed to get job result {}: {}", job_result_id, e),
+        }
+    }
+
+    /// Finishes the result of a run removed from the queue as aborted.
+    fn stop_queued(job_result_id: &str, reason: String, cancelled_by: Option<String>) {
+        let message = format!(
+            "Stopped by {} while queued: {}",
+            cancelled_by.as_deref().unwrap_or("unknown"),
+            reason
+        );
+        let cancellation = JobCancellation {
+            reason,
+            cancelled_by,
+            cancelled_at: Utc::now(),
+        };
+        Self::finish_queued(job_result_id, ScriptStatus::Aborted, message, Some(cancellation));
+    }
+
+    async fn running_count(&self) -> usize {
+        let handles = self.handles.lock().await;
+        Self::count_running(&handles)
+    }
+
+    /// Runs counting towards the limit, dry runs don't.
+    fn count_running(handles: &HashMap<String, RunningJob>) -> usize {
+        handles
+            .values()
+            .filter(|running| !running.dry_run && !running.abort_handle.is_finished())
+            .count()
+    }
+
+    /// Metadata of the run, available as `$(nomos.result_id)`, `$(nomos.job_id)`, `$(nomos.job_name)` and
+    /// `$(nomos.started_at)`. Job and script parameters live under `parameters.`, so they can't shadow these.
+    fn insert_builtin_parameters(
+        job: &Job,
+        job_result: &JobResult,
+        parameters: &mut HashMap<String, ScriptParameterType>,
+    ) {
+        let builtins = [
+            ("result_id", job_result.id.clone()),
+            ("job_id", job.id.clone()),
+            ("job_name", job.name.clone()),
+            ("started_at", job_result.started_at.to_rfc3339()),
+        ];
+        for (name, value) in builtins {
+            parameters.insert(format!("nomos.{}", name), ScriptParameterType::String(value));
+        }
+    }
+
+    async fn spawn(
+        &self,
+        job: &Job,
+        parameters: HashMap<String, ScriptParameterType>,
+        script: &Script,
+        options: SpawnOptions<'_>,
+    ) -> Result<String, String> {
+        let SpawnOptions { dry_run, rerun, queued } = options;
         job.validate_parameters(Some(script))?;
+        job.validate_provided_parameters(Some(script), &parameters)?;
+
+        // Held until the new run is registered, so concurrent triggers can't both pass the concurrency check.
+        let mut handles = self.handles.lock().await;
+        let mut running: Vec<String> = handles
+            .iter()
+            .filter(|(_, running)| running.job_id == job.id && !running.dry_run && !running.abort_handle.is_finished())
+            .map(|(id, _)| id.clone())
+            .collect();
+        running.sort();
+        match job.concurrency {
+            // Applied when a queued run was submitted
+            _ if dry_run || queued.is_some() => {}
+            ConcurrencyPolicy::Allow => {}
+            ConcurrencyPolicy::SkipIfRunning => {
+                if let Some(id) = running.first() {
+                    eprintln!("Job {} is already running as {}, skipping", job.id, id);
+                    return Ok(id.clone());
+                }
+            }
+            ConcurrencyPolicy::CancelPrevious => {
+                for id in running {
+                    eprintln!("Cancelling previous run {} of job {}", id, job.id);
+                    self.abort(&mut handles, &id, "Cancelled by a newer run".to_string(), None)
+                        .await;
+                }
+            }
+        }
 
         let mut merged_parameters = job.merged_parameters(Some(script), parameters.clone())?;
-        let job_result = JobResult::try_from((job, script, false))?;
+        let mut job_result = match &queued {
+            Some(entry) => JobResult::from_script(job, script, entry.job_result_id.clone(), false)?,
+            None => JobResult::try_from((job, script, false))?,
+        };
+        if dry_run {
+            job_result.dry_run = true;
+            job_result.add_log(
+                crate::log::LogLevel::Info,
+                "Dry run: commands are logged but not executed".to_string(),
+            );
+        }
         let id = job_result.id.clone();
         let cloned_id = id.clone();
         let other_id = id.clone();
@@ -53,54 +420,133 @@ impl JobExecutor {
         let directory = crate::job::utils::default_job_results_location()?.join(&job_result.id);
         fs::create_dir_all(&directory).map_err(|e| format!("Failed to create job result directory: {}", e))?;
 
+        // Dry runs and reruns bypass the limit, the queue can't hold the state of a rerun.
+        if let (Some(max_running), false, true) = (self.max_running, dry_run, rerun.is_none()) {
+            let running = Self::count_running(&handles);
+            let mut queue = self.queue.lock().await;
+            match queued {
+                Some(entry) if running >= max_running => {
+                    // Still the first in line
+                    queue.push_front(entry)?;
+                    return Ok(id);
+                }
+                Some(entry) => {
+                    job_result.add_log(
+                        crate::log::LogLevel::Info,
+                        format!("Started after waiting in the queue since {}", entry.queued_at),
+                    );
+                }
+                None if running >= max_running || !queue.is_empty() => {
+                    job_result.queued = true;
+                    job_result.add_log(
+                        crate::log::LogLevel::Info,
+                        format!("Queued, {} of at most {} runs are running", running, max_running),
+                    );
+                    job_result.save()?;
+                    // The queue is persisted, the secrets wait in memory
+                    let (secrets, parameters): (HashMap<_, _>, HashMap<_, _>) = job
+                        .with_declared_types(script, parameters)
+                        .into_iter()
+                        .partition(|(_, value)| value.is_secret());
+                    self.secrets.lock().await.insert(&id, &secrets);
+                    queue.push_back(QueuedExecution {
+                        job_result_id: id.clone(),
+                        job_id: job.id.clone(),
+                        parameters,
+                        secret_parameters: secrets.into_keys().collect(),
+                        queued_at: Utc::now(),
+                    })?;
+                    return Ok(id);
+                }
+                None => {}
+            }
+        }
+
+        if let Some((previous, step_name, secrets)) = rerun {
+            Self::prepare_rerun(
+                &mut job_result,
+                &mut merged_parameters,
+                previous,
+                secrets,
+                step_name,
+                &directory,
+            )?;
+        }
+        Self::insert_builtin_parameters(job, &job_result, &mut merged_parameters);
+        job_result.parameters = merged_parameters.clone();
         job_result.save()?;
+        if !dry_run {
+            self.secrets.lock().await.insert(&job_result.id, &merged_parameters);
+        }
 
         let mut job_result_clone = job_result.clone();
+        let cleanup = job.cleanup;
+        let aborted_directory = directory.clone();
+        let circuit_breaker = Arc::clone(&self.circuit_breaker);
+        let job_id = job.id.clone();
+        let notifications = job.notifications.clone();
+        let aborted_notifications = notifications.clone();
         let handle = task::spawn(async move {
             let _res =
-                Self::execute_job_result_internal(&mut job_result_clone, &directory, &mut merged_parameters).await;
+                Self::execute_job_result_internal(&mut job_result_clone, &directory, &mut merged_parameters, cleanup)
+                    .await;
+            if dry_run {
+                return;
+            }
+            if let Err(e) = circuit_breaker.record(&job_id, &job_result_clone.status) {
+                eprintln!("Failed to record the result of job {}: {}", job_id, e);
+            }
+            notify(&notifications, &job_result_clone).await;
         });
         let abort_handle = handle.abort_handle();
+        let cancellations = Arc::clone(&self.cancellations);
+        let executor = self.clone();
         task::spawn(async move {
             match handle.await {
                 Ok(_) => {}
                 Err(e) => {
                     if e.is_cancelled() {
                         let message = format!("Cancelled job {}: {}", other_id, e);
+                        let cancellation = cancellations.lock().await.remove(&other_id);
                         match JobResult::get(other_id.as_str()) {
                             Ok(Some(mut job_result)) => {
                                 job_result.add_log(crate::log::LogLevel::Error, message.clone());
-                                let s = System::new_all();
-                                for child_process in &job_result.child_process_ids {
-                                    let mut processes = get_process_recursive(*child_process);
-                                    processes.reverse(); // Kill child processes first
-                                    eprintln!("Killing processes with PID {}", child_process);
-                                    for process in processes {
-                                        if let Some(process) = s.process(process) {
-                                            job_result.add_log(
-                                                crate::log::LogLevel::Info,
-                                                format!("Killing process with PID {}", process.pid()),
-                                            );
-                                            process.kill();
-                                        } else {
-                                            eprintln!("Process with PID {} not found", process);
-                                        }
-                                    }
+                                if let Some(cancellation) = &cancellation {
+                                    job_result.add_log(
+                                        crate::log::LogLevel::Error,
+                                        format!(
+                                            "Stopped by {}: {}",
+                                            cancellation.cancelled_by.as_deref().unwrap_or("unknown"),
+                                            cancellation.reason
+                                        ),
+                                    );
                                 }
+                                job_result.cancellation = cancellation;
+                                job_result.kill_child_processes();
                                 match job_result.finish_step(ScriptStatus::Aborted) {
                                     Ok(_) => {}
                                     Err(e) => {
                                         eprintln!("Failed to finish step: {}", e);
                                     }
                                 }
-                                job_result.child_process_ids.clear();
                                 job_result.status = ScriptStatus::Aborted;
+                                if cleanup.should_cleanup(&job_result.status) {
+                                    if let Err(e) = Self::cleanup_directory(&aborted_directory) {
+                                        eprintln!("Failed to clean up working directory: {}", e);
+                                    }
+                                }
                                 match job_result.save() {
                                     Ok(_) => {}
                                     Err(e) => {
                                         eprintln!("Failed to save job result: {}", e);
                                     }
                                 }
+                                if compress_results_enabled() {
+                                    if let Err(e) = job_result.compress() {
+                                        eprintln!("Failed to compress job result: {}", e);
+                                    }
+                                }
+                                notify(&aborted_notifications, &job_result).await;
                             }
                             Ok(None) => {
                                 eprintln!("{}", message);
@@ -112,19 +558,95 @@ impl JobExecutor {
                     }
                 }
             }
+            // The slot of the run is free now
+            executor.start_queued().await;
         });
 
-        self.handles.lock().await.insert(id, abort_handle);
+        handles.insert(
+            id,
+            RunningJob {
+                job_id: job.id.clone(),
+                dry_run,
+                abort_handle,
+            },
+        );
 
         Ok(cloned_id)
     }
 
+    /// Skips the steps before `step_name` and carries forward the state of `previous`, with the secrets it was run
+    /// with. Files are copied, so the previous run stays untouched.
+    fn prepare_rerun(
+        job_result: &mut JobResult,
+        parameters: &mut HashMap<String, ScriptParameterType>,
+        previous: &JobResult,
+        secrets: HashMap<String, ScriptParameterType>,
+        step_name: &str,
+        directory: &Path,
+    ) -> Result<(), String> {
+        let previous_directory = crate::job::utils::default_job_results_location()?.join(&previous.id);
+        if previous_directory.exists() {
+            Self::copy_directory(&previous_directory, directory, true)?;
+        }
+
+        for (key, value) in previous.parameters.iter().filter(|(_, value)| !value.is_secret()) {
+            // Outputs like the clone directory point into the previous working directory
+            let value = match value {
+                ScriptParameterType::String(s) => match Path::new(s).strip_prefix(&previous_directory) {
+                    Ok(relative) => ScriptParameterType::String(directory.join(relative).to_string_lossy().to_string()),
+                    Err(_) => value.clone(),
+                },
+                _ => value.clone(),
+            };
+            parameters.insert(key.clone(), value);
+        }
+        parameters.extend(secrets);
+        job_result.working_subdir = previous.working_subdir.clone();
+
+        for step in job_result.steps.iter_mut().take_while(|step| step.name != step_name) {
+            step.status = ScriptStatus::Skipped;
+        }
+        job_result.current_step_name = Some(step_name.to_string());
+        job_result.add_log(
+            crate::log::LogLevel::Info,
+            format!("Rerun of {} from step {}", previous.id, step_name),
+        );
+        Ok(())
+    }
+
+    /// Copies the contents of `from` into `to`. With `skip_results`, the result and log files of `from` are left out.
+    fn copy_directory(from: &Path, to: &Path, skip_results: bool) -> Result<(), String> {
+        for entry in fs::read_dir(from).map_err(|e| e.to_string())? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            if skip_results
+                && ["result.yml", "result.yml.gz", "log", "log.gz"]
+                    .iter()
+                    .any(|n| name == *n)
+            {
+                continue;
+            }
+            let target = to.join(name);
+            if path.is_dir() {
+                fs::create_dir_all(&target).map_err(|e| e.to_string())?;
+                Self::copy_directory(&path, &target, false)?;
+            } else {
+                fs::copy(&path, &target).map_err(|e| format!("Failed to copy {}: {}", path.display(), e))?;
+            }
+        }
+        Ok(())
+    }
+
     async fn execute_job_result_internal(
         job_result: &mut JobResult,
         directory: &Path,
         parameters: &mut HashMap<String, ScriptParameterType>,
+        cleanup: CleanupPolicy,
     ) -> Result<(), String> {
         let mut is_success = true;
+        let mut current_working_subdir = job_result.working_subdir.as_ref().map(|subdir| directory.join(subdir));
 
         while job_result.finished_at.is_none() {
             job_result.start_step()?;
@@ -141,17 +663,42 @@ impl JobExecutor {
                 directory,
                 step_name: &step_name,
                 job_result,
+                current_working_subdir: &mut current_working_subdir,
             };
 
-            if let Err(e) = current_step.execute(&mut context).await {
+            let step_started_at = Instant::now();
+            let res = current_step.execute(&mut context).await;
+            job_result.parameters = parameters.clone();
+            job_result.working_subdir = current_working_subdir
+                .as_ref()
+                .and_then(|subdir| subdir.strip_prefix(directory).ok())
+                .map(Path::to_path_buf);
+            if let Err(e) = res {
                 let message = format!("Error in step {}: {}", step_name, e);
                 job_result.add_log(crate::log::LogLevel::Error, message.clone());
-                job_result.finish_step(ScriptStatus::Failed)?;
-                is_success = false;
+                let timed_out = current_step
+                    .timeout_seconds
+                    .is_some_and(|timeout| step_started_at.elapsed() >= Duration::from_secs(timeout));
+                let continues = current_step.continue_on_error && !timed_out && !job_result.ephemeral;
+                if continues {
+                    job_result.add_log(
+                        crate::log::LogLevel::Warning,
+                        format!("Step {} failed, continuing", step_name),
+                    );
+                }
+                job_result.finish_step(if timed_out {
+                    ScriptStatus::Aborted
+                } else {
+                    ScriptStatus::Failed
+                })?;
 
-                if job_result.dry_run {
+                if job_result.ephemeral {
                     return Err(message);
                 }
+                if continues {
+                    continue;
+                }
+                is_success = false;
                 break;
             }
 
@@ -160,7 +707,7 @@ impl JobExecutor {
                 job_result.add_log(crate::log::LogLevel::Error, message.clone());
                 is_success = false;
 
-                if job_result.dry_run {
+                if job_result.ephemeral {
                     return Err(message);
                 }
                 break;
@@ -172,32 +719,168 @@ impl JobExecutor {
         } else {
             ScriptStatus::Failed
         };
+        if !job_result.dry_run && cleanup.should_cleanup(&job_result.status) {
+            match Self::cleanup_directory(directory) {
+                Ok(_) => job_result.add_log(crate::log::LogLevel::Info, "Cleaned up working directory".to_string()),
+                Err(e) => job_result.add_log(
+                    crate::log::LogLevel::Warning,
+                    format!("Failed to clean up working directory: {}", e),
+                ),
+            }
+        }
         job_result.save()?;
+        if compress_results_enabled() {
+            job_result.compress()?;
+        }
 
         Ok(())
     }
 
+    /// Removes everything in the working directory except the result and its logs.
+    fn cleanup_directory(directory: &Path) -> Result<(), String> {
+        for entry in fs::read_dir(directory).map_err(|e| e.to_string())? {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path
+                .file_name()
+                .is_some_and(|name| KEPT_FILES.iter().any(|kept| name == *kept))
+            {
+                continue;
+            }
+            if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            }
+            .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
     pub async fn validate(
         &self,
         job: &Job,
         script: &Script,
         parameters: HashMap<String, ScriptParameterType>,
     ) -> Result<(), String> {
+        match self.dry_run(job, script, parameters).await?.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Executes the script in dry-run mode and reports which steps would run. Step failures are returned in
+    /// `DryRunResult::error` rather than as `Err`.
+    pub async fn dry_run(
+        &self,
+        job: &Job,
+        script: &Script,
+        parameters: HashMap<String, ScriptParameterType>,
+    ) -> Result<DryRunResult, String> {
         let mut merged_parameters = job.merged_parameters(Some(script), parameters)?;
         let mut job_result = JobResult::try_from((job, script, true))?;
-        let directory = PathBuf::from("tmp");
+        Self::insert_builtin_parameters(job, &job_result, &mut merged_parameters);
+        let directory = dry_run_directory()?;
 
-        Self::execute_job_result_internal(&mut job_result, &directory, &mut merged_parameters).await
+        let res = Self::execute_job_result_internal(
+            &mut job_result,
+            &directory,
+            &mut merged_parameters,
+            CleanupPolicy::Never,
+        )
+        .await;
+        Ok(DryRunResult {
+            steps: job_result.steps,
+            error: res.err(),
+        })
     }
 
-    pub async fn stop_job(&self, id: &str) -> Result<(), String> {
+    pub async fn stop_job(&self, id: &str, reason: String, cancelled_by: Option<String>) -> Result<(), String> {
         let mut handles = self.handles.lock().await;
-        if let Some(handle) = handles.get(id) {
-            handle.abort();
-            handles.remove(id);
-            Ok(())
-        } else {
-            Err(format!("Job {} not found", id))
+        if self.abort(&mut handles, id, reason.clone(), cancelled_by.clone()).await {
+            return Ok(());
+        }
+        if self.queue.lock().await.remove(id)?.is_some() {
+            Self::stop_queued(id, reason, cancelled_by);
+            return Ok(());
         }
+        Err(format!("Job {} not found", id))
+    }
+
+    /// Stops every running and queued run and returns their job result ids. The runs are collected and aborted under
+    /// the lock that new runs register with, so a run starting meanwhile is either stopped or starts afterwards.
+    pub async fn stop_all(&self, reason: String, cancelled_by: Option<String>) -> Result<Vec<String>, String> {
+        let mut handles = self.handles.lock().await;
+        // Emptied first, so aborted runs don't start the queued ones
+        let mut queued = vec![];
+        {
+            let mut queue = self.queue.lock().await;
+            while let Some(entry) = queue.pop_front()? {
+                queued.push(entry.job_result_id);
+            }
+        }
+
+        let mut running: Vec<String> = handles
+            .iter()
+            .filter(|(_, running)| !running.abort_handle.is_finished())
+            .map(|(id, _)| id.clone())
+            .collect();
+        running.sort();
+        for id in &running {
+            self.abort(&mut handles, id, reason.clone(), cancelled_by.clone()).await;
+        }
+
+        for id in &queued {
+            Self::stop_queued(id, reason.clone(), cancelled_by.clone());
+        }
+
+        running.extend(queued);
+        Ok(running)
+    }
+
+    /// Aborts the run of job result `id`. Returns false if it isn't known.
+    async fn abort(
+        &self,
+        handles: &mut HashMap<String, RunningJob>,
+        id: &str,
+        reason: String,
+        cancelled_by: Option<String>,
+    ) -> bool {
+        let Some(running) = handles.remove(id) else {
+            return false;
+        };
+        self.cancellations.lock().await.insert(
+            id.to_string(),
+            JobCancellation {
+                reason,
+                cancelled_by,
+                cancelled_at: Utc::now(),
+            },
+        );
+        running.abort_handle.abort();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cleanup_keeps_logs() {
+        let directory = tempfile::tempdir().unwrap();
+        for file in ["result.yml", "log.gz", "build.o"] {
+            fs::write(directory.path().join(file), "").unwrap();
+        }
+        fs::create_dir_all(directory.path().join("steps")).unwrap();
+        fs::create_dir_all(directory.path().join("target")).unwrap();
+
+        JobExecutor::cleanup_directory(directory.path()).unwrap();
+
+        let mut left = fs::read_dir(directory.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(left, vec!["log.gz", "result.yml", "steps"]);
     }
 }
@@ -0,0 +1,213 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::utils::data_root;
+
+/// Number of keys remembered before the least recently used ones are dropped.
+pub const IDEMPOTENCY_KEYS_CAPACITY: usize = 1000;
+/// How long a key is remembered.
+pub const IDEMPOTENCY_KEYS_TTL_SECONDS: i64 = 60 * 60;
+
+pub fn default_idempotency_keys_location() -> Result<PathBuf, String> {
+    Ok(data_root()?.join("idempotency_keys.json"))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IdempotencyEntry {
+    key: String,
+    job_result_id: String,
+    seen_at: DateTime<Utc>,
+}
+
+/// Result of `IdempotencyKeys::reserve`.
+#[derive(Debug)]
+pub enum Reservation {
+    /// The key was seen recently, with the job result it started.
+    Seen(String),
+    /// Another execution holds the key. The receiver gets its job result id, or `None` if it failed.
+    Running(watch::Receiver<Option<String>>),
+    /// The key is held by the caller until it is passed to `IdempotencyKeys::release`.
+    Reserved(watch::Sender<Option<String>>),
+}
+
+/// Recently seen idempotency keys and the job results they started. Least recently used first.
+#[derive(Debug)]
+pub struct IdempotencyKeys {
+    entries: VecDeque<IdempotencyEntry>,
+    /// Keys of the executions in progress, not persisted.
+    running: HashMap<String, watch::Receiver<Option<String>>>,
+    capacity: usize,
+    ttl: Duration,
+    path: Option<PathBuf>,
+}
+
+impl IdempotencyKeys {
+    pub fn new(capacity: usize, ttl: Duration, path: Option<PathBuf>) -> Self {
+        IdempotencyKeys {
+            entries: VecDeque::new(),
+            running: HashMap::new(),
+            capacity,
+            ttl,
+            path,
+        }
+    }
+
+    /// Loads the keys persisted at the default location. Starts empty if they can't be read.
+    pub fn load() -> Self {
+        let path = default_idempotency_keys_location().ok();
+        let mut keys = Self::new(
+            IDEMPOTENCY_KEYS_CAPACITY,
+            Duration::seconds(IDEMPOTENCY_KEYS_TTL_SECONDS),
+            path.clone(),
+        );
+
+        if let Some(content) = path.and_then(|path| std::fs::read_to_string(path).ok()) {
+            match serde_json::from_str::<VecDeque<IdempotencyEntry>>(&content) {
+                Ok(entries) => keys.entries = entries,
+                Err(e) => eprintln!("Failed to parse idempotency keys: {}", e),
+            }
+        }
+        keys
+    }
+
+    /// Returns the job result id started for `key`, if it was seen within the TTL.
+    pub fn get(&mut self, key: &str) -> Option<String> {
+        self.prune();
+        let index = self.entries.iter().position(|entry| entry.key == key)?;
+        let entry = self.entries.remove(index)?;
+        let job_result_id = entry.job_result_id.clone();
+        self.entries.push_back(entry);
+        Some(job_result_id)
+    }
+
+    /// Reserves `key` for an execution, unless it was seen or another execution holds it. A reservation dropped
+    /// without `release`, e.g. by a cancelled request, is taken over.
+    pub fn reserve(&mut self, key: &str) -> Reservation {
+        if let Some(job_result_id) = self.get(key) {
+            return Reservation::Seen(job_result_id);
+        }
+        if let Some(receiver) = self.running.get(key).filter(|receiver| receiver.has_changed().is_ok()) {
+            return Reservation::Running(receiver.clone());
+        }
+        let (sender, receiver) = watch::channel(None);
+        self.running.insert(key.to_string(), receiver);
+        Reservation::Reserved(sender)
+    }
+
+    /// Releases a key of `reserve`. It is remembered if the execution started a job result, otherwise the next
+    /// request with the key executes again.
+    pub fn release(&mut self, key: String, sender: watch::Sender<Option<String>>, job_result_id: Option<String>) {
+        self.running.remove(&key);
+        if let Some(job_result_id) = &job_result_id {
+            self.insert(key, job_result_id.clone());
+        }
+        sender.send_replace(job_result_id);
+    }
+
+    pub fn insert(&mut self, key: String, job_result_id: String) {
+        self.prune();
+        self.entries.retain(|entry| entry.key != key);
+        self.entries.push_back(IdempotencyEntry {
+            key,
+            job_result_id,
+            seen_at: Utc::now(),
+        });
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+
+        if let Err(e) = self.save() {
+            eprintln!("Failed to save idempotency keys: {}", e);
+        }
+    }
+
+    fn prune(&mut self) {
+        let now = Utc::now();
+        self.entries.retain(|entry| now - entry.seen_at < self.ttl);
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let content = serde_json::to_string(&self.entries).map_err(|e| e.to_string())?;
+        // Written to a temporary file first, so a crash can't leave partial keys behind
+        let temporary = path.with_extension("json.tmp");
+        std::fs::write(&temporary, content).map_err(|e| e.to_string())?;
+        std::fs::rename(&temporary, path).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_and_insert() {
+        let mut keys = IdempotencyKeys::new(2, Duration::seconds(60), None);
+        assert_eq!(keys.get("a"), None);
+
+        keys.insert("a".to_string(), "1".to_string());
+        keys.insert("b".to_string(), "2".to_string());
+        assert_eq!(keys.get("a"), Some("1".to_string()));
+
+        // "b" is the least recently used key now
+        keys.insert("c".to_string(), "3".to_string());
+        assert_eq!(keys.get("b"), None);
+        assert_eq!(keys.get("a"), Some("1".to_string()));
+        assert_eq!(keys.get("c"), Some("3".to_string()));
+    }
+
+    #[test]
+    fn test_reserve_and_release() {
+        let mut keys = IdempotencyKeys::new(10, Duration::seconds(60), None);
+        let Reservation::Reserved(sender) = keys.reserve("a") else {
+            panic!("a not reserved");
+        };
+        let Reservation::Running(receiver) = keys.reserve("a") else {
+            panic!("a not running");
+        };
+        assert_eq!(*receiver.borrow(), None);
+
+        keys.release("a".to_string(), sender, Some("1".to_string()));
+        assert_eq!(*receiver.borrow(), Some("1".to_string()));
+        assert!(matches!(keys.reserve("a"), Reservation::Seen(id) if id == "1"));
+
+        // Failed and abandoned executions don't hold the key
+        let Reservation::Reserved(sender) = keys.reserve("b") else {
+            panic!("b not reserved");
+        };
+        keys.release("b".to_string(), sender, None);
+        let Reservation::Reserved(sender) = keys.reserve("b") else {
+            panic!("b not reserved again");
+        };
+        drop(sender);
+        assert!(matches!(keys.reserve("b"), Reservation::Reserved(_)));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("idempotency_keys.json");
+        let mut keys = IdempotencyKeys::new(10, Duration::seconds(60), Some(path.clone()));
+        keys.insert("a".to_string(), "1".to_string());
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let entries: VecDeque<IdempotencyEntry> = serde_json::from_str(&content).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn test_expired_keys() {
+        let mut keys = IdempotencyKeys::new(10, Duration::zero(), None);
+        keys.insert("a".to_string(), "1".to_string());
+        assert_eq!(keys.get("a"), None);
+    }
+}
@@ -1,9 +1,21 @@
+mod circuit_breaker;
 mod execution;
+mod idempotency;
 mod models;
+mod queue;
+mod secrets;
 mod utils;
 
+#[allow(unused_imports)]
+pub use circuit_breaker::*;
 #[allow(unused_imports)]
 pub use execution::*;
+#[allow(unused_imports)]
+pub use idempotency::*;
 pub use models::*;
 #[allow(unused_imports)]
+pub use queue::*;
+#[allow(unused_imports)]
+pub use secrets::*;
+#[allow(unused_imports)]
 pub use utils::*;
@@ -1,23 +1,35 @@
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self, File},
     io::BufReader,
     path::PathBuf,
 };
 
 use crate::{
+    credential::Credential,
+    error::NomosError,
+    git::validate_url,
     job::{
-        execution::JobExecutor,
+        execution::{DryRunResult, JobExecutor},
         models::{JobParameterDefinition, JobResult},
         utils::default_jobs_location,
     },
-    script::{models::Script, ScriptParameter, ScriptParameterType},
+    log::LogLevel,
+    notification::NotificationTarget,
+    script::{
+        models::{Script, ScriptStatus},
+        types::ScriptType,
+        utils::{parameter_text, ParameterSubstitution, SubstitutionResult},
+        ScriptParameter, ScriptParameterType,
+    },
+    settings::SyncAction,
+    utils::entity_path,
 };
 
 use super::{trigger::TriggerType, TriggerPlaceHolder};
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Job {
     pub id: String,
     pub name: String,
@@ -25,6 +37,162 @@ pub struct Job {
     pub triggers: Vec<TriggerType>,
     pub script_id: String,
     pub read_only: bool,
+    /// Disabled jobs are ignored by triggers. Manual executions need to be forced.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// When to delete the working directory of a result after the run.
+    #[serde(default)]
+    pub cleanup: CleanupPolicy,
+    /// Logs below this level are not written to the log file. Errors are always written.
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// What to do when the job is triggered while a previous run is still going.
+    #[serde(default)]
+    pub concurrency: ConcurrencyPolicy,
+    /// Run commands with only `PATH`, `HOME` and the env configured for the job and its steps, instead of the whole
+    /// server environment, which may hold secrets like `NOMOS_SECRET_KEY`.
+    #[serde(default = "default_clean_env")]
+    pub clean_env: bool,
+    /// Free-form labels, e.g. team or environment, to filter jobs by.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Branch of the git-clone steps that don't set one. Falls back to `NOMOS_GIT_DEFAULT_BRANCH`, then `main`.
+    #[serde(default)]
+    pub git_default_branch: Option<String>,
+    /// Sent once a run has finished, e.g. a webhook for a dashboard.
+    #[serde(default)]
+    pub notifications: Vec<NotificationTarget>,
+}
+
+/// Matches the defaults of the YAML fields, e.g. enabled and with a clean environment.
+impl Default for Job {
+    fn default() -> Self {
+        Job {
+            id: String::new(),
+            name: String::new(),
+            parameters: vec![],
+            triggers: vec![],
+            script_id: String::new(),
+            read_only: false,
+            enabled: default_enabled(),
+            cleanup: CleanupPolicy::default(),
+            log_level: LogLevel::default(),
+            concurrency: ConcurrencyPolicy::default(),
+            clean_env: default_clean_env(),
+            tags: vec![],
+            git_default_branch: None,
+            notifications: vec![],
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_clean_env() -> bool {
+    true
+}
+
+/// `result.yml` is always kept, logs are stored outside the working directory.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+pub enum CleanupPolicy {
+    #[serde(rename = "on-success")]
+    OnSuccess,
+    #[serde(rename = "always")]
+    Always,
+    #[default]
+    #[serde(rename = "never")]
+    Never,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+pub enum ConcurrencyPolicy {
+    /// Runs overlap.
+    #[default]
+    #[serde(rename = "allow")]
+    Allow,
+    /// Running results are stopped before the new run starts.
+    #[serde(rename = "cancel-previous")]
+    CancelPrevious,
+    /// The id of the running result is returned and no new run starts.
+    #[serde(rename = "skip-if-running")]
+    SkipIfRunning,
+}
+
+/// A script parameter with the default that applies when running the job.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct EffectiveParameter {
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+    /// Type of the default value, if there is one.
+    #[serde(rename = "type")]
+    pub parameter_type: Option<String>,
+    pub default: Option<ScriptParameterType>,
+    /// Whether the job defines the parameter, overriding the script default.
+    pub overridden: bool,
+}
+
+/// A step with the parameters substituted into its values, see `Job::preview`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PreviewStep {
+    pub name: String,
+    /// The values as in the script, e.g. the `code` of a bash value, with the references replaced. Pure references
+    /// to string arrays become arrays.
+    pub values: Vec<serde_json::Value>,
+    /// References that couldn't be substituted, e.g. outputs of earlier steps that only exist while running. Their
+    /// fields are left as they are.
+    pub errors: Vec<String>,
+}
+
+/// A problem found by `Job::validate_all`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ValidationError {
+    pub kind: ValidationErrorKind,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum ValidationErrorKind {
+    #[serde(rename = "missing-script")]
+    MissingScript,
+    #[serde(rename = "missing-parameter")]
+    MissingParameter,
+    #[serde(rename = "duplicate-step-name")]
+    DuplicateStepName,
+    #[serde(rename = "no-steps")]
+    NoSteps,
+    #[serde(rename = "unknown-credential")]
+    UnknownCredential,
+    /// A credential of the wrong type, e.g. an ssh credential as a webhook secret.
+    #[serde(rename = "invalid-credential")]
+    InvalidCredential,
+    /// A trigger with invalid settings, e.g. a header mapped to an invalid parameter name.
+    #[serde(rename = "invalid-trigger")]
+    InvalidTrigger,
+    /// A git URL of a step that git would not accept, e.g. `htps://...`.
+    #[serde(rename = "invalid-url")]
+    InvalidUrl,
+    /// A step failed in the dry run.
+    #[serde(rename = "step")]
+    Step,
+}
+
+impl ValidationError {
+    fn new(kind: ValidationErrorKind, message: String) -> Self {
+        ValidationError { kind, message }
+    }
+}
+
+impl CleanupPolicy {
+    pub fn should_cleanup(&self, status: &ScriptStatus) -> bool {
+        match self {
+            CleanupPolicy::OnSuccess => *status == ScriptStatus::Success,
+            CleanupPolicy::Always => true,
+            CleanupPolicy::Never => false,
+        }
+    }
 }
 
 impl Job {
@@ -35,23 +203,47 @@ impl Job {
         }
     }
 
-    pub fn get(id: &str) -> Result<Option<Self>, String> {
-        let path = default_jobs_location()?.join(format!("{}.yml", id));
+    pub fn get(id: &str) -> Result<Option<Self>, NomosError> {
+        let path = entity_path(&default_jobs_location()?, id)?;
         if !path.exists() {
             return Ok(None);
         }
 
-        let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read job file: {}", e))?;
-        let job: Job = serde_yaml::from_str(&content).map_err(|e| format!("Failed to parse job YAML: {}", e))?;
+        let content = fs::read_to_string(&path)?;
+        let job: Job = serde_yaml::from_str(&content)
+            .map_err(|e| NomosError::Other(format!("Failed to parse job {}: {}", path.display(), e)))?;
         Ok(Some(job))
     }
 
-    pub fn get_all() -> Result<Vec<Self>, String> {
+    /// Like `get`, but a missing job is a `NotFound` error.
+    pub fn require(id: &str) -> Result<Self, NomosError> {
+        Self::get(id)?.ok_or_else(|| NomosError::NotFound(format!("Job {:?}", id)))
+    }
+
+    /// The YAML of the job as stored, `None` if it doesn't exist.
+    pub fn get_source(id: &str) -> Result<Option<String>, NomosError> {
+        let path = entity_path(&default_jobs_location()?, id)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(fs::read_to_string(&path)?))
+    }
+
+    /// Why the job can't run, e.g. because its script was deleted. `None` if it is runnable.
+    pub fn broken_reason(&self) -> Option<String> {
+        match Script::get(&self.script_id) {
+            Ok(Some(_)) => None,
+            Ok(None) => Some(format!("Script not found: {}", self.script_id)),
+            Err(e) => Some(format!("Failed to read script {}: {}", self.script_id, e)),
+        }
+    }
+
+    pub fn get_all() -> Result<Vec<Self>, NomosError> {
         let path = default_jobs_location()?;
         let mut jobs = Vec::new();
 
-        for entry in fs::read_dir(path).map_err(|e| format!("Failed to read jobs directory: {}", e))? {
-            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
             let path = entry.path();
 
             match Job::try_from(path.clone()) {
@@ -60,32 +252,39 @@ impl Job {
             }
         }
 
+        // Directory order differs between platforms
+        jobs.sort_by(|a, b| a.id.cmp(&b.id));
         Ok(jobs)
     }
 
-    pub async fn sync(&self, job_result: Option<&mut JobResult>) -> Result<(), String> {
+    pub async fn sync(&self, job_result: Option<&mut JobResult>) -> Result<SyncAction, String> {
         self.validate(None, Default::default()).await?;
+        let existing_job = Job::get(&self.id).ok().flatten();
         if job_result.is_none() {
             eprintln!("Syncing job {:?}", self.id);
             self.save()?;
-            return Ok(());
+            return Ok(if existing_job.is_some() {
+                SyncAction::Updated
+            } else {
+                SyncAction::Created
+            });
         }
 
-        match Job::get(&self.id).ok().flatten() {
+        match existing_job {
             Some(existing_job) => {
-                let needs_update = existing_job.name != self.name
-                    || existing_job.parameters != self.parameters
-                    || existing_job.triggers != self.triggers
-                    || existing_job.script_id != self.script_id
-                    || existing_job.read_only != self.read_only;
+                let needs_update = existing_job != *self;
 
                 if needs_update {
                     self.save()?;
                     if let Some(result) = job_result {
                         result.add_log(crate::log::LogLevel::Info, format!("Updated job {}", self.id));
                     }
-                } else if let Some(result) = job_result {
-                    result.add_log(crate::log::LogLevel::Info, format!("No changes in job {}", self.id));
+                    Ok(SyncAction::Updated)
+                } else {
+                    if let Some(result) = job_result {
+                        result.add_log(crate::log::LogLevel::Info, format!("No changes in job {}", self.id));
+                    }
+                    Ok(SyncAction::Unchanged)
                 }
             }
             None => {
@@ -93,22 +292,20 @@ impl Job {
                 if let Some(result) = job_result {
                     result.add_log(crate::log::LogLevel::Info, format!("Created job {}", self.id));
                 }
+                Ok(SyncAction::Created)
             }
         }
-
-        Ok(())
     }
 
-    fn save(&self) -> Result<(), String> {
-        let path = default_jobs_location()?.join(format!("{}.yml", self.id));
-        let file = File::create(&path).map_err(|e| format!("Failed to create job file {}: {}", path.display(), e))?;
-
-        serde_yaml::to_writer(file, self).map_err(|e| format!("Failed to write job YAML: {}", e))
+    fn save(&self) -> Result<(), NomosError> {
+        let path = entity_path(&default_jobs_location()?, &self.id)?;
+        let file = File::create(&path)?;
+        Ok(serde_yaml::to_writer(file, self)?)
     }
 
-    pub fn delete(&self) -> Result<(), String> {
-        let path = default_jobs_location()?.join(format!("{}.yml", self.id));
-        fs::remove_file(&path).map_err(|e| format!("Failed to delete job file {}: {}", path.display(), e))
+    pub fn delete(&self) -> Result<(), NomosError> {
+        let path = entity_path(&default_jobs_location()?, &self.id)?;
+        Ok(fs::remove_file(&path)?)
     }
 
     pub async fn execute(
@@ -120,17 +317,162 @@ impl Job {
         executor.execute_with_script(self, parameters, &script).await
     }
 
+    /// Same as `execute`, but commands are only logged. The result is persisted.
+    pub async fn dry_execute(
+        &self,
+        executor: &JobExecutor,
+        parameters: HashMap<String, ScriptParameterType>,
+    ) -> Result<String, String> {
+        let script = self.get_script(None)?;
+        executor.dry_execute(self, parameters, &script).await
+    }
+
+    /// Starts a new run from `step_name` of the current script, carrying forward the state of `previous`.
+    pub async fn rerun_from(
+        &self,
+        executor: &JobExecutor,
+        previous: &JobResult,
+        step_name: &str,
+    ) -> Result<String, NomosError> {
+        let script = self.get_script(None)?;
+        executor.rerun_from(self, &script, previous, step_name).await
+    }
+
     pub async fn validate(
         &self,
         script: Option<&Script>,
         parameters: HashMap<String, ScriptParameterType>,
     ) -> Result<(), String> {
         self.validate_parameters(script)?;
+        for trigger in &self.triggers {
+            if let TriggerType::Github(github) = trigger {
+                github.validate_headers()?;
+                github.validate_secret()?;
+            }
+        }
         let script = self.get_script(script)?;
         let executor = JobExecutor::new();
         executor.validate(self, &script, parameters).await
     }
 
+    /// Same as `validate`, but reports the steps that would run.
+    pub async fn dry_run(
+        &self,
+        script: Option<&Script>,
+        parameters: HashMap<String, ScriptParameterType>,
+    ) -> Result<DryRunResult, String> {
+        self.validate_parameters(script)?;
+        let script = self.get_script(script)?;
+        let executor = JobExecutor::new();
+        executor.dry_run(self, &script, parameters).await
+    }
+
+    /// Like `validate`, but reports every problem instead of the first one. The dry run only happens if the job is
+    /// otherwise valid, since it would fail on the same problems.
+    pub async fn validate_all(&self, script: Option<&Script>) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        let mut credential_ids = Vec::new();
+
+        let script = match self.get_script(script) {
+            Ok(script) => Some(script),
+            Err(e) => {
+                errors.push(ValidationError::new(ValidationErrorKind::MissingScript, e));
+                None
+            }
+        };
+        if let Some(script) = &script {
+            for parameter in &script.parameters {
+                let is_parameter_defined = self.parameters.iter().any(|p| p.name == parameter.name);
+                if !is_parameter_defined && parameter.default.is_none() && parameter.required {
+                    errors.push(ValidationError::new(
+                        ValidationErrorKind::MissingParameter,
+                        format!("Missing required parameter: {}", parameter.name),
+                    ));
+                }
+            }
+
+            if let Err(e) = script.validate_has_steps() {
+                errors.push(ValidationError::new(ValidationErrorKind::NoSteps, e.to_string()));
+            }
+
+            let mut names = HashSet::new();
+            for step in &script.steps {
+                if !names.insert(step.name.as_str()) {
+                    errors.push(ValidationError::new(
+                        ValidationErrorKind::DuplicateStepName,
+                        format!("Duplicate step name {:?} in script {:?}", step.name, script.id),
+                    ));
+                }
+                for value in &step.values {
+                    // URLs with parameters are checked by the dry run, once substituted
+                    if let ScriptType::GitClone(git_clone) = value {
+                        if !git_clone.url.contains("$(") {
+                            if let Err(e) = validate_url(&git_clone.url) {
+                                errors.push(ValidationError::new(
+                                    ValidationErrorKind::InvalidUrl,
+                                    format!("{} (step {})", e, step.name),
+                                ));
+                            }
+                        }
+                    }
+                    credential_ids.extend(
+                        value
+                            .credential_ids()
+                            .into_iter()
+                            .map(|id| (id.to_string(), format!("step {}", step.name))),
+                    );
+                }
+            }
+        }
+
+        for notification in &self.notifications {
+            match notification {
+                NotificationTarget::Webhook(webhook) => credential_ids.extend(
+                    webhook
+                        .credential_id
+                        .iter()
+                        .map(|id| (id.clone(), "notification webhook".to_string())),
+                ),
+            }
+        }
+
+        // Ids with parameters are only known when running
+        for (credential_id, location) in credential_ids.iter().filter(|(id, _)| !id.contains("$(")) {
+            let message = match Credential::get(credential_id, None) {
+                Ok(Some(_)) => continue,
+                Ok(None) => format!("Credential not found: {} ({})", credential_id, location),
+                Err(e) => format!("Failed to read credential {} ({}): {}", credential_id, location, e),
+            };
+            errors.push(ValidationError::new(ValidationErrorKind::UnknownCredential, message));
+        }
+        for trigger in &self.triggers {
+            if let TriggerType::Github(github) = trigger {
+                if let Err(e) = github.validate_headers() {
+                    errors.push(ValidationError::new(
+                        ValidationErrorKind::InvalidTrigger,
+                        format!("{} (trigger github)", e),
+                    ));
+                }
+                if let Err(e) = github.validate_secret() {
+                    let kind = match e {
+                        NomosError::Validation(_) => ValidationErrorKind::InvalidCredential,
+                        _ => ValidationErrorKind::UnknownCredential,
+                    };
+                    errors.push(ValidationError::new(kind, format!("{} (trigger github)", e)));
+                }
+            }
+        }
+
+        if let (Some(script), true) = (&script, errors.is_empty()) {
+            let error = match JobExecutor::new().dry_run(self, script, HashMap::new()).await {
+                Ok(result) => result.error,
+                Err(e) => Some(e),
+            };
+            errors.extend(error.map(|e| ValidationError::new(ValidationErrorKind::Step, e)));
+        }
+        errors
+    }
+
     pub fn validate_parameters(&self, script: Option<&Script>) -> Result<(), String> {
         let script = self.get_script(script)?;
         let mut missing_parameters = Vec::new();
@@ -153,6 +495,41 @@ impl Job {
         Ok(())
     }
 
+    /// Checks the provided values against the declared types and the `pattern`s of the script parameters.
+    pub fn validate_provided_parameters(
+        &self,
+        script: Option<&Script>,
+        parameters: &HashMap<String, ScriptParameterType>,
+    ) -> Result<(), NomosError> {
+        let script = self.get_script(script)?;
+        for parameter in &script.parameters {
+            let Some(value) = parameters.get(&parameter.name) else {
+                continue;
+            };
+            let declared = self.declared_type(parameter);
+            let value = value.clone().into_declared(declared);
+            if let Some(declared) = declared.filter(|declared| declared.type_name() != value.type_name()) {
+                return Err(NomosError::Validation(format!(
+                    "Parameter {} must be of type {}, not {}",
+                    parameter.name,
+                    declared.type_name(),
+                    value.type_name()
+                )));
+            }
+            let matches = parameter
+                .matches_pattern(&value)
+                .map_err(|e| NomosError::Validation(format!("{} in script {:?}", e, script.id)))?;
+            if !matches {
+                return Err(NomosError::Validation(format!(
+                    "Parameter {} does not match the pattern {}",
+                    parameter.name,
+                    parameter.pattern.as_deref().unwrap_or_default()
+                )));
+            }
+        }
+        Ok(())
+    }
+
     pub fn merged_parameters(
         &self,
         script: Option<&Script>,
@@ -160,18 +537,103 @@ impl Job {
     ) -> Result<HashMap<String, ScriptParameterType>, String> {
         let script = self.get_script(script)?;
         let mut merged_parameters = HashMap::new();
+        let mut expressions = Vec::new();
 
         for parameter in &script.parameters {
             let value = self.resolve_parameter_value(parameter, &parameters)?;
 
-            if let Some(value) = value {
-                merged_parameters.insert(format!("parameters.{}", parameter.name), value);
+            match value {
+                Some(ScriptParameterType::String(expression))
+                    if expression.contains("$(") && !parameters.contains_key(&parameter.name) =>
+                {
+                    expressions.push((format!("parameters.{}", parameter.name), expression));
+                }
+                Some(value) => {
+                    merged_parameters.insert(format!("parameters.{}", parameter.name), value);
+                }
+                None => {}
             }
         }
 
+        resolve_default_expressions(&mut merged_parameters, expressions)?;
         Ok(merged_parameters)
     }
 
+    /// The script of the job, with the defaults of its parameters replaced by the job defaults. This is what a run
+    /// without provided parameters executes.
+    pub fn resolved_script(&self, script: Option<&Script>) -> Result<Script, String> {
+        let mut script = self.get_script(script)?;
+        let no_parameters = HashMap::new();
+        for parameter in script.parameters.iter_mut() {
+            parameter.default = self.resolve_parameter_value(parameter, &no_parameters)?;
+        }
+        Ok(script)
+    }
+
+    /// The steps of the script with `parameters` and the job defaults substituted, without executing anything.
+    /// Password and credential values are masked. Of the built-ins only `nomos.job_id` and `nomos.job_name` are known.
+    pub fn preview(
+        &self,
+        script: Option<&Script>,
+        parameters: HashMap<String, ScriptParameterType>,
+    ) -> Result<Vec<PreviewStep>, String> {
+        let script = self.get_script(script)?;
+        let mut parameters: HashMap<String, ScriptParameterType> = self
+            .merged_parameters(Some(&script), parameters)?
+            .into_iter()
+            .map(|(name, value)| (name, value.redacted()))
+            .collect();
+        parameters.insert("nomos.job_id".to_string(), ScriptParameterType::String(self.id.clone()));
+        parameters.insert(
+            "nomos.job_name".to_string(),
+            ScriptParameterType::String(self.name.clone()),
+        );
+
+        script
+            .steps
+            .iter()
+            .map(|step| {
+                let mut errors = Vec::new();
+                let values = step
+                    .values
+                    .iter()
+                    .map(|value| {
+                        let mut value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+                        substitute_value(&mut value, &parameters, &mut errors);
+                        Ok(value)
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(PreviewStep {
+                    name: step.name.clone(),
+                    values,
+                    errors,
+                })
+            })
+            .collect()
+    }
+
+    /// Script parameters merged with the job defaults, in script order.
+    pub fn effective_parameters(&self, script: Option<&Script>) -> Result<Vec<EffectiveParameter>, String> {
+        let script = self.get_script(script)?;
+        let no_parameters = HashMap::new();
+
+        script
+            .parameters
+            .iter()
+            .map(|parameter| {
+                let default = self.resolve_parameter_value(parameter, &no_parameters)?;
+                Ok(EffectiveParameter {
+                    name: parameter.name.clone(),
+                    description: parameter.description.clone(),
+                    required: parameter.required,
+                    parameter_type: default.as_ref().map(|d| d.type_name().to_string()),
+                    default,
+                    overridden: self.parameters.iter().any(|p| p.name == parameter.name),
+                })
+            })
+            .collect()
+    }
+
     fn resolve_parameter_value(
         &self,
         script_parameter: &ScriptParameter,
@@ -182,11 +644,171 @@ impl Job {
         Ok(match job_parameter {
             Some(job_param) => provided_parameters
                 .get(&job_param.name)
-                .cloned()
+                .map(|value| value.clone().into_declared(self.declared_type(script_parameter)))
                 .or_else(|| job_param.default.clone()),
             None => script_parameter.default.clone(),
         })
     }
+
+    /// `parameters` converted to the declared types of the script parameters, see `ScriptParameterType::into_declared`.
+    pub fn with_declared_types(
+        &self,
+        script: &Script,
+        parameters: HashMap<String, ScriptParameterType>,
+    ) -> HashMap<String, ScriptParameterType> {
+        parameters
+            .into_iter()
+            .map(|(name, value)| {
+                let declared = script
+                    .parameters
+                    .iter()
+                    .find(|parameter| parameter.name == name)
+                    .and_then(|parameter| self.declared_type(parameter));
+                (name, value.into_declared(declared))
+            })
+            .collect()
+    }
+
+    /// The type of a parameter is the type of its default, of the job if it has one and of the script otherwise.
+    /// Without a default any type is accepted.
+    pub fn declared_type<'a>(&'a self, script_parameter: &'a ScriptParameter) -> Option<&'a ScriptParameterType> {
+        self.parameters
+            .iter()
+            .find(|p| p.name == script_parameter.name)
+            .and_then(|p| p.default.as_ref())
+            .or(script_parameter.default.as_ref())
+    }
+}
+
+/// Substitutes the references in every string of `value`. Strings that fail keep their references and the error is
+/// added to `errors`.
+fn substitute_value(
+    value: &mut serde_json::Value,
+    parameters: &HashMap<String, ScriptParameterType>,
+    errors: &mut Vec<String>,
+) {
+    match value {
+        serde_json::Value::String(text) if text.contains("$(") => match text.substitute_parameters(parameters, false) {
+            Ok(Some(SubstitutionResult::Single(substituted))) => *value = serde_json::Value::String(substituted),
+            Ok(Some(SubstitutionResult::Multiple(items))) => *value = serde_json::Value::from(items),
+            Ok(None) => {}
+            Err(e) if !errors.contains(&e) => errors.push(e),
+            Err(_) => {}
+        },
+        serde_json::Value::Array(items) => {
+            for item in items {
+                substitute_value(item, parameters, errors);
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for field in fields.values_mut() {
+                substitute_value(field, parameters, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Environment variables defaults can read as `env.NAME`. The rest of the server environment, which may hold secrets
+/// like `NOMOS_SECRET_KEY`, is hidden unless listed in `NOMOS_EXPOSED_ENV`.
+const EXPOSED_ENV: [&str; 3] = ["HOME", "PATH", "USER"];
+
+/// `EXPOSED_ENV` and the comma separated names of `NOMOS_EXPOSED_ENV`.
+fn exposed_env() -> Vec<String> {
+    let configured = std::env::var("NOMOS_EXPOSED_ENV").unwrap_or_default();
+    EXPOSED_ENV
+        .iter()
+        .map(|name| name.to_string())
+        .chain(configured.split(',').map(|name| name.trim().to_string()))
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Resolves defaults like `$(parameters.branch)-$(now)` against the other parameters and the built-ins `env.NAME`
+/// (see `exposed_env`), `now` and `uuid`. A default referencing another default is resolved after it; a cycle is an
+/// error. Other references, like `$(nomos.job_id)` or step outputs, only exist during the run and are left in the
+/// value for the substitution of the steps.
+fn resolve_default_expressions(
+    parameters: &mut HashMap<String, ScriptParameterType>,
+    mut expressions: Vec<(String, String)>,
+) -> Result<(), String> {
+    if expressions.is_empty() {
+        return Ok(());
+    }
+
+    let mut context: HashMap<String, ScriptParameterType> = exposed_env()
+        .into_iter()
+        .filter_map(|name| {
+            let value = std::env::var(&name).ok()?;
+            Some((format!("env.{}", name), ScriptParameterType::String(value)))
+        })
+        .collect();
+    context.insert(
+        "now".to_string(),
+        ScriptParameterType::String(chrono::Utc::now().to_rfc3339()),
+    );
+    context.insert(
+        "uuid".to_string(),
+        ScriptParameterType::String(uuid::Uuid::new_v4().to_string()),
+    );
+
+    while !expressions.is_empty() {
+        let pending: Vec<String> = expressions.iter().map(|(name, _)| format!("$({})", name)).collect();
+        let (ready, waiting): (Vec<_>, Vec<_>) = expressions
+            .into_iter()
+            .partition(|(_, expression)| !pending.iter().any(|reference| expression.contains(reference)));
+        if ready.is_empty() {
+            let names: Vec<&str> = waiting.iter().map(|(name, _)| name.as_str()).collect();
+            return Err(format!("Circular parameter defaults: {}", names.join(", ")));
+        }
+
+        context.extend(parameters.iter().map(|(k, v)| (k.clone(), v.clone())));
+        for (name, expression) in ready {
+            let value =
+                substitute_default(&expression, &context).map_err(|e| format!("Invalid default of {}: {}", name, e))?;
+            parameters.insert(name, value);
+        }
+        expressions = waiting;
+    }
+
+    Ok(())
+}
+
+/// Whether a reference in a default is resolved by `resolve_default_expressions`.
+fn is_default_reference(name: &str) -> bool {
+    name.starts_with("env.") || name.starts_with("parameters.") || name == "now" || name == "uuid"
+}
+
+/// Substitutes the references of `is_default_reference` in `expression` and keeps any other reference as it is.
+fn substitute_default(
+    expression: &str,
+    context: &HashMap<String, ScriptParameterType>,
+) -> Result<ScriptParameterType, String> {
+    let mut result = String::new();
+    let mut remaining = expression;
+    while let Some(start) = remaining.find("$(") {
+        let end = remaining[start..]
+            .find(')')
+            .ok_or_else(|| "Missing closing bracket ')'".to_string())?
+            + start;
+        let reference = &remaining[start..=end];
+        let name = &reference[2..reference.len() - 1];
+        result.push_str(&remaining[..start]);
+        if is_default_reference(name) {
+            let value = context
+                .get(name)
+                .ok_or_else(|| format!("Parameter '{}' not found", name))?;
+            if reference.len() == expression.len() {
+                return Ok(value.clone());
+            }
+            result.push_str(&parameter_text(value));
+        } else {
+            result.push_str(reference);
+        }
+        remaining = &remaining[end + 1..];
+    }
+    result.push_str(remaining);
+    Ok(ScriptParameterType::String(result))
 }
 
 impl TryFrom<PathBuf> for Job {
@@ -220,7 +842,7 @@ impl From<&Script> for Job {
                 TriggerType::Github(TriggerPlaceHolder::get_place_holder()),
             ],
             script_id: script.id.clone(),
-            read_only: false,
+            ..Default::default()
         }
     }
 }
@@ -228,7 +850,10 @@ impl From<&Script> for Job {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::script::models::ScriptStep;
+    use crate::script::{
+        models::ScriptStep,
+        types::{GitCloneScript, ScriptType},
+    };
 
     #[test]
     fn test_validate_parameters() {
@@ -239,9 +864,8 @@ mod tests {
                 name: "param1".to_string(),
                 default: None,
             }],
-            triggers: vec![],
             script_id: "test_script".to_string(),
-            read_only: false,
+            ..Default::default()
         };
 
         let script = Script {
@@ -253,17 +877,20 @@ mod tests {
                     description: "Parameter 1".to_string(),
                     default: None,
                     required: true,
+                    pattern: None,
                 },
                 ScriptParameter {
                     name: "param2".to_string(),
                     description: "Parameter 2".to_string(),
                     default: None,
                     required: true,
+                    pattern: None,
                 },
             ],
             steps: vec![ScriptStep {
                 name: "step1".to_string(),
                 values: vec![],
+                ..Default::default()
             }],
         };
 
@@ -281,9 +908,8 @@ mod tests {
                 name: "param1".to_string(),
                 default: Some(ScriptParameterType::String("default1".to_string())),
             }],
-            triggers: vec![],
             script_id: "test_script".to_string(),
-            read_only: false,
+            ..Default::default()
         };
 
         let script = Script {
@@ -295,17 +921,20 @@ mod tests {
                     description: "Parameter 1".to_string(),
                     default: None,
                     required: true,
+                    pattern: None,
                 },
                 ScriptParameter {
                     name: "param2".to_string(),
                     description: "Parameter 2".to_string(),
                     default: Some(ScriptParameterType::String("default2".to_string())),
                     required: false,
+                    pattern: None,
                 },
             ],
             steps: vec![ScriptStep {
                 name: "step1".to_string(),
                 values: vec![],
+                ..Default::default()
             }],
         };
 
@@ -325,6 +954,519 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_sync_updates_tags() {
+        let script = Script {
+            id: "sync-tags-script".to_string(),
+            name: "Sync Tags Script".to_string(),
+            parameters: vec![],
+            steps: vec![ScriptStep {
+                name: "step1".to_string(),
+                values: vec![ScriptType::Bash(crate::script::types::BashScript {
+                    code: "echo sync".to_string(),
+                    shell: None,
+                    directory: None,
+                    run_as: None,
+                    capture_output_to: None,
+                })],
+                ..Default::default()
+            }],
+        };
+        script.sync(None).unwrap();
+        let mut job = Job {
+            id: "sync-tags-job".to_string(),
+            name: "Sync Tags Job".to_string(),
+            script_id: script.id.clone(),
+            ..Default::default()
+        };
+        job.sync(None).await.unwrap();
+
+        let id = format!("sync-tags-{}", uuid::Uuid::new_v4());
+        let logger = std::sync::Arc::new(std::sync::Mutex::new(
+            crate::log::JobLogger::new("sync-tags".to_string(), id.clone(), true).unwrap(),
+        ));
+        let mut job_result = JobResult::new(id, "sync-tags".to_string(), vec![], logger, true);
+
+        // Synced like from a config repository
+        job.tags = vec!["prod".to_string()];
+        job.clean_env = false;
+        assert_eq!(job.sync(Some(&mut job_result)).await.unwrap(), SyncAction::Updated);
+        let saved = Job::get(&job.id).unwrap().unwrap();
+        assert_eq!(saved.tags, vec!["prod"]);
+        assert!(!saved.clean_env);
+        assert_eq!(job.sync(Some(&mut job_result)).await.unwrap(), SyncAction::Unchanged);
+    }
+
+    #[tokio::test]
+    async fn test_sync_github_trigger_credential() {
+        let script = Script {
+            id: "github-secret-script".to_string(),
+            name: "Github Secret Script".to_string(),
+            parameters: vec![],
+            steps: vec![ScriptStep {
+                name: "step1".to_string(),
+                values: vec![ScriptType::Bash(crate::script::types::BashScript {
+                    code: "echo webhook".to_string(),
+                    shell: None,
+                    directory: None,
+                    run_as: None,
+                    capture_output_to: None,
+                })],
+                ..Default::default()
+            }],
+        };
+        script.sync(None).unwrap();
+        let ssh = Credential {
+            id: "github-secret-ssh".to_string(),
+            value: crate::credential::CredentialType::Ssh(Default::default()),
+            read_only: false,
+        };
+        ssh.sync(&mut None).unwrap();
+        let job = |secret_credential_id: &str| Job {
+            id: "github-secret-job".to_string(),
+            name: "Github Secret Job".to_string(),
+            triggers: vec![TriggerType::Github(super::super::trigger::GithubTriggerParameter {
+                secret_credential_id: secret_credential_id.to_string(),
+                ..TriggerPlaceHolder::get_place_holder()
+            })],
+            script_id: script.id.clone(),
+            ..Default::default()
+        };
+
+        let error = job("github-secret-missing").sync(None).await.unwrap_err();
+        assert!(error.contains("github-secret-missing"), "{}", error);
+        let error = job(&ssh.id).sync(None).await.unwrap_err();
+        assert!(error.contains("must be a text credential"), "{}", error);
+
+        let errors = job(&ssh.id).validate_all(None).await;
+        assert!(errors.iter().any(|e| e.kind == ValidationErrorKind::InvalidCredential));
+    }
+
+    #[tokio::test]
+    async fn test_validate_all() {
+        let step = |name: &str, value: ScriptType| ScriptStep {
+            name: name.to_string(),
+            values: vec![value],
+            ..Default::default()
+        };
+        let script = Script {
+            id: "validate-all-script".to_string(),
+            name: "Validate All Script".to_string(),
+            parameters: vec![ScriptParameter {
+                name: "image".to_string(),
+                description: "Docker image".to_string(),
+                default: None,
+                required: true,
+                pattern: None,
+            }],
+            steps: vec![
+                step(
+                    "Clone",
+                    ScriptType::GitClone(GitCloneScript {
+                        url: "git@github.com:nomos-cicd/nomos-rust.git".to_string(),
+                        credential_id: Some("validate-all-missing-credential".to_string()),
+                        branch: None,
+                        depth: None,
+                        submodules: false,
+                    }),
+                ),
+                step(
+                    "Clone",
+                    ScriptType::GitClone(GitCloneScript {
+                        url: "git@github.com:nomos-cicd/nomos-rust.git".to_string(),
+                        credential_id: Some("$(parameters.credential)".to_string()),
+                        branch: None,
+                        depth: None,
+                        submodules: false,
+                    }),
+                ),
+            ],
+        };
+        let job = Job {
+            id: "validate-all-job".to_string(),
+            name: "Validate All Job".to_string(),
+            script_id: script.id.clone(),
+            ..Default::default()
+        };
+
+        let kinds: Vec<ValidationErrorKind> = job
+            .validate_all(Some(&script))
+            .await
+            .into_iter()
+            .map(|error| error.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ValidationErrorKind::MissingParameter,
+                ValidationErrorKind::DuplicateStepName,
+                ValidationErrorKind::UnknownCredential,
+            ]
+        );
+
+        let job = Job {
+            script_id: "validate-all-missing-script".to_string(),
+            ..job
+        };
+        let errors = job.validate_all(None).await;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ValidationErrorKind::MissingScript);
+    }
+
+    #[test]
+    fn test_validate_provided_parameters() {
+        let script = |pattern: &str| Script {
+            id: "pattern-script".to_string(),
+            name: "Pattern Script".to_string(),
+            parameters: vec![ScriptParameter {
+                name: "version".to_string(),
+                description: "Release version".to_string(),
+                default: None,
+                required: true,
+                pattern: Some(pattern.to_string()),
+            }],
+            steps: vec![],
+        };
+        let job = Job {
+            id: "pattern-job".to_string(),
+            name: "Pattern Job".to_string(),
+            script_id: "pattern-script".to_string(),
+            ..Default::default()
+        };
+        let version =
+            |value: &str| HashMap::from([("version".to_string(), ScriptParameterType::String(value.to_string()))]);
+        let semver = script(r"v\d+\.\d+\.\d+");
+
+        assert!(job
+            .validate_provided_parameters(Some(&semver), &version("v1.2.3"))
+            .is_ok());
+        assert!(job.validate_provided_parameters(Some(&semver), &HashMap::new()).is_ok());
+        // The whole value must match
+        let error = job
+            .validate_provided_parameters(Some(&semver), &version("v1.2.3-rc1"))
+            .unwrap_err();
+        assert!(matches!(error, NomosError::Validation(_)));
+        assert_eq!(
+            error.to_string(),
+            r"Parameter version does not match the pattern v\d+\.\d+\.\d+"
+        );
+
+        // Secrets and every element of an array are checked too
+        let provided = |value: ScriptParameterType| HashMap::from([("version".to_string(), value)]);
+        assert!(job
+            .validate_provided_parameters(
+                Some(&semver),
+                &provided(ScriptParameterType::Password("v1".to_string()))
+            )
+            .is_err());
+        let versions = |values: &[&str]| {
+            provided(ScriptParameterType::StringArray(
+                values.iter().map(|value| value.to_string()).collect(),
+            ))
+        };
+        assert!(job
+            .validate_provided_parameters(Some(&semver), &versions(&["v1.2.3", "v2.0.0"]))
+            .is_ok());
+        assert!(job
+            .validate_provided_parameters(Some(&semver), &versions(&["v1.2.3", "latest"]))
+            .is_err());
+
+        // The default declares the type
+        let typed = Script {
+            parameters: vec![ScriptParameter {
+                default: Some(ScriptParameterType::String("v0.0.0".to_string())),
+                ..semver.parameters[0].clone()
+            }],
+            ..semver.clone()
+        };
+        let error = job
+            .validate_provided_parameters(Some(&typed), &provided(ScriptParameterType::Number(1)))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Parameter version must be of type string, not number"
+        );
+
+        let invalid = script("v(");
+        assert!(matches!(
+            invalid.validate_parameter_patterns(),
+            Err(NomosError::Validation(_))
+        ));
+        assert!(invalid.sync(None).is_err());
+        assert!(job
+            .validate_provided_parameters(Some(&invalid), &version("v1"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_substitute_value() {
+        let parameters = HashMap::from([(
+            "parameters.files".to_string(),
+            ScriptParameterType::StringArray(vec!["a.txt".to_string(), "b.txt".to_string()]),
+        )]);
+        let mut value = serde_json::json!({
+            "type": "docker-run",
+            "args": ["$(parameters.files)", "--files=$(parameters.files)", "$(parameters.missing)"],
+        });
+        let mut errors = Vec::new();
+        substitute_value(&mut value, &parameters, &mut errors);
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "type": "docker-run",
+                "args": [["a.txt", "b.txt"], "--files=a.txt, b.txt", "$(parameters.missing)"],
+            })
+        );
+        assert_eq!(errors, vec!["Parameter 'parameters.missing' not found"]);
+    }
+
+    #[tokio::test]
+    async fn test_validate_git_url() {
+        let script = |url: &str| Script {
+            id: "validate-git-url-script".to_string(),
+            name: "Validate Git URL Script".to_string(),
+            parameters: vec![ScriptParameter {
+                name: "repository".to_string(),
+                description: "Repository to clone".to_string(),
+                default: None,
+                required: false,
+                pattern: None,
+            }],
+            steps: vec![ScriptStep {
+                name: "Clone".to_string(),
+                values: vec![ScriptType::GitClone(GitCloneScript {
+                    url: url.to_string(),
+                    credential_id: None,
+                    branch: None,
+                    depth: None,
+                    submodules: false,
+                })],
+                ..Default::default()
+            }],
+        };
+        let job = Job {
+            id: "validate-git-url-job".to_string(),
+            name: "Validate Git URL Job".to_string(),
+            parameters: vec![JobParameterDefinition {
+                name: "repository".to_string(),
+                default: Some(ScriptParameterType::String(
+                    "github.com/nomos-cicd/nomos-rust".to_string(),
+                )),
+            }],
+            script_id: "validate-git-url-script".to_string(),
+            ..Default::default()
+        };
+
+        let errors = job
+            .validate_all(Some(&script("htps://github.com/nomos-cicd/nomos-rust.git")))
+            .await;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ValidationErrorKind::InvalidUrl);
+        assert!(errors[0].message.contains("step Clone"), "{}", errors[0].message);
+
+        // Checked once the parameter is substituted
+        let errors = job.validate_all(Some(&script("$(parameters.repository)"))).await;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, ValidationErrorKind::Step);
+        assert!(errors[0].message.contains("Invalid git URL"), "{}", errors[0].message);
+    }
+
+    fn default_expressions_job(defaults: &[(&str, &str)]) -> (Job, Script) {
+        let job = Job {
+            id: "test_job".to_string(),
+            name: "Test Job".to_string(),
+            parameters: defaults
+                .iter()
+                .map(|(name, default)| JobParameterDefinition {
+                    name: name.to_string(),
+                    default: Some(ScriptParameterType::String(default.to_string())),
+                })
+                .collect(),
+            script_id: "test_script".to_string(),
+            ..Default::default()
+        };
+        let script = Script {
+            id: "test_script".to_string(),
+            name: "Test Script".to_string(),
+            parameters: defaults
+                .iter()
+                .map(|(name, _)| ScriptParameter {
+                    name: name.to_string(),
+                    description: String::new(),
+                    default: None,
+                    required: true,
+                    pattern: None,
+                })
+                .collect(),
+            steps: vec![],
+        };
+        (job, script)
+    }
+
+    #[test]
+    fn test_merged_parameters_env_default() {
+        let (job, script) = default_expressions_job(&[("path", "$(env.PATH)"), ("id", "build-$(uuid)")]);
+
+        let merged = job.merged_parameters(Some(&script), HashMap::new()).unwrap();
+        assert_eq!(
+            merged.get("parameters.path"),
+            Some(&ScriptParameterType::String(std::env::var("PATH").unwrap()))
+        );
+        let Some(ScriptParameterType::String(id)) = merged.get("parameters.id") else {
+            panic!("id not resolved");
+        };
+        assert!(id.starts_with("build-") && id.len() == "build-".len() + 36);
+    }
+
+    #[test]
+    fn test_merged_parameters_hidden_env_default() {
+        std::env::set_var("NOMOS_TEST_HIDDEN_ENV", "secret");
+        let (job, script) = default_expressions_job(&[("key", "$(env.NOMOS_TEST_HIDDEN_ENV)")]);
+
+        let error = job.merged_parameters(Some(&script), HashMap::new()).unwrap_err();
+        assert!(error.contains("env.NOMOS_TEST_HIDDEN_ENV' not found"), "{}", error);
+    }
+
+    #[test]
+    fn test_merged_parameters_runtime_default() {
+        let (job, script) = default_expressions_job(&[
+            ("build", "$(nomos.job_id)"),
+            (
+                "tag",
+                "$(parameters.branch)-$(nomos.job_id)-$(steps.Clone.git-clone.directory)",
+            ),
+            ("branch", "main"),
+        ]);
+
+        // References of the run are kept for the substitution of the steps
+        let merged = job.merged_parameters(Some(&script), HashMap::new()).unwrap();
+        assert_eq!(
+            merged.get("parameters.build"),
+            Some(&ScriptParameterType::String("$(nomos.job_id)".to_string()))
+        );
+        assert_eq!(
+            merged.get("parameters.tag"),
+            Some(&ScriptParameterType::String(
+                "main-$(nomos.job_id)-$(steps.Clone.git-clone.directory)".to_string()
+            ))
+        );
+
+        let mut runtime = merged.clone();
+        runtime.insert("nomos.job_id".to_string(), ScriptParameterType::String(job.id.clone()));
+        assert_eq!(
+            "build $(parameters.build)"
+                .to_string()
+                .substitute_parameters(&runtime, false)
+                .unwrap(),
+            Some(SubstitutionResult::Single("build test_job".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_merged_parameters_cross_parameter_default() {
+        let (job, script) = default_expressions_job(&[
+            ("tag", "$(parameters.image):$(parameters.version)"),
+            ("image", "nomos"),
+            ("version", "$(parameters.branch)-latest"),
+            ("branch", "main"),
+        ]);
+
+        let merged = job.merged_parameters(Some(&script), HashMap::new()).unwrap();
+        assert_eq!(
+            merged.get("parameters.tag"),
+            Some(&ScriptParameterType::String("nomos:main-latest".to_string()))
+        );
+
+        // Provided values replace the default expression
+        let provided = HashMap::from([
+            ("branch".to_string(), ScriptParameterType::String("dev".to_string())),
+            ("version".to_string(), ScriptParameterType::String("1.0".to_string())),
+        ]);
+        let merged = job.merged_parameters(Some(&script), provided).unwrap();
+        assert_eq!(
+            merged.get("parameters.tag"),
+            Some(&ScriptParameterType::String("nomos:1.0".to_string()))
+        );
+
+        let (job, script) = default_expressions_job(&[("a", "$(parameters.b)"), ("b", "$(parameters.a)")]);
+        let error = job.merged_parameters(Some(&script), HashMap::new()).unwrap_err();
+        assert!(error.contains("Circular parameter defaults"), "{}", error);
+    }
+
+    #[test]
+    fn test_effective_parameters() {
+        let job = Job {
+            id: "test_job".to_string(),
+            name: "Test Job".to_string(),
+            parameters: vec![JobParameterDefinition {
+                name: "branch".to_string(),
+                default: Some(ScriptParameterType::String("develop".to_string())),
+            }],
+            script_id: "test_script".to_string(),
+            ..Default::default()
+        };
+
+        let script = Script {
+            id: "test_script".to_string(),
+            name: "Test Script".to_string(),
+            parameters: vec![
+                ScriptParameter {
+                    name: "branch".to_string(),
+                    description: "Branch to build".to_string(),
+                    default: Some(ScriptParameterType::String("main".to_string())),
+                    required: true,
+                    pattern: None,
+                },
+                ScriptParameter {
+                    name: "lfs".to_string(),
+                    description: "Pull LFS files".to_string(),
+                    default: Some(ScriptParameterType::Boolean(false)),
+                    required: false,
+                    pattern: None,
+                },
+                ScriptParameter {
+                    name: "image".to_string(),
+                    description: "Docker image".to_string(),
+                    default: None,
+                    required: true,
+                    pattern: None,
+                },
+            ],
+            steps: vec![],
+        };
+
+        let parameters = job.effective_parameters(Some(&script)).unwrap();
+        assert_eq!(
+            parameters,
+            vec![
+                EffectiveParameter {
+                    name: "branch".to_string(),
+                    description: "Branch to build".to_string(),
+                    required: true,
+                    parameter_type: Some("string".to_string()),
+                    default: Some(ScriptParameterType::String("develop".to_string())),
+                    overridden: true,
+                },
+                EffectiveParameter {
+                    name: "lfs".to_string(),
+                    description: "Pull LFS files".to_string(),
+                    required: false,
+                    parameter_type: Some("boolean".to_string()),
+                    default: Some(ScriptParameterType::Boolean(false)),
+                    overridden: false,
+                },
+                EffectiveParameter {
+                    name: "image".to_string(),
+                    description: "Docker image".to_string(),
+                    required: true,
+                    parameter_type: None,
+                    default: None,
+                    overridden: false,
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_resolve_parameter_value() {
         let job = Job {
@@ -334,9 +1476,8 @@ mod tests {
                 name: "param1".to_string(),
                 default: Some(ScriptParameterType::String("default1".to_string())),
             }],
-            triggers: vec![],
             script_id: "test_script".to_string(),
-            read_only: false,
+            ..Default::default()
         };
 
         let script_param = ScriptParameter {
@@ -344,6 +1485,7 @@ mod tests {
             description: "Parameter 1".to_string(),
             default: None,
             required: true,
+            pattern: None,
         };
 
         let provided_params =
@@ -1,18 +1,32 @@
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use std::{
+    collections::{HashMap, HashSet},
     fs::{self, File},
-    io::BufReader,
     path::PathBuf,
     sync::{Arc, Mutex},
 };
+use sysinfo::{Pid, System};
 
 use crate::{
     job::{models::Job, utils::default_job_results_location},
-    log::{JobLogger, LogLevel},
-    script::models::{RunningScriptStep, Script, ScriptStatus},
+    log::{max_log_line, truncate_line, JobLogger, LogLevel},
+    script::{
+        models::{RunningScriptStep, Script, ScriptStatus},
+        ScriptParameterType,
+    },
+    settings::SyncSummary,
+    utils::{get_process_recursive, gz_path, gzip_file, read_maybe_compressed},
 };
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JobCancellation {
+    pub reason: String,
+    /// Username of the user who stopped the job, if it was stopped via the API by a logged in user.
+    pub cancelled_by: Option<String>,
+    pub cancelled_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JobResult {
     pub id: String,
@@ -24,9 +38,62 @@ pub struct JobResult {
     pub updated_at: DateTime<Utc>,
     pub finished_at: Option<DateTime<Utc>>,
     pub logger: Arc<Mutex<JobLogger>>,
-    #[serde(skip)]
+    /// Commands are logged but not executed.
+    #[serde(default)]
     pub dry_run: bool,
+    /// Waiting in the queue for a free slot, see `NOMOS_MAX_RUNNING_JOBS`.
+    #[serde(default)]
+    pub queued: bool,
+    /// Neither logs nor the result are written, used to validate jobs.
+    #[serde(skip)]
+    pub ephemeral: bool,
     pub child_process_ids: Vec<usize>,
+    pub cancellation: Option<JobCancellation>,
+    /// Commit SHA checked out by each git step, keyed by step name.
+    #[serde(default)]
+    pub source_refs: HashMap<String, String>,
+    /// Threshold of the job, logs below it are not written to the log file.
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// Commands don't inherit the server environment, see `Job::clean_env`.
+    #[serde(default = "default_clean_env")]
+    pub clean_env: bool,
+    /// See `Job::git_default_branch`.
+    #[serde(default)]
+    pub git_default_branch: Option<String>,
+    /// Short description of the run, captured from the output of a step, see `BashScript::capture_output_to`.
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Set by sync steps.
+    #[serde(default)]
+    pub sync_summary: Option<SyncSummary>,
+    /// Parameters of the run including the outputs of finished steps, e.g. `steps.<name>.git-clone.directory`.
+    /// Carried forward when the run is rerun from a later step. Password and credential values are redacted when
+    /// written.
+    #[serde(default, serialize_with = "serialize_redacted")]
+    pub parameters: HashMap<String, ScriptParameterType>,
+    /// Directory of the last git-clone, relative to the job directory.
+    #[serde(default)]
+    pub working_subdir: Option<PathBuf>,
+    /// Warnings logged with `add_warning_once`.
+    #[serde(skip)]
+    pub emitted_warnings: HashSet<String>,
+    /// Values of the Vault credentials fetched by this run, by `path#field`.
+    #[serde(skip)]
+    pub vault_secrets: HashMap<String, String>,
+    /// Variables of the `env_file` of the running step, set for its commands.
+    #[serde(skip)]
+    pub step_env: Vec<(String, String)>,
+    /// Whether docker was found, checked once by the first docker step.
+    #[serde(skip)]
+    pub docker_available: Option<bool>,
+    /// Standard output of the running bash step with `capture_output_to`. Shared with the clones reading the output.
+    #[serde(skip)]
+    pub captured_output: Option<Arc<Mutex<String>>>,
+}
+
+fn default_clean_env() -> bool {
+    true
 }
 
 impl JobResult {
@@ -49,10 +116,42 @@ impl JobResult {
             finished_at: None,
             logger,
             dry_run,
+            queued: false,
+            ephemeral: dry_run,
             child_process_ids: vec![],
+            cancellation: None,
+            source_refs: HashMap::new(),
+            log_level: LogLevel::default(),
+            clean_env: true,
+            git_default_branch: None,
+            summary: None,
+            sync_summary: None,
+            parameters: HashMap::new(),
+            working_subdir: None,
+            emitted_warnings: HashSet::new(),
+            vault_secrets: HashMap::new(),
+            step_env: vec![],
+            docker_available: None,
+            captured_output: None,
         }
     }
 
+    /// New result of `job` with the steps of `script`, e.g. to start a queued run under the id it was queued with.
+    pub fn from_script(job: &Job, script: &Script, id: String, dry_mode: bool) -> Result<Self, String> {
+        script.validate_step_names()?;
+        script.validate_has_steps()?;
+
+        let steps: Vec<RunningScriptStep> = script.steps.iter().map(RunningScriptStep::from).collect();
+        let logger = Arc::new(Mutex::new(JobLogger::new(job.id.clone(), id.clone(), dry_mode)?));
+
+        Ok(Self {
+            log_level: job.log_level.clone(),
+            clean_env: job.clean_env,
+            git_default_branch: job.git_default_branch.clone(),
+            ..Self::new(id, job.id.clone(), steps, logger, dry_mode)
+        })
+    }
+
     pub fn get_current_step_mut(&mut self) -> Option<&mut RunningScriptStep> {
         self.current_step_name
             .as_ref()
@@ -74,13 +173,15 @@ impl JobResult {
 
         let current_step_name = self.current_step_name.clone().ok_or("No current step")?;
 
-        if let Some(current_step) = self.get_current_step_mut() {
-            current_step.finish(status.clone());
-        } else {
-            return Err("Failed to get current step".to_string());
-        }
+        let continues = match self.get_current_step_mut() {
+            Some(current_step) => {
+                current_step.finish(status.clone());
+                status == ScriptStatus::Success || (status == ScriptStatus::Failed && current_step.continue_on_error)
+            }
+            None => return Err("Failed to get current step".to_string()),
+        };
 
-        if status != ScriptStatus::Success {
+        if !continues {
             self.status = status;
             self.updated_at = now;
             self.finished_at = Some(now);
@@ -102,10 +203,40 @@ impl JobResult {
         Ok(())
     }
 
+    /// Kills the running child processes and their descendants, descendants first.
+    pub fn kill_child_processes(&mut self) {
+        let s = System::new_all();
+        for child_process in std::mem::take(&mut self.child_process_ids) {
+            let mut processes = get_process_recursive(child_process);
+            processes.reverse(); // Kill child processes first
+            processes.push(Pid::from(child_process));
+            eprintln!("Killing processes with PID {}", child_process);
+            for process in processes {
+                if let Some(process) = s.process(process) {
+                    self.add_log(LogLevel::Info, format!("Killing process with PID {}", process.pid()));
+                    process.kill();
+                } else {
+                    eprintln!("Process with PID {} not found", process);
+                }
+            }
+        }
+    }
+
+    /// Messages longer than `NOMOS_MAX_LOG_LINE` are truncated. Values fetched from Vault are masked here, other
+    /// secrets must be masked by the caller, before the message is cut.
+    /// `text` with the secrets fetched by the run replaced by `***`, as they are in the logs.
+    pub fn mask_secrets(&self, text: String) -> String {
+        self.vault_secrets
+            .values()
+            .filter(|secret| !secret.is_empty())
+            .fold(text, |text, secret| text.replace(secret.as_str(), "***"))
+    }
+
     pub fn add_log(&self, level: LogLevel, message: String) {
+        let message = truncate_line(self.mask_secrets(message), max_log_line());
         eprintln!("{:?}: {}", level, message);
 
-        if self.dry_run {
+        if self.ephemeral || (level < self.log_level && level != LogLevel::Error) {
             return;
         }
 
@@ -117,6 +248,13 @@ impl JobResult {
         }
     }
 
+    /// Like `add_log` with `Warning`, but a message already logged by this run is dropped.
+    pub fn add_warning_once(&mut self, message: String) {
+        if self.emitted_warnings.insert(message.clone()) {
+            self.add_log(LogLevel::Warning, message);
+        }
+    }
+
     pub fn get_all(job_id: Option<String>) -> Result<Vec<Self>, String> {
         let path = default_job_results_location()?;
         let mut job_results = Vec::new();
@@ -140,22 +278,34 @@ impl JobResult {
             }
         }
 
-        job_results.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        job_results.sort_by_key(|b| std::cmp::Reverse(b.started_at));
         Ok(job_results)
     }
 
     pub fn get(id: &str) -> Result<Option<Self>, String> {
         let path = default_job_results_location()?.join(id).join("result.yml");
-        if !path.exists() {
+        if !path.exists() && !gz_path(&path).exists() {
             return Ok(None);
         }
 
-        let content = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        serde_yaml::from_str(&content).map_err(|e| e.to_string())
+        let content = read_maybe_compressed(&path)?;
+        serde_yaml::from_slice(&content).map_err(|e| e.to_string())
+    }
+
+    /// Compresses `result.yml` and the log of a finished result to `.gz` files. Both stay readable through `get`
+    /// and the logger.
+    pub fn compress(&self) -> Result<(), String> {
+        if self.ephemeral || self.finished_at.is_none() {
+            return Ok(());
+        }
+
+        let path = default_job_results_location()?.join(&self.id).join("result.yml");
+        gzip_file(&path)?;
+        self.logger.lock().map_err(|e| e.to_string())?.compress()
     }
 
     pub fn save(&self) -> Result<(), String> {
-        if self.dry_run {
+        if self.ephemeral {
             return Ok(());
         }
 
@@ -189,13 +339,17 @@ impl JobResult {
     }
 }
 
+/// `NOMOS_COMPRESS_RESULTS`, whether finished results are compressed.
+pub fn compress_results_enabled() -> bool {
+    std::env::var("NOMOS_COMPRESS_RESULTS").is_ok_and(|v| v == "1" || v == "true")
+}
+
 impl TryFrom<PathBuf> for JobResult {
     type Error = String;
 
     fn try_from(path: PathBuf) -> Result<Self, Self::Error> {
-        let file = File::open(&path).map_err(|e| format!("Could not open file: {}", e))?;
-        let reader = BufReader::new(file);
-        serde_yaml::from_reader(reader).map_err(|e| e.to_string())
+        let content = read_maybe_compressed(&path).map_err(|e| format!("Could not open file: {}", e))?;
+        serde_yaml::from_slice(&content).map_err(|e| e.to_string())
     }
 }
 
@@ -210,7 +364,12 @@ impl TryFrom<&Job> for JobResult {
         let steps: Vec<RunningScriptStep> = script.steps.iter().map(RunningScriptStep::from).collect();
         let logger = Arc::new(Mutex::new(JobLogger::new(job.id.clone(), id.clone(), false)?));
 
-        Ok(Self::new(id, job.id.clone(), steps, logger, false))
+        Ok(Self {
+            log_level: job.log_level.clone(),
+            clean_env: job.clean_env,
+            git_default_branch: job.git_default_branch.clone(),
+            ..Self::new(id, job.id.clone(), steps, logger, false)
+        })
     }
 }
 
@@ -223,14 +382,17 @@ impl TryFrom<(&Job, &Script, bool)> for JobResult {
         } else {
             "dry_run".to_string()
         };
-
-        let steps: Vec<RunningScriptStep> = script.steps.iter().map(RunningScriptStep::from).collect();
-        let logger = Arc::new(Mutex::new(JobLogger::new(job.id.clone(), id.clone(), dry_mode)?));
-
-        Ok(Self::new(id, job.id.clone(), steps, logger, dry_mode))
+        Self::from_script(job, script, id, dry_mode)
     }
 }
 
+fn serialize_redacted<S: Serializer>(
+    parameters: &HashMap<String, ScriptParameterType>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(parameters.iter().map(|(name, value)| (name, value.redacted())))
+}
+
 impl Clone for JobResult {
     fn clone(&self) -> Self {
         Self {
@@ -244,7 +406,23 @@ impl Clone for JobResult {
             finished_at: self.finished_at,
             logger: Arc::clone(&self.logger),
             dry_run: self.dry_run,
+            queued: self.queued,
+            ephemeral: self.ephemeral,
             child_process_ids: self.child_process_ids.clone(),
+            cancellation: self.cancellation.clone(),
+            source_refs: self.source_refs.clone(),
+            log_level: self.log_level.clone(),
+            clean_env: self.clean_env,
+            git_default_branch: self.git_default_branch.clone(),
+            summary: self.summary.clone(),
+            sync_summary: self.sync_summary.clone(),
+            parameters: self.parameters.clone(),
+            working_subdir: self.working_subdir.clone(),
+            emitted_warnings: self.emitted_warnings.clone(),
+            vault_secrets: self.vault_secrets.clone(),
+            step_env: self.step_env.clone(),
+            docker_available: self.docker_available,
+            captured_output: self.captured_output.clone(),
         }
     }
 }
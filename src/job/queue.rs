@@ -0,0 +1,119 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{script::ScriptParameterType, utils::data_root};
+
+pub fn default_queue_location() -> Result<PathBuf, String> {
+    Ok(data_root()?.join("queue.json"))
+}
+
+/// `NOMOS_MAX_RUNNING_JOBS`, the number of runs executed at the same time. Further runs wait in the queue.
+/// Unlimited if unset.
+pub fn max_running_jobs() -> Option<usize> {
+    std::env::var("NOMOS_MAX_RUNNING_JOBS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|max| *max > 0)
+}
+
+/// A run waiting for a free slot. Its job result already exists, so the id can be returned right away.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedExecution {
+    pub job_result_id: String,
+    pub job_id: String,
+    /// As provided, defaults are applied when the run starts. Secrets are left out, see `secret_parameters`.
+    pub parameters: HashMap<String, ScriptParameterType>,
+    /// Names of the provided password and credential parameters. Their values are only kept in memory, so the run
+    /// fails if the server restarts while it is queued.
+    #[serde(default)]
+    pub secret_parameters: Vec<String>,
+    pub queued_at: DateTime<Utc>,
+}
+
+/// Runs waiting for a free slot, in submission order. With a path, every change is written to the queue file and
+/// the file is read again before each change, so the queue survives restarts.
+#[derive(Debug)]
+pub struct ExecutionQueue {
+    entries: VecDeque<QueuedExecution>,
+    path: Option<PathBuf>,
+}
+
+impl ExecutionQueue {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        ExecutionQueue {
+            entries: VecDeque::new(),
+            path,
+        }
+    }
+
+    pub fn is_empty(&mut self) -> bool {
+        self.reload();
+        self.entries.is_empty()
+    }
+
+    pub fn push_back(&mut self, entry: QueuedExecution) -> Result<(), String> {
+        self.reload();
+        self.entries.push_back(entry);
+        self.save()
+    }
+
+    /// Puts back an entry that couldn't be started, ahead of the others.
+    pub fn push_front(&mut self, entry: QueuedExecution) -> Result<(), String> {
+        self.reload();
+        self.entries.push_front(entry);
+        self.save()
+    }
+
+    pub fn pop_front(&mut self) -> Result<Option<QueuedExecution>, String> {
+        self.reload();
+        let entry = self.entries.pop_front();
+        if entry.is_some() {
+            self.save()?;
+        }
+        Ok(entry)
+    }
+
+    /// Removes the entry of `job_result_id`, e.g. when the run is stopped before it started.
+    pub fn remove(&mut self, job_result_id: &str) -> Result<Option<QueuedExecution>, String> {
+        self.reload();
+        let Some(index) = self
+            .entries
+            .iter()
+            .position(|entry| entry.job_result_id == job_result_id)
+        else {
+            return Ok(None);
+        };
+        let entry = self.entries.remove(index);
+        self.save()?;
+        Ok(entry)
+    }
+
+    fn reload(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.entries = match std::fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("Failed to parse execution queue: {}", e);
+                VecDeque::new()
+            }),
+            Err(_) => VecDeque::new(),
+        };
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let content = serde_json::to_string(&self.entries).map_err(|e| e.to_string())?;
+        // Written to a temporary file first, so a crash can't leave a partial queue behind
+        let temporary = path.with_extension("json.tmp");
+        std::fs::write(&temporary, content).map_err(|e| e.to_string())?;
+        std::fs::rename(&temporary, path).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::script::ScriptParameterType;
+
+/// Number of runs whose secrets are remembered before the oldest are dropped.
+pub const RUN_SECRETS_CAPACITY: usize = 1000;
+
+/// Password and credential parameters of recent runs by job result id, kept in memory only. Results and the execution
+/// queue are written with secrets left out, so reruns and queued runs take them from here. They are lost on restart.
+#[derive(Debug)]
+pub struct RunSecrets {
+    entries: VecDeque<(String, HashMap<String, ScriptParameterType>)>,
+    capacity: usize,
+}
+
+impl Default for RunSecrets {
+    fn default() -> Self {
+        Self::new(RUN_SECRETS_CAPACITY)
+    }
+}
+
+impl RunSecrets {
+    pub fn new(capacity: usize) -> Self {
+        RunSecrets {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Remembers the secrets among `parameters`. Nothing is stored for a run without secrets.
+    pub fn insert(&mut self, job_result_id: &str, parameters: &HashMap<String, ScriptParameterType>) {
+        let secrets: HashMap<_, _> = parameters
+            .iter()
+            .filter(|(_, value)| value.is_secret())
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        self.entries.retain(|(id, _)| id != job_result_id);
+        if secrets.is_empty() {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((job_result_id.to_string(), secrets));
+    }
+
+    pub fn get(&self, job_result_id: &str) -> Option<&HashMap<String, ScriptParameterType>> {
+        self.entries
+            .iter()
+            .find(|(id, _)| id == job_result_id)
+            .map(|(_, secrets)| secrets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parameters(password: &str) -> HashMap<String, ScriptParameterType> {
+        HashMap::from([
+            (
+                "parameters.password".to_string(),
+                ScriptParameterType::Password(password.to_string()),
+            ),
+            (
+                "parameters.branch".to_string(),
+                ScriptParameterType::String("main".to_string()),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_insert_keeps_secrets_only() {
+        let mut secrets = RunSecrets::new(2);
+        secrets.insert("1", &parameters("first"));
+        secrets.insert("2", &HashMap::new());
+
+        assert_eq!(
+            secrets.get("1"),
+            Some(&HashMap::from([(
+                "parameters.password".to_string(),
+                ScriptParameterType::Password("first".to_string())
+            )]))
+        );
+        assert_eq!(secrets.get("2"), None);
+
+        secrets.insert("3", &parameters("third"));
+        secrets.insert("4", &parameters("fourth"));
+        // The oldest run is dropped
+        assert_eq!(secrets.get("1"), None);
+        assert!(secrets.get("3").is_some());
+        assert!(secrets.get("4").is_some());
+    }
+}
@@ -1,88 +1,102 @@
 use once_cell::sync::Lazy;
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Seek, SeekFrom, Write},
-    path::PathBuf,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::Mutex,
 };
 
-use super::models::JobResult;
+use crate::utils::{data_directory, data_root};
 
 pub fn default_job_results_location() -> Result<PathBuf, String> {
-    let path = if cfg!(target_os = "windows") {
-        let appdata = std::env::var("APPDATA").map_err(|e| e.to_string())?;
-        PathBuf::from(appdata).join("nomos").join("job_results")
-    } else {
-        PathBuf::from("/var/lib/nomos/job_results")
-    };
-    std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
-    Ok(path)
+    data_directory("job_results")
 }
 
 pub fn default_jobs_location() -> Result<PathBuf, String> {
-    let path = if cfg!(target_os = "windows") {
-        let appdata = std::env::var("APPDATA").map_err(|e| e.to_string())?;
-        PathBuf::from(appdata).join("nomos").join("jobs")
-    } else {
-        PathBuf::from("/var/lib/nomos/jobs")
-    };
-    std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
-    Ok(path)
+    data_directory("jobs")
 }
 
-static JOB_RESULTS: Lazy<Arc<Mutex<File>>> = Lazy::new(|| {
-    let path = if cfg!(target_os = "windows") {
-        let appdata = std::env::var("APPDATA").unwrap_or_else(|_| ".".to_string());
-        let mut path = PathBuf::from(appdata);
-        path.push("nomos");
-        path.push("ids.txt");
-        path
-    } else {
-        let mut path = PathBuf::from("/var/lib/nomos");
-        path.push("ids.txt");
-        path
+fn ids_location() -> Result<PathBuf, String> {
+    Ok(data_root()?.join("ids.txt"))
+}
+
+/// Last job result id handed out, recovered from disk by the first `next_job_result_id`.
+static LAST_JOB_RESULT_ID: Lazy<Mutex<Option<u64>>> = Lazy::new(|| Mutex::new(None));
+
+/// Returns the next job result id, counted in .../nomos/ids.txt
+pub fn next_job_result_id() -> Result<String, String> {
+    let ids_path = ids_location()?;
+    let results_directory = default_job_results_location()?;
+    let mut last_id = LAST_JOB_RESULT_ID.lock().unwrap_or_else(|e| e.into_inner());
+
+    let last = match *last_id {
+        Some(id) => id,
+        None => recover_last_id(&ids_path, &results_directory)?,
     };
+    let id = next_id(last, &ids_path, &results_directory)?;
+    *last_id = Some(id);
+    Ok(id.to_string())
+}
 
-    // Ensure the parent directory exists
-    if let Some(parent) = path.parent() {
-        if !parent.exists() {
-            std::fs::create_dir_all(parent).expect("Failed to create directories");
-        }
+/// The counter of `ids_path`, or the largest result directory if the counter is missing, corrupt (e.g. the process
+/// was killed while writing it) or behind.
+fn recover_last_id(ids_path: &Path, results_directory: &Path) -> Result<u64, String> {
+    let counter = std::fs::read_to_string(ids_path)
+        .ok()
+        .and_then(|content| content.trim().parse::<u64>().ok())
+        .unwrap_or(0);
+    let largest = std::fs::read_dir(results_directory)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u64>().ok())
+        .max()
+        .unwrap_or(0);
+    if largest > counter {
+        eprintln!(
+            "Job result counter {} is behind the results, continuing after {}",
+            counter, largest
+        );
     }
+    Ok(counter.max(largest))
+}
 
-    // Open the file with read/write permissions, create if it doesn't exist
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&path)
-        .expect("Failed to open or create file");
+/// The id after `last` without a result directory, saved as the new counter.
+fn next_id(last: u64, ids_path: &Path, results_directory: &Path) -> Result<u64, String> {
+    let mut id = last + 1;
+    while results_directory.join(id.to_string()).exists() {
+        id += 1;
+    }
 
-    Arc::new(Mutex::new(file))
-});
+    // Renamed over the counter, so a crash leaves either the old or the new value
+    let tmp_path = ids_path.with_extension("txt.tmp");
+    std::fs::write(&tmp_path, id.to_string()).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp_path, ids_path).map_err(|e| e.to_string())?;
+    Ok(id)
+}
 
-/// Reads .../nomos/ids.txt and returns the next job id
-pub fn next_job_result_id() -> Result<String, String> {
-    let binding = Arc::clone(&JOB_RESULTS);
-    let mut file = binding.lock().unwrap_or_else(|e| e.into_inner());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut content = String::new();
-    file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
-    file.read_to_string(&mut content).map_err(|e| e.to_string())?;
+    #[test]
+    fn test_recover_corrupt_counter() {
+        let directory = tempfile::tempdir().unwrap();
+        let ids_path = directory.path().join("ids.txt");
+        let results_directory = directory.path().join("job_results");
+        for id in ["3", "41", "backup"] {
+            std::fs::create_dir_all(results_directory.join(id)).unwrap();
+        }
 
-    let id = content.trim().parse::<u64>().unwrap_or(0);
+        for content in ["", "4\0\0", "not a number"] {
+            std::fs::write(&ids_path, content).unwrap();
+            let last = recover_last_id(&ids_path, &results_directory).unwrap();
+            assert_eq!(last, 41);
+            assert_eq!(next_id(last, &ids_path, &results_directory).unwrap(), 42);
+            assert_eq!(std::fs::read_to_string(&ids_path).unwrap(), "42");
+            assert!(!ids_path.with_extension("txt.tmp").exists());
+        }
 
-    let mut next_id = id + 1;
-    while JobResult::get(&next_id.to_string())?.is_some() {
-        next_id += 1;
+        // A counter ahead of the results is kept, existing directories are skipped
+        std::fs::write(&ids_path, "99").unwrap();
+        std::fs::create_dir_all(results_directory.join("100")).unwrap();
+        let last = recover_last_id(&ids_path, &results_directory).unwrap();
+        assert_eq!(next_id(last, &ids_path, &results_directory).unwrap(), 101);
     }
-
-    file.seek(SeekFrom::Start(0)).map_err(|e| e.to_string())?;
-    file.set_len(0).map_err(|e| e.to_string())?;
-    file.write_all(next_id.to_string().as_bytes())
-        .map_err(|e| e.to_string())?;
-    file.flush().map_err(|e| e.to_string())?;
-
-    Ok(next_id.to_string())
 }
@@ -1,8 +1,14 @@
+pub mod backup;
+pub mod cache;
 pub mod credential;
 pub mod docker;
+pub mod error;
 pub mod git;
 pub mod job;
 pub mod log;
+pub mod maintenance;
+pub mod notification;
 pub mod script;
+pub mod server;
 pub mod settings;
 pub mod utils;
@@ -2,11 +2,15 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::fs::OpenOptions;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::utils::{gz_path, gzip_file, read_maybe_compressed};
+
+/// Ordered by severity, `Info` being the lowest.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub enum LogLevel {
+    #[default]
     Info,
     Warning,
     Error,
@@ -22,6 +26,56 @@ impl Display for LogLevel {
     }
 }
 
+/// Appended to log lines cut by `truncate_line`.
+pub const TRUNCATED_MARKER: &str = "…(truncated)";
+
+/// `NOMOS_MAX_LOG_LINE`, the maximum length of a log line in bytes. Defaults to 64 KiB.
+pub fn max_log_line() -> usize {
+    std::env::var("NOMOS_MAX_LOG_LINE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(64 * 1024)
+}
+
+/// `NOMOS_STEP_LOG_FILES`, whether each step is also logged to its own file next to the combined log.
+pub fn step_log_files() -> bool {
+    std::env::var("NOMOS_STEP_LOG_FILES").is_ok_and(|v| v == "true")
+}
+
+/// File name of the log of `step_name`. Characters other than ASCII letters, digits, `-` and `_` are replaced, so
+/// different steps can share a file. Their lines are told apart by `Log::step_name`.
+pub fn step_log_file_name(step_name: &str) -> String {
+    format!("{}.log", safe_file_name(step_name))
+}
+
+/// `name` with characters other than ASCII letters, digits, `-` and `_` replaced by `_`.
+pub fn safe_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Cuts `line` to at most `max` bytes, at a character boundary, and marks it as truncated.
+pub fn truncate_line(mut line: String, max: usize) -> String {
+    if line.len() <= max {
+        return line;
+    }
+    let mut end = max;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    line.truncate(end);
+    line.push_str(TRUNCATED_MARKER);
+    line
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Log {
     pub level: LogLevel,
@@ -35,6 +89,9 @@ pub struct JobLogger {
     log_filename: PathBuf,
     job_id: String,
     result_id: String,
+    /// Also write each step to `steps/<step>.log`. The combined log stays the source of truth.
+    #[serde(default)]
+    step_files: bool,
 }
 
 impl JobLogger {
@@ -45,6 +102,7 @@ impl JobLogger {
                 log_filename: log_path.clone(),
                 job_id,
                 result_id,
+                step_files: false,
             });
         }
 
@@ -63,9 +121,17 @@ impl JobLogger {
             log_filename: log_path.clone(),
             job_id,
             result_id,
+            step_files: step_log_files(),
         })
     }
 
+    /// Only used by the handler tests, which are not part of the library.
+    #[cfg(test)]
+    #[allow(dead_code)]
+    pub(crate) fn set_step_files(&mut self, step_files: bool) {
+        self.step_files = step_files;
+    }
+
     pub fn log(&mut self, level: LogLevel, step_name: &str, message: &str) -> Result<(), String> {
         let log = Log {
             level,
@@ -74,27 +140,119 @@ impl JobLogger {
             timestamp: Utc::now(),
         };
 
+        let line = serde_json::to_string(&log).map_err(|e| e.to_string())?;
         let mut file = OpenOptions::new()
             .append(true)
             .open(&self.log_filename)
             .map_err(|e| e.to_string())?;
 
-        writeln!(file, "{}", serde_json::to_string(&log).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())?;
+
+        if self.step_files && !step_name.is_empty() {
+            let path = step_logs_directory(&self.log_filename).join(step_log_file_name(step_name));
+            let written = std::fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))
+                .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+                .and_then(|mut file| writeln!(file, "{}", line));
+            // The combined log already has the line
+            if let Err(e) = written {
+                eprintln!("Failed to write step log {:?}: {}", path, e);
+            }
+        }
 
         Ok(())
     }
 
     pub fn get_logs(&self) -> Result<Vec<Log>, String> {
-        let path = get_log_file_path(&self.job_id, &self.result_id)?;
-        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        read_logs(&self.log_filename)
+    }
 
-        let logs = content
-            .lines()
-            .filter_map(|line| serde_json::from_str::<Log>(line).ok())
-            .collect();
+    /// Logs of `step_name`, from its own file if there is one and from the combined log otherwise.
+    pub fn get_step_logs(&self, step_name: &str) -> Result<Vec<Log>, String> {
+        let path = &self.log_filename;
+        let step_path = step_logs_directory(path).join(step_log_file_name(step_name));
+        let logs = if step_path.exists() || gz_path(&step_path).exists() {
+            read_logs(&step_path)?
+        } else {
+            read_logs(path)?
+        };
+        Ok(logs.into_iter().filter(|log| log.step_name == step_name).collect())
+    }
 
-        Ok(logs)
+    /// Logs of each step log file by file name, e.g. `Build.log`, sorted by name. Empty if the steps were not logged
+    /// to their own files.
+    pub fn get_step_files_logs(&self) -> Result<Vec<(String, Vec<Log>)>, String> {
+        let directory = step_logs_directory(&self.log_filename);
+        let Ok(entries) = std::fs::read_dir(&directory) else {
+            return Ok(vec![]);
+        };
+        let mut names = entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let name = name.strip_suffix(".gz").map(str::to_string).unwrap_or(name);
+                name.ends_with(".log").then_some(name)
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .map(|name| Ok((name.clone(), read_logs(&directory.join(&name))?)))
+            .collect()
     }
+
+    /// Raw bytes of the log file starting at `offset`, and the offset to continue from. If the file shrank below
+    /// `offset` (truncated or replaced), it is read again from the start.
+    pub fn read_raw(&self, offset: u64) -> Result<(Vec<u8>, u64), String> {
+        let path = &self.log_filename;
+        if !path.exists() {
+            // Compressed, the log is complete
+            let bytes = read_maybe_compressed(path)?;
+            let len = bytes.len() as u64;
+            let offset = if offset > len { 0 } else { offset };
+            return Ok((bytes[offset as usize..].to_vec(), len));
+        }
+
+        let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        let len = file.metadata().map_err(|e| e.to_string())?.len();
+        let offset = if offset > len { 0 } else { offset };
+
+        file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(|e| e.to_string())?;
+        let next_offset = offset + bytes.len() as u64;
+        Ok((bytes, next_offset))
+    }
+
+    /// Replaces the log file with `log.gz`. Nothing can be logged afterwards.
+    pub fn compress(&self) -> Result<(), String> {
+        let path = &self.log_filename;
+        if path.exists() {
+            gzip_file(path)?;
+        }
+        if let Ok(entries) = std::fs::read_dir(step_logs_directory(path)) {
+            for entry in entries.flatten() {
+                if entry.path().extension().is_some_and(|extension| extension == "log") {
+                    gzip_file(&entry.path())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn step_logs_directory(log_path: &Path) -> PathBuf {
+    log_path.parent().unwrap_or(Path::new(".")).join("steps")
+}
+
+fn read_logs(path: &Path) -> Result<Vec<Log>, String> {
+    let content = read_maybe_compressed(path)?;
+    let content = String::from_utf8_lossy(&content);
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Log>(line).ok())
+        .collect())
 }
 
 fn get_log_file_path(_job_id: &str, result_id: &str) -> Result<PathBuf, String> {
@@ -112,3 +270,46 @@ fn get_log_file_path(_job_id: &str, result_id: &str) -> Result<PathBuf, String>
             .join("log"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_log_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log_filename = temp_dir.path().join("log");
+        std::fs::write(&log_filename, "").unwrap();
+        let mut logger = JobLogger {
+            log_filename: log_filename.clone(),
+            job_id: "step-log-files-job".to_string(),
+            result_id: "step-log-files-result".to_string(),
+            step_files: true,
+        };
+        logger.log(LogLevel::Info, "", "before the steps").unwrap();
+        logger.log(LogLevel::Info, "Build", "building").unwrap();
+        logger.log(LogLevel::Info, "Test / unit", "unit tests").unwrap();
+        logger.log(LogLevel::Info, "Test : unit", "same file").unwrap();
+        logger.log(LogLevel::Error, "Build", "build failed").unwrap();
+
+        let directory = step_logs_directory(&log_filename);
+        let build = read_logs(&directory.join("Build.log")).unwrap();
+        assert_eq!(
+            build.iter().map(|log| log.message.as_str()).collect::<Vec<_>>(),
+            vec!["building", "build failed"]
+        );
+        assert_eq!(step_log_file_name("Test / unit"), "Test___unit.log");
+        assert_eq!(read_logs(&directory.join("Test___unit.log")).unwrap().len(), 2);
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 2);
+
+        let unit = logger.get_step_logs("Test / unit").unwrap();
+        assert_eq!(unit.len(), 1);
+        assert_eq!(unit[0].message, "unit tests");
+        // The combined log has everything
+        assert_eq!(logger.get_logs().unwrap().len(), 5);
+
+        logger.compress().unwrap();
+        assert_eq!(logger.get_step_logs("Build").unwrap().len(), 2);
+        assert!(logger.get_step_logs("Deploy").unwrap().is_empty());
+    }
+}
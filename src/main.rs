@@ -1,14 +1,23 @@
+// The OpenAPI document is one large `json!` literal
+#![recursion_limit = "256"]
+
+mod backup;
+mod cache;
 mod credential;
 mod docker;
+mod error;
 mod git;
 mod handlers;
 mod job;
 mod log;
+mod maintenance;
+mod notification;
 mod script;
+mod server;
 mod settings;
 mod utils;
 
-use axum::{routing, Router};
+use axum::{handler::Handler, middleware, routing, Router};
 use axum_login::{
     login_required,
     tower_sessions::{MemoryStore, SessionManagerLayer},
@@ -16,13 +25,21 @@ use axum_login::{
 };
 use handlers::*;
 use job::JobExecutor;
+use maintenance::Maintenance;
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 #[derive(Clone)]
 struct AppState {
     job_executor: Arc<JobExecutor>,
+    /// External URL of the server, used for absolute links. Read from `NOMOS_BASE_URL`.
+    base_url: Option<String>,
+    /// While enabled, new executions are answered with 503.
+    maintenance: Arc<Maintenance>,
+    /// Recent API requests, filled by the `record_requests` middleware.
+    request_log: Arc<RequestLog>,
+    /// Recent `X-GitHub-Delivery` ids of the webhook route.
+    webhook_deliveries: Arc<WebhookDeliveries>,
 }
 
 fn create_router() -> Router<AppState> {
@@ -31,20 +48,51 @@ fn create_router() -> Router<AppState> {
         .route("/api/credentials/:id", routing::get(get_credential))
         .route("/api/credentials", routing::post(create_credential))
         .route("/api/credentials/:id", routing::delete(delete_credential))
+        .route("/api/credentials/:id/usages", routing::get(get_credential_usages))
         .route("/api/scripts", routing::get(get_scripts))
         .route("/api/scripts/:id", routing::get(get_script))
         .route("/api/scripts", routing::post(create_script))
         .route("/api/scripts/:id", routing::delete(delete_script))
+        .route("/api/scripts/:id/yaml", routing::get(get_script_yaml))
         .route("/api/jobs", routing::get(get_jobs))
         .route("/api/jobs/:id", routing::get(get_job))
         .route("/api/jobs", routing::post(create_job))
         .route("/api/jobs/:id", routing::delete(delete_job))
+        .route("/api/jobs/:id/status", routing::get(get_job_status))
+        .route("/api/jobs/:id/parameters", routing::get(get_job_parameters))
+        .route("/api/jobs/:id/yaml", routing::get(get_job_yaml))
+        .route("/api/jobs/:id/resolved", routing::get(get_job_resolved))
+        .route("/api/jobs/:id/preview", routing::post(preview_job))
         .route("/api/jobs/:id/execute", routing::post(execute_job))
         .route("/api/jobs/dry-run", routing::post(dry_run_job))
+        .route("/api/jobs/stop-all", routing::post(stop_all_jobs))
+        .route("/api/jobs/execute-batch", routing::post(execute_batch))
+        .route("/api/jobs/:id/dry-run", routing::post(dry_run_saved_job))
         .route("/api/job-results", routing::get(get_job_results))
         .route("/api/job-results/:id", routing::get(get_job_result))
         .route("/api/job-results/:id/stop", routing::post(stop_job))
+        .route(
+            "/api/job-results/:id/rerun-from/:step_name",
+            routing::post(rerun_from_step),
+        )
         .route("/api/job-results/:id/logs", routing::get(get_job_result_logs))
+        .route("/api/job-results/:id/logs/raw", routing::get(get_job_result_raw_logs))
+        .route(
+            "/api/job-results/:id/logs/download",
+            routing::get(download_job_result_logs),
+        )
+        .route(
+            "/api/job-results/:id/logs/step/:step_name",
+            routing::get(get_job_result_step_logs),
+        )
+        .route("/api/openapi.json", routing::get(get_openapi))
+        .route("/api/webhook/debug", routing::post(debug_webhook_signature))
+        .route("/api/export", routing::get(export_configuration))
+        .route("/api/import", routing::post(import_configuration))
+        .route("/api/sync", routing::post(sync_configuration))
+        .route("/api/maintenance", routing::get(get_maintenance))
+        .route("/api/maintenance", routing::post(set_maintenance))
+        .route("/api/debug/requests", routing::get(get_recent_requests))
         .route("/", routing::get(template_job_results))
         .route("/credentials", routing::get(template_credentials))
         .route("/credentials/create", routing::get(template_create_credential))
@@ -79,22 +127,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         });
     }
 
-    // initialize tracing
+    // Credentials saved before NOMOS_SECRET_KEY was set are rejected until signed
+    if std::env::args().nth(1).as_deref() == Some("sign-credentials") {
+        for path in credential::sign_unsigned_credentials()? {
+            println!("Signed {}", path.display());
+        }
+        return Ok(());
+    }
+
+    // initialize tracing, as JSON lines with NOMOS_LOG_FORMAT=json
+    let json = server::LogFormat::from_env()? == server::LogFormat::Json;
     tracing_subscriber::registry()
-        .with(EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "axum_login=debug,tower_http=debug".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
+        .with(EnvFilter::new(std::env::var("RUST_LOG").unwrap_or_else(|_| {
+            "axum_login=debug,tower_http=debug,nomos_rust=info".into()
+        })))
+        .with((!json).then(tracing_subscriber::fmt::layer))
+        .with(json.then(server::json_log_layer))
         .try_init()?;
 
-    // Session layer.
-    let session_store = MemoryStore::default();
-    let session_layer = SessionManagerLayer::new(session_store);
-
-    // Auth service.
-    let backend = Backend::default();
-    let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
-
     let mut app = create_router();
 
     // Only add authentication layer in release mode
@@ -102,22 +152,63 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         app = app.route_layer(login_required!(Backend, login_url = "/login"));
     }
 
+    let rate_limiter = Arc::new(RateLimiter::from_env()?);
     app = app
         .route("/login", routing::get(template_get_login))
         .route("/login", routing::post(template_post_login))
-        .route("/public/api/webhook", routing::post(job_webhook_trigger))
-        .layer(auth_layer)
-        .layer(CorsLayer::permissive());
+        .route(
+            "/public/api/webhook",
+            routing::post(job_webhook_trigger.layer(webhook_body_limit()))
+                .layer(middleware::from_fn_with_state(rate_limiter.clone(), rate_limit)),
+        )
+        .route(
+            "/public/api/webhook/:job_id",
+            routing::post(job_targeted_webhook_trigger.layer(webhook_body_limit()))
+                .layer(middleware::from_fn_with_state(rate_limiter, rate_limit)),
+        );
+
+    // Session layer and auth service. Sessions are kept in memory unless NOMOS_SESSION_STORE selects another store.
+    let backend = Backend::from_env()?;
+    app = match FileSessionStore::from_env()? {
+        Some(session_store) => {
+            tokio::spawn(
+                session_store
+                    .clone()
+                    .delete_expired_periodically(std::time::Duration::from_secs(3600)),
+            );
+            app.layer(AuthManagerLayerBuilder::new(backend, SessionManagerLayer::new(session_store)).build())
+        }
+        None => {
+            let session_layer = SessionManagerLayer::new(MemoryStore::default());
+            app.layer(AuthManagerLayerBuilder::new(backend, session_layer).build())
+        }
+    };
+    let request_log = Arc::new(RequestLog::default());
+    app = app
+        .layer(middleware::from_fn_with_state(request_log.clone(), record_requests))
+        .layer(server::cors_layer_from_env()?);
+
+    let job_executor = Arc::new(JobExecutor::new());
+    // Runs that were queued before the restart
+    job_executor.resume_queue().await;
 
     // Apply state to the router
     let app_state = AppState {
-        job_executor: Arc::new(JobExecutor::new()),
+        job_executor,
+        base_url: std::env::var("NOMOS_BASE_URL").ok().filter(|v| !v.is_empty()),
+        maintenance: Arc::new(Maintenance::load()?),
+        request_log,
+        webhook_deliveries: Default::default(),
     };
     let app = app.with_state(app_state);
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
-        .await
-        .map_err(|e| e.to_string())?;
-    axum::serve(listener, app).await.map_err(|e| e.into())
+    // Clone and sync the config repository in the background, failures must not prevent serving.
+    if let Some(config_repository) = settings::ConfigRepository::from_env() {
+        tokio::spawn(config_repository.run());
+    }
+
+    // Listening globally on port 3000, over HTTPS if a certificate is configured
+    let tls = server::TlsFiles::from_env()?;
+    let listener = std::net::TcpListener::bind("0.0.0.0:3000").map_err(|e| e.to_string())?;
+    server::serve(listener, app, tls).await.map_err(|e| e.into())
 }
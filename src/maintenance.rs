@@ -0,0 +1,67 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::utils::data_root;
+
+/// While enabled, new executions are rejected. Running jobs and read endpoints are not affected.
+///
+/// The flag is persisted as the existence of a marker file, so it survives a restart.
+#[derive(Debug)]
+pub struct Maintenance {
+    path: PathBuf,
+    enabled: AtomicBool,
+}
+
+impl Maintenance {
+    pub fn new(path: PathBuf) -> Self {
+        let enabled = AtomicBool::new(path.exists());
+        Maintenance { path, enabled }
+    }
+
+    /// Reads the flag from `default_maintenance_location()`.
+    pub fn load() -> Result<Self, String> {
+        Ok(Self::new(default_maintenance_location()?))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set(&self, enabled: bool) -> Result<(), String> {
+        if enabled {
+            std::fs::write(&self.path, "").map_err(|e| e.to_string())?;
+        } else if self.path.exists() {
+            std::fs::remove_file(&self.path).map_err(|e| e.to_string())?;
+        }
+        self.enabled.store(enabled, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+pub fn default_maintenance_location() -> Result<PathBuf, String> {
+    Ok(data_root()?.join("maintenance"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maintenance_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("maintenance");
+
+        let maintenance = Maintenance::new(path.clone());
+        assert!(!maintenance.is_enabled());
+
+        maintenance.set(true).unwrap();
+        assert!(maintenance.is_enabled());
+        assert!(Maintenance::new(path.clone()).is_enabled());
+
+        maintenance.set(false).unwrap();
+        assert!(!maintenance.is_enabled());
+        assert!(!Maintenance::new(path).is_enabled());
+    }
+}
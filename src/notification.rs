@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    credential::{Credential, CredentialType},
+    job::JobResult,
+    script::models::ScriptStatus,
+    utils::compute_signature,
+};
+
+/// Header of the HMAC-SHA256 signature of the body, `sha256=<hex>` like the GitHub webhooks.
+pub const SIGNATURE_HEADER: &str = "X-Nomos-Signature-256";
+
+/// Where to report a run once it has finished.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(tag = "type")]
+pub enum NotificationTarget {
+    #[serde(rename = "webhook")]
+    Webhook(WebhookNotification),
+}
+
+/// POSTs a `WebhookPayload` as JSON to `url`.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct WebhookNotification {
+    pub url: String,
+    /// Text credential signing the body, see `SIGNATURE_HEADER`. Unsigned if unset.
+    #[serde(default)]
+    pub credential_id: Option<String>,
+    #[serde(default)]
+    pub on: NotifyOn,
+}
+
+/// Results a notification is sent for.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+pub enum NotifyOn {
+    #[default]
+    #[serde(rename = "always")]
+    Always,
+    #[serde(rename = "success")]
+    Success,
+    /// Failed and aborted runs.
+    #[serde(rename = "failure")]
+    Failure,
+}
+
+impl NotifyOn {
+    pub fn matches(&self, status: &ScriptStatus) -> bool {
+        match self {
+            NotifyOn::Always => true,
+            NotifyOn::Success => *status == ScriptStatus::Success,
+            NotifyOn::Failure => matches!(status, ScriptStatus::Failed | ScriptStatus::Aborted),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct WebhookPayload {
+    pub job_id: String,
+    pub job_result_id: String,
+    pub status: ScriptStatus,
+    pub duration_ms: Option<i64>,
+    /// Commit checked out by the first git step, if any.
+    pub sha: Option<String>,
+}
+
+impl From<&JobResult> for WebhookPayload {
+    fn from(job_result: &JobResult) -> Self {
+        WebhookPayload {
+            job_id: job_result.job_id.clone(),
+            job_result_id: job_result.id.clone(),
+            status: job_result.status.clone(),
+            duration_ms: job_result
+                .finished_at
+                .map(|finished_at| (finished_at - job_result.started_at).num_milliseconds()),
+            sha: job_result
+                .steps
+                .iter()
+                .find_map(|step| job_result.source_refs.get(&step.name).cloned()),
+        }
+    }
+}
+
+impl WebhookNotification {
+    /// Blocks until the endpoint answered.
+    pub fn send(&self, payload: &WebhookPayload) -> Result<(), String> {
+        let body = serde_json::to_string(payload).map_err(|e| e.to_string())?;
+        let mut request = ureq::post(&self.url)
+            .set("Content-Type", "application/json")
+            .timeout(std::time::Duration::from_secs(10));
+        if let Some(credential_id) = &self.credential_id {
+            request = request.set(
+                SIGNATURE_HEADER,
+                &compute_signature(&body, &signing_key(credential_id)?)?,
+            );
+        }
+        request
+            .send_string(&body)
+            .map_err(|e| format!("Failed to notify {}: {}", self.url, e))?;
+        Ok(())
+    }
+}
+
+fn signing_key(credential_id: &str) -> Result<String, String> {
+    let credential =
+        Credential::get(credential_id, None)?.ok_or_else(|| format!("Credential not found: {}", credential_id))?;
+    match credential.value {
+        CredentialType::Text(text) => Ok(text.value),
+        CredentialType::Vault(vault) => Ok(vault.fetch()?),
+        _ => Err(format!("Credential is not Text: {}", credential_id)),
+    }
+}
+
+/// Sends the notifications of a finished run. Dry runs notify nobody. Failures are only logged, they don't change
+/// the result.
+pub async fn notify(targets: &[NotificationTarget], job_result: &JobResult) {
+    if job_result.dry_run {
+        return;
+    }
+    let payload = WebhookPayload::from(job_result);
+    for target in targets {
+        match target {
+            NotificationTarget::Webhook(webhook) => {
+                if !webhook.on.matches(&payload.status) {
+                    continue;
+                }
+                let webhook = webhook.clone();
+                let payload = payload.clone();
+                match tokio::task::spawn_blocking(move || webhook.send(&payload)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => eprintln!("Notification of job result {} failed: {}", job_result.id, e),
+                    Err(e) => eprintln!("Notification of job result {} failed: {}", job_result.id, e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_on() {
+        assert!(NotifyOn::Always.matches(&ScriptStatus::Failed));
+        assert!(NotifyOn::Success.matches(&ScriptStatus::Success));
+        assert!(!NotifyOn::Success.matches(&ScriptStatus::Aborted));
+        assert!(NotifyOn::Failure.matches(&ScriptStatus::Aborted));
+        assert!(!NotifyOn::Failure.matches(&ScriptStatus::Success));
+    }
+
+    #[test]
+    fn test_deserialize_webhook() {
+        let target: NotificationTarget = serde_yaml::from_str(
+            r#"
+type: webhook
+url: https://dashboard.example.com/hooks/nomos
+on: failure
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            target,
+            NotificationTarget::Webhook(WebhookNotification {
+                url: "https://dashboard.example.com/hooks/nomos".to_string(),
+                credential_id: None,
+                on: NotifyOn::Failure,
+            })
+        );
+    }
+}
@@ -1,8 +1,22 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use crate::{job::JobResult, log::LogLevel};
+use crate::{
+    credential::parse_env,
+    job::JobResult,
+    log::LogLevel,
+    utils::{resolve_file, resolve_subdirectory},
+};
 
-use super::{models::RunningScriptStep, types::ScriptType, ScriptParameterType};
+use super::{
+    models::RunningScriptStep,
+    types::ScriptType,
+    utils::{ParameterSubstitution, SubstitutionResult},
+    ScriptParameterType,
+};
 use async_trait::async_trait;
 
 pub struct ScriptExecutionContext<'a> {
@@ -10,6 +24,8 @@ pub struct ScriptExecutionContext<'a> {
     pub directory: &'a Path,
     pub step_name: &'a str,
     pub job_result: &'a mut JobResult,
+    /// Directory of the last git-clone. Bash steps run inside it unless they specify a directory.
+    pub current_working_subdir: &'a mut Option<PathBuf>,
 }
 
 #[async_trait]
@@ -24,9 +40,112 @@ impl ScriptExecutor for RunningScriptStep {
         context
             .job_result
             .add_log(LogLevel::Info, format!("Executing step: {}", context.step_name));
+
+        context.job_result.step_env = self.load_env_file(context)?;
+        let result = self.execute_in_working_directory(context).await;
+        context.job_result.step_env.clear();
+        result
+    }
+}
+
+impl RunningScriptStep {
+    async fn execute_in_working_directory(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+        let Some(working_directory) = &self.working_directory else {
+            return self.execute_values(context).await;
+        };
+        let working_directory = match working_directory
+            .substitute_parameters(context.parameters, false)?
+            .ok_or("Working directory is required")?
+        {
+            SubstitutionResult::Single(s) => s,
+            SubstitutionResult::Multiple(_) => {
+                return Err("Working directory parameter cannot be an array".to_string());
+            }
+        };
+        let directory = resolve_subdirectory(context.directory, &working_directory, !context.job_result.dry_run)?;
+
+        // The last git-clone only applies to the steps without a working directory, but a clone inside this step
+        // is still visible to the next steps.
+        let mut current_working_subdir = None;
+        let mut step_context = ScriptExecutionContext {
+            parameters: context.parameters,
+            directory: &directory,
+            step_name: context.step_name,
+            job_result: context.job_result,
+            current_working_subdir: &mut current_working_subdir,
+        };
+        let result = self.execute_values(&mut step_context).await;
+        if current_working_subdir.is_some() {
+            *context.current_working_subdir = current_working_subdir;
+        }
+        result
+    }
+
+    /// Variables of the `env_file` of the step. Nothing is cloned in dry run mode, so a missing file is skipped.
+    fn load_env_file(&self, context: &mut ScriptExecutionContext<'_>) -> Result<Vec<(String, String)>, String> {
+        let Some(env_file) = &self.env_file else {
+            return Ok(vec![]);
+        };
+        let env_file = match env_file
+            .substitute_parameters(context.parameters, false)?
+            .ok_or("Env file is required")?
+        {
+            SubstitutionResult::Single(s) => s,
+            SubstitutionResult::Multiple(_) => {
+                return Err("Env file parameter cannot be an array".to_string());
+            }
+        };
+        let path = resolve_file(context.directory, &env_file)?;
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) if context.job_result.dry_run => {
+                context
+                    .job_result
+                    .add_log(LogLevel::Info, format!("Dry run: env file {} is not read", env_file));
+                return Ok(vec![]);
+            }
+            Err(e) => return Err(format!("Failed to read env file {}: {}", env_file, e)),
+        };
+        let env = parse_env(&content);
+        context.job_result.add_log(
+            LogLevel::Info,
+            format!("Loaded {} variables from env file {}", env.len(), env_file),
+        );
+        Ok(env)
+    }
+
+    async fn execute_values(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+        let started_at = Instant::now();
         for value in self.values.iter() {
             tokio::task::yield_now().await;
-            value.execute(context).await?;
+            let value_started_at = Instant::now();
+            let result = match self.timeout_seconds {
+                Some(timeout_seconds) => {
+                    let remaining = Duration::from_secs(timeout_seconds).saturating_sub(started_at.elapsed());
+                    match tokio::time::timeout(remaining, value.execute(context)).await {
+                        Ok(result) => result,
+                        Err(_) => {
+                            context.job_result.add_log(
+                                LogLevel::Error,
+                                format!("Step {} timed out after {}s", context.step_name, timeout_seconds),
+                            );
+                            context.job_result.kill_child_processes();
+                            Err(format!("Timed out after {}s", timeout_seconds))
+                        }
+                    }
+                }
+                None => value.execute(context).await,
+            };
+            let duration_ms = value_started_at.elapsed().as_millis() as u64;
+            if let Some(step) = context
+                .job_result
+                .steps
+                .iter_mut()
+                .find(|step| step.name == context.step_name)
+            {
+                step.value_durations_ms.push(duration_ms);
+            }
+            result?;
             tokio::task::yield_now().await;
         }
         tokio::task::yield_now().await;
@@ -39,12 +158,16 @@ impl ScriptExecutor for ScriptType {
     async fn execute(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
         match self {
             ScriptType::Bash(bash) => bash.execute(context).await,
+            ScriptType::PowerShell(powershell) => powershell.execute(context).await,
             ScriptType::GitClone(git_clone) => git_clone.execute(context).await,
             ScriptType::GitPull(git_pull) => git_pull.execute(context).await,
             ScriptType::Sync(sync) => sync.execute(context).await,
             ScriptType::DockerBuild(docker_build) => docker_build.execute(context).await,
             ScriptType::DockerStop(docker_stop) => docker_stop.execute(context).await,
             ScriptType::DockerRun(docker_run) => docker_run.execute(context).await,
+            ScriptType::DockerExec(docker_exec) => docker_exec.execute(context).await,
+            ScriptType::DockerPush(docker_push) => docker_push.execute(context).await,
+            ScriptType::WaitFor(wait_for) => wait_for.execute(context).await,
         }
     }
 }
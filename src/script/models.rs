@@ -1,12 +1,18 @@
-use std::{fmt::Display, fs::File, io::BufReader, path::PathBuf};
+use std::{collections::HashSet, fmt::Display, fs::File, io::BufReader, path::PathBuf};
 
 use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 
-use crate::{job::JobResult, log::LogLevel};
+use crate::{
+    cache::FileCache, error::NomosError, job::JobResult, log::LogLevel, settings::SyncAction, utils::entity_path,
+};
 
 use super::{default_scripts_location, types::ScriptType, ScriptParameter};
 
+/// Scripts are read several times per run, e.g. for validation and execution.
+static SCRIPTS: Lazy<FileCache<Script>> = Lazy::new(FileCache::new);
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum ScriptStatus {
     #[serde(rename = "success")]
@@ -15,6 +21,9 @@ pub enum ScriptStatus {
     Failed,
     #[serde(rename = "aborted")]
     Aborted,
+    /// Not run because the job was rerun from a later step.
+    #[serde(rename = "skipped")]
+    Skipped,
 }
 
 impl Display for ScriptStatus {
@@ -23,6 +32,7 @@ impl Display for ScriptStatus {
             ScriptStatus::Success => write!(f, "Success"),
             ScriptStatus::Failed => write!(f, "Failed"),
             ScriptStatus::Aborted => write!(f, "Aborted"),
+            ScriptStatus::Skipped => write!(f, "Skipped"),
         }
     }
 }
@@ -39,75 +49,182 @@ pub struct Script {
 pub struct RunningScriptStep {
     pub name: String,
     pub values: Vec<ScriptType>,
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    #[serde(default)]
+    pub working_directory: Option<String>,
+    #[serde(default)]
+    pub env_file: Option<String>,
+    #[serde(default)]
+    pub continue_on_error: bool,
     pub status: ScriptStatus,
     pub started_at: Option<DateTime<Utc>>,
     pub finished_at: Option<DateTime<Utc>>,
+    /// Time from `started_at` to `finished_at`.
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// Duration of each value that ran, in the order of `values`. Shorter than `values` if the step failed early.
+    #[serde(default)]
+    pub value_durations_ms: Vec<u64>,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug, Default)]
 pub struct ScriptStep {
     pub name: String,
     pub values: Vec<ScriptType>,
+    /// The step is aborted and its processes are killed when it runs longer than this.
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    /// Directory the step runs in, relative to the job directory. Supports parameters.
+    #[serde(default)]
+    pub working_directory: Option<String>,
+    /// `.env` file, relative to the job directory, whose variables are set for the commands of the step, e.g. one
+    /// checked into a cloned repository. Supports parameters.
+    #[serde(default)]
+    pub env_file: Option<String>,
+    /// A failure of the step is recorded, but the next steps still run and the job can succeed. Timeouts still stop
+    /// the job.
+    #[serde(default)]
+    pub continue_on_error: bool,
 }
 
 impl Script {
     /// Reads as YamlScript and converts to Script. Primarily used before executing a job.
-    pub(crate) fn get(script_id: &str) -> Result<Option<Self>, String> {
-        let path = default_scripts_location()?.join(format!("{}.yml", script_id));
+    pub(crate) fn get(script_id: &str) -> Result<Option<Self>, NomosError> {
+        let path = entity_path(&default_scripts_location()?, script_id)?;
         if path.exists() {
-            let yaml_script = Script::try_from(path)?;
+            let yaml_script = SCRIPTS
+                .get_or_load(&path, |path| Script::try_from(path.to_path_buf()))
+                .map_err(|e| NomosError::Other(format!("{}: {}", e, path.display())))?;
             Ok(Some(yaml_script))
         } else {
             Ok(None)
         }
     }
 
-    pub fn get_all() -> Result<Vec<Self>, String> {
+    /// Like `get`, but a missing script is a `NotFound` error.
+    pub fn require(script_id: &str) -> Result<Self, NomosError> {
+        Self::get(script_id)?.ok_or_else(|| NomosError::NotFound(format!("Script {:?}", script_id)))
+    }
+
+    /// The YAML of the script as stored, including the comments kept by `sync_source`. `None` if it doesn't exist.
+    pub fn get_source(script_id: &str) -> Result<Option<String>, NomosError> {
+        let path = entity_path(&default_scripts_location()?, script_id)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(std::fs::read_to_string(path)?))
+    }
+
+    pub fn get_all() -> Result<Vec<Self>, NomosError> {
         let scripts_path = default_scripts_location()?;
         let mut scripts = vec![];
-        for entry in std::fs::read_dir(scripts_path).map_err(|e| e.to_string())? {
-            let entry = entry.map_err(|e| e.to_string())?;
+        for entry in std::fs::read_dir(scripts_path)? {
+            let entry = entry?;
             let path: PathBuf = entry.path();
             match Script::try_from(path) {
                 Ok(script) => scripts.push(script),
                 Err(e) => eprintln!("Error reading script: {:?}", e),
             }
         }
+        // Directory order differs between platforms
+        scripts.sort_by(|a, b| a.id.cmp(&b.id));
         Ok(scripts)
     }
 
+    /// Like `try_from`, but also returns the file content for `sync_source`.
+    pub fn read_with_source(path: &std::path::Path) -> Result<(Self, String), &'static str> {
+        let source = std::fs::read_to_string(path).map_err(|_| "Could not open file")?;
+        let script = serde_yaml::from_str(&source).map_err(|e| {
+            eprintln!("Error reading YAML: {}", e);
+            "Could not parse YAML"
+        })?;
+        Ok((script, source))
+    }
+
+    /// Steps are tracked by name while running, so they must be unique.
+    pub fn validate_step_names(&self) -> Result<(), NomosError> {
+        let mut names = HashSet::new();
+        for step in &self.steps {
+            if !names.insert(step.name.as_str()) {
+                return Err(NomosError::Validation(format!(
+                    "Duplicate step name {:?} in script {:?}",
+                    step.name, self.id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checked before running, otherwise the run fails without a current step. Empty scripts can still be saved.
+    pub fn validate_has_steps(&self) -> Result<(), NomosError> {
+        if self.steps.is_empty() {
+            return Err(NomosError::Validation(format!("Script {:?} has no steps", self.id)));
+        }
+        Ok(())
+    }
+
+    /// Patterns are compiled when a script is saved, so runs don't fail on them.
+    pub fn validate_parameter_patterns(&self) -> Result<(), NomosError> {
+        for parameter in &self.parameters {
+            parameter
+                .pattern_regex()
+                .map_err(|e| NomosError::Validation(format!("{} in script {:?}", e, self.id)))?;
+        }
+        Ok(())
+    }
+
     /// Save as YamlScript. Primarily used after creating a new script.
-    pub fn sync(&self, job_result: Option<&mut JobResult>) -> Result<(), String> {
+    #[allow(dead_code)]
+    pub fn sync(&self, job_result: Option<&mut JobResult>) -> Result<SyncAction, String> {
+        self.sync_from(None, job_result)
+    }
+
+    /// Like `sync`, but writes `source`, the YAML this script was parsed from, so its comments and key order are
+    /// kept on disk.
+    pub fn sync_source(&self, source: &str, job_result: Option<&mut JobResult>) -> Result<SyncAction, String> {
+        self.sync_from(Some(source), job_result)
+    }
+
+    /// The file is only written when the parsed script differs, an unchanged script is never reformatted.
+    fn sync_from(&self, source: Option<&str>, job_result: Option<&mut JobResult>) -> Result<SyncAction, String> {
+        self.validate_step_names()?;
+        self.validate_parameter_patterns()?;
         let existing_script = Script::get(self.id.as_str())?;
 
         if let Some(existing_script) = existing_script {
             if existing_script != *self {
-                self.save()?;
+                self.save(source)?;
                 if let Some(job_result) = job_result {
                     job_result.add_log(LogLevel::Info, format!("Updated script {:?}", self.id))
                 }
-            } else if let Some(job_result) = job_result {
-                job_result.add_log(LogLevel::Info, format!("No changes in script {:?}", self.id))
+                Ok(SyncAction::Updated)
+            } else {
+                if let Some(job_result) = job_result {
+                    job_result.add_log(LogLevel::Info, format!("No changes in script {:?}", self.id))
+                }
+                Ok(SyncAction::Unchanged)
             }
         } else {
-            self.save()?;
+            self.save(source)?;
             if let Some(job_result) = job_result {
                 job_result.add_log(LogLevel::Info, format!("Created script {:?}", self.id))
             }
+            Ok(SyncAction::Created)
         }
-
-        Ok(())
     }
 
-    fn save(&self) -> Result<(), String> {
-        let path = default_scripts_location()?.join(format!("{}.yml", self.id));
-        let file = File::create(path).map_err(|e| e.to_string())?;
-        serde_yaml::to_writer(file, self).map_err(|e| e.to_string())
+    fn save(&self, source: Option<&str>) -> Result<(), NomosError> {
+        let path = entity_path(&default_scripts_location()?, &self.id)?;
+        match source {
+            Some(source) => Ok(std::fs::write(path, source)?),
+            None => Ok(serde_yaml::to_writer(File::create(path)?, self)?),
+        }
     }
 
-    pub fn delete(&self) -> Result<(), String> {
-        let path = default_scripts_location()?.join(format!("{}.yml", self.id));
-        std::fs::remove_file(path).map_err(|e| e.to_string())
+    pub fn delete(&self) -> Result<(), NomosError> {
+        let path = entity_path(&default_scripts_location()?, &self.id)?;
+        Ok(std::fs::remove_file(path)?)
     }
 }
 
@@ -128,11 +245,16 @@ impl TryFrom<PathBuf> for Script {
 impl RunningScriptStep {
     pub fn start(&mut self) {
         self.started_at = Some(Utc::now());
+        self.value_durations_ms.clear();
     }
 
     pub fn finish(&mut self, status: ScriptStatus) {
+        let finished_at = Utc::now();
         self.status = status;
-        self.finished_at = Some(Utc::now());
+        self.finished_at = Some(finished_at);
+        self.duration_ms = self
+            .started_at
+            .map(|started_at| (finished_at - started_at).num_milliseconds().max(0) as u64);
     }
 }
 
@@ -141,9 +263,15 @@ impl Default for RunningScriptStep {
         RunningScriptStep {
             name: String::new(),
             values: vec![],
+            timeout_seconds: None,
+            working_directory: None,
+            env_file: None,
+            continue_on_error: false,
             status: ScriptStatus::Failed,
             started_at: None,
             finished_at: None,
+            duration_ms: None,
+            value_durations_ms: vec![],
         }
     }
 }
@@ -153,6 +281,10 @@ impl From<&ScriptStep> for RunningScriptStep {
         RunningScriptStep {
             name: step.name.clone(),
             values: step.values.clone(),
+            timeout_seconds: step.timeout_seconds,
+            working_directory: step.working_directory.clone(),
+            env_file: step.env_file.clone(),
+            continue_on_error: step.continue_on_error,
             ..Default::default()
         }
     }
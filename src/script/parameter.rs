@@ -1,5 +1,12 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
+/// Written instead of the value of password and credential parameters.
+pub const REDACTED_VALUE: &str = "***";
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(tag = "type", content = "value")]
 pub enum ScriptParameterType {
@@ -15,6 +22,55 @@ pub enum ScriptParameterType {
     Credential(String),
     #[serde(rename = "string-array")]
     StringArray(Vec<String>),
+    /// Structured input, substituted as compact JSON.
+    #[serde(rename = "object")]
+    Object(serde_json::Value),
+}
+
+impl ScriptParameterType {
+    /// The serialized `type` tag of the value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ScriptParameterType::String(_) => "string",
+            ScriptParameterType::Boolean(_) => "boolean",
+            ScriptParameterType::Number(_) => "number",
+            ScriptParameterType::Password(_) => "password",
+            ScriptParameterType::Credential(_) => "credential",
+            ScriptParameterType::StringArray(_) => "string-array",
+            ScriptParameterType::Object(_) => "object",
+        }
+    }
+
+    /// Password and credential values, which are not written to job results.
+    pub fn is_secret(&self) -> bool {
+        matches!(
+            self,
+            ScriptParameterType::Password(_) | ScriptParameterType::Credential(_)
+        )
+    }
+
+    /// The value as the `declared` type of its parameter, so a string provided for a password is treated as a secret
+    /// too. Other values are returned as they are.
+    pub fn into_declared(self, declared: Option<&ScriptParameterType>) -> Self {
+        match (self, declared) {
+            (ScriptParameterType::String(value), Some(ScriptParameterType::Password(_))) => {
+                ScriptParameterType::Password(value)
+            }
+            (ScriptParameterType::String(value), Some(ScriptParameterType::Credential(_))) => {
+                ScriptParameterType::Credential(value)
+            }
+            (value, _) => value,
+        }
+    }
+
+    /// The value with secrets replaced by `REDACTED_VALUE`.
+    pub fn redacted(&self) -> Self {
+        match self {
+            ScriptParameterType::Password(_) => ScriptParameterType::Password(REDACTED_VALUE.to_string()),
+            ScriptParameterType::Credential(_) => ScriptParameterType::Credential(REDACTED_VALUE.to_string()),
+            value => value.clone(),
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
@@ -23,4 +79,45 @@ pub struct ScriptParameter {
     pub description: String,
     pub required: bool,
     pub default: Option<ScriptParameterType>,
+    /// Regex that provided values must match as a whole, e.g. `v\d+\.\d+\.\d+` for a version tag. See
+    /// `matches_pattern`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+/// Compiled `ScriptParameter::pattern`s, by pattern.
+static PATTERNS: Lazy<Mutex<HashMap<String, Regex>>> = Lazy::new(Default::default);
+
+impl ScriptParameter {
+    /// The compiled `pattern`, compiled on first use. An error if it isn't a valid regex.
+    pub fn pattern_regex(&self) -> Result<Option<Regex>, String> {
+        let Some(pattern) = &self.pattern else {
+            return Ok(None);
+        };
+        let mut patterns = PATTERNS.lock().map_err(|e| e.to_string())?;
+        if let Some(regex) = patterns.get(pattern) {
+            return Ok(Some(regex.clone()));
+        }
+        let regex = Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|e| format!("Invalid pattern {} of parameter {}: {}", pattern, self.name, e))?;
+        patterns.insert(pattern.clone(), regex.clone());
+        Ok(Some(regex))
+    }
+
+    /// Whether `value` matches the pattern. Every element of a string array must match, numbers and booleans are
+    /// matched as written and objects never match.
+    pub fn matches_pattern(&self, value: &ScriptParameterType) -> Result<bool, String> {
+        let Some(regex) = self.pattern_regex()? else {
+            return Ok(true);
+        };
+        Ok(match value {
+            ScriptParameterType::String(value)
+            | ScriptParameterType::Password(value)
+            | ScriptParameterType::Credential(value) => regex.is_match(value),
+            ScriptParameterType::StringArray(values) => values.iter().all(|value| regex.is_match(value)),
+            ScriptParameterType::Number(value) => regex.is_match(&value.to_string()),
+            ScriptParameterType::Boolean(value) => regex.is_match(&value.to_string()),
+            ScriptParameterType::Object(_) => false,
+        })
+    }
 }
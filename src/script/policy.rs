@@ -0,0 +1,248 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::utils::data_root;
+
+/// Allowed and denied commands of bash steps, read from `command-policy.yml` in the data directory. Without the file
+/// every command is allowed.
+///
+/// Each command of a line, e.g. both sides of `&&` or a pipe, is matched by its leading words, so `rm -rf` matches
+/// `cd build && sudo rm -rf .` but not `rmdir build`. Variable assignments and `sudo`, `env`, `exec`, `command` and
+/// `nohup` in front of a command are skipped. This is a guardrail against mistakes, not a sandbox: the shell can
+/// still run anything, e.g. through `eval` or a script file.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct CommandPolicy {
+    /// If not empty, only commands starting with one of these are allowed.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Commands starting with one of these are rejected, even if they are allowed.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl CommandPolicy {
+    /// `None` if there is no policy file.
+    pub fn load() -> Result<Option<Self>, String> {
+        Self::load_from(&default_command_policy_location()?)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Option<Self>, String> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => serde_yaml::from_str(&content)
+                .map(Some)
+                .map_err(|e| format!("Invalid command policy {}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read command policy {}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn check(&self, line: &str) -> Result<(), String> {
+        for command in simple_commands(line) {
+            let words = command_words(&command);
+            if let Some(prefix) = self.deny.iter().find(|prefix| starts_with_words(&words, prefix)) {
+                return Err(format!(
+                    "Command {:?} is denied by the command policy ({:?})",
+                    line.trim(),
+                    prefix
+                ));
+            }
+            if !self.allow.is_empty() && !self.allow.iter().any(|prefix| starts_with_words(&words, prefix)) {
+                return Err(format!(
+                    "Command {:?} is not allowed by the command policy",
+                    line.trim()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The commands of `line`, split at `;`, `&`, `&&`, `|`, `||`, newlines, subshells and command substitutions.
+/// Separators inside quotes are kept, except command substitutions in double quotes, which still run.
+fn simple_commands(line: &str) -> Vec<String> {
+    let mut commands = vec![];
+    let mut current = String::new();
+    let mut quote = None;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let separator = match (quote, c) {
+            (_, '\\') if quote != Some('\'') => {
+                current.push(c);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                false
+            }
+            (Some('\''), '\'') | (Some('"'), '"') => {
+                quote = None;
+                current.push(c);
+                false
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.push(c);
+                false
+            }
+            (Some('"') | None, '`') => true,
+            (Some('"') | None, '$') if chars.peek() == Some(&'(') => {
+                chars.next();
+                true
+            }
+            (None, ';' | '&' | '|' | '\n' | '(' | ')') => true,
+            _ => {
+                current.push(c);
+                false
+            }
+        };
+        if separator {
+            let command = std::mem::take(&mut current);
+            if !command.trim().is_empty() {
+                commands.push(command);
+            }
+        }
+    }
+    if !current.trim().is_empty() {
+        commands.push(current);
+    }
+    commands
+}
+
+/// Words of `command` without quotes and escapes, starting at the command that runs: leading variable assignments
+/// and wrappers like `sudo -u deploy` or `env -i` are skipped.
+fn command_words(command: &str) -> Vec<String> {
+    let mut words = command
+        .split_whitespace()
+        .map(|word| word.replace(['"', '\'', '\\'], ""))
+        .filter(|word| !word.is_empty())
+        .peekable();
+    while let Some(word) = words.peek() {
+        if is_assignment(word) {
+            words.next();
+            continue;
+        }
+        let options_with_value: &[&str] = match word.as_str() {
+            "sudo" => &["-u", "-g", "-C", "-D", "-h", "-p", "-r", "-t", "-U", "-T"],
+            "env" => &["-u", "-C", "-S"],
+            "exec" => &["-a"],
+            "command" | "nohup" => &[],
+            _ => break,
+        };
+        words.next();
+        while let Some(option) = words.next_if(|word| word.starts_with('-')) {
+            if options_with_value.contains(&option.as_str()) {
+                words.next();
+            }
+        }
+    }
+    words.collect()
+}
+
+/// `NAME=value` in front of a command.
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Whether the words of `prefix` are the first words of the command. The program also matches by its file name,
+/// so `rm` matches `/bin/rm`.
+fn starts_with_words(words: &[String], prefix: &str) -> bool {
+    let mut words = words.iter();
+    let mut prefix_words = prefix.split_whitespace().enumerate().peekable();
+    prefix_words.peek().is_some()
+        && prefix_words.all(|(i, prefix_word)| {
+            words.next().is_some_and(|word| {
+                word == prefix_word
+                    || (i == 0 && !prefix_word.contains('/') && word.rsplit('/').next() == Some(prefix_word))
+            })
+        })
+}
+
+pub fn default_command_policy_location() -> Result<PathBuf, String> {
+    Ok(data_root()?.join("command-policy.yml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_denied_command() {
+        let policy = CommandPolicy {
+            allow: vec![],
+            deny: vec!["rm -rf".to_string(), "shutdown".to_string()],
+        };
+        assert!(policy.check("rm -rf /").is_err());
+        assert!(policy.check("  shutdown now").is_err());
+        assert!(policy.check("rm build.log").is_ok());
+        assert!(policy.check("rmdir build").is_ok());
+        assert!(policy.check("echo shutdown").is_ok());
+    }
+
+    #[test]
+    fn test_chained_commands() {
+        let policy = CommandPolicy {
+            allow: vec![],
+            deny: vec!["rm -rf".to_string()],
+        };
+        for command in [
+            "cd build && rm -rf .",
+            "make; rm -rf out",
+            "false || rm -rf out",
+            "ls | rm -rf out",
+            "sleep 1 & rm -rf out",
+            "FORCE=1 rm -rf out",
+            "sudo -u deploy rm -rf out",
+            "env -i PATH=/bin rm -rf out",
+            "/bin/rm -rf out",
+            "\"rm\" -rf out",
+            "(cd out; rm -rf .)",
+            "echo \"$(rm -rf out)\"",
+            "echo `rm -rf out`",
+        ] {
+            assert!(policy.check(command).is_err(), "{}", command);
+        }
+        assert!(policy.check("echo 'rm -rf out; done'").is_ok());
+        assert!(policy.check("echo \"rm -rf && done\"").is_ok());
+        assert!(policy.check("git commit -m 'a && b' && echo rm -rf").is_ok());
+
+        let policy = CommandPolicy {
+            allow: vec!["cargo".to_string(), "echo".to_string()],
+            deny: vec![],
+        };
+        assert!(policy.check("cargo build && echo done").is_ok());
+        assert!(policy.check("RUSTFLAGS=-Dwarnings cargo build").is_ok());
+        assert!(policy.check("cargo build && curl https://example.com").is_err());
+        assert!(policy.check("echo done | sh").is_err());
+    }
+
+    #[test]
+    fn test_allowed_command() {
+        let policy = CommandPolicy {
+            allow: vec!["cargo".to_string(), "echo".to_string()],
+            deny: vec!["cargo publish".to_string()],
+        };
+        assert!(policy.check("cargo build --release").is_ok());
+        assert!(policy.check("echo done").is_ok());
+        assert!(policy.check("curl https://example.com").is_err());
+        assert!(policy.check("cargo publish").is_err());
+    }
+
+    #[test]
+    fn test_load_policy() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("command-policy.yml");
+        assert_eq!(CommandPolicy::load_from(&path).unwrap(), None);
+
+        std::fs::write(&path, "deny:\n  - rm -rf\n").unwrap();
+        let policy = CommandPolicy::load_from(&path).unwrap().unwrap();
+        assert_eq!(policy.deny, vec!["rm -rf"]);
+        assert!(policy.allow.is_empty());
+
+        std::fs::write(&path, "deny: rm").unwrap();
+        assert!(CommandPolicy::load_from(&path).is_err());
+    }
+}
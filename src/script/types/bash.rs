@@ -1,23 +1,95 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    log::LogLevel,
+    log::{max_log_line, truncate_line, LogLevel},
     script::{
+        policy::CommandPolicy,
         utils::{ParameterSubstitution, SubstitutionResult},
-        ScriptExecutionContext, ScriptExecutor,
+        ScriptExecutionContext, ScriptExecutor, ScriptParameterType,
     },
-    utils::execute_command,
+    utils::{execute_program, is_program_available, resolve_subdirectory, shell_args},
 };
 use async_trait::async_trait;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct BashScript {
     pub code: String,
+    /// Interpreter invoked as `{shell} -c {line}`, e.g. `/bin/bash` or `python3`. Defaults to `sh`.
+    pub shell: Option<String>,
+    /// Directory to run in, relative to the directory of the step and not leaving it. That is the
+    /// `working_directory` of the step if set, otherwise the job directory. Defaults to the directory of the last
+    /// git-clone, or the directory of the step if nothing was cloned.
+    pub directory: Option<String>,
+    /// Runs the code as this user with `sudo -u`. Only the users listed in `NOMOS_RUN_AS_USERS` are allowed, and
+    /// it is not supported on Windows.
+    pub run_as: Option<String>,
+    /// Parameter the standard output of the code is stored in, trimmed, e.g. `$(deployed_version)` in later steps.
+    /// Like a log line, it is cut to `NOMOS_MAX_LOG_LINE` bytes and fetched secrets are masked. `nomos.summary` is
+    /// shown at the top of the job result. Other `nomos.`, `parameters.` and `steps.` names are
+    /// reserved.
+    #[serde(default)]
+    pub capture_output_to: Option<String>,
+}
+
+/// Output of a bash step captured here becomes `JobResult::summary`.
+pub const SUMMARY_PARAMETER: &str = "nomos.summary";
+
+fn validate_capture_parameter(name: &str) -> Result<(), String> {
+    let reserved = ["nomos.", "parameters.", "steps."]
+        .iter()
+        .any(|prefix| name.starts_with(prefix));
+    if name.is_empty() || (reserved && name != SUMMARY_PARAMETER) {
+        return Err(format!("Output can't be captured to {:?}", name));
+    }
+    Ok(())
+}
+
+/// Users allowed in `run_as`, from the comma separated `NOMOS_RUN_AS_USERS`.
+fn run_as_allowlist() -> Vec<String> {
+    std::env::var("NOMOS_RUN_AS_USERS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|user| user.trim().to_string())
+        .filter(|user| !user.is_empty())
+        .collect()
+}
+
+/// Wraps the program and arguments with `sudo` so they run as `user`.
+fn run_as_args(args: Vec<String>, user: &str, allowlist: &[String]) -> Result<Vec<String>, String> {
+    if cfg!(target_os = "windows") {
+        return Err("run_as is not supported on Windows".to_string());
+    }
+    if !allowlist.iter().any(|allowed| allowed == user) {
+        return Err(format!(
+            "User {:?} is not allowed in run_as, see NOMOS_RUN_AS_USERS",
+            user
+        ));
+    }
+    // -n fails instead of prompting for a password
+    let mut wrapped = vec![
+        "sudo".to_string(),
+        "-n".to_string(),
+        "-u".to_string(),
+        user.to_string(),
+        "--".to_string(),
+    ];
+    wrapped.extend(args);
+    Ok(wrapped)
 }
 
 #[async_trait]
 impl ScriptExecutor for BashScript {
     async fn execute(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+        self.execute_with_policy(context, CommandPolicy::load()?).await
+    }
+}
+
+impl BashScript {
+    async fn execute_with_policy(
+        &self,
+        context: &mut ScriptExecutionContext<'_>,
+        policy: Option<CommandPolicy>,
+    ) -> Result<(), String> {
         // Replace all parameter references in the code
         let replaced_code = self.code.substitute_parameters(context.parameters, false)?;
         let replaced_code = match replaced_code {
@@ -30,24 +102,202 @@ impl ScriptExecutor for BashScript {
             None => return Ok(()),
         };
 
+        if let Some(shell) = &self.shell {
+            if !is_program_available(shell) {
+                context
+                    .job_result
+                    .add_log(LogLevel::Warning, format!("Shell not found: {}", shell));
+            }
+        }
+
+        let directory = match &self.directory {
+            Some(directory) => match directory
+                .substitute_parameters(context.parameters, false)?
+                .ok_or("Directory is required")?
+            {
+                SubstitutionResult::Single(s) => {
+                    resolve_subdirectory(context.directory, &s, !context.job_result.dry_run)?
+                }
+                SubstitutionResult::Multiple(_) => {
+                    return Err("Directory parameter cannot be an array".to_string());
+                }
+            },
+            None => context
+                .current_working_subdir
+                .clone()
+                .unwrap_or_else(|| context.directory.to_path_buf()),
+        };
+        let run_as = match &self.run_as {
+            Some(user) => match user
+                .substitute_parameters(context.parameters, false)?
+                .ok_or("User is required")?
+            {
+                SubstitutionResult::Single(s) => {
+                    // Validated in dry runs too
+                    run_as_args(vec![], &s, &run_as_allowlist())?;
+                    Some(s)
+                }
+                SubstitutionResult::Multiple(_) => {
+                    return Err("User parameter cannot be an array".to_string());
+                }
+            },
+            None => None,
+        };
+
+        let mut context = ScriptExecutionContext {
+            parameters: context.parameters,
+            directory: &directory,
+            step_name: context.step_name,
+            job_result: context.job_result,
+            current_working_subdir: context.current_working_subdir,
+        };
+
+        if let Some(name) = &self.capture_output_to {
+            validate_capture_parameter(name)?;
+            context.job_result.captured_output = Some(Default::default());
+        }
+
         let original_lines = self.code.lines().collect::<Vec<&str>>();
-        let lines = replaced_code.lines();
-        let mut i = 0;
-        for line in lines {
-            if line.is_empty() {
+        let result = async {
+            let lines = replaced_code.lines();
+            let mut i = 0;
+            for line in lines {
+                if line.is_empty() {
+                    i += 1;
+                    continue;
+                }
+                tokio::task::yield_now().await;
+                context
+                    .job_result
+                    .add_log(LogLevel::Info, format!("command: {}", original_lines[i]));
+                // Checked after substitution, and in dry runs too
+                if let Some(policy) = &policy {
+                    policy.check(line)?;
+                }
+                if !context.job_result.dry_run {
+                    let mut args = shell_args(line, self.shell.as_deref());
+                    if let Some(user) = &run_as {
+                        args = run_as_args(args, user, &run_as_allowlist())?;
+                    }
+                    execute_program(args, vec![], &mut context).await?;
+                }
                 i += 1;
-                continue;
             }
-            tokio::task::yield_now().await;
-            context
-                .job_result
-                .add_log(LogLevel::Info, format!("command: {}", original_lines[i]));
+            Ok::<(), String>(())
+        }
+        .await;
+
+        let captured = context.job_result.captured_output.take();
+        result?;
+        if let (Some(name), Some(captured)) = (&self.capture_output_to, captured) {
+            // Nothing runs in dry runs, so nothing is captured either
             if !context.job_result.dry_run {
-                execute_command(line, context).await?;
+                let output = captured.lock().unwrap_or_else(|e| e.into_inner()).trim().to_string();
+                let output = truncate_line(context.job_result.mask_secrets(output), max_log_line());
+                if name == SUMMARY_PARAMETER {
+                    context.job_result.summary = Some(output.clone());
+                }
+                context
+                    .parameters
+                    .insert(name.clone(), ScriptParameterType::String(output));
             }
-            i += 1;
         }
-
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::job::JobResult;
+
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run_as_args() {
+        let allowlist = vec!["deploy".to_string()];
+        let args = shell_args("whoami", Some("/bin/bash"));
+
+        assert_eq!(
+            run_as_args(args.clone(), "deploy", &allowlist).unwrap(),
+            vec!["sudo", "-n", "-u", "deploy", "--", "/bin/bash", "-c", "whoami"]
+        );
+        assert!(run_as_args(args.clone(), "root", &allowlist).is_err());
+        assert!(run_as_args(args, "deploy", &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_command_policy() {
+        let policy = CommandPolicy {
+            allow: vec![],
+            deny: vec!["rm -rf".to_string()],
+        };
+        let directory = tempfile::tempdir().unwrap();
+        let mut job_result = JobResult::create_dummy();
+        job_result.dry_run = true;
+
+        for (code, allowed) in [
+            ("echo $(out)\necho 'rm -rf out'", true),
+            ("echo build\ncd out && rm -rf .", false),
+            ("DEBUG=1 sudo rm -rf $(out)", false),
+        ] {
+            let script = BashScript {
+                code: code.to_string(),
+                ..Default::default()
+            };
+            let mut parameters =
+                HashMap::from([("out".to_string(), ScriptParameterType::String("/tmp/out".to_string()))]);
+            let mut context = ScriptExecutionContext {
+                parameters: &mut parameters,
+                directory: directory.path(),
+                step_name: "Build",
+                job_result: &mut job_result,
+                current_working_subdir: &mut None,
+            };
+            let result = script.execute_with_policy(&mut context, Some(policy.clone())).await;
+            assert_eq!(result.is_ok(), allowed, "{}: {:?}", code, result);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_directory_stays_inside() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::create_dir(directory.path().join("app")).unwrap();
+        let mut job_result = JobResult::create_dummy();
+        job_result.dry_run = true;
+
+        for (subdirectory, allowed) in [
+            ("app", true),
+            ("../outside", false),
+            ("app/../..", false),
+            ("/etc", false),
+        ] {
+            let script = BashScript {
+                code: "pwd".to_string(),
+                directory: Some(subdirectory.to_string()),
+                ..Default::default()
+            };
+            let mut context = ScriptExecutionContext {
+                parameters: &mut HashMap::new(),
+                directory: directory.path(),
+                step_name: "Build",
+                job_result: &mut job_result,
+                current_working_subdir: &mut None,
+            };
+            let result = script.execute_with_policy(&mut context, None).await;
+            assert_eq!(result.is_ok(), allowed, "{}: {:?}", subdirectory, result);
+        }
+    }
+
+    #[test]
+    fn test_validate_capture_parameter() {
+        assert!(validate_capture_parameter(SUMMARY_PARAMETER).is_ok());
+        assert!(validate_capture_parameter("deployed_version").is_ok());
+        assert!(validate_capture_parameter("nomos.job_id").is_err());
+        assert!(validate_capture_parameter("parameters.tag").is_err());
+        assert!(validate_capture_parameter("steps.Clone.git-clone.directory").is_err());
+        assert!(validate_capture_parameter("").is_err());
+    }
+}
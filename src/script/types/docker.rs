@@ -1,14 +1,22 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
 
 use crate::{
-    credential::{Credential, CredentialType},
-    docker::{docker_build, docker_run, docker_stop_and_rm},
+    credential::{parse_env, Credential, CredentialType},
+    docker::{
+        docker_build, docker_exec, docker_push, docker_run, docker_run_flags, docker_stop_and_rm, DockerBuildSecret,
+        DockerBuildTarget, DockerLogin,
+    },
     script::{
         utils::{ParameterSubstitution, SubstitutionResult},
-        ScriptExecutionContext, ScriptExecutor,
+        ScriptExecutionContext, ScriptExecutor, ScriptParameterType,
     },
+    utils::{shell_quote, tmp_dir},
 };
 use async_trait::async_trait;
 
@@ -16,6 +24,18 @@ use async_trait::async_trait;
 pub struct DockerBuildScript {
     pub image: String,
     pub dockerfile: Option<String>,
+    /// Build context directory. Defaults to the directory of the Dockerfile.
+    pub context: Option<String>,
+    /// Passed as `--build-arg KEY=VALUE`.
+    pub build_args: Option<HashMap<String, String>>,
+    /// Credential ids passed as `--secret id={credential_id},src=<temp_file>`.
+    pub secrets: Option<Vec<String>>,
+    /// Stage of a multi-stage Dockerfile to build, passed as `--target`. The last stage if unset.
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Target platforms, passed as `--platform`, e.g. `linux/arm64`. The platform of the host if unset.
+    #[serde(default)]
+    pub platform: Option<String>,
 }
 
 #[async_trait]
@@ -49,17 +69,7 @@ impl ScriptExecutor for DockerBuildScript {
             None => "Dockerfile".to_string(),
         };
 
-        let dockerfile_path = if cfg!(windows) {
-            if dockerfile.chars().nth(1) == Some(':') {
-                PathBuf::from(dockerfile)
-            } else {
-                context.directory.join(dockerfile)
-            }
-        } else if dockerfile.starts_with('/') {
-            PathBuf::from(dockerfile)
-        } else {
-            context.directory.join(dockerfile)
-        };
+        let dockerfile_path = resolve_path(&dockerfile, context.directory);
 
         if !context.job_result.dry_run && !dockerfile_path.exists() {
             return Err(format!(
@@ -67,8 +77,138 @@ impl ScriptExecutor for DockerBuildScript {
                 dockerfile_path.display()
             ));
         }
+
+        // Get build context with parameter substitution
+        let build_context = match &self.context {
+            Some(build_context) => {
+                match build_context
+                    .substitute_parameters(context.parameters, false)?
+                    .ok_or("Build context is required")?
+                {
+                    SubstitutionResult::Single(s) => Some(resolve_path(&s, context.directory)),
+                    SubstitutionResult::Multiple(_) => {
+                        return Err("Build context parameter cannot be an array".to_string());
+                    }
+                }
+            }
+            None => None,
+        };
+
+        if let Some(build_context) = &build_context {
+            if !context.job_result.dry_run && !build_context.is_dir() {
+                return Err(format!(
+                    "Build context does not exist at path: {}",
+                    build_context.display()
+                ));
+            }
+        }
+
+        let build_target = DockerBuildTarget {
+            target: substitute_build_flag(&self.target, "Target", context)?,
+            platform: substitute_build_flag(&self.platform, "Platform", context)?,
+        };
+
+        // Get build args with parameter substitution, sorted for a stable command
+        let mut build_args = Vec::new();
+        for (key, value) in self.build_args.iter().flatten() {
+            match value
+                .substitute_parameters(context.parameters, false)?
+                .ok_or("Build arg is required")?
+            {
+                SubstitutionResult::Single(s) => build_args.push((key.clone(), s)),
+                SubstitutionResult::Multiple(_) => {
+                    return Err(format!("Build arg {} cannot be an array", key));
+                }
+            }
+        }
+        build_args.sort();
+
+        // Temp files are removed when dropped, so they are kept until the build finishes
+        let mut secret_files = Vec::new();
+        let mut secrets = Vec::new();
+        for credential_id in self.secrets.iter().flatten() {
+            let id = match credential_id
+                .substitute_parameters(context.parameters, true)?
+                .ok_or("Secret credential id is required")?
+            {
+                SubstitutionResult::Single(s) => s,
+                SubstitutionResult::Multiple(_) => {
+                    return Err("Secret credential id cannot be an array".to_string());
+                }
+            };
+            let credential =
+                Credential::get(&id, Some(context.job_result))?.ok_or(format!("Credential not found: {}", id))?;
+            let value = match credential.value {
+                CredentialType::Text(text) => text.value,
+                CredentialType::Env(env) => env.value,
+                CredentialType::Ssh(ssh) => ssh.private_key,
+                // Fetched as text by `Credential::get`
+                CredentialType::Vault(_) => return Err(format!("Vault credential was not fetched: {}", id)),
+            };
+
+            let secret_file = NamedTempFile::new_in(tmp_dir()?).map_err(|e| e.to_string())?;
+            if !context.job_result.dry_run {
+                std::fs::write(secret_file.path(), &value).map_err(|e| e.to_string())?;
+            }
+            secrets.push(DockerBuildSecret {
+                id,
+                src: secret_file.path().to_path_buf(),
+                value,
+            });
+            secret_files.push(secret_file);
+        }
+
         tokio::task::yield_now().await;
-        docker_build(&image, &dockerfile_path, context).await
+        docker_build(
+            &image,
+            &dockerfile_path,
+            build_context.as_deref(),
+            &build_target,
+            &build_args,
+            &secrets,
+            context,
+        )
+        .await
+    }
+}
+
+/// Substitutes the value of a `--target` or `--platform` flag, which is passed unquoted, so it may only contain
+/// letters, digits and `-_./,`.
+fn substitute_build_flag(
+    value: &Option<String>,
+    name: &str,
+    context: &ScriptExecutionContext<'_>,
+) -> Result<Option<String>, String> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    let value = match value
+        .substitute_parameters(context.parameters, false)?
+        .ok_or(format!("{} is required", name))?
+    {
+        SubstitutionResult::Single(s) => s,
+        SubstitutionResult::Multiple(_) => {
+            return Err(format!("{} parameter cannot be an array", name));
+        }
+    };
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphanumeric() || "-_./,".contains(c)) {
+        return Err(format!("Invalid {}: {:?}", name.to_lowercase(), value));
+    }
+    Ok(Some(value))
+}
+
+/// Absolute paths are used as is, relative paths are resolved against `directory`.
+fn resolve_path(path: &str, directory: &Path) -> PathBuf {
+    let is_absolute = if cfg!(windows) {
+        path.chars().nth(1) == Some(':')
+    } else {
+        path.starts_with('/')
+    };
+
+    if is_absolute {
+        PathBuf::from(path)
+    } else {
+        directory.join(path)
     }
 }
 
@@ -110,9 +250,58 @@ pub enum DockerRunArg {
 pub struct DockerRunScript {
     pub image: String,
     pub container: Option<String>,
+    /// Passed as `--restart`, e.g. `unless-stopped` or `on-failure:3`.
+    #[serde(default)]
+    pub restart: Option<String>,
+    /// Passed as `-p`, e.g. `8080:80`. Array parameters publish every port.
+    #[serde(default)]
+    pub ports: Option<Vec<String>>,
+    /// Passed as `-v`, e.g. `/srv/app/data:/data`. Array parameters mount every volume.
+    #[serde(default)]
+    pub volumes: Option<Vec<String>>,
+    /// Free-form arguments, added after the flags of the other fields.
     pub args: Vec<DockerRunArg>,
 }
 
+impl DockerRunScript {
+    /// Flags of `restart`, `ports` and `volumes` after parameter substitution.
+    pub(crate) fn structured_args(
+        &self,
+        parameters: &HashMap<String, ScriptParameterType>,
+    ) -> Result<Vec<String>, String> {
+        let restart = match &self.restart {
+            Some(restart) => match restart
+                .substitute_parameters(parameters, false)?
+                .ok_or("Restart policy is required")?
+            {
+                SubstitutionResult::Single(s) => Some(s),
+                SubstitutionResult::Multiple(_) => {
+                    return Err("Restart policy parameter cannot be an array".to_string());
+                }
+            },
+            None => None,
+        };
+        let substitute_all = |values: &Option<Vec<String>>| -> Result<Vec<String>, String> {
+            let mut substituted = Vec::new();
+            for value in values.iter().flatten() {
+                match value
+                    .substitute_parameters(parameters, false)?
+                    .ok_or("Argument substitution failed")?
+                {
+                    SubstitutionResult::Single(s) => substituted.push(s),
+                    SubstitutionResult::Multiple(a) => substituted.extend(a),
+                }
+            }
+            Ok(substituted)
+        };
+        docker_run_flags(
+            restart.as_deref(),
+            &substitute_all(&self.ports)?,
+            &substitute_all(&self.volumes)?,
+        )
+    }
+}
+
 #[async_trait]
 impl ScriptExecutor for DockerRunScript {
     async fn execute(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
@@ -145,6 +334,8 @@ impl ScriptExecutor for DockerRunScript {
             final_args.push(name);
         }
 
+        final_args.extend(self.structured_args(context.parameters)?);
+
         // Process each argument
         for arg in &self.args {
             match arg {
@@ -162,30 +353,9 @@ impl ScriptExecutor for DockerRunScript {
                     }
                 }
                 DockerRunArg::EnvFromCredential { credential_id } => {
-                    let credential_id_resolved = credential_id.substitute_parameters(context.parameters, true)?;
-                    if let Some(SubstitutionResult::Single(id)) = credential_id_resolved {
-                        if let Some(credential) = Credential::get(&id, Some(context.job_result))? {
-                            match credential.value {
-                                CredentialType::Env(env) => {
-                                    for line in env.value.lines() {
-                                        if line.trim().is_empty() {
-                                            continue;
-                                        }
-                                        if line.trim().starts_with('#') {
-                                            continue;
-                                        }
-                                        let key = match line.split('=').next() {
-                                            Some(k) => k,
-                                            None => return Err("Invalid env credential: missing key".to_string()),
-                                        };
-                                        let value = line[key.len() + 1..].trim();
-                                        final_args.push("--env".to_string());
-                                        final_args.push(format!("\"{}={}\"", key, value));
-                                    }
-                                }
-                                _ => return Err("Credential is not of type Env".to_string()),
-                            }
-                        }
+                    for (key, value) in env_from_credential(credential_id, context)? {
+                        final_args.push("--env".to_string());
+                        final_args.push(shell_quote(&format!("{}={}", key, value)));
                     }
                 }
             }
@@ -198,3 +368,163 @@ impl ScriptExecutor for DockerRunScript {
         docker_run(&image, args_ref, context).await
     }
 }
+
+/// Reads the variables of an Env credential. Missing credentials are ignored.
+fn env_from_credential(
+    credential_id: &String,
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<Vec<(String, String)>, String> {
+    let credential_id_resolved = credential_id.substitute_parameters(context.parameters, true)?;
+    if let Some(SubstitutionResult::Single(id)) = credential_id_resolved {
+        if let Some(credential) = Credential::get(&id, Some(context.job_result))? {
+            return match credential.value {
+                CredentialType::Env(env) => Ok(parse_env(&env.value)),
+                _ => Err("Credential is not of type Env".to_string()),
+            };
+        }
+    }
+    Ok(vec![])
+}
+
+/// Runs a command inside an already running container.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct DockerExecScript {
+    pub container: String,
+    pub command: String,
+    pub env_credential_id: Option<String>,
+}
+
+#[async_trait]
+impl ScriptExecutor for DockerExecScript {
+    async fn execute(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+        // Get container name with parameter substitution
+        let container = self
+            .container
+            .substitute_parameters(context.parameters, false)?
+            .ok_or("Container name is required")?;
+        let container = match container {
+            SubstitutionResult::Single(s) => s,
+            SubstitutionResult::Multiple(_) => {
+                return Err("Container name parameter cannot be an array".to_string());
+            }
+        };
+
+        // Get command with parameter substitution
+        let command = self
+            .command
+            .substitute_parameters(context.parameters, false)?
+            .ok_or("Command is required")?;
+        let command = match command {
+            SubstitutionResult::Single(s) => s,
+            SubstitutionResult::Multiple(_) => {
+                return Err("Command parameter cannot be an array".to_string());
+            }
+        };
+
+        let env = match &self.env_credential_id {
+            Some(credential_id) => env_from_credential(credential_id, context)?,
+            None => vec![],
+        };
+
+        tokio::task::yield_now().await;
+        docker_exec(&container, &command, env, context).await
+    }
+}
+
+/// Pushes an image, logging in to its registry first if `registry_credential_id` is set. The credential is an Env
+/// credential with `USERNAME` and `PASSWORD`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct DockerPushScript {
+    pub image: String,
+    pub registry_credential_id: Option<String>,
+}
+
+#[async_trait]
+impl ScriptExecutor for DockerPushScript {
+    async fn execute(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+        // Get image name with parameter substitution
+        let image = self
+            .image
+            .substitute_parameters(context.parameters, false)?
+            .ok_or("Image name is required")?;
+        let image = match image {
+            SubstitutionResult::Single(s) => s,
+            SubstitutionResult::Multiple(_) => {
+                return Err("Image name parameter cannot be an array".to_string());
+            }
+        };
+
+        let login = match &self.registry_credential_id {
+            Some(credential_id) => {
+                let id = match credential_id
+                    .substitute_parameters(context.parameters, false)?
+                    .ok_or("Registry credential id is required")?
+                {
+                    SubstitutionResult::Single(s) => s,
+                    SubstitutionResult::Multiple(_) => {
+                        return Err("Registry credential id cannot be an array".to_string());
+                    }
+                };
+                let credential =
+                    Credential::get(&id, Some(context.job_result))?.ok_or(format!("Credential not found: {}", id))?;
+                let CredentialType::Env(env) = credential.value else {
+                    return Err("Credential is not of type Env".to_string());
+                };
+                let variables: HashMap<String, String> = parse_env(&env.value).into_iter().collect();
+                match (variables.get("USERNAME"), variables.get("PASSWORD")) {
+                    (Some(username), Some(password)) => Some(DockerLogin {
+                        username: username.clone(),
+                        password: password.clone(),
+                    }),
+                    _ => return Err(format!("Credential {} requires USERNAME and PASSWORD", id)),
+                }
+            }
+            None => None,
+        };
+
+        tokio::task::yield_now().await;
+        docker_push(&image, login, context).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_docker_run_structured_args() {
+        let script: DockerRunScript = serde_yaml::from_str(
+            r#"
+image: nginx
+restart: $(parameters.restart)
+ports:
+  - 8080:80
+  - $(parameters.ports)
+volumes:
+  - /srv/app/data:/data
+args:
+  - --network web
+"#,
+        )
+        .unwrap();
+        let mut parameters = HashMap::new();
+        parameters.insert(
+            "parameters.restart".to_string(),
+            ScriptParameterType::String("unless-stopped".to_string()),
+        );
+        parameters.insert(
+            "parameters.ports".to_string(),
+            ScriptParameterType::StringArray(vec!["8443:443".to_string(), "9090:9090".to_string()]),
+        );
+        assert_eq!(
+            script.structured_args(&parameters).unwrap().join(" "),
+            "--restart unless-stopped -p '8080:80' -p '8443:443' -p '9090:9090' -v '/srv/app/data:/data'"
+        );
+
+        parameters.insert(
+            "parameters.restart".to_string(),
+            ScriptParameterType::String("sometimes".to_string()),
+        );
+        assert!(script.structured_args(&parameters).is_err());
+    }
+}
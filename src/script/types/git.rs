@@ -4,7 +4,7 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    git::{git_clone, git_pull},
+    git::{default_branch, git_clone, git_pull, validate_url, CloneOptions},
     script::{
         utils::{ParameterSubstitution, SubstitutionResult},
         ScriptExecutionContext, ScriptExecutor, ScriptParameterType,
@@ -15,7 +15,14 @@ use crate::{
 pub struct GitCloneScript {
     pub url: String,
     pub credential_id: Option<String>,
+    /// Defaults to the branch of `git::default_branch`.
     pub branch: Option<String>,
+    /// Shallow clone of the last `depth` commits. Full clone if unset.
+    #[serde(default)]
+    pub depth: Option<u32>,
+    /// Also clone the submodules.
+    #[serde(default)]
+    pub submodules: bool,
 }
 
 #[async_trait]
@@ -32,6 +39,7 @@ impl ScriptExecutor for GitCloneScript {
                 return Err("URL parameter cannot be an array".to_string());
             }
         };
+        validate_url(&url)?;
 
         let credential_id = match &self.credential_id {
             Some(id) => id.substitute_parameters(context.parameters, true)?,
@@ -57,17 +65,13 @@ impl ScriptExecutor for GitCloneScript {
                             return Err("Branch parameter cannot be an array".to_string());
                         }
                     },
-                    None => "main".to_string(),
+                    None => default_branch(context.job_result.git_default_branch.as_deref()),
                 }
             }
-            None => "main".to_string(),
+            None => default_branch(context.job_result.git_default_branch.as_deref()),
         };
 
-        tokio::task::yield_now().await;
-        git_clone(&url, branch.as_str(), credential_id.as_deref(), context).await?;
-        tokio::task::yield_now().await;
-
-        let mut new_dir = match url.split('/').last() {
+        let mut new_dir = match url.split('/').next_back() {
             Some(last_part) => context.directory.join(last_part),
             None => return Err("Invalid URL format".to_string()),
         };
@@ -84,14 +88,34 @@ impl ScriptExecutor for GitCloneScript {
         }
 
         let new_dir_str = match new_dir.to_str() {
-            Some(s) => s,
+            Some(s) => s.to_string(),
             None => return Err("Invalid directory path".to_string()),
         };
 
+        tokio::task::yield_now().await;
+        let options = CloneOptions {
+            depth: self.depth,
+            submodules: self.submodules,
+        };
+        let sha = git_clone(
+            &url,
+            branch.as_str(),
+            &new_dir,
+            credential_id.as_deref(),
+            &options,
+            context,
+        )
+        .await?;
+        tokio::task::yield_now().await;
+
         context.parameters.insert(
             format!("steps.{}.git-clone.directory", context.step_name),
-            ScriptParameterType::String(new_dir_str.to_string()),
+            ScriptParameterType::String(new_dir_str),
         );
+        *context.current_working_subdir = Some(new_dir);
+        if let Some(sha) = sha {
+            record_sha("git-clone", sha, context);
+        }
 
         Ok(())
     }
@@ -99,7 +123,9 @@ impl ScriptExecutor for GitCloneScript {
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct GitPullScript {
-    pub directory: String,
+    /// Repository to pull. Defaults to the directory of the last git-clone step before this one.
+    #[serde(default)]
+    pub directory: Option<String>,
     pub credential_id: Option<String>,
     pub lfs: Option<String>,
 }
@@ -108,16 +134,24 @@ pub struct GitPullScript {
 impl ScriptExecutor for GitPullScript {
     async fn execute(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
         // Substitute parameters
-        let directory = self
-            .directory
-            .substitute_parameters(context.parameters, false)?
-            .ok_or("Directory is required")?;
-        let directory = match directory {
-            SubstitutionResult::Single(s) => s,
-            SubstitutionResult::Multiple(_) => {
-                return Err("Directory parameter cannot be an array".to_string());
+        let directory = match &self.directory {
+            Some(directory) => {
+                let directory = directory
+                    .substitute_parameters(context.parameters, false)?
+                    .ok_or("Directory is required")?;
+                match directory {
+                    SubstitutionResult::Single(s) => s,
+                    SubstitutionResult::Multiple(_) => {
+                        return Err("Directory parameter cannot be an array".to_string());
+                    }
+                }
             }
+            None => last_clone_directory(context)?,
         };
+        // Nothing is cloned in dry run mode
+        if !context.job_result.dry_run && !context.directory.join(&directory).join(".git").exists() {
+            return Err(format!("{} is not a git repository", directory));
+        }
 
         let credential_id = match &self.credential_id {
             Some(id) => id.substitute_parameters(context.parameters, true)?,
@@ -137,7 +171,7 @@ impl ScriptExecutor for GitPullScript {
             Some(lfs) => lfs.substitute_parameters(context.parameters, false)?,
             None => return Err("Git LFS parameter must be a boolean".to_string()),
         };
-        
+
         let lfs_str = match lfs_str {
             Some(lfs) => match lfs {
                 SubstitutionResult::Single(s) => s,
@@ -155,6 +189,41 @@ impl ScriptExecutor for GitPullScript {
         };
 
         tokio::task::yield_now().await;
-        git_pull(&directory, lfs, credential_id.as_deref(), context).await
+        if let Some(sha) = git_pull(&directory, lfs, credential_id.as_deref(), context).await? {
+            record_sha("git-pull", sha, context);
+        }
+        Ok(())
     }
 }
+
+/// `steps.<step>.git-clone.directory` of the last git-clone step before the current one.
+fn last_clone_directory(context: &ScriptExecutionContext<'_>) -> Result<String, String> {
+    context
+        .job_result
+        .steps
+        .iter()
+        .take_while(|step| step.name != context.step_name)
+        .filter_map(|step| {
+            match context
+                .parameters
+                .get(&format!("steps.{}.git-clone.directory", step.name))
+            {
+                Some(ScriptParameterType::String(directory)) => Some(directory.clone()),
+                _ => None,
+            }
+        })
+        .last()
+        .ok_or_else(|| "Directory is required, there is no git-clone step before this one".to_string())
+}
+
+/// Exposes the checked out commit as `steps.<step>.<script_type>.sha` and records it on the job result.
+fn record_sha(script_type: &str, sha: String, context: &mut ScriptExecutionContext<'_>) {
+    context.parameters.insert(
+        format!("steps.{}.{}.sha", context.step_name, script_type),
+        ScriptParameterType::String(sha.clone()),
+    );
+    context
+        .job_result
+        .source_refs
+        .insert(context.step_name.to_string(), sha);
+}
@@ -1,18 +1,24 @@
 pub mod bash;
 pub mod docker;
 pub mod git;
+pub mod powershell;
 pub mod sync;
+pub mod wait_for;
 
 pub use bash::BashScript;
 pub use git::{GitCloneScript, GitPullScript};
+pub use powershell::PowerShellScript;
 use serde::{Deserialize, Serialize};
 pub use sync::SyncScript;
+pub use wait_for::WaitForScript;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(tag = "type")]
 pub enum ScriptType {
     #[serde(rename = "bash")]
     Bash(BashScript),
+    #[serde(rename = "powershell")]
+    PowerShell(PowerShellScript),
     #[serde(rename = "git-clone")]
     GitClone(GitCloneScript),
     #[serde(rename = "git-pull")]
@@ -25,4 +31,36 @@ pub enum ScriptType {
     DockerStop(docker::DockerStopScript),
     #[serde(rename = "docker-run")]
     DockerRun(docker::DockerRunScript),
+    #[serde(rename = "docker-exec")]
+    DockerExec(docker::DockerExecScript),
+    #[serde(rename = "docker-push")]
+    DockerPush(docker::DockerPushScript),
+    #[serde(rename = "wait-for")]
+    WaitFor(WaitForScript),
+}
+
+impl ScriptType {
+    /// Ids of the credentials the script references.
+    pub fn credential_ids(&self) -> Vec<&str> {
+        match self {
+            ScriptType::GitClone(script) => script.credential_id.as_deref().into_iter().collect(),
+            ScriptType::GitPull(script) => script.credential_id.as_deref().into_iter().collect(),
+            ScriptType::DockerBuild(script) => script.secrets.iter().flatten().map(String::as_str).collect(),
+            ScriptType::DockerRun(script) => script
+                .args
+                .iter()
+                .filter_map(|arg| match arg {
+                    docker::DockerRunArg::EnvFromCredential { credential_id } => Some(credential_id.as_str()),
+                    docker::DockerRunArg::Direct(_) => None,
+                })
+                .collect(),
+            ScriptType::DockerExec(script) => script.env_credential_id.as_deref().into_iter().collect(),
+            ScriptType::DockerPush(script) => script.registry_credential_id.as_deref().into_iter().collect(),
+            ScriptType::Bash(_)
+            | ScriptType::PowerShell(_)
+            | ScriptType::Sync(_)
+            | ScriptType::DockerStop(_)
+            | ScriptType::WaitFor(_) => vec![],
+        }
+    }
 }
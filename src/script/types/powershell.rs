@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    log::LogLevel,
+    script::{
+        utils::{ParameterSubstitution, SubstitutionResult},
+        ScriptExecutionContext, ScriptExecutor,
+    },
+    utils::{execute_program, is_program_available},
+};
+use async_trait::async_trait;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PowerShellScript {
+    pub code: String,
+    /// Executable, e.g. `pwsh`. Defaults to `powershell` on Windows and `pwsh` elsewhere.
+    #[serde(default)]
+    pub shell: Option<String>,
+}
+
+impl PowerShellScript {
+    fn shell(&self) -> &str {
+        match &self.shell {
+            Some(shell) => shell,
+            None if cfg!(target_os = "windows") => "powershell",
+            None => "pwsh",
+        }
+    }
+}
+
+/// Program and arguments running `command` with the PowerShell executable `shell`.
+fn powershell_args(command: &str, shell: &str) -> Vec<String> {
+    vec![
+        shell.to_string(),
+        "-NoProfile".to_string(),
+        "-NonInteractive".to_string(),
+        "-Command".to_string(),
+        command.to_string(),
+    ]
+}
+
+#[async_trait]
+impl ScriptExecutor for PowerShellScript {
+    async fn execute(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+        let replaced_code = match self.code.substitute_parameters(context.parameters, false)? {
+            Some(SubstitutionResult::Single(s)) => s,
+            Some(SubstitutionResult::Multiple(_)) => return Err("Code parameter cannot be an array".to_string()),
+            None => return Ok(()),
+        };
+
+        let shell = self.shell();
+        if !is_program_available(shell) {
+            let message = format!("PowerShell not found: {}", shell);
+            if !context.job_result.dry_run {
+                return Err(message);
+            }
+            context.job_result.add_log(LogLevel::Warning, message);
+        }
+
+        let directory = context
+            .current_working_subdir
+            .clone()
+            .unwrap_or_else(|| context.directory.to_path_buf());
+        let mut context = ScriptExecutionContext {
+            parameters: context.parameters,
+            directory: &directory,
+            step_name: context.step_name,
+            job_result: context.job_result,
+            current_working_subdir: context.current_working_subdir,
+        };
+
+        let original_lines = self.code.lines().collect::<Vec<&str>>();
+        for (i, line) in replaced_code.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            tokio::task::yield_now().await;
+            context.job_result.add_log(
+                LogLevel::Info,
+                format!("command: {}", original_lines.get(i).unwrap_or(&line)),
+            );
+            if !context.job_result.dry_run {
+                execute_program(powershell_args(line, shell), vec![], &mut context).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_powershell_args() {
+        assert_eq!(
+            powershell_args("Write-Output hi", "pwsh"),
+            vec!["pwsh", "-NoProfile", "-NonInteractive", "-Command", "Write-Output hi"]
+        );
+        let script = PowerShellScript {
+            code: String::new(),
+            shell: None,
+        };
+        assert_eq!(
+            script.shell(),
+            if cfg!(target_os = "windows") {
+                "powershell"
+            } else {
+                "pwsh"
+            }
+        );
+    }
+}
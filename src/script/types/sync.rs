@@ -7,7 +7,7 @@ use crate::{
         utils::{ParameterSubstitution, SubstitutionResult},
         ScriptExecutionContext, ScriptExecutor,
     },
-    settings,
+    settings::{self, SyncFilter},
 };
 use async_trait::async_trait;
 
@@ -15,6 +15,10 @@ use async_trait::async_trait;
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct SyncScript {
     pub directory: String,
+    /// Glob patterns of the files to sync, relative to `directory`, e.g. `jobs/deploy-*.yml`. Defaults to all files.
+    pub include: Option<Vec<String>>,
+    /// Glob patterns of the files to leave alone. Takes precedence over `include`.
+    pub exclude: Option<Vec<String>>,
 }
 
 #[async_trait]
@@ -42,7 +46,12 @@ impl ScriptExecutor for SyncScript {
             param_directory = context.directory.join(param_directory);
         }
 
+        let filter = SyncFilter {
+            include: self.include.clone().unwrap_or_default(),
+            exclude: self.exclude.clone().unwrap_or_default(),
+        };
+
         tokio::task::yield_now().await;
-        settings::sync(param_directory, context.job_result).await
+        settings::sync(param_directory, &filter, context.job_result).await
     }
 }
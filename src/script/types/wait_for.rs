@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Deserializer, Serialize};
+use tokio::net::TcpStream;
+
+use crate::{
+    log::LogLevel,
+    script::{
+        utils::{ParameterSubstitution, SubstitutionResult},
+        ScriptExecutionContext, ScriptExecutor, ScriptParameterType,
+    },
+};
+use async_trait::async_trait;
+
+fn default_timeout_seconds() -> u64 {
+    60
+}
+
+fn default_interval_seconds() -> u64 {
+    1
+}
+
+/// A zero interval would retry the connection without pausing.
+fn deserialize_interval_seconds<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match u64::deserialize(deserializer)? {
+        0 => Err(serde::de::Error::custom("interval_seconds must be at least 1")),
+        n => Ok(n),
+    }
+}
+
+/// Accepts both `port: 8080` and `port: $(parameters.port)`.
+fn deserialize_port<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Port {
+        Number(u16),
+        Text(String),
+    }
+
+    Ok(match Port::deserialize(deserializer)? {
+        Port::Number(n) => n.to_string(),
+        Port::Text(s) => s,
+    })
+}
+
+/// Waits until a TCP connection to `host:port` succeeds.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct WaitForScript {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_port")]
+    pub port: String,
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(
+        default = "default_interval_seconds",
+        deserialize_with = "deserialize_interval_seconds"
+    )]
+    pub interval_seconds: u64,
+}
+
+#[async_trait]
+impl ScriptExecutor for WaitForScript {
+    async fn execute(&self, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
+        // Get host with parameter substitution
+        let host = self
+            .host
+            .substitute_parameters(context.parameters, false)?
+            .ok_or("Host is required")?;
+        let host = match host {
+            SubstitutionResult::Single(s) => s,
+            SubstitutionResult::Multiple(_) => {
+                return Err("Host parameter cannot be an array".to_string());
+            }
+        };
+
+        // Get port with parameter substitution
+        let port = self
+            .port
+            .substitute_parameters_typed(context.parameters, false)?
+            .ok_or("Port is required")?;
+        let port = match port {
+            ScriptParameterType::Number(n) => u16::try_from(n).map_err(|e| format!("Invalid port {}: {}", n, e))?,
+            ScriptParameterType::String(s) => s.parse::<u16>().map_err(|e| format!("Invalid port {}: {}", s, e))?,
+            _ => return Err("Port parameter must be a number or a string".to_string()),
+        };
+
+        let address = format!("{}:{}", host, port);
+        context.job_result.add_log(
+            LogLevel::Info,
+            format!("Waiting for {} (timeout: {}s)", address, self.timeout_seconds),
+        );
+        if context.job_result.dry_run {
+            return Ok(());
+        }
+
+        let timeout = Duration::from_secs(self.timeout_seconds);
+        let interval = Duration::from_secs(self.interval_seconds);
+        let started_at = Instant::now();
+        let mut attempt = 1;
+        loop {
+            let remaining = timeout.saturating_sub(started_at.elapsed());
+            let connect_timeout = remaining.max(Duration::from_millis(100));
+            match tokio::time::timeout(connect_timeout, TcpStream::connect(&address)).await {
+                Ok(Ok(_)) => {
+                    context
+                        .job_result
+                        .add_log(LogLevel::Info, format!("{} is reachable", address));
+                    return Ok(());
+                }
+                Ok(Err(e)) => context
+                    .job_result
+                    .add_log(LogLevel::Info, format!("Attempt {}: {} {}", attempt, address, e)),
+                Err(_) => context
+                    .job_result
+                    .add_log(LogLevel::Info, format!("Attempt {}: {} timed out", attempt, address)),
+            }
+
+            if started_at.elapsed() + interval > timeout {
+                return Err(format!(
+                    "Timed out after {}s waiting for {}",
+                    self.timeout_seconds, address
+                ));
+            }
+            attempt += 1;
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_seconds_zero() {
+        let script: WaitForScript = serde_yaml::from_str("host: localhost\nport: 8080\n").unwrap();
+        assert_eq!(script.interval_seconds, 1);
+        let script: WaitForScript = serde_yaml::from_str("host: localhost\nport: 8080\ninterval_seconds: 5\n").unwrap();
+        assert_eq!(script.interval_seconds, 5);
+
+        let err = serde_yaml::from_str::<WaitForScript>("host: localhost\nport: 8080\ninterval_seconds: 0\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("interval_seconds must be at least 1"), "{}", err);
+    }
+}
@@ -13,6 +13,14 @@ pub(crate) trait ParameterSubstitution {
         parameters: &HashMap<String, ScriptParameterType>,
         optional: bool,
     ) -> Result<Option<SubstitutionResult>, String>;
+
+    /// Like `substitute_parameters`, but a pure `$(param)` reference keeps the type of the parameter. Anything
+    /// else is substituted and returned as `ScriptParameterType::String`.
+    fn substitute_parameters_typed(
+        &self,
+        parameters: &HashMap<String, ScriptParameterType>,
+        optional: bool,
+    ) -> Result<Option<ScriptParameterType>, String>;
 }
 
 impl ParameterSubstitution for String {
@@ -49,23 +57,59 @@ impl ParameterSubstitution for String {
                 if let ScriptParameterType::StringArray(arr) = param_value {
                     return Ok(Some(SubstitutionResult::Multiple(arr.clone())));
                 }
+                // A JSON array would be passed as one value where a list may be expected
+                if let ScriptParameterType::Object(serde_json::Value::Array(_)) = param_value {
+                    return Err(format!(
+                        "Parameter '{}' is an object holding an array, use a string-array parameter for lists",
+                        param_name
+                    ));
+                }
             }
 
             // For all other cases, convert to string
-            let value = match param_value {
-                ScriptParameterType::String(s) => s.clone(),
-                ScriptParameterType::Credential(c) => c.clone(),
-                ScriptParameterType::Password(p) => p.clone(),
-                ScriptParameterType::Boolean(b) => b.to_string(),
-                ScriptParameterType::Number(n) => n.to_string(),
-                ScriptParameterType::StringArray(a) => a.join(", "),
-            };
+            let value = parameter_text(param_value);
 
             result = result.replace(full_param_ref, &value);
         }
 
         Ok(Some(SubstitutionResult::Single(result)))
     }
+
+    fn substitute_parameters_typed(
+        &self,
+        parameters: &HashMap<String, ScriptParameterType>,
+        optional: bool,
+    ) -> Result<Option<ScriptParameterType>, String> {
+        // Whole reference: no text around it and no other reference inside
+        if let Some(param_name) = self.strip_prefix("$(").and_then(|s| s.strip_suffix(')')) {
+            if !param_name.contains("$(") && !param_name.contains(')') {
+                return match parameters.get(param_name) {
+                    Some(param_value) => Ok(Some(param_value.clone())),
+                    None if optional => Ok(None),
+                    None => Err(format!("Parameter '{}' not found", param_name)),
+                };
+            }
+        }
+
+        match self.substitute_parameters(parameters, optional)? {
+            Some(SubstitutionResult::Single(s)) => Ok(Some(ScriptParameterType::String(s))),
+            Some(SubstitutionResult::Multiple(a)) => Ok(Some(ScriptParameterType::StringArray(a))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The text of a parameter value embedded in a string.
+pub(crate) fn parameter_text(value: &ScriptParameterType) -> String {
+    match value {
+        ScriptParameterType::String(s) => s.clone(),
+        ScriptParameterType::Credential(c) => c.clone(),
+        ScriptParameterType::Password(p) => p.clone(),
+        ScriptParameterType::Boolean(b) => b.to_string(),
+        ScriptParameterType::Number(n) => n.to_string(),
+        ScriptParameterType::StringArray(a) => a.join(", "),
+        ScriptParameterType::Object(o) => o.to_string(),
+    }
 }
 
 #[cfg(test)]
@@ -135,5 +179,81 @@ mod tests {
             let input = "$(env.VERSION".to_string();
             assert!(input.substitute_parameters(&parameters, false).is_err());
         }
+
+        #[test]
+        fn test_parameter_substitution_typed() {
+            let mut parameters = HashMap::new();
+            parameters.insert("parameters.count".to_string(), ScriptParameterType::Number(3));
+            parameters.insert("parameters.enabled".to_string(), ScriptParameterType::Boolean(true));
+
+            // Whole references keep their type
+            let input = "$(parameters.count)".to_string();
+            assert_eq!(
+                input.substitute_parameters_typed(&parameters, false).unwrap(),
+                Some(ScriptParameterType::Number(3))
+            );
+            let input = "$(parameters.enabled)".to_string();
+            assert_eq!(
+                input.substitute_parameters_typed(&parameters, false).unwrap(),
+                Some(ScriptParameterType::Boolean(true))
+            );
+
+            // Embedded references are stringified
+            let input = "count=$(parameters.count)".to_string();
+            assert_eq!(
+                input.substitute_parameters_typed(&parameters, false).unwrap(),
+                Some(ScriptParameterType::String("count=3".to_string()))
+            );
+            let input = "$(parameters.enabled)-$(parameters.count)".to_string();
+            assert_eq!(
+                input.substitute_parameters_typed(&parameters, false).unwrap(),
+                Some(ScriptParameterType::String("true-3".to_string()))
+            );
+
+            // Missing parameters
+            let input = "$(unknown.param)".to_string();
+            assert_eq!(input.substitute_parameters_typed(&parameters, true).unwrap(), None);
+            assert!(input.substitute_parameters_typed(&parameters, false).is_err());
+        }
+
+        #[test]
+        fn test_object_parameter_substitution() {
+            let mut parameters = HashMap::new();
+            let config = serde_json::json!({ "name": "nomos", "replicas": 2, "tags": ["a", "b"] });
+            parameters.insert(
+                "parameters.config".to_string(),
+                ScriptParameterType::Object(config.clone()),
+            );
+            parameters.insert(
+                "parameters.list".to_string(),
+                ScriptParameterType::Object(serde_json::json!(["a", "b"])),
+            );
+
+            let input = "deploy --config '$(parameters.config)'".to_string();
+            assert_eq!(
+                input.substitute_parameters(&parameters, false).unwrap().unwrap(),
+                SubstitutionResult::Single(
+                    r#"deploy --config '{"name":"nomos","replicas":2,"tags":["a","b"]}'"#.to_string()
+                )
+            );
+            let input = "$(parameters.config)".to_string();
+            assert_eq!(
+                input.substitute_parameters(&parameters, false).unwrap().unwrap(),
+                SubstitutionResult::Single(config.to_string())
+            );
+            assert_eq!(
+                input.substitute_parameters_typed(&parameters, false).unwrap(),
+                Some(ScriptParameterType::Object(config))
+            );
+
+            // Embedded it is plain JSON, but not as a whole value where a list may be expected
+            let input = "echo '$(parameters.list)'".to_string();
+            assert_eq!(
+                input.substitute_parameters(&parameters, false).unwrap().unwrap(),
+                SubstitutionResult::Single(r#"echo '["a","b"]'"#.to_string())
+            );
+            let input = "$(parameters.list)".to_string();
+            assert!(input.substitute_parameters(&parameters, false).is_err());
+        }
     }
 }
@@ -0,0 +1,127 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use axum::{http::HeaderValue, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
+use tracing::Subscriber;
+use tracing_subscriber::{
+    fmt::{
+        self,
+        format::{Format, Json, JsonFields},
+    },
+    registry::LookupSpan,
+};
+
+/// Certificate and private key of the server, PEM files read from `NOMOS_TLS_CERT` and `NOMOS_TLS_KEY`.
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsFiles {
+    /// `None` if neither variable is set, an error if only one of them is.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let var = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+        match (var("NOMOS_TLS_CERT"), var("NOMOS_TLS_KEY")) {
+            (Some(cert), Some(key)) => Ok(Some(TlsFiles {
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+            })),
+            (None, None) => Ok(None),
+            _ => Err("NOMOS_TLS_CERT and NOMOS_TLS_KEY must be set together".to_string()),
+        }
+    }
+}
+
+/// CORS of `NOMOS_ALLOWED_ORIGINS`, see `cors_layer`. Debug builds fall back to any origin.
+pub fn cors_layer_from_env() -> Result<CorsLayer, String> {
+    let origins = std::env::var("NOMOS_ALLOWED_ORIGINS").ok();
+    cors_layer(origins.as_deref(), cfg!(debug_assertions))
+}
+
+/// Allows the comma separated `allowed_origins`, e.g. `https://ci.example.com`, to call the API with the session
+/// cookie. Without origins only the same origin may, or any origin without credentials if `permissive`.
+pub fn cors_layer(allowed_origins: Option<&str>, permissive: bool) -> Result<CorsLayer, String> {
+    let origins = allowed_origins
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            // Browsers reject a wildcard origin on requests with credentials
+            if origin == "*" || !(origin.starts_with("http://") || origin.starts_with("https://")) {
+                return Err(format!("Invalid origin in NOMOS_ALLOWED_ORIGINS: {:?}", origin));
+            }
+            HeaderValue::from_str(origin.trim_end_matches('/'))
+                .map_err(|_| format!("Invalid origin in NOMOS_ALLOWED_ORIGINS: {:?}", origin))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !origins.is_empty() {
+        Ok(CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods(AllowMethods::mirror_request())
+            .allow_headers(AllowHeaders::mirror_request())
+            .allow_credentials(true))
+    } else if permissive {
+        Ok(CorsLayer::permissive())
+    } else {
+        Ok(CorsLayer::new())
+    }
+}
+
+/// Output of the server's own tracing, read from `NOMOS_LOG_FORMAT`. Job logs are not affected.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LogFormat {
+    /// Human-readable lines of `tracing_subscriber`.
+    #[default]
+    Text,
+    /// See `json_log_layer`.
+    Json,
+}
+
+impl LogFormat {
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(std::env::var("NOMOS_LOG_FORMAT").ok().as_deref())
+    }
+
+    /// `json`, or `text` and unset for the default.
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.map(str::trim).unwrap_or_default() {
+            "" | "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("Unknown NOMOS_LOG_FORMAT {:?}, expected text or json", other)),
+        }
+    }
+}
+
+/// The JSON lines layer of `tracing_subscriber`: one object per event with `timestamp`, `level`, `target`, the
+/// `fields` of the event, including `message`, and the enclosing `span` and `spans` with their fields.
+pub fn json_log_layer<S>() -> fmt::Layer<S, JsonFields, Format<Json>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fmt::layer().json().with_current_span(true).with_span_list(true)
+}
+
+/// Serves `app` on `listener`, over HTTPS with `tls` and plain HTTP otherwise.
+pub async fn serve(listener: std::net::TcpListener, app: Router, tls: Option<TlsFiles>) -> Result<(), String> {
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    match tls {
+        Some(tls) => {
+            let config = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+                .await
+                .map_err(|e| format!("Failed to load TLS certificate {:?}: {}", tls.cert, e))?;
+            axum_server::from_tcp_rustls(listener, config)
+                .serve(service)
+                .await
+                .map_err(|e| e.to_string())
+        }
+        None => {
+            listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+            let listener = tokio::net::TcpListener::from_std(listener).map_err(|e| e.to_string())?;
+            axum::serve(listener, service).await.map_err(|e| e.to_string())
+        }
+    }
+}
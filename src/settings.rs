@@ -1,21 +1,78 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use serde::Deserialize;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     credential::Credential,
-    job::{Job, JobResult},
-    log::LogLevel,
-    script::models::Script,
+    error::NomosError,
+    job::{default_job_results_location, next_job_result_id, Job, JobResult},
+    log::{JobLogger, LogLevel},
+    script::{
+        models::{RunningScriptStep, Script, ScriptStatus},
+        types::GitCloneScript,
+        ScriptExecutionContext, ScriptExecutor,
+    },
+    utils::filter_files,
 };
 
+/// What syncing a credential, script or job did.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncAction {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+/// Ids of the entities a sync touched, by action.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SyncChanges {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl SyncChanges {
+    pub(crate) fn add(&mut self, id: &str, action: SyncAction) {
+        let ids = match action {
+            SyncAction::Created => &mut self.created,
+            SyncAction::Updated => &mut self.updated,
+            SyncAction::Unchanged => &mut self.unchanged,
+        };
+        ids.push(id.to_string());
+    }
+}
+
+/// Machine-readable result of a sync, stored on the job result.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SyncSummary {
+    pub credentials: SyncChanges,
+    pub scripts: SyncChanges,
+    pub jobs: SyncChanges,
+}
+
+/// Glob patterns, relative to the synced directory, limiting which files are synced. Excluded files are neither
+/// synced nor deleted. Exclude takes precedence over include.
+#[derive(Debug, Default)]
+pub struct SyncFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Settings {
     pub credentials: Vec<Credential>,
 }
 
 impl Settings {
-    pub async fn sync(&self, job_result: &mut JobResult) -> Result<(), String> {
+    pub async fn sync(&self, job_result: &mut JobResult) -> Result<SyncChanges, String> {
+        let mut changes = SyncChanges::default();
         let mut credential_ids: Vec<String> = Vec::new();
         for credential in &self.credentials {
             tokio::task::yield_now().await;
@@ -27,10 +84,12 @@ impl Settings {
                 continue;
             }
 
-            let res = credential.sync(&mut job_result.into());
-            if let Err(e) = res {
-                job_result.add_log(LogLevel::Error, format!("Error syncing credential: {:?}", e));
-                continue;
+            match credential.sync(&mut job_result.into()) {
+                Ok(action) => changes.add(&credential.id, action),
+                Err(e) => {
+                    job_result.add_log(LogLevel::Error, format!("Error syncing credential: {}", e));
+                    continue;
+                }
             }
             credential_ids.push(credential.id.clone());
         }
@@ -44,10 +103,11 @@ impl Settings {
                     job_result.add_log(LogLevel::Error, format!("Error deleting credential: {:?}", e));
                     continue;
                 }
+                changes.deleted.push(credential.id.clone());
             }
         }
 
-        Ok(())
+        Ok(changes)
     }
 }
 
@@ -61,17 +121,32 @@ impl TryFrom<PathBuf> for Settings {
     }
 }
 
-pub async fn sync(directory: PathBuf, job_result: &mut JobResult) -> Result<(), String> {
+pub async fn sync(directory: PathBuf, filter: &SyncFilter, job_result: &mut JobResult) -> Result<(), String> {
     if job_result.dry_run {
         job_result.add_log(LogLevel::Info, "Dry run enabled, skipping sync".to_string());
         return Ok(());
     }
 
+    let allowed = if filter.include.is_empty() && filter.exclude.is_empty() {
+        None
+    } else {
+        Some(filter_files(&directory, &filter.include, &filter.exclude)?)
+    };
+    let is_excluded = |path: &Path| {
+        allowed
+            .as_ref()
+            .is_some_and(|allowed| !path.canonicalize().is_ok_and(|path| allowed.contains(&path)))
+    };
+
+    let mut summary = SyncSummary::default();
+
     tokio::task::yield_now().await;
     let settings_path = directory.join("settings.yml");
-    if settings_path.exists() {
+    if settings_path.exists() && is_excluded(&settings_path) {
+        job_result.add_log(LogLevel::Info, "Skipping excluded settings file".to_string());
+    } else if settings_path.exists() {
         let settings = Settings::try_from(settings_path)?;
-        settings.sync(job_result).await?;
+        summary.credentials = settings.sync(job_result).await?;
     } else {
         job_result.add_log(LogLevel::Info, "No settings file found".to_string());
     }
@@ -84,9 +159,17 @@ pub async fn sync(directory: PathBuf, job_result: &mut JobResult) -> Result<(),
             tokio::task::yield_now().await;
             let entry = entry.map_err(|e| e.to_string())?;
             let path = entry.path();
-            match Script::try_from(path) {
-                Ok(script) => match script.sync(job_result.into()) {
-                    Ok(_) => script_ids.push(script.id.clone()),
+            let excluded = is_excluded(&path);
+            match Script::read_with_source(&path) {
+                Ok((script, _)) if excluded => {
+                    job_result.add_log(LogLevel::Info, format!("Skipping excluded script {:?}", script.id));
+                    script_ids.push(script.id.clone());
+                }
+                Ok((script, source)) => match script.sync_source(&source, job_result.into()) {
+                    Ok(action) => {
+                        summary.scripts.add(&script.id, action);
+                        script_ids.push(script.id.clone())
+                    }
                     Err(e) => job_result.add_log(LogLevel::Error, format!("Error syncing script: {:?}", e)),
                 },
                 Err(e) => job_result.add_log(LogLevel::Error, format!("Error creating script: {:?}", e)),
@@ -97,7 +180,10 @@ pub async fn sync(directory: PathBuf, job_result: &mut JobResult) -> Result<(),
             tokio::task::yield_now().await;
             if !script_ids.contains(&script.id) {
                 match script.delete() {
-                    Ok(_) => job_result.add_log(LogLevel::Info, format!("Deleted script {:?}", script.id)),
+                    Ok(_) => {
+                        job_result.add_log(LogLevel::Info, format!("Deleted script {:?}", script.id));
+                        summary.scripts.deleted.push(script.id.clone());
+                    }
                     Err(e) => job_result.add_log(LogLevel::Error, format!("Error deleting script: {:?}", e)),
                 }
             }
@@ -115,14 +201,22 @@ pub async fn sync(directory: PathBuf, job_result: &mut JobResult) -> Result<(),
             tokio::task::yield_now().await;
             let entry = entry.map_err(|e| e.to_string())?;
             let path = entry.path();
+            let excluded = is_excluded(&path);
             match Job::try_from(path) {
+                Ok(job) if excluded => {
+                    job_result.add_log(LogLevel::Info, format!("Skipping excluded job {:?}", job.id));
+                    job_ids.push(job.id.clone());
+                }
                 Ok(job) => {
                     if job.read_only {
                         job_result.add_log(LogLevel::Info, format!("Skipping read-only job {:?}", job.id));
                         continue;
                     }
                     match job.sync(job_result.into()).await {
-                        Ok(_) => job_ids.push(job.id.clone()),
+                        Ok(action) => {
+                            summary.jobs.add(&job.id, action);
+                            job_ids.push(job.id.clone())
+                        }
                         Err(e) => job_result.add_log(LogLevel::Error, format!("Error syncing job: {:?}", e)),
                     }
                 }
@@ -135,7 +229,10 @@ pub async fn sync(directory: PathBuf, job_result: &mut JobResult) -> Result<(),
             tokio::task::yield_now().await;
             if !job_ids.contains(&job.id) && !job.read_only {
                 match job.delete() {
-                    Ok(_) => job_result.add_log(LogLevel::Info, format!("Deleted job {:?}", job.id)),
+                    Ok(_) => {
+                        job_result.add_log(LogLevel::Info, format!("Deleted job {:?}", job.id));
+                        summary.jobs.deleted.push(job.id.clone());
+                    }
                     Err(e) => job_result.add_log(LogLevel::Error, format!("Error deleting job: {:?}", e)),
                 }
             }
@@ -144,5 +241,141 @@ pub async fn sync(directory: PathBuf, job_result: &mut JobResult) -> Result<(),
         job_result.add_log(LogLevel::Info, "No jobs directory found".to_string());
     }
 
+    job_result.sync_summary = Some(summary);
     Ok(())
 }
+
+/// Held while the config repository or a directory is synced. Syncing deletes what it doesn't contain, so a sync
+/// requested through the API must not run at the same time as a scheduled one.
+static SYNC_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(Default::default);
+
+/// Repository holding the settings, scripts and jobs of the server, synced on startup.
+#[derive(Debug, Clone)]
+pub struct ConfigRepository {
+    pub url: String,
+    pub branch: Option<String>,
+    pub credential_id: Option<String>,
+    /// Re-sync interval. Synced only once on startup if `None`.
+    pub interval: Option<Duration>,
+}
+
+impl ConfigRepository {
+    /// Job id of the results recording config repository syncs.
+    pub const JOB_ID: &'static str = "config-repository";
+
+    /// Reads `NOMOS_CONFIG_REPO`, `NOMOS_CONFIG_BRANCH`, `NOMOS_CONFIG_CREDENTIAL_ID` and
+    /// `NOMOS_CONFIG_SYNC_INTERVAL` (seconds). `None` if no repository is configured.
+    pub fn from_env() -> Option<Self> {
+        let url = std::env::var("NOMOS_CONFIG_REPO").ok().filter(|v| !v.is_empty())?;
+        Some(Self {
+            url,
+            branch: std::env::var("NOMOS_CONFIG_BRANCH").ok().filter(|v| !v.is_empty()),
+            credential_id: std::env::var("NOMOS_CONFIG_CREDENTIAL_ID")
+                .ok()
+                .filter(|v| !v.is_empty()),
+            interval: std::env::var("NOMOS_CONFIG_SYNC_INTERVAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|v| *v > 0)
+                .map(Duration::from_secs),
+        })
+    }
+
+    /// Clones the repository into a temporary directory and syncs it. The logs are recorded in a job result under
+    /// `JOB_ID`, whose id is returned. Waits for a sync that is already running.
+    pub async fn sync(&self) -> Result<String, NomosError> {
+        let _sync = SYNC_LOCK.lock().await;
+        let step_name = "Sync config repository".to_string();
+        let mut job_result = start_sync_result(&step_name)?;
+
+        let directory = tempfile::tempdir().map_err(|e| e.to_string())?;
+        let clone = GitCloneScript {
+            url: self.url.clone(),
+            credential_id: self.credential_id.clone(),
+            branch: self.branch.clone(),
+            depth: None,
+            submodules: false,
+        };
+        let mut parameters = HashMap::new();
+        let mut current_working_subdir = None;
+        let mut context = ScriptExecutionContext {
+            parameters: &mut parameters,
+            directory: directory.path(),
+            step_name: &step_name,
+            job_result: &mut job_result,
+            current_working_subdir: &mut current_working_subdir,
+        };
+
+        let res = match clone.execute(&mut context).await {
+            Ok(_) => match current_working_subdir {
+                Some(repository) => sync(repository, &SyncFilter::default(), &mut job_result).await,
+                None => Err("Cloned repository not found".to_string()),
+            },
+            Err(e) => Err(format!("Failed to clone {}: {}", self.url, e)),
+        };
+
+        finish_sync_result(&mut job_result, res)
+    }
+
+    /// Syncs a local directory laid out like the config repository, recorded the same way as `sync`.
+    pub async fn sync_directory(directory: PathBuf) -> Result<String, NomosError> {
+        if !directory.is_dir() {
+            return Err(NomosError::Validation(format!(
+                "Directory does not exist: {:?}",
+                directory
+            )));
+        }
+        let _sync = SYNC_LOCK.lock().await;
+        let mut job_result = start_sync_result("Sync directory")?;
+        let res = sync(directory, &SyncFilter::default(), &mut job_result).await;
+        finish_sync_result(&mut job_result, res)
+    }
+
+    /// Syncs now and then every `interval`. Failures are logged and don't stop later syncs.
+    pub async fn run(self) {
+        loop {
+            match self.sync().await {
+                Ok(id) => eprintln!("Synced config repository {}, result: {}", self.url, id),
+                Err(e) => eprintln!("Failed to sync config repository {}: {}", self.url, e),
+            }
+            match self.interval {
+                Some(interval) => tokio::time::sleep(interval).await,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Job result under `ConfigRepository::JOB_ID` with a single running step.
+fn start_sync_result(step_name: &str) -> Result<JobResult, String> {
+    let id = next_job_result_id()?;
+    let steps = vec![RunningScriptStep {
+        name: step_name.to_string(),
+        ..Default::default()
+    }];
+    let logger = Arc::new(Mutex::new(JobLogger::new(
+        ConfigRepository::JOB_ID.to_string(),
+        id.clone(),
+        false,
+    )?));
+    let mut job_result = JobResult::new(id.clone(), ConfigRepository::JOB_ID.to_string(), steps, logger, false);
+    std::fs::create_dir_all(default_job_results_location()?.join(&id)).map_err(|e| e.to_string())?;
+    job_result.start_step()?;
+    Ok(job_result)
+}
+
+/// Finishes and saves the job result of `start_sync_result`, returning its id if the sync succeeded.
+fn finish_sync_result(job_result: &mut JobResult, res: Result<(), String>) -> Result<String, NomosError> {
+    let status = match &res {
+        Ok(_) => ScriptStatus::Success,
+        Err(e) => {
+            job_result.add_log(LogLevel::Error, e.clone());
+            ScriptStatus::Failed
+        }
+    };
+    job_result.finish_step(status.clone())?;
+    job_result.status = status;
+    job_result.save()?;
+    res.map(|_| job_result.id.clone())
+        .map_err(|e| NomosError::Other(format!("Sync {} failed: {}", job_result.id, e)))
+}
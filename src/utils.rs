@@ -1,36 +1,29 @@
 use std::{
-    io::{BufRead, BufReader},
-    process::{Child, Command, Stdio},
+    collections::HashSet,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
 };
 
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 use sysinfo::{Pid, System};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    task::JoinSet,
+};
 
-use crate::script::ScriptExecutionContext;
+use crate::{error::NomosError, script::ScriptExecutionContext};
 
-use crate::log::LogLevel;
+use crate::log::{max_log_line, LogLevel};
 
 pub async fn execute_command(command: &str, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
-    let child = if cfg!(target_os = "windows") {
-        let mut cmd = Command::new("cmd");
-        cmd.args(["/C", command]);
-        cmd.current_dir(context.directory);
-        cmd.stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| e.to_string())?
-    } else {
-        let mut cmd = Command::new("sh");
-        cmd.arg("-c").arg(command);
-        cmd.current_dir(context.directory);
-        cmd.stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| e.to_string())?
-    };
-
-    execute_script(child, context).await
+    execute_command_in_shell(command, None, vec![], context).await
 }
 
 pub async fn execute_command_with_env(
@@ -38,37 +31,143 @@ pub async fn execute_command_with_env(
     env: Vec<(String, String)>,
     context: &mut ScriptExecutionContext<'_>,
 ) -> Result<(), String> {
-    let child = if cfg!(target_os = "windows") {
-        let mut cmd = Command::new("cmd");
-        cmd.args(["/C", command]).current_dir(context.directory);
-        for (key, value) in env {
-            cmd.env(key, value);
-        }
-        cmd.stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| e.to_string())?
-    } else {
-        let mut cmd = Command::new("sh");
-        cmd.arg("-c").arg(command).current_dir(context.directory);
-        for (key, value) in env {
-            cmd.env(key, value);
+    execute_command_in_shell(command, None, env, context).await
+}
+
+/// Runs the command with `{shell} -c {command}`. Defaults to `sh` (`cmd /C` on Windows) when `shell` is `None`.
+pub async fn execute_command_in_shell(
+    command: &str,
+    shell: Option<&str>,
+    env: Vec<(String, String)>,
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<(), String> {
+    execute_program(shell_args(command, shell), env, context).await
+}
+
+/// Program and arguments running `command` in `shell`, see `execute_command_in_shell`.
+pub fn shell_args(command: &str, shell: Option<&str>) -> Vec<String> {
+    match shell {
+        Some(shell) => vec![shell.to_string(), "-c".to_string(), command.to_string()],
+        None if cfg!(target_os = "windows") => vec!["cmd".to_string(), "/C".to_string(), command.to_string()],
+        None => vec!["sh".to_string(), "-c".to_string(), command.to_string()],
+    }
+}
+
+/// Quotes `value` as one word of a `sh` command.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Runs `args[0]` with the remaining arguments in the directory of the context.
+pub async fn execute_program(
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<(), String> {
+    spawn_program(args, env, None, context).await
+}
+
+/// Like `execute_program`, but writes `input` to the standard input of the process, e.g. to pass a password without
+/// it showing up in the arguments.
+pub async fn execute_program_with_stdin(
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    input: &str,
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<(), String> {
+    spawn_program(args, env, Some(input), context).await
+}
+
+/// Server environment variables kept with `Job::clean_env`. Programs need `PATH` to be found and tools like git, ssh
+/// and docker read their configuration from `HOME`. The others are required by processes on Windows.
+const CLEAN_ENV_VARS: [&str; 7] = ["PATH", "HOME", "SYSTEMROOT", "TEMP", "TMP", "PATHEXT", "USERPROFILE"];
+
+async fn spawn_program(
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    input: Option<&str>,
+    context: &mut ScriptExecutionContext<'_>,
+) -> Result<(), String> {
+    let (program, args) = args.split_first().ok_or("Empty command")?;
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    cmd.current_dir(context.directory);
+    if context.job_result.clean_env {
+        cmd.env_clear();
+        for key in CLEAN_ENV_VARS {
+            if let Some(value) = std::env::var_os(key) {
+                cmd.env(key, value);
+            }
         }
-        cmd.stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| e.to_string())?
-    };
+    }
+    for (key, value) in context.job_result.step_env.iter().cloned().chain(env) {
+        cmd.env(key, value);
+    }
+    if input.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // Dropping stdin closes it, so the process sees the end of the input.
+        stdin.write_all(input.as_bytes()).await.map_err(|e| e.to_string())?;
+    }
 
     execute_script(child, context).await
 }
 
+/// Checks whether `program` is an existing path or can be found in `PATH`.
+pub fn is_program_available(program: &str) -> bool {
+    is_program_in_path(program, std::env::var_os("PATH").as_deref())
+}
+
+/// Like `is_program_available`, but searches `paths` instead of `PATH`.
+pub fn is_program_in_path(program: &str, paths: Option<&std::ffi::OsStr>) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+
+    match paths {
+        Some(paths) => std::env::split_paths(paths).any(|dir| {
+            let candidate = dir.join(program);
+            candidate.is_file() || (cfg!(target_os = "windows") && candidate.with_extension("exe").is_file())
+        }),
+        None => false,
+    }
+}
+
+/// Number of running stdout/stderr readers of child processes, across all jobs.
+pub static OUTPUT_READERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts a reader while it's alive, also when its task is aborted.
+struct OutputReaderGuard;
+
+impl OutputReaderGuard {
+    fn new() -> Self {
+        OUTPUT_READERS.fetch_add(1, Ordering::SeqCst);
+        OutputReaderGuard
+    }
+}
+
+impl Drop for OutputReaderGuard {
+    fn drop(&mut self) {
+        OUTPUT_READERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Background processes started by the step can keep the pipes open after the process exited. Their output is only
+/// read for this long.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
 async fn execute_script(mut child: Child, context: &mut ScriptExecutionContext<'_>) -> Result<(), String> {
-    eprintln!("Child process id: {}", child.id());
-    context
-        .job_result
-        .child_process_ids
-        .push(child.id().try_into().unwrap());
+    let child_id = child.id().ok_or("Process exited before it could be tracked")?;
+    eprintln!("Child process id: {}", child_id);
+    context.job_result.child_process_ids.push(child_id as usize);
     context.job_result.save()?;
     let stdout = child.stdout.take();
     if stdout.is_none() {
@@ -83,45 +182,49 @@ async fn execute_script(mut child: Child, context: &mut ScriptExecutionContext<'
     }
     let stderr = stderr.unwrap();
 
-    let stdout_reader = BufReader::new(stdout);
-    let stderr_reader = BufReader::new(stderr);
-
-    // Spawn a task to handle stdout
+    // Owned by this future: when the job is aborted, dropping the set aborts the readers and closes the pipes.
+    let mut readers = JoinSet::new();
     let job_result_clone = context.job_result.clone();
-    tokio::spawn(async move {
-        for line in stdout_reader.lines().map_while(Result::ok) {
+    readers.spawn(async move {
+        let _guard = OutputReaderGuard::new();
+        let mut reader = BufReader::new(stdout);
+        while let Ok(Some(line)) = read_capped_line(&mut reader, max_log_line()).await {
+            if let Some(captured) = &job_result_clone.captured_output {
+                let mut captured = captured.lock().unwrap_or_else(|e| e.into_inner());
+                // Cut to the budget when the step finishes, whole lines are kept until then for masking
+                if captured.len() <= max_log_line() {
+                    captured.push_str(&line);
+                    captured.push('\n');
+                }
+            }
             if !line.is_empty() {
                 job_result_clone.add_log(LogLevel::Info, line);
             }
         }
     });
-
-    // Spawn a task to handle stderr
     let job_result_clone = context.job_result.clone();
-    tokio::spawn(async move {
-        for line in stderr_reader.lines().map_while(Result::ok) {
+    readers.spawn(async move {
+        let _guard = OutputReaderGuard::new();
+        let mut reader = BufReader::new(stderr);
+        while let Ok(Some(line)) = read_capped_line(&mut reader, max_log_line()).await {
             if !line.is_empty() {
                 job_result_clone.add_log(LogLevel::Error, line);
             }
         }
     });
 
-    loop {
-        let is_child_running = match child.try_wait() {
-            Ok(Some(_)) => false,
-            Ok(None) => true,
-            Err(_) => false,
-        };
-        if !is_child_running {
-            break;
-        }
-
-        tokio::task::yield_now().await;
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    let drained = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, async {
+        while readers.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        eprintln!(
+            "Stopped reading the output of process {}, it is still held open",
+            child_id
+        );
+        readers.shutdown().await;
     }
-    tokio::task::yield_now().await;
-
-    let status = child.wait().map_err(|e| e.to_string())?;
     context.job_result.child_process_ids.pop();
 
     if status.success() {
@@ -131,13 +234,246 @@ async fn execute_script(mut child: Child, context: &mut ScriptExecutionContext<'
     }
 }
 
+/// Reads a line without the line ending. At most `max + 1` bytes are kept, so `add_log` still sees that the line is
+/// too long, and the rest of the line is skipped without buffering it. `None` at the end of the input.
+async fn read_capped_line(reader: &mut (impl AsyncBufRead + Unpin), max: usize) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    if (&mut *reader).take(max as u64 + 1).read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
+    }
+
+    if line.last() == Some(&b'\n') {
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+    } else if line.len() > max {
+        loop {
+            let buffer = reader.fill_buf().await?;
+            if buffer.is_empty() {
+                break;
+            }
+            match buffer.iter().position(|b| *b == b'\n') {
+                Some(i) => {
+                    reader.consume(i + 1);
+                    break;
+                }
+                None => {
+                    let len = buffer.len();
+                    reader.consume(len);
+                }
+            }
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
 type HmacSha256 = Hmac<Sha256>;
-pub fn is_signature_valid(payload: &str, signature: &str, secret: &str) -> Result<bool, String> {
+
+/// GitHub style `sha256=<hex>` HMAC of the payload.
+pub fn compute_signature(payload: &str, secret: &str) -> Result<String, String> {
     let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).map_err(|e| e.to_string())?;
     mac.update(payload.as_bytes());
     let result = mac.finalize();
-    let result = format!("sha256={}", hex::encode(result.into_bytes()));
-    Ok(result == signature)
+    Ok(format!("sha256={}", hex::encode(result.into_bytes())))
+}
+
+/// Checks a `sha256=<hex>` signature of `compute_signature` in constant time.
+pub fn is_signature_valid(payload: &str, signature: &str, secret: &str) -> Result<bool, String> {
+    let Some(Ok(expected)) = signature.strip_prefix("sha256=").map(hex::decode) else {
+        return Ok(false);
+    };
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).map_err(|e| e.to_string())?;
+    mac.update(payload.as_bytes());
+    Ok(mac.verify_slice(&expected).is_ok())
+}
+
+/// Absolute link to the page of a job result.
+pub fn result_url(base: &str, id: &str) -> String {
+    format!("{}/job-results/{}", base.trim_end_matches('/'), id)
+}
+
+/// Whether `relative` is a relative path without `..`, so joining it to a directory can't leave that directory.
+fn is_relative_inside(relative: &str) -> bool {
+    !Path::new(relative)
+        .components()
+        .any(|c| matches!(c, Component::ParentDir | Component::RootDir | Component::Prefix(_)))
+}
+
+/// Files in `directory` matching `pattern`, e.g. `dist/**/*.js`.
+///
+/// The pattern is relative to `directory` and may not point outside of it. Matches leaving the directory through a
+/// symlink are skipped. Returned paths are canonical.
+pub fn glob_files(directory: &Path, pattern: &str) -> Result<Vec<PathBuf>, String> {
+    if !is_relative_inside(pattern) {
+        return Err(format!("Pattern must stay inside the directory: {}", pattern));
+    }
+
+    let directory = directory.canonicalize().map_err(|e| e.to_string())?;
+    let full_pattern = format!("{}/{}", glob::Pattern::escape(&directory.to_string_lossy()), pattern);
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+
+    let mut files = Vec::new();
+    for entry in glob::glob_with(&full_pattern, options).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?;
+        if !path.is_file() {
+            continue;
+        }
+        let path = path.canonicalize().map_err(|e| e.to_string())?;
+        if path.starts_with(&directory) {
+            files.push(path);
+        } else {
+            eprintln!("Skipping {:?}, it is outside of {:?}", path, directory);
+        }
+    }
+    Ok(files)
+}
+
+/// Joins `relative` to `directory`, rejecting paths that leave it. Unless `must_exist` is false, the path must be
+/// an existing directory and symlinks pointing outside are rejected too.
+pub fn resolve_subdirectory(directory: &Path, relative: &str, must_exist: bool) -> Result<PathBuf, String> {
+    if !is_relative_inside(relative) {
+        return Err(format!("Directory must stay inside the job directory: {}", relative));
+    }
+
+    let path = directory.join(relative);
+    if !must_exist {
+        return Ok(path);
+    }
+    if !path.is_dir() {
+        return Err(format!("Directory does not exist: {}", path.display()));
+    }
+    let canonical = path.canonicalize().map_err(|e| e.to_string())?;
+    if !canonical.starts_with(directory.canonicalize().map_err(|e| e.to_string())?) {
+        return Err(format!("Directory must stay inside the job directory: {}", relative));
+    }
+    Ok(path)
+}
+
+/// Joins the file path `relative` to `directory` like `resolve_subdirectory`. A missing file is returned as is, an
+/// existing one must not be a symlink pointing outside.
+pub fn resolve_file(directory: &Path, relative: &str) -> Result<PathBuf, String> {
+    if !is_relative_inside(relative) {
+        return Err(format!("File must stay inside the job directory: {}", relative));
+    }
+
+    let path = directory.join(relative);
+    if !path.exists() {
+        return Ok(path);
+    }
+    let canonical = path.canonicalize().map_err(|e| e.to_string())?;
+    if !canonical.starts_with(directory.canonicalize().map_err(|e| e.to_string())?) {
+        return Err(format!("File must stay inside the job directory: {}", relative));
+    }
+    Ok(path)
+}
+
+/// Files in `directory` matching any of `include` and none of `exclude`. An empty `include` matches all files.
+pub fn filter_files(directory: &Path, include: &[String], exclude: &[String]) -> Result<HashSet<PathBuf>, String> {
+    let mut files = HashSet::new();
+    if include.is_empty() {
+        files.extend(glob_files(directory, "**/*")?);
+    }
+    for pattern in include {
+        files.extend(glob_files(directory, pattern)?);
+    }
+    for pattern in exclude {
+        for file in glob_files(directory, pattern)? {
+            files.remove(&file);
+        }
+    }
+    Ok(files)
+}
+
+/// Ids of jobs, scripts and credentials are used as file names, so only ASCII letters, digits, `-` and `_` are
+/// allowed.
+pub fn validate_id(id: &str) -> Result<(), NomosError> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(NomosError::Validation(format!(
+            "Invalid id {:?}, only ASCII letters, digits, - and _ are allowed",
+            id
+        )));
+    }
+    Ok(())
+}
+
+/// Path of the `<id>.yml` file of a job, script or credential in `directory`.
+pub fn entity_path(directory: &Path, id: &str) -> Result<PathBuf, NomosError> {
+    validate_id(id)?;
+    Ok(directory.join(format!("{}.yml", id)))
+}
+
+/// Directory of the nomos state, `/var/lib/nomos`, or `%APPDATA%\nomos` on Windows. Created if missing.
+pub fn data_root() -> Result<PathBuf, String> {
+    let path = if cfg!(target_os = "windows") {
+        let appdata = std::env::var("APPDATA").map_err(|e| e.to_string())?;
+        PathBuf::from(appdata).join("nomos")
+    } else {
+        PathBuf::from("/var/lib/nomos")
+    };
+    std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Subdirectory `name` of `data_root`. Created if missing.
+pub fn data_directory(name: &str) -> Result<PathBuf, String> {
+    let path = data_root()?.join(name);
+    std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Directory of temporary files like SSH keys and registry logins, `NOMOS_TMP_DIR` or the temp directory of the
+/// system. Created if missing.
+pub fn tmp_dir() -> Result<PathBuf, String> {
+    let path = std::env::var_os("NOMOS_TMP_DIR")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Working directory of dry runs inside `tmp_dir`. Commands are only logged, so nothing is written to it.
+pub fn dry_run_directory() -> Result<PathBuf, String> {
+    let path = tmp_dir()?.join("dry-run");
+    std::fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// `<path>.gz`
+pub fn gz_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// Compresses `path` to `<path>.gz` and removes the original.
+pub fn gzip_file(path: &Path) -> Result<(), String> {
+    let mut input = File::open(path).map_err(|e| e.to_string())?;
+    let output = File::create(gz_path(path)).map_err(|e| e.to_string())?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    std::io::copy(&mut input, &mut encoder).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())?;
+    std::fs::remove_file(path).map_err(|e| e.to_string())
+}
+
+/// Contents of `path`, or of `<path>.gz` if only the compressed file exists.
+pub fn read_maybe_compressed(path: &Path) -> Result<Vec<u8>, String> {
+    if !path.exists() {
+        let gz_path = gz_path(path);
+        if gz_path.exists() {
+            let file = File::open(&gz_path).map_err(|e| e.to_string())?;
+            let mut bytes = Vec::new();
+            GzDecoder::new(file)
+                .read_to_end(&mut bytes)
+                .map_err(|e| e.to_string())?;
+            return Ok(bytes);
+        }
+    }
+    std::fs::read(path).map_err(|e| e.to_string())
 }
 
 pub fn get_process_recursive(pid: usize) -> Vec<Pid> {
@@ -162,3 +498,168 @@ pub fn get_process_recursive(pid: usize) -> Vec<Pid> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_signature_valid() {
+        let signature = compute_signature("payload", "secret").unwrap();
+        assert!(is_signature_valid("payload", &signature, "secret").unwrap());
+        assert!(!is_signature_valid("payload", &signature, "other-secret").unwrap());
+        assert!(!is_signature_valid("other-payload", &signature, "secret").unwrap());
+        assert!(!is_signature_valid("payload", signature.trim_start_matches("sha256="), "secret").unwrap());
+        assert!(!is_signature_valid("payload", "sha256=not-hex", "secret").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_read_capped_line() {
+        let input = format!("{}\r\nshort\nlast", "a".repeat(100));
+        let mut reader = input.as_bytes();
+
+        assert_eq!(
+            read_capped_line(&mut reader, 10).await.unwrap().unwrap(),
+            "a".repeat(11)
+        );
+        assert_eq!(read_capped_line(&mut reader, 10).await.unwrap().unwrap(), "short");
+        assert_eq!(read_capped_line(&mut reader, 10).await.unwrap().unwrap(), "last");
+        assert_eq!(read_capped_line(&mut reader, 10).await.unwrap(), None);
+
+        let truncated = crate::log::truncate_line("a".repeat(11), 10);
+        assert_eq!(truncated, format!("{}{}", "a".repeat(10), crate::log::TRUNCATED_MARKER));
+        // Cut at a character boundary
+        assert_eq!(
+            crate::log::truncate_line("aéé".to_string(), 2),
+            format!("a{}", crate::log::TRUNCATED_MARKER)
+        );
+    }
+
+    #[test]
+    fn test_result_url() {
+        assert_eq!(
+            result_url("https://ci.example.com", "42"),
+            "https://ci.example.com/job-results/42"
+        );
+        assert_eq!(
+            result_url("https://ci.example.com/", "42"),
+            "https://ci.example.com/job-results/42"
+        );
+        assert_eq!(
+            result_url("https://example.com/nomos/", "42"),
+            "https://example.com/nomos/job-results/42"
+        );
+    }
+
+    fn create_files(paths: &[&str]) -> tempfile::TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        for path in paths {
+            let path = directory.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        directory
+    }
+
+    fn relative(directory: &Path, files: impl IntoIterator<Item = PathBuf>) -> Vec<String> {
+        let directory = directory.canonicalize().unwrap();
+        let mut files: Vec<String> = files
+            .into_iter()
+            .map(|file| file.strip_prefix(&directory).unwrap().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_glob_files_nested() {
+        let directory = create_files(&[
+            "dist/app.js",
+            "dist/vendor/lib.js",
+            "dist/vendor/lib.css",
+            "src/main.js",
+        ]);
+
+        let files = glob_files(directory.path(), "dist/**/*.js").unwrap();
+        assert_eq!(
+            relative(directory.path(), files),
+            vec!["dist/app.js", "dist/vendor/lib.js"]
+        );
+
+        let files = glob_files(directory.path(), "dist/*.js").unwrap();
+        assert_eq!(relative(directory.path(), files), vec!["dist/app.js"]);
+    }
+
+    #[test]
+    fn test_glob_files_outside_directory() {
+        let directory = create_files(&["a.txt"]);
+        assert!(glob_files(directory.path(), "../*").is_err());
+        assert!(glob_files(directory.path(), "dist/../../*").is_err());
+        assert!(glob_files(directory.path(), "/etc/*").is_err());
+    }
+
+    #[test]
+    fn test_is_program_in_path() {
+        let directory = create_files(&["bin/docker"]);
+        let paths = std::env::join_paths([directory.path().join("missing"), directory.path().join("bin")]).unwrap();
+        assert!(is_program_in_path("docker", Some(&paths)));
+        assert!(!is_program_in_path("podman", Some(&paths)));
+        assert!(!is_program_in_path("docker", None));
+    }
+
+    #[test]
+    fn test_resolve_subdirectory() {
+        let directory = create_files(&["app/main.rs"]);
+        assert_eq!(
+            resolve_subdirectory(directory.path(), "app", true).unwrap(),
+            directory.path().join("app")
+        );
+        assert!(resolve_subdirectory(directory.path(), "missing", true).is_err());
+        assert!(resolve_subdirectory(directory.path(), "missing", false).is_ok());
+        assert!(resolve_subdirectory(directory.path(), "app/../..", false).is_err());
+        assert!(resolve_subdirectory(directory.path(), "/tmp", false).is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/tmp", directory.path().join("escape")).unwrap();
+            assert!(resolve_subdirectory(directory.path(), "escape", true).is_err());
+        }
+    }
+
+    #[test]
+    fn test_resolve_file() {
+        let directory = create_files(&["app/.env"]);
+        assert_eq!(
+            resolve_file(directory.path(), "app/.env").unwrap(),
+            directory.path().join("app/.env")
+        );
+        assert!(resolve_file(directory.path(), "missing.env").is_ok());
+        assert!(resolve_file(directory.path(), "../.env").is_err());
+        assert!(resolve_file(directory.path(), "/etc/passwd").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/", directory.path().join("escape.env")).unwrap();
+            assert!(resolve_file(directory.path(), "escape.env").is_err());
+        }
+    }
+
+    #[test]
+    fn test_filter_files_exclude_precedence() {
+        let directory = create_files(&["jobs/deploy.yml", "jobs/test.yml", "scripts/deploy.yml", "settings.yml"]);
+
+        let files = filter_files(
+            directory.path(),
+            &["jobs/*.yml".to_string(), "scripts/*.yml".to_string()],
+            &["**/deploy.yml".to_string()],
+        )
+        .unwrap();
+        assert_eq!(relative(directory.path(), files), vec!["jobs/test.yml"]);
+
+        let files = filter_files(directory.path(), &[], &["jobs/*".to_string()]).unwrap();
+        assert_eq!(
+            relative(directory.path(), files),
+            vec!["scripts/deploy.yml", "settings.yml"]
+        );
+    }
+}
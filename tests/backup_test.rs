@@ -0,0 +1,142 @@
+use flate2::{write::GzEncoder, Compression};
+use nomos_rust::{
+    backup,
+    credential::{Credential, CredentialType, TextCredentialParameter},
+    job::{CleanupPolicy, ConcurrencyPolicy, Job},
+    log::LogLevel,
+    script::{
+        models::{Script, ScriptStep},
+        types::{BashScript, ScriptType},
+    },
+};
+
+fn script() -> Script {
+    Script {
+        id: "backup-script".to_string(),
+        name: "Backup Script".to_string(),
+        parameters: vec![],
+        steps: vec![ScriptStep {
+            name: "Echo".to_string(),
+            timeout_seconds: None,
+            working_directory: None,
+            env_file: None,
+            continue_on_error: false,
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo backup".to_string(),
+                shell: None,
+                directory: None,
+                run_as: None,
+                capture_output_to: None,
+            })],
+        }],
+    }
+}
+
+fn job() -> Job {
+    Job {
+        id: "backup-job".to_string(),
+        name: "Backup Job".to_string(),
+        parameters: vec![],
+        triggers: vec![],
+        script_id: "backup-script".to_string(),
+        read_only: false,
+        enabled: true,
+        cleanup: CleanupPolicy::Never,
+        log_level: LogLevel::Info,
+        concurrency: ConcurrencyPolicy::Allow,
+        clean_env: true,
+        tags: vec![],
+        git_default_branch: None,
+        notifications: vec![],
+    }
+}
+
+fn script_exists(id: &str) -> bool {
+    Script::get_all().unwrap().iter().any(|script| script.id == id)
+}
+
+#[tokio::test]
+async fn export_import_round_trip() {
+    let credential = Credential {
+        id: "backup-credential".to_string(),
+        value: CredentialType::Text(TextCredentialParameter {
+            value: "secret".to_string(),
+        }),
+        read_only: false,
+    };
+    credential.sync(&mut None).unwrap();
+    script().sync(None).unwrap();
+    job().sync(None).await.unwrap();
+
+    let archive = backup::export(false).unwrap();
+    let archive_with_credentials = backup::export(true).unwrap();
+
+    job().delete().unwrap();
+    script().delete().unwrap();
+    credential.delete().unwrap();
+    assert!(Job::get("backup-job").unwrap().is_none());
+    assert!(!script_exists("backup-script"));
+
+    let summary = backup::import(&archive).await.unwrap();
+    assert!(summary.changes.scripts.created.contains(&"backup-script".to_string()));
+    assert!(summary.changes.jobs.created.contains(&"backup-job".to_string()));
+    assert!(summary.changes.credentials.created.is_empty());
+    assert_eq!(Job::get("backup-job").unwrap().unwrap().script_id, "backup-script");
+    assert!(script_exists("backup-script"));
+    assert!(Credential::get("backup-credential", None).unwrap().is_none());
+
+    let summary = backup::import(&archive_with_credentials).await.unwrap();
+    assert!(summary
+        .changes
+        .credentials
+        .created
+        .contains(&"backup-credential".to_string()));
+    assert!(Credential::get("backup-credential", None).unwrap().is_some());
+}
+
+#[tokio::test]
+async fn import_rejects_path_traversal() {
+    let content = b"id: escaped\n";
+    let mut header = tar::Header::new_gnu();
+    // `set_path` refuses `..`, so the name is written directly like a crafted archive would.
+    let name = b"jobs/../../../tmp/escaped.yml";
+    header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name);
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    builder.append(&header, &content[..]).unwrap();
+    let archive = builder.into_inner().unwrap().finish().unwrap();
+
+    let error = backup::import(&archive).await.unwrap_err();
+    assert!(error.contains("Unexpected entry"), "{}", error);
+}
+
+#[tokio::test]
+async fn import_rejects_invalid_ids() {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, id) in [
+        ("jobs/backup-valid-job.yml", "backup-valid-job"),
+        ("jobs/escaped.yml", "../../../tmp/escaped"),
+    ] {
+        let content = serde_yaml::to_string(&Job {
+            id: id.to_string(),
+            ..job()
+        })
+        .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_path(name).unwrap();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, content.as_bytes()).unwrap();
+    }
+    let archive = builder.into_inner().unwrap().finish().unwrap();
+
+    let error = backup::import(&archive).await.unwrap_err();
+    assert!(error.contains("Invalid id"), "{}", error);
+    // Nothing is imported, not even the valid job before it
+    assert!(Job::get("backup-valid-job").unwrap().is_none());
+    assert!(Job::get("../../../tmp/escaped").is_err());
+}
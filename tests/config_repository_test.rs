@@ -0,0 +1,110 @@
+use std::{fs, path::Path, process::Command};
+
+use nomos_rust::{
+    credential::{Credential, CredentialType, SshCredentialParameter},
+    job::{Job, JobResult},
+    script::models::{Script, ScriptStatus},
+    settings::ConfigRepository,
+};
+
+const SETTINGS: &str = r#"
+credentials:
+  - id: config-repository-credential
+    read_only: false
+    value:
+      type: ssh
+      username: git
+      private_key: dummy
+"#;
+
+const SCRIPT: &str = r#"
+id: config-repository-script
+name: Config Repository Script
+parameters: []
+steps:
+  - name: Echo
+    values:
+      - type: bash
+        code: echo "configured"
+"#;
+
+const JOB: &str = r#"
+id: config-repository-job
+name: Config Repository Job
+parameters: []
+triggers:
+  - type: manual
+script_id: config-repository-script
+read_only: false
+"#;
+
+fn git(directory: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args(["-c", "user.name=nomos", "-c", "user.email=nomos@example.com"])
+        .args(args)
+        .current_dir(directory)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[tokio::test]
+async fn config_repository_sync() {
+    let work = tempfile::tempdir().unwrap();
+    fs::write(work.path().join("settings.yml"), SETTINGS).unwrap();
+    fs::create_dir(work.path().join("scripts")).unwrap();
+    fs::write(work.path().join("scripts").join("config-repository-script.yml"), SCRIPT).unwrap();
+    fs::create_dir(work.path().join("jobs")).unwrap();
+    fs::write(work.path().join("jobs").join("config-repository-job.yml"), JOB).unwrap();
+    git(work.path(), &["init", "-b", "main"]);
+    git(work.path(), &["add", "."]);
+    git(work.path(), &["commit", "-m", "Add config"]);
+
+    let remote = tempfile::tempdir().unwrap();
+    let bare = remote.path().join("config.git");
+    git(
+        remote.path(),
+        &["clone", "--bare", work.path().to_str().unwrap(), bare.to_str().unwrap()],
+    );
+
+    // The clone credential has to exist before the first sync, afterwards it is managed by the repository.
+    let credential = Credential {
+        id: "config-repository-credential".to_string(),
+        value: CredentialType::Ssh(SshCredentialParameter {
+            username: "git".to_string(),
+            private_key: "dummy".to_string(),
+        }),
+        read_only: false,
+    };
+    credential.sync(&mut None).unwrap();
+
+    let config_repository = ConfigRepository {
+        url: bare.to_str().unwrap().to_string(),
+        branch: Some("main".to_string()),
+        credential_id: Some(credential.id.clone()),
+        interval: None,
+    };
+    let result_id = config_repository.sync().await.unwrap();
+
+    let result = JobResult::get(&result_id).unwrap().unwrap();
+    assert_eq!(result.job_id, ConfigRepository::JOB_ID);
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert!(Credential::get("config-repository-credential", None).unwrap().is_some());
+    assert!(Script::get_all()
+        .unwrap()
+        .iter()
+        .any(|script| script.id == "config-repository-script"));
+    assert!(Job::get("config-repository-job").unwrap().is_some());
+}
+
+#[tokio::test]
+async fn config_repository_clone_failure() {
+    let missing = tempfile::tempdir().unwrap();
+    let config_repository = ConfigRepository {
+        url: missing.path().join("missing.git").to_str().unwrap().to_string(),
+        branch: None,
+        credential_id: None,
+        interval: None,
+    };
+    assert!(config_repository.sync().await.is_err());
+}
@@ -1,6 +1,12 @@
 use std::path::PathBuf;
 
-use nomos_rust::credential::{Credential, CredentialType};
+use nomos_rust::{
+    credential::{
+        default_credentials_location, sign_file, sign_unsigned_files, Credential, CredentialType,
+        TextCredentialParameter,
+    },
+    error::NomosError,
+};
 
 #[test]
 fn read_yml() {
@@ -15,3 +21,109 @@ fn read_yml() {
     }
     assert!(!credential.read_only);
 }
+
+#[test]
+fn signed_file() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("signed-credential.yml");
+    std::fs::copy("tests/credentials/test-credential.yml", &path).unwrap();
+
+    // Unsigned files are only accepted without a key
+    assert!(Credential::load(&path, None).is_ok());
+    assert!(matches!(
+        Credential::load(&path, Some("secret")),
+        Err(NomosError::Credential(_))
+    ));
+
+    sign_file(&path, "secret").unwrap();
+    let credential = Credential::load(&path, Some("secret")).unwrap();
+    assert_eq!(credential.id, "test-credential");
+    assert!(Credential::load(&path, Some("other-secret")).is_err());
+
+    let mut content = std::fs::read(&path).unwrap();
+    let last = content.len() - 2;
+    content[last] ^= 1;
+    std::fs::write(&path, content).unwrap();
+    assert!(matches!(
+        Credential::load(&path, Some("secret")),
+        Err(NomosError::Credential(_))
+    ));
+}
+
+#[test]
+fn sign_unsigned() {
+    let directory = tempfile::tempdir().unwrap();
+    let unsigned = directory.path().join("unsigned-credential.yml");
+    let modified = directory.path().join("modified-credential.yml");
+    for path in [&unsigned, &modified] {
+        std::fs::copy("tests/credentials/test-credential.yml", path).unwrap();
+    }
+    sign_file(&modified, "secret").unwrap();
+    std::fs::write(&modified, "id: modified-credential\n").unwrap();
+
+    assert_eq!(
+        sign_unsigned_files(directory.path(), "secret").unwrap(),
+        vec![unsigned.clone()]
+    );
+    assert!(Credential::load(&unsigned, Some("secret")).is_ok());
+    // Existing signatures are kept, so a modified file stays rejected
+    assert!(Credential::load(&modified, Some("secret")).is_err());
+    assert!(sign_unsigned_files(directory.path(), "secret").unwrap().is_empty());
+}
+
+#[test]
+fn get_all_sorted_by_id() {
+    for id in ["sorted-credential-c", "sorted-credential-a", "sorted-credential-b"] {
+        let credential = Credential {
+            id: id.to_string(),
+            value: CredentialType::Text(TextCredentialParameter {
+                value: "value".to_string(),
+            }),
+            read_only: false,
+        };
+        credential.sync(&mut None).unwrap();
+    }
+
+    let ids = Credential::get_all()
+        .unwrap()
+        .into_iter()
+        .map(|c| c.id)
+        .collect::<Vec<_>>();
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+    let created = ids
+        .iter()
+        .filter(|id| id.starts_with("sorted-credential-"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        created,
+        vec!["sorted-credential-a", "sorted-credential-b", "sorted-credential-c"]
+    );
+}
+
+#[test]
+fn broken_last_use() {
+    let credential = Credential {
+        id: "broken-last-use-credential".to_string(),
+        value: CredentialType::Text(TextCredentialParameter {
+            value: "value".to_string(),
+        }),
+        read_only: false,
+    };
+    credential.sync(&mut None).unwrap();
+    let directory = default_credentials_location().unwrap();
+    std::fs::write(directory.join("broken-last-use-credential.used"), "{").unwrap();
+    std::fs::write(directory.join("broken-last-use-credential.used.tmp"), "{").unwrap();
+
+    // Treated as never used
+    assert_eq!(credential.last_use().unwrap(), None);
+    let credentials = Credential::get_all().unwrap();
+    assert_eq!(
+        credentials
+            .iter()
+            .filter(|c| c.id.starts_with("broken-last-use-credential"))
+            .count(),
+        1
+    );
+}
@@ -1,9 +1,22 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 
-use nomos_rust::job::{Job, JobExecutor, JobResult};
+use nomos_rust::credential::{
+    Credential, CredentialType, EnvCredentialParameter, SshCredentialParameter, TextCredentialParameter,
+};
+use nomos_rust::docker::DOCKER_NOT_FOUND;
+use nomos_rust::job::{
+    default_job_results_location, CleanupPolicy, ConcurrencyPolicy, Job, JobExecutor, JobParameterDefinition, JobResult,
+};
+use nomos_rust::log::{max_log_line, JobLogger, LogLevel, TRUNCATED_MARKER};
+use nomos_rust::notification::{NotificationTarget, NotifyOn, WebhookNotification, WebhookPayload};
 use nomos_rust::script::models::{Script, ScriptStatus, ScriptStep};
-use nomos_rust::script::types::{BashScript, ScriptType};
-use nomos_rust::script::ScriptParameterType;
+use nomos_rust::script::types::{
+    docker::{DockerExecScript, DockerPushScript},
+    BashScript, GitCloneScript, GitPullScript, ScriptType, WaitForScript,
+};
+use nomos_rust::script::{ScriptParameter, ScriptParameterType};
+use nomos_rust::utils::{compute_signature, dry_run_directory};
 
 #[test]
 fn read_yml() {
@@ -113,7 +126,9 @@ async fn validation() {
             name: "Test Step".to_string(),
             values: vec![ScriptType::Bash(BashScript {
                 code: "echo $(missing.param)".to_string(),
+                ..Default::default()
             })],
+            ..Default::default()
         }],
         id: "test-script".to_string(),
         name: "Test Script".to_string(),
@@ -122,10 +137,8 @@ async fn validation() {
     let job = Job {
         id: "test-job".to_string(),
         name: "Test Job".to_string(),
-        parameters: vec![],
-        triggers: vec![],
         script_id: "test-script".to_string(),
-        read_only: false,
+        ..Default::default()
     };
     let result = job.validate(Some(&script), Default::default()).await;
     assert!(result.is_err());
@@ -134,3 +147,1815 @@ async fn validation() {
         "Error in step Test Step: Parameter 'missing.param' not found"
     );
 }
+
+#[tokio::test]
+async fn dry_run_with_parameters() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Greet".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo $(parameters.name)".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "dry-run-script".to_string(),
+        name: "Dry Run Script".to_string(),
+        parameters: vec![ScriptParameter {
+            name: "name".to_string(),
+            description: "Name".to_string(),
+            required: true,
+            default: None,
+            pattern: None,
+        }],
+    };
+    let job = Job {
+        id: "dry-run-job".to_string(),
+        name: "Dry Run Job".to_string(),
+        parameters: vec![JobParameterDefinition {
+            name: "name".to_string(),
+            default: None,
+        }],
+        script_id: "dry-run-script".to_string(),
+        ..Default::default()
+    };
+
+    let parameters = HashMap::from([("name".to_string(), ScriptParameterType::String("nomos".to_string()))]);
+    let result = job.dry_run(Some(&script), parameters).await.unwrap();
+    assert!(result.error.is_none());
+    assert_eq!(result.steps.len(), 1);
+    assert_eq!(result.steps[0].status, ScriptStatus::Success);
+
+    let result = job.dry_run(Some(&script), HashMap::new()).await.unwrap();
+    assert_eq!(
+        result.error,
+        Some("Error in step Greet: Parameter 'parameters.name' not found".to_string())
+    );
+    assert_eq!(result.steps[0].status, ScriptStatus::Failed);
+}
+
+#[tokio::test]
+async fn bash_shell() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Bash Step".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "[[ \"nomos\" == n* ]] && echo matched".to_string(),
+                shell: Some("/bin/bash".to_string()),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "bash-shell-script".to_string(),
+        name: "Bash Shell Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "bash-shell-job".to_string(),
+        name: "Bash Shell Job".to_string(),
+        script_id: "bash-shell-script".to_string(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+}
+
+#[tokio::test]
+async fn stop_job() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Sleep".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "sleep 30".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "stop-job-script".to_string(),
+        name: "Stop Job Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "stop-job".to_string(),
+        name: "Stop Job".to_string(),
+        script_id: "stop-job-script".to_string(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result_id = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    job_executor
+        .stop_job(
+            &result_id,
+            "Deploy window closed".to_string(),
+            Some("admin".to_string()),
+        )
+        .await
+        .unwrap();
+
+    let result = JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Aborted);
+    assert_eq!(result.steps[0].status, ScriptStatus::Aborted);
+    let cancellation = result.cancellation.unwrap();
+    assert_eq!(cancellation.reason, "Deploy window closed");
+    assert_eq!(cancellation.cancelled_by, Some("admin".to_string()));
+}
+
+fn wait_for_job(id: &str, port: u16, timeout_seconds: u64) -> (Job, Script) {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Wait For".to_string(),
+            values: vec![ScriptType::WaitFor(WaitForScript {
+                host: "127.0.0.1".to_string(),
+                port: port.to_string(),
+                timeout_seconds,
+                interval_seconds: 1,
+            })],
+            ..Default::default()
+        }],
+        id: format!("{}-script", id),
+        name: "Wait For Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: id.to_string(),
+        name: "Wait For Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    (job, script)
+}
+
+#[tokio::test]
+async fn wait_for_port() {
+    // Reserve a free port, then start listening on it after a delay
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let listener = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+        let _ = listener.accept().await;
+    });
+
+    let (job, script) = wait_for_job("wait-for-port-job", port, 30);
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+    listener.abort();
+}
+
+#[tokio::test]
+async fn wait_for_port_timeout() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let (job, script) = wait_for_job("wait-for-port-timeout-job", port, 2);
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Failed);
+}
+
+/// Creates a local repository with a single commit on main and an SSH credential to clone it with.
+fn create_local_repository(name: &str) -> (tempfile::TempDir, PathBuf, Credential) {
+    let repository = tempfile::tempdir().unwrap();
+    let repository_path = repository.path().join(name);
+    std::fs::create_dir_all(&repository_path).unwrap();
+    for args in [
+        vec!["init", "-b", "main"],
+        vec![
+            "-c",
+            "user.name=nomos",
+            "-c",
+            "user.email=nomos@localhost",
+            "commit",
+            "--allow-empty",
+            "-m",
+            "initial",
+        ],
+    ] {
+        let status = std::process::Command::new("git")
+            .args(&args)
+            .current_dir(&repository_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    let credential = Credential {
+        id: format!("{}-credential", name),
+        value: CredentialType::Ssh(SshCredentialParameter {
+            username: "git".to_string(),
+            private_key: "unused".to_string(),
+        }),
+        read_only: false,
+    };
+    credential.sync(&mut None).unwrap();
+    (repository, repository_path, credential)
+}
+
+#[tokio::test]
+async fn git_clone_records_sha() {
+    let (_repository, repository_path, credential) = create_local_repository("git-clone-sha-repo");
+
+    let script = Script {
+        steps: vec![
+            ScriptStep {
+                name: "Clone".to_string(),
+                values: vec![ScriptType::GitClone(GitCloneScript {
+                    url: repository_path.to_str().unwrap().to_string(),
+                    credential_id: Some(credential.id.clone()),
+                    branch: Some("main".to_string()),
+                    depth: None,
+                    submodules: false,
+                })],
+                ..Default::default()
+            },
+            ScriptStep {
+                name: "Print SHA".to_string(),
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "test -n \"$(steps.Clone.git-clone.sha)\"".to_string(),
+                    ..Default::default()
+                })],
+                ..Default::default()
+            },
+        ],
+        id: "git-clone-sha-script".to_string(),
+        name: "Git Clone SHA Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "git-clone-sha-job".to_string(),
+        name: "Git Clone SHA Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let sha = result.source_refs.get("Clone").unwrap();
+    assert_eq!(sha.len(), 40);
+}
+
+#[tokio::test]
+async fn git_clone_job_default_branch() {
+    let (_repository, repository_path, credential) = create_local_repository("git-default-branch-repo");
+    let status = std::process::Command::new("git")
+        .args(["branch", "develop"])
+        .current_dir(&repository_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let script = Script {
+        steps: vec![
+            ScriptStep {
+                name: "Clone".to_string(),
+                values: vec![ScriptType::GitClone(GitCloneScript {
+                    url: repository_path.to_str().unwrap().to_string(),
+                    credential_id: Some(credential.id.clone()),
+                    branch: None,
+                    depth: None,
+                    submodules: false,
+                })],
+                ..Default::default()
+            },
+            ScriptStep {
+                name: "Check branch".to_string(),
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "test `git rev-parse --abbrev-ref HEAD` = develop".to_string(),
+                    ..Default::default()
+                })],
+                ..Default::default()
+            },
+        ],
+        id: "git-default-branch-script".to_string(),
+        name: "Git Default Branch Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "git-default-branch-job".to_string(),
+        name: "Git Default Branch Job".to_string(),
+        script_id: script.id.clone(),
+        git_default_branch: Some("develop".to_string()),
+        ..Default::default()
+    };
+    let result = JobExecutor::new()
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+}
+
+/// Accepts a single request, answers 200 and returns its lowercased head and its body.
+fn mock_endpoint() -> (String, std::thread::JoinHandle<(String, String)>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hooks/nomos", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        let head_end = loop {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            if let Some(position) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+                break position + 4;
+            }
+        };
+        let head = String::from_utf8(request[..head_end].to_vec()).unwrap().to_lowercase();
+        let content_length: usize = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length: "))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        while request.len() < head_end + content_length {
+            let read = stream.read(&mut buffer).unwrap();
+            request.extend_from_slice(&buffer[..read]);
+        }
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )
+        .unwrap();
+        (head, String::from_utf8(request[head_end..].to_vec()).unwrap())
+    });
+    (url, handle)
+}
+
+#[tokio::test]
+async fn webhook_notification() {
+    let (url, request) = mock_endpoint();
+    let credential = Credential {
+        id: "webhook-notification-secret".to_string(),
+        value: CredentialType::Text(TextCredentialParameter {
+            value: "dashboard-secret".to_string(),
+        }),
+        read_only: false,
+    };
+    credential.sync(&mut None).unwrap();
+
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Echo".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo notify".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "webhook-notification-script".to_string(),
+        name: "Webhook Notification Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "webhook-notification-job".to_string(),
+        name: "Webhook Notification Job".to_string(),
+        script_id: script.id.clone(),
+        notifications: vec![NotificationTarget::Webhook(WebhookNotification {
+            url,
+            credential_id: Some(credential.id.clone()),
+            on: NotifyOn::Always,
+        })],
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+
+    // Dry runs notify nobody, the endpoint only sees the real run
+    let dry_run = job_executor
+        .dry_execute(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    JobResult::wait_for_completion(&dry_run).await.unwrap();
+    let result_id = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let (head, body) = tokio::task::spawn_blocking(move || request.join().unwrap())
+        .await
+        .unwrap();
+    assert!(head.starts_with("post /hooks/nomos "));
+    let signature = compute_signature(&body, "dashboard-secret").unwrap();
+    assert!(
+        head.contains(&format!("x-nomos-signature-256: {}", signature)),
+        "{}",
+        head
+    );
+
+    let payload: WebhookPayload = serde_json::from_str(&body).unwrap();
+    assert_eq!(payload.job_id, job.id);
+    assert_eq!(payload.job_result_id, result_id);
+    assert_eq!(payload.status, ScriptStatus::Success);
+    assert!(payload.duration_ms.is_some());
+    assert_eq!(payload.sha, None);
+}
+
+#[tokio::test]
+async fn credential_last_use() {
+    let (_repository, repository_path, credential) = create_local_repository("credential-last-use-repo");
+    // Forget the last use of previous test runs
+    credential.delete().unwrap();
+    credential.sync(&mut None).unwrap();
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Clone".to_string(),
+            values: vec![ScriptType::GitClone(GitCloneScript {
+                url: repository_path.to_str().unwrap().to_string(),
+                credential_id: Some(credential.id.clone()),
+                branch: Some("main".to_string()),
+                depth: None,
+                submodules: false,
+            })],
+            ..Default::default()
+        }],
+        id: "credential-last-use-script".to_string(),
+        name: "Credential Last Use Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "credential-last-use-job".to_string(),
+        name: "Credential Last Use Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+
+    // Dry runs don't count as a use
+    let result_id = job_executor
+        .dry_execute(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(credential.last_use().unwrap(), None);
+
+    let result_id = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let last_use = credential.last_use().unwrap().unwrap();
+    assert_eq!(last_use.job_id, job.id);
+    assert_eq!(last_use.job_result_id, result_id);
+    assert!(last_use.last_used_at >= result.started_at);
+
+    // Saving the credential again keeps the last use
+    credential.sync(&mut None).unwrap();
+    assert_eq!(credential.last_use().unwrap(), Some(last_use));
+}
+
+async fn run_git_pull(id: &str, clone: bool, credential: &Credential, repository_path: &std::path::Path) -> JobResult {
+    let mut steps = vec![];
+    if clone {
+        steps.push(ScriptStep {
+            name: "Clone".to_string(),
+            values: vec![ScriptType::GitClone(GitCloneScript {
+                url: repository_path.to_str().unwrap().to_string(),
+                credential_id: Some(credential.id.clone()),
+                branch: Some("main".to_string()),
+                depth: None,
+                submodules: false,
+            })],
+            ..Default::default()
+        });
+    }
+    steps.push(ScriptStep {
+        name: "Pull".to_string(),
+        values: vec![ScriptType::GitPull(GitPullScript {
+            directory: None,
+            credential_id: Some(credential.id.clone()),
+            lfs: Some("false".to_string()),
+        })],
+        ..Default::default()
+    });
+    let script = Script {
+        steps,
+        id: format!("{}-script", id),
+        name: "Git Pull Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: id.to_string(),
+        name: "Git Pull Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let result_id = JobExecutor::new()
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    JobResult::wait_for_completion(&result_id).await.unwrap()
+}
+
+#[tokio::test]
+async fn git_pull_cloned_directory() {
+    let (_repository, repository_path, credential) = create_local_repository("git-pull-default-repo");
+
+    let result = run_git_pull("git-pull-default-job", true, &credential, &repository_path).await;
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert_eq!(result.source_refs.get("Pull"), result.source_refs.get("Clone"));
+
+    // Without a git-clone step there is nothing to default to
+    let result = run_git_pull("git-pull-no-clone-job", false, &credential, &repository_path).await;
+    assert_eq!(result.status, ScriptStatus::Failed);
+    let logs = JobLogger::new(result.job_id.clone(), result.id.clone(), true)
+        .unwrap()
+        .get_logs()
+        .unwrap();
+    assert!(logs
+        .iter()
+        .any(|log| log.message.contains("there is no git-clone step before this one")));
+}
+
+#[tokio::test]
+async fn bash_runs_in_cloned_directory() {
+    let (_repository, repository_path, credential) = create_local_repository("bash-cloned-directory-repo");
+
+    let script = Script {
+        steps: vec![
+            ScriptStep {
+                name: "Clone".to_string(),
+                values: vec![ScriptType::GitClone(GitCloneScript {
+                    url: repository_path.to_str().unwrap().to_string(),
+                    credential_id: Some(credential.id.clone()),
+                    branch: Some("main".to_string()),
+                    depth: None,
+                    submodules: false,
+                })],
+                ..Default::default()
+            },
+            ScriptStep {
+                name: "Inside clone".to_string(),
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "test \"`pwd`\" = \"$(steps.Clone.git-clone.directory)\"".to_string(),
+                    ..Default::default()
+                })],
+                ..Default::default()
+            },
+            ScriptStep {
+                name: "Job directory".to_string(),
+                values: vec![ScriptType::Bash(BashScript {
+                    code: "test -d bash-cloned-directory-repo".to_string(),
+                    directory: Some(".".to_string()),
+                    ..Default::default()
+                })],
+                ..Default::default()
+            },
+        ],
+        id: "bash-cloned-directory-script".to_string(),
+        name: "Bash Cloned Directory Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "bash-cloned-directory-job".to_string(),
+        name: "Bash Cloned Directory Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+}
+
+/// Runs a job that creates a file in its working directory, returning the result and whether the file still exists.
+async fn run_with_cleanup(id: &str, cleanup: CleanupPolicy, fail: bool) -> (JobResult, bool) {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Create artifact".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: format!("touch artifact.txt\n{}", if fail { "false" } else { "true" }),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: format!("{}-script", id),
+        name: "Cleanup Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: id.to_string(),
+        name: "Cleanup Job".to_string(),
+        script_id: script.id.clone(),
+        cleanup,
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+
+    let directory = default_job_results_location().unwrap().join(&result.id);
+    assert!(directory.join("result.yml").exists());
+    let artifact_exists = directory.join("artifact.txt").exists();
+    (result, artifact_exists)
+}
+
+#[tokio::test]
+async fn cleanup_never() {
+    let (result, artifact_exists) = run_with_cleanup("cleanup-never-job", CleanupPolicy::Never, false).await;
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert!(artifact_exists);
+}
+
+#[tokio::test]
+async fn cleanup_always() {
+    let (result, artifact_exists) = run_with_cleanup("cleanup-always-job", CleanupPolicy::Always, false).await;
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert!(!artifact_exists);
+
+    let (result, artifact_exists) = run_with_cleanup("cleanup-always-failed-job", CleanupPolicy::Always, true).await;
+    assert_eq!(result.status, ScriptStatus::Failed);
+    assert!(!artifact_exists);
+}
+
+#[tokio::test]
+async fn cleanup_on_success() {
+    let (result, artifact_exists) = run_with_cleanup("cleanup-on-success-job", CleanupPolicy::OnSuccess, false).await;
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert!(!artifact_exists);
+
+    let (result, artifact_exists) =
+        run_with_cleanup("cleanup-on-success-failed-job", CleanupPolicy::OnSuccess, true).await;
+    assert_eq!(result.status, ScriptStatus::Failed);
+    assert!(artifact_exists);
+}
+
+#[tokio::test]
+async fn step_timeout() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Sleep".to_string(),
+            timeout_seconds: Some(2),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "sleep 30".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "step-timeout-script".to_string(),
+        name: "Step Timeout Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "step-timeout-job".to_string(),
+        name: "Step Timeout Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let started_at = std::time::Instant::now();
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+
+    assert!(started_at.elapsed() < std::time::Duration::from_secs(20));
+    assert_ne!(result.status, ScriptStatus::Success);
+    assert_eq!(result.steps[0].status, ScriptStatus::Aborted);
+    assert!(result.child_process_ids.is_empty());
+}
+
+#[tokio::test]
+async fn log_level_threshold() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Log".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo visible-on-info\necho visible-on-error >&2".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "log-level-script".to_string(),
+        name: "Log Level Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "log-level-job".to_string(),
+        name: "Log Level Job".to_string(),
+        script_id: script.id.clone(),
+        log_level: LogLevel::Error,
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let logs = JobLogger::new(job.id.clone(), result.id.clone(), true)
+        .unwrap()
+        .get_logs()
+        .unwrap();
+    assert!(logs.iter().all(|log| log.level == LogLevel::Error));
+    assert!(logs.iter().any(|log| log.message == "visible-on-error"));
+    assert!(!logs.iter().any(|log| log.message == "visible-on-info"));
+}
+
+#[tokio::test]
+async fn long_log_line_truncated() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Log".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "printf '%200000s\\n' x | tr ' ' a\necho after".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "long-log-line-script".to_string(),
+        name: "Long Log Line Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "long-log-line-job".to_string(),
+        name: "Long Log Line Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let logs = JobLogger::new(job.id.clone(), result.id.clone(), true)
+        .unwrap()
+        .get_logs()
+        .unwrap();
+    let long = logs.iter().find(|log| log.message.starts_with("aaa")).unwrap();
+    assert!(long.message.ends_with(TRUNCATED_MARKER));
+    assert_eq!(long.message.len(), max_log_line() + TRUNCATED_MARKER.len());
+    assert!(logs.iter().any(|log| log.message == "after"));
+}
+
+#[tokio::test]
+async fn docker_push_dry_run() {
+    let credential = Credential {
+        id: "docker-push-registry".to_string(),
+        value: CredentialType::Env(EnvCredentialParameter {
+            value: "USERNAME=nomos\nPASSWORD=registry-s3cr3t".to_string(),
+        }),
+        read_only: false,
+    };
+    credential.sync(&mut None).unwrap();
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Push".to_string(),
+            values: vec![ScriptType::DockerPush(DockerPushScript {
+                image: "ghcr.io/nomos-cicd/nomos:$(parameters.tag)".to_string(),
+                registry_credential_id: Some(credential.id.clone()),
+            })],
+            ..Default::default()
+        }],
+        id: "docker-push-script".to_string(),
+        name: "Docker Push Script".to_string(),
+        parameters: vec![ScriptParameter {
+            name: "tag".to_string(),
+            description: "Image tag".to_string(),
+            default: Some(ScriptParameterType::String("1.0".to_string())),
+            required: false,
+            pattern: None,
+        }],
+    };
+    let job = Job {
+        id: "docker-push-job".to_string(),
+        name: "Docker Push Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .dry_execute(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let logs = JobLogger::new(job.id.clone(), result.id.clone(), true)
+        .unwrap()
+        .get_logs()
+        .unwrap();
+    let messages: Vec<&str> = logs.iter().map(|log| log.message.as_str()).collect();
+    assert!(messages.contains(&"command: docker login ghcr.io -u nomos --password-stdin"));
+    assert!(messages.contains(&"command: docker push ghcr.io/nomos-cicd/nomos:1.0"));
+    assert!(!messages.iter().any(|message| message.contains("registry-s3cr3t")));
+}
+
+/// Starts a sleeping job twice with `concurrency` and returns both result ids.
+async fn run_twice(id: &str, concurrency: ConcurrencyPolicy) -> (String, String) {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Sleep".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "sleep 3".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: format!("{}-script", id),
+        name: "Concurrency Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: id.to_string(),
+        name: "Concurrency Job".to_string(),
+        script_id: script.id.clone(),
+        concurrency,
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let first = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    let second = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    (first, second)
+}
+
+#[tokio::test]
+async fn concurrency_allow() {
+    let (first, second) = run_twice("concurrency-allow-job", ConcurrencyPolicy::Allow).await;
+    assert_ne!(first, second);
+
+    let first = JobResult::wait_for_completion(&first).await.unwrap();
+    let second = JobResult::wait_for_completion(&second).await.unwrap();
+    assert_eq!(first.status, ScriptStatus::Success);
+    assert_eq!(second.status, ScriptStatus::Success);
+}
+
+#[tokio::test]
+async fn concurrency_skip_if_running() {
+    let (first, second) = run_twice("concurrency-skip-job", ConcurrencyPolicy::SkipIfRunning).await;
+    assert_eq!(first, second);
+
+    let first = JobResult::wait_for_completion(&first).await.unwrap();
+    assert_eq!(first.status, ScriptStatus::Success);
+}
+
+#[tokio::test]
+async fn concurrency_cancel_previous() {
+    let (first, second) = run_twice("concurrency-cancel-job", ConcurrencyPolicy::CancelPrevious).await;
+    assert_ne!(first, second);
+
+    let first = JobResult::wait_for_completion(&first).await.unwrap();
+    let second = JobResult::wait_for_completion(&second).await.unwrap();
+    assert_eq!(first.status, ScriptStatus::Aborted);
+    assert_eq!(first.cancellation.unwrap().reason, "Cancelled by a newer run");
+    assert_eq!(second.status, ScriptStatus::Success);
+}
+
+#[tokio::test]
+async fn step_working_directory() {
+    let bash_step = |name: &str, working_directory: Option<&str>, code: &str| ScriptStep {
+        name: name.to_string(),
+        working_directory: working_directory.map(str::to_string),
+        values: vec![ScriptType::Bash(BashScript {
+            code: code.to_string(),
+            ..Default::default()
+        })],
+        ..Default::default()
+    };
+    let script = Script {
+        steps: vec![
+            bash_step("Create", None, "mkdir -p services/api services/web"),
+            bash_step("Api", Some("services/api"), "pwd > pwd.txt"),
+            bash_step("Web", Some("services/$(parameters.web)"), "pwd > pwd.txt"),
+        ],
+        id: "step-working-directory-script".to_string(),
+        name: "Step Working Directory Script".to_string(),
+        parameters: vec![ScriptParameter {
+            name: "web".to_string(),
+            description: "Directory of the web service".to_string(),
+            required: true,
+            default: None,
+            pattern: None,
+        }],
+    };
+    let job = Job {
+        id: "step-working-directory-job".to_string(),
+        name: "Step Working Directory Job".to_string(),
+        parameters: vec![JobParameterDefinition {
+            name: "web".to_string(),
+            default: Some(ScriptParameterType::String("web".to_string())),
+        }],
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let directory = default_job_results_location().unwrap().join(&result.id);
+    for service in ["api", "web"] {
+        let service_directory = directory.join("services").join(service);
+        let pwd = std::fs::read_to_string(service_directory.join("pwd.txt")).unwrap();
+        assert_eq!(
+            std::path::Path::new(pwd.trim()).canonicalize().unwrap(),
+            service_directory.canonicalize().unwrap()
+        );
+    }
+}
+
+#[tokio::test]
+async fn compressed_result() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Echo".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo compressed-output".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "compressed-result-script".to_string(),
+        name: "Compressed Result Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "compressed-result-job".to_string(),
+        name: "Compressed Result Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    result.compress().unwrap();
+
+    let directory = default_job_results_location().unwrap().join(&result.id);
+    assert!(!directory.join("result.yml").exists());
+    assert!(directory.join("result.yml.gz").exists());
+
+    let compressed = JobResult::get(&result.id).unwrap().unwrap();
+    assert_eq!(compressed.status, ScriptStatus::Success);
+    assert_eq!(compressed.finished_at, result.finished_at);
+    let logs = compressed.logger.lock().unwrap().get_logs().unwrap();
+    assert!(logs.iter().any(|log| log.message == "compressed-output"));
+    let (raw, offset) = compressed.logger.lock().unwrap().read_raw(0).unwrap();
+    assert_eq!(offset, raw.len() as u64);
+
+    let listed = JobResult::get_all(Some(job.id.clone())).unwrap();
+    assert!(listed.iter().any(|listed| listed.id == result.id));
+}
+
+/// Runs a failing step followed by a succeeding one.
+async fn run_after_failure(id: &str, continue_on_error: bool) -> JobResult {
+    let step = |name: &str, code: &str, continue_on_error: bool| ScriptStep {
+        name: name.to_string(),
+        continue_on_error,
+        values: vec![ScriptType::Bash(BashScript {
+            code: code.to_string(),
+            ..Default::default()
+        })],
+        ..Default::default()
+    };
+    let script = Script {
+        steps: vec![
+            step("Fail", "exit 1", continue_on_error),
+            step("Touch", "touch after-failure", false),
+        ],
+        id: format!("{}-script", id),
+        name: "After Failure Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: id.to_string(),
+        name: "After Failure Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    JobResult::wait_for_completion(&result).await.unwrap()
+}
+
+#[tokio::test]
+async fn continue_on_error() {
+    let result = run_after_failure("continue-on-error-job", true).await;
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert_eq!(result.steps[0].status, ScriptStatus::Failed);
+    assert_eq!(result.steps[1].status, ScriptStatus::Success);
+    let directory = default_job_results_location().unwrap().join(&result.id);
+    assert!(directory.join("after-failure").exists());
+
+    let result = run_after_failure("stop-on-error-job", false).await;
+    assert_eq!(result.status, ScriptStatus::Failed);
+    assert_eq!(result.steps[0].status, ScriptStatus::Failed);
+    assert!(result.steps[1].started_at.is_none());
+    let directory = default_job_results_location().unwrap().join(&result.id);
+    assert!(!directory.join("after-failure").exists());
+}
+
+#[tokio::test]
+#[cfg(target_os = "windows")]
+async fn powershell_write_output() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "PowerShell".to_string(),
+            values: vec![ScriptType::PowerShell(nomos_rust::script::types::PowerShellScript {
+                code: "Write-Output \"hello from powershell\"".to_string(),
+                shell: None,
+            })],
+            ..Default::default()
+        }],
+        id: "powershell-script".to_string(),
+        name: "PowerShell Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "powershell-job".to_string(),
+        name: "PowerShell Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let logs = JobLogger::new(job.id.clone(), result.id.clone(), true)
+        .unwrap()
+        .get_logs()
+        .unwrap();
+    assert!(logs.iter().any(|log| log.message == "hello from powershell"));
+}
+
+#[tokio::test]
+async fn queued_run_resumes_after_restart() {
+    let directory = tempfile::tempdir().unwrap();
+    let queue_path = directory.path().join("queue.json");
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Sleep".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "sleep 2".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "queue-script".to_string(),
+        name: "Queue Script".to_string(),
+        parameters: vec![],
+    };
+    script.sync(None).unwrap();
+    let job = Job {
+        id: "queue-job".to_string(),
+        name: "Queue Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    job.sync(None).await.unwrap();
+    let deleted_job = Job {
+        id: "queue-deleted-job".to_string(),
+        ..job.clone()
+    };
+    deleted_job.sync(None).await.unwrap();
+
+    let job_executor = JobExecutor::with_queue(Some(1), Some(queue_path.clone()));
+    let running = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let queued = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let deleted = job_executor
+        .execute_with_script(&deleted_job, Default::default(), &script)
+        .await
+        .unwrap();
+    assert!(!JobResult::get(&running).unwrap().unwrap().queued);
+    assert!(JobResult::get(&queued).unwrap().unwrap().queued);
+    deleted_job.delete().unwrap();
+
+    // A restarted server has nothing running and starts the queued runs in order
+    let restarted = JobExecutor::with_queue(Some(1), Some(queue_path));
+    restarted.resume_queue().await;
+
+    let result = JobResult::wait_for_completion(&queued).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert!(!result.queued);
+    let result = JobResult::wait_for_completion(&deleted).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Failed);
+    let result = JobResult::wait_for_completion(&running).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+}
+
+#[tokio::test]
+async fn queued_secrets_not_persisted() {
+    let directory = tempfile::tempdir().unwrap();
+    let queue_path = directory.path().join("queue.json");
+    let script = Script {
+        id: "queue-secret-script".to_string(),
+        name: "Queue Secret Script".to_string(),
+        parameters: vec![ScriptParameter {
+            name: "token".to_string(),
+            description: "Secret token".to_string(),
+            required: true,
+            default: None,
+            pattern: None,
+        }],
+        steps: vec![ScriptStep {
+            name: "Write token".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo $(parameters.token) > token.txt && sleep 1".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+    };
+    script.sync(None).unwrap();
+    let job = Job {
+        id: "queue-secret-job".to_string(),
+        name: "Queue Secret Job".to_string(),
+        parameters: vec![JobParameterDefinition {
+            name: "token".to_string(),
+            default: Some(ScriptParameterType::Password("default-token".to_string())),
+        }],
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    job.sync(None).await.unwrap();
+    let token = |value: &str| HashMap::from([("token".to_string(), ScriptParameterType::String(value.to_string()))]);
+    let token_file = |id: &str| {
+        let path = default_job_results_location().unwrap().join(id).join("token.txt");
+        std::fs::read_to_string(path).unwrap()
+    };
+
+    let job_executor = JobExecutor::with_queue(Some(1), Some(queue_path.clone()));
+    let running = job_executor
+        .execute_with_script(&job, token("running-token"), &script)
+        .await
+        .unwrap();
+    let queued = job_executor
+        .execute_with_script(&job, token("queued-token"), &script)
+        .await
+        .unwrap();
+    let persisted = std::fs::read_to_string(&queue_path).unwrap();
+    assert!(!persisted.contains("queued-token"), "{}", persisted);
+    assert!(persisted.contains(r#""secret_parameters":["token"]"#), "{}", persisted);
+
+    // Started by the same server, the secret is still in memory
+    let result = JobResult::wait_for_completion(&queued).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert_eq!(token_file(&queued), "queued-token\n");
+    assert_eq!(token_file(&running), "running-token\n");
+
+    let running = job_executor
+        .execute_with_script(&job, token("running-token"), &script)
+        .await
+        .unwrap();
+    let lost = job_executor
+        .execute_with_script(&job, token("lost-token"), &script)
+        .await
+        .unwrap();
+    // A restarted server doesn't have it
+    let restarted = JobExecutor::with_queue(Some(1), Some(queue_path));
+    restarted.resume_queue().await;
+    let result = JobResult::wait_for_completion(&lost).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Failed);
+    assert!(result
+        .logger
+        .lock()
+        .unwrap()
+        .get_logs()
+        .unwrap()
+        .iter()
+        .any(|log| log.message.contains("secret parameters of the run were lost")));
+    JobResult::wait_for_completion(&running).await.unwrap();
+}
+
+#[tokio::test]
+async fn stop_all_jobs() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Sleep".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "sleep 30".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "stop-all-script".to_string(),
+        name: "Stop All Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "stop-all-job".to_string(),
+        name: "Stop All Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let mut ids = vec![];
+    for _ in 0..3 {
+        ids.push(
+            job_executor
+                .execute_with_script(&job, Default::default(), &script)
+                .await
+                .unwrap(),
+        );
+    }
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let mut stopped = job_executor
+        .stop_all("Incident".to_string(), Some("admin".to_string()))
+        .await
+        .unwrap();
+    stopped.sort();
+    ids.sort();
+    assert_eq!(stopped, ids);
+
+    for id in ids {
+        let result = JobResult::wait_for_completion(&id).await.unwrap();
+        assert_eq!(result.status, ScriptStatus::Aborted);
+        assert_eq!(result.cancellation.unwrap().reason, "Incident");
+    }
+}
+
+#[tokio::test]
+async fn get_all_sorted_by_id() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Echo".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo sorted".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "sorted-job-script".to_string(),
+        name: "Sorted Job Script".to_string(),
+        parameters: vec![],
+    };
+    script.sync(None).unwrap();
+    for id in ["sorted-job-c", "sorted-job-a", "sorted-job-b"] {
+        let job = Job {
+            id: id.to_string(),
+            name: id.to_string(),
+            script_id: script.id.clone(),
+            ..Default::default()
+        };
+        job.sync(None).await.unwrap();
+    }
+
+    let ids = Job::get_all().unwrap().into_iter().map(|j| j.id).collect::<Vec<_>>();
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+    let created = ids
+        .iter()
+        .filter(|id| id.starts_with("sorted-job-"))
+        .collect::<Vec<_>>();
+    assert_eq!(created, vec!["sorted-job-a", "sorted-job-b", "sorted-job-c"]);
+}
+
+#[tokio::test]
+async fn builtin_parameters() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Echo".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo \"result $(nomos.result_id)\"\necho \"job $(nomos.job_id) $(nomos.job_name)\"".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "builtin-parameters-script".to_string(),
+        name: "Builtin Parameters Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "builtin-parameters-job".to_string(),
+        name: "Builtin".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let id = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert_eq!(
+        result.parameters.get("nomos.started_at"),
+        Some(&ScriptParameterType::String(result.started_at.to_rfc3339()))
+    );
+
+    let logs = JobLogger::new(job.id.clone(), id.clone(), true)
+        .unwrap()
+        .get_logs()
+        .unwrap();
+    assert!(logs.iter().any(|log| log.message == format!("result {}", id)));
+    assert!(logs
+        .iter()
+        .any(|log| log.message == "job builtin-parameters-job Builtin"));
+}
+
+#[tokio::test]
+async fn script_without_steps() {
+    let script = Script {
+        steps: vec![],
+        id: "no-steps-script".to_string(),
+        name: "No Steps Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "no-steps-job".to_string(),
+        name: "No Steps Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let error = JobExecutor::new()
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap_err();
+    assert_eq!(error, "Script \"no-steps-script\" has no steps");
+
+    let errors = job.validate_all(Some(&script)).await;
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].message, "Script \"no-steps-script\" has no steps");
+}
+
+#[tokio::test]
+async fn object_parameter() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Echo".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo '$(parameters.config)'".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "object-parameter-script".to_string(),
+        name: "Object Parameter Script".to_string(),
+        parameters: vec![ScriptParameter {
+            name: "config".to_string(),
+            description: "Deployment config".to_string(),
+            required: true,
+            default: None,
+            pattern: None,
+        }],
+    };
+    let job = Job {
+        id: "object-parameter-job".to_string(),
+        name: "Object Parameter Job".to_string(),
+        parameters: vec![JobParameterDefinition {
+            name: "config".to_string(),
+            default: Some(ScriptParameterType::Object(serde_json::json!({
+                "name": "nomos",
+                "replicas": 2,
+            }))),
+        }],
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let id = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    let logs = JobLogger::new(job.id.clone(), id, true).unwrap().get_logs().unwrap();
+    assert!(logs.iter().any(|log| log.message == r#"{"name":"nomos","replicas":2}"#));
+}
+
+async fn run_with_env(id: &str, clean_env: bool) -> Vec<String> {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Env".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo \"secret=${NOMOS_TEST_SERVER_SECRET:-unset}\"\necho \"path=${PATH:+set}\"".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: format!("{}-script", id),
+        name: "Clean Env Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: id.to_string(),
+        name: "Clean Env Job".to_string(),
+        script_id: script.id.clone(),
+        clean_env,
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let result_id = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    JobLogger::new(job.id, result_id, true)
+        .unwrap()
+        .get_logs()
+        .unwrap()
+        .into_iter()
+        .map(|log| log.message)
+        .collect()
+}
+
+#[tokio::test]
+async fn clean_env() {
+    std::env::set_var("NOMOS_TEST_SERVER_SECRET", "visible");
+
+    let logs = run_with_env("clean-env-job", true).await;
+    assert!(logs.contains(&"secret=unset".to_string()));
+    assert!(logs.contains(&"path=set".to_string()));
+
+    let logs = run_with_env("inherited-env-job", false).await;
+    assert!(logs.contains(&"secret=visible".to_string()));
+}
+
+#[tokio::test]
+async fn step_durations() {
+    let bash = |code: &str| {
+        ScriptType::Bash(BashScript {
+            code: code.to_string(),
+            ..Default::default()
+        })
+    };
+    let script = Script {
+        steps: vec![
+            ScriptStep {
+                name: "Two values".to_string(),
+                values: vec![bash("sleep 0.2"), bash("sleep 0.1")],
+                ..Default::default()
+            },
+            ScriptStep {
+                name: "One value".to_string(),
+                values: vec![bash("sleep 0.1")],
+                ..Default::default()
+            },
+        ],
+        id: "step-durations-script".to_string(),
+        name: "Step Durations Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "step-durations-job".to_string(),
+        name: "Step Durations Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let result_id = JobExecutor::new()
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+
+    for step in result.steps.iter() {
+        let duration_ms = step.duration_ms.unwrap();
+        assert_eq!(step.value_durations_ms.len(), step.values.len());
+        assert!(step.value_durations_ms.iter().all(|value| *value >= 100));
+        assert!(step.value_durations_ms.iter().sum::<u64>() <= duration_ms);
+        assert!(step.started_at.unwrap() <= step.finished_at.unwrap());
+    }
+    assert!(result.steps[0].value_durations_ms[0] >= 200);
+    assert!(result.steps[0].finished_at.unwrap() <= result.steps[1].started_at.unwrap());
+}
+
+#[tokio::test]
+async fn password_parameter_redacted() {
+    let script = Script {
+        id: "redacted-password-script".to_string(),
+        name: "Redacted Password Script".to_string(),
+        parameters: vec![ScriptParameter {
+            name: "token".to_string(),
+            description: "Secret token".to_string(),
+            required: true,
+            default: None,
+            pattern: None,
+        }],
+        steps: vec![ScriptStep {
+            name: "Write token".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "echo $(parameters.token) > token.txt".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+    };
+    script.sync(None).unwrap();
+    let job = Job {
+        id: "redacted-password-job".to_string(),
+        name: "Redacted Password Job".to_string(),
+        parameters: vec![JobParameterDefinition {
+            name: "token".to_string(),
+            default: Some(ScriptParameterType::Password("redacted-password-value".to_string())),
+        }],
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    job.sync(None).await.unwrap();
+
+    let executor = JobExecutor::new();
+    // Sent as a string, but declared as a password by the default
+    let parameters = HashMap::from([(
+        "token".to_string(),
+        ScriptParameterType::String("provided-password-value".to_string()),
+    )]);
+    let result_id = executor.execute_with_script(&job, parameters, &script).await.unwrap();
+    let result = JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+    let directory = default_job_results_location().unwrap().join(&result.id);
+    let saved = std::fs::read_to_string(directory.join("result.yml")).unwrap();
+    assert!(!saved.contains("provided-password-value"));
+    assert_eq!(
+        result.parameters.get("parameters.token"),
+        Some(&ScriptParameterType::Password("***".to_string()))
+    );
+
+    // A rerun uses the provided value instead of the placeholder
+    let rerun_id = job.rerun_from(&executor, &result, "Write token").await.unwrap();
+    let rerun = JobResult::wait_for_completion(&rerun_id).await.unwrap();
+    assert_eq!(rerun.status, ScriptStatus::Success);
+    let token = default_job_results_location()
+        .unwrap()
+        .join(&rerun.id)
+        .join("token.txt");
+    assert_eq!(std::fs::read_to_string(token).unwrap(), "provided-password-value\n");
+
+    // Secrets are only kept in memory, e.g. not after a restart
+    let error = job
+        .rerun_from(&JobExecutor::new(), &result, "Write token")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("no longer available"), "{}", error);
+}
+
+async fn run_with_env_file(id: &str, env_file: &str) -> JobResult {
+    let step = |name: &str, env_file: Option<&str>, code: &str| ScriptStep {
+        name: name.to_string(),
+        env_file: env_file.map(str::to_string),
+        values: vec![ScriptType::Bash(BashScript {
+            code: code.to_string(),
+            ..Default::default()
+        })],
+        ..Default::default()
+    };
+    let script = Script {
+        id: format!("{}-script", id),
+        name: "Env File Script".to_string(),
+        parameters: vec![],
+        steps: vec![
+            step(
+                "Write env file",
+                None,
+                "printf '# Checked in\\nexport GREETING=\"hello from env\"\\n' > app.env",
+            ),
+            step("Use env file", Some(env_file), "echo \"$GREETING\" > greeting.txt"),
+        ],
+    };
+    let job = Job {
+        id: id.to_string(),
+        name: "Env File Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let result_id = JobExecutor::new()
+        .execute_with_script(&job, HashMap::new(), &script)
+        .await
+        .unwrap();
+    JobResult::wait_for_completion(&result_id).await.unwrap()
+}
+
+#[tokio::test]
+async fn step_env_file() {
+    let result = run_with_env_file("env-file-job", "app.env").await;
+    assert_eq!(result.status, ScriptStatus::Success);
+    let greeting = default_job_results_location()
+        .unwrap()
+        .join(&result.id)
+        .join("greeting.txt");
+    assert_eq!(std::fs::read_to_string(greeting).unwrap(), "hello from env\n");
+
+    let result = run_with_env_file("env-file-missing-job", "missing.env").await;
+    assert_eq!(result.status, ScriptStatus::Failed);
+    assert_eq!(result.steps[1].status, ScriptStatus::Failed);
+
+    let result = run_with_env_file("env-file-outside-job", "../env-file-job/app.env").await;
+    assert_eq!(result.status, ScriptStatus::Failed);
+    assert_eq!(result.steps[1].status, ScriptStatus::Failed);
+}
+
+#[tokio::test]
+async fn docker_not_installed() {
+    let step = |name: &str, env_file: Option<&str>, value: ScriptType| ScriptStep {
+        name: name.to_string(),
+        env_file: env_file.map(str::to_string),
+        continue_on_error: true,
+        values: vec![value],
+        ..Default::default()
+    };
+    let exec = || {
+        ScriptType::DockerExec(DockerExecScript {
+            container: "app".to_string(),
+            command: "true".to_string(),
+            env_credential_id: None,
+        })
+    };
+    // No docker in an empty directory
+    let script = Script {
+        id: "docker-missing-script".to_string(),
+        name: "Docker Missing Script".to_string(),
+        parameters: vec![],
+        steps: vec![
+            step(
+                "Write env file",
+                None,
+                ScriptType::Bash(BashScript {
+                    code: "mkdir -p empty-bin && echo \"PATH=$PWD/empty-bin\" > docker.env".to_string(),
+                    ..Default::default()
+                }),
+            ),
+            step("Exec", Some("docker.env"), exec()),
+            step("Exec again", Some("docker.env"), exec()),
+        ],
+    };
+    let job = Job {
+        id: "docker-missing-job".to_string(),
+        name: "Docker Missing Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let result_id = JobExecutor::new()
+        .execute_with_script(&job, HashMap::new(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(result.steps[0].status, ScriptStatus::Success);
+    assert_eq!(result.steps[1].status, ScriptStatus::Failed);
+    assert_eq!(result.steps[2].status, ScriptStatus::Failed);
+
+    let logs = JobLogger::new(job.id.clone(), result.id.clone(), false)
+        .unwrap()
+        .get_logs()
+        .unwrap();
+    let errors = logs.iter().filter(|log| log.message.contains(DOCKER_NOT_FOUND)).count();
+    assert_eq!(errors, 2);
+    assert!(!logs.iter().any(|log| log.message.starts_with("command: docker")));
+
+    let dry_run = JobExecutor::new()
+        .dry_execute(&job, HashMap::new(), &script)
+        .await
+        .unwrap();
+    let dry_run = JobResult::wait_for_completion(&dry_run).await.unwrap();
+    assert_eq!(dry_run.steps[1].status, ScriptStatus::Success);
+}
+
+#[tokio::test]
+async fn dry_run_in_tmp_dir() {
+    // Not removed, other tests running meanwhile use it too
+    let tmp_dir = tempfile::tempdir().unwrap().into_path().join("nomos-tmp");
+    std::env::set_var("NOMOS_TMP_DIR", &tmp_dir);
+    let script = Script {
+        id: "dry-run-tmp-dir-script".to_string(),
+        name: "Dry Run Tmp Dir Script".to_string(),
+        parameters: vec![],
+        steps: vec![ScriptStep {
+            name: "Build".to_string(),
+            values: vec![ScriptType::Bash(BashScript {
+                code: "make".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+    };
+    let job = Job {
+        id: "dry-run-tmp-dir-job".to_string(),
+        name: "Dry Run Tmp Dir Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let result = JobExecutor::new().dry_run(&job, &script, HashMap::new()).await.unwrap();
+    assert_eq!(result.error, None);
+    assert!(tmp_dir.join("dry-run").is_dir());
+    assert_eq!(dry_run_directory().unwrap(), tmp_dir.join("dry-run"));
+}
+
+#[tokio::test]
+async fn step_output_summary() {
+    let step = |name: &str, code: &str, capture_output_to: Option<&str>| ScriptStep {
+        name: name.to_string(),
+        values: vec![ScriptType::Bash(BashScript {
+            code: code.to_string(),
+            capture_output_to: capture_output_to.map(str::to_string),
+            ..Default::default()
+        })],
+        ..Default::default()
+    };
+    let script = Script {
+        id: "summary-script".to_string(),
+        name: "Summary Script".to_string(),
+        parameters: vec![],
+        steps: vec![
+            step("Version", "echo v1.2.3", Some("deployed_version")),
+            step(
+                "Deploy",
+                "echo Deployed $(deployed_version)\necho to prod",
+                Some("nomos.summary"),
+            ),
+            step("Large", "yes 0123456789 | head -n 20000", Some("large_output")),
+        ],
+    };
+    let job = Job {
+        id: "summary-job".to_string(),
+        name: "Summary Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let result_id = JobExecutor::new()
+        .execute_with_script(&job, HashMap::new(), &script)
+        .await
+        .unwrap();
+    let result = JobResult::wait_for_completion(&result_id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Success);
+    assert_eq!(result.summary.as_deref(), Some("Deployed v1.2.3\nto prod"));
+    let json = serde_json::to_value(&result).unwrap();
+    assert_eq!(json["summary"], "Deployed v1.2.3\nto prod");
+    // The output is still logged
+    let logs = JobLogger::new(job.id.clone(), result.id.clone(), false)
+        .unwrap()
+        .get_logs()
+        .unwrap();
+    assert!(logs.iter().any(|log| log.message == "to prod"));
+
+    // Cut like a log line
+    let Some(ScriptParameterType::String(large_output)) = result.parameters.get("large_output") else {
+        panic!("Output not captured: {:?}", result.parameters.get("large_output"));
+    };
+    assert_eq!(large_output.len(), max_log_line() + TRUNCATED_MARKER.len());
+    assert!(large_output.ends_with(TRUNCATED_MARKER));
+}
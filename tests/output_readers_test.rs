@@ -0,0 +1,53 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use nomos_rust::job::{Job, JobExecutor, JobResult};
+use nomos_rust::script::models::{Script, ScriptStatus, ScriptStep};
+use nomos_rust::script::types::{BashScript, ScriptType};
+use nomos_rust::utils::OUTPUT_READERS;
+
+async fn wait_for_readers(count: usize) -> bool {
+    for _ in 0..50 {
+        if OUTPUT_READERS.load(Ordering::SeqCst) == count {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+// Its own test binary, so no other job's readers are counted
+#[tokio::test]
+async fn readers_stopped_with_job() {
+    let script = Script {
+        steps: vec![ScriptStep {
+            name: "Sleep".to_string(),
+            // The detached process keeps the output pipes open after the job's processes are killed
+            values: vec![ScriptType::Bash(BashScript {
+                code: "(setsid sleep 20 &)\nsleep 30".to_string(),
+                ..Default::default()
+            })],
+            ..Default::default()
+        }],
+        id: "output-readers-script".to_string(),
+        name: "Output Readers Script".to_string(),
+        parameters: vec![],
+    };
+    let job = Job {
+        id: "output-readers-job".to_string(),
+        name: "Output Readers Job".to_string(),
+        script_id: script.id.clone(),
+        ..Default::default()
+    };
+    let job_executor = JobExecutor::new();
+    let id = job_executor
+        .execute_with_script(&job, Default::default(), &script)
+        .await
+        .unwrap();
+    assert!(wait_for_readers(2).await);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    job_executor.stop_job(&id, "Test".to_string(), None).await.unwrap();
+    let result = JobResult::wait_for_completion(&id).await.unwrap();
+    assert_eq!(result.status, ScriptStatus::Aborted);
+    assert!(wait_for_readers(0).await);
+}
@@ -1,6 +1,9 @@
 use std::path::PathBuf;
 
-use nomos_rust::script::{models::Script, ScriptParameterType};
+use nomos_rust::{
+    script::{default_scripts_location, models::Script, ScriptParameterType},
+    settings::SyncAction,
+};
 
 #[test]
 fn read_yml() {
@@ -15,3 +18,60 @@ fn read_yml() {
     assert_eq!(script.parameters[1].name, "test_param2");
     assert_eq!(script.parameters[1].default, Some(ScriptParameterType::Boolean(true)));
 }
+
+#[test]
+fn get_all_sorted_by_id() {
+    for id in ["sorted-script-c", "sorted-script-a", "sorted-script-b"] {
+        let script = Script {
+            id: id.to_string(),
+            name: id.to_string(),
+            parameters: vec![],
+            steps: vec![],
+        };
+        script.sync(None).unwrap();
+    }
+
+    let ids = Script::get_all().unwrap().into_iter().map(|s| s.id).collect::<Vec<_>>();
+    let mut sorted = ids.clone();
+    sorted.sort();
+    assert_eq!(ids, sorted);
+    let created = ids
+        .iter()
+        .filter(|id| id.starts_with("sorted-script-"))
+        .collect::<Vec<_>>();
+    assert_eq!(created, vec!["sorted-script-a", "sorted-script-b", "sorted-script-c"]);
+}
+
+#[test]
+fn sync_keeps_source() {
+    let source = r#"# Deploys the web frontend
+name: Keep source
+id: keep-source-script
+parameters: []
+steps:
+  - name: Build # no parameters yet
+    values:
+      - type: bash
+        code: echo build
+"#;
+    let path = default_scripts_location().unwrap().join("keep-source-script.yml");
+    let _ = std::fs::remove_file(&path);
+    let script: Script = serde_yaml::from_str(source).unwrap();
+    assert_eq!(script.sync_source(source, None).unwrap(), SyncAction::Created);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), source);
+    let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    let reordered =
+        "id: keep-source-script\nname: Keep source\nparameters: []\nsteps:\n- name: Build\n  values:\n  - code: echo build\n    type: bash\n";
+    let unchanged: Script = serde_yaml::from_str(reordered).unwrap();
+    assert_eq!(unchanged.sync_source(reordered, None).unwrap(), SyncAction::Unchanged);
+    assert_eq!(unchanged.sync(None).unwrap(), SyncAction::Unchanged);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), source);
+    assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+    let changed_source = source.replace("echo build", "echo rebuild");
+    let changed: Script = serde_yaml::from_str(&changed_source).unwrap();
+    assert_eq!(changed.sync_source(&changed_source, None).unwrap(), SyncAction::Updated);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), changed_source);
+}
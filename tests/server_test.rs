@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use axum::{routing, Router};
+use nomos_rust::server::{cors_layer, json_log_layer, serve, LogFormat, TlsFiles};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::{
+    rustls::{pki_types::ServerName, ClientConfig, RootCertStore},
+    TlsConnector,
+};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[tokio::test]
+async fn serves_over_tls() {
+    let certificate = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let directory = tempfile::tempdir().unwrap();
+    let tls = TlsFiles {
+        cert: directory.path().join("cert.pem"),
+        key: directory.path().join("key.pem"),
+    };
+    std::fs::write(&tls.cert, certificate.cert.pem()).unwrap();
+    std::fs::write(&tls.key, certificate.key_pair.serialize_pem()).unwrap();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let app = Router::new().route("/", routing::get(|| async { "hello over tls" }));
+    tokio::spawn(serve(listener, app, Some(tls)));
+
+    let mut roots = RootCertStore::empty();
+    roots.add(certificate.cert.der().clone()).unwrap();
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let stream = tokio::net::TcpStream::connect(address).await.unwrap();
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+    assert!(response.ends_with("hello over tls"));
+
+    // Plain HTTP is not answered on the TLS port
+    let mut plain = tokio::net::TcpStream::connect(address).await.unwrap();
+    plain
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    let _ = plain.read_to_string(&mut response).await;
+    assert!(!response.contains("hello over tls"));
+}
+
+async fn allowed_origin(cors: tower_http::cors::CorsLayer, origin: &str) -> (Option<String>, Option<String>) {
+    use tower::ServiceExt;
+
+    let app = Router::new()
+        .route("/api/jobs", routing::get(|| async { "[]" }))
+        .layer(cors);
+    let request = axum::http::Request::builder()
+        .uri("/api/jobs")
+        .header("origin", origin)
+        .body(axum::body::Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .map(|value: &axum::http::HeaderValue| value.to_str().unwrap().to_string())
+    };
+    (
+        header("access-control-allow-origin"),
+        header("access-control-allow-credentials"),
+    )
+}
+
+#[tokio::test]
+async fn cors_allowed_origins() {
+    let origins = Some("https://ci.example.com, https://dashboard.example.com/");
+    let cors = || cors_layer(origins, true).unwrap();
+    assert_eq!(
+        allowed_origin(cors(), "https://dashboard.example.com").await,
+        (
+            Some("https://dashboard.example.com".to_string()),
+            Some("true".to_string())
+        )
+    );
+    assert_eq!(allowed_origin(cors(), "https://evil.example.com").await.0, None);
+
+    // Without origins, any origin in debug builds and only the same origin in release builds
+    assert_eq!(
+        allowed_origin(cors_layer(None, true).unwrap(), "https://evil.example.com")
+            .await
+            .0,
+        Some("*".to_string())
+    );
+    assert_eq!(
+        allowed_origin(cors_layer(Some(""), false).unwrap(), "https://evil.example.com").await,
+        (None, None)
+    );
+
+    assert!(cors_layer(Some("*"), false).is_err());
+    assert!(cors_layer(Some("ci.example.com"), false).is_err());
+}
+
+#[test]
+fn log_format_from_env_value() {
+    assert_eq!(LogFormat::parse(None).unwrap(), LogFormat::Text);
+    assert_eq!(LogFormat::parse(Some("text")).unwrap(), LogFormat::Text);
+    assert_eq!(LogFormat::parse(Some(" json ")).unwrap(), LogFormat::Json);
+    assert!(LogFormat::parse(Some("yaml")).is_err());
+}
+
+#[test]
+fn json_log_format() {
+    let output = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let writer = output.clone();
+    let subscriber = tracing_subscriber::registry().with(json_log_layer().with_writer(move || WriteTo(writer.clone())));
+    tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("request", method = "GET").entered();
+        tracing::warn!(status = 503, maintenance = true, "Maintenance mode is enabled");
+    });
+
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+    assert_eq!(line["level"], "WARN");
+    assert_eq!(line["target"], "server_test");
+    assert_eq!(line["span"], serde_json::json!({ "name": "request", "method": "GET" }));
+    assert_eq!(
+        line["spans"],
+        serde_json::json!([{ "name": "request", "method": "GET" }])
+    );
+    assert_eq!(
+        line["fields"],
+        serde_json::json!({ "message": "Maintenance mode is enabled", "status": 503, "maintenance": true })
+    );
+    assert!(line["timestamp"].is_string());
+}
+
+struct WriteTo(Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for WriteTo {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
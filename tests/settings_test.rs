@@ -2,9 +2,10 @@ use std::path::PathBuf;
 
 use chrono::Utc;
 use nomos_rust::{
-    job::{Job, JobResult},
+    job::{CleanupPolicy, ConcurrencyPolicy, Job, JobResult},
+    log::LogLevel,
     script::models::{Script, ScriptStatus},
-    settings,
+    settings::{self, SyncFilter},
 };
 
 #[tokio::test]
@@ -17,6 +18,14 @@ async fn sync() {
         read_only: false,
         script_id: "test-script".to_string(),
         triggers: vec![],
+        enabled: true,
+        cleanup: CleanupPolicy::Never,
+        log_level: LogLevel::Info,
+        concurrency: ConcurrencyPolicy::Allow,
+        clean_env: true,
+        tags: vec![],
+        git_default_branch: None,
+        notifications: vec![],
     };
     let script = Script {
         id: "test-script".to_string(),
@@ -26,7 +35,7 @@ async fn sync() {
     };
     let mut job_result = JobResult::try_from((&job, &script, false)).unwrap();
     job_result.save().unwrap(); // Workaround for creating yml file.
-    let res = settings::sync(path, &mut job_result).await;
+    let res = settings::sync(path, &SyncFilter::default(), &mut job_result).await;
     job_result.save().unwrap(); // Workaround for creating yml file.
     assert!(res.is_ok());
     job_result.finished_at = Some(Utc::now());
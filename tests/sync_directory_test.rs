@@ -0,0 +1,64 @@
+use std::fs;
+
+use nomos_rust::{
+    job::{Job, JobResult},
+    script::models::{Script, ScriptStatus},
+    settings::ConfigRepository,
+};
+
+const SCRIPT: &str = r#"
+id: sync-directory-script
+name: Sync Directory Script
+parameters: []
+steps:
+  - name: Echo
+    values:
+      - type: bash
+        code: echo "synced"
+"#;
+
+const JOB: &str = r#"
+id: sync-directory-job
+name: Sync Directory Job
+parameters: []
+triggers:
+  - type: manual
+script_id: sync-directory-script
+read_only: false
+"#;
+
+#[tokio::test]
+async fn sync_directory() {
+    let directory = tempfile::tempdir().unwrap();
+    fs::create_dir(directory.path().join("scripts")).unwrap();
+    fs::write(
+        directory.path().join("scripts").join("sync-directory-script.yml"),
+        SCRIPT,
+    )
+    .unwrap();
+    fs::create_dir(directory.path().join("jobs")).unwrap();
+    fs::write(directory.path().join("jobs").join("sync-directory-job.yml"), JOB).unwrap();
+
+    // A leftover of a previous run would be reported as unchanged
+    if let Some(job) = Job::get("sync-directory-job").unwrap() {
+        job.delete().unwrap();
+    }
+
+    let result_id = ConfigRepository::sync_directory(directory.path().to_path_buf())
+        .await
+        .unwrap();
+
+    let result = JobResult::get(&result_id).unwrap().unwrap();
+    assert_eq!(result.job_id, ConfigRepository::JOB_ID);
+    assert_eq!(result.status, ScriptStatus::Success);
+    let summary = result.sync_summary.unwrap();
+    assert_eq!(summary.jobs.created, vec!["sync-directory-job"]);
+    assert!(Script::get_all()
+        .unwrap()
+        .iter()
+        .any(|script| script.id == "sync-directory-script"));
+    assert!(Job::get("sync-directory-job").unwrap().is_some());
+
+    let missing = directory.path().join("missing");
+    assert!(ConfigRepository::sync_directory(missing).await.is_err());
+}
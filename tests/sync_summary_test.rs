@@ -0,0 +1,111 @@
+use std::fs;
+
+use nomos_rust::{
+    credential::Credential,
+    job::{CleanupPolicy, ConcurrencyPolicy, Job, JobResult},
+    log::LogLevel,
+    script::models::Script,
+    settings::{self, SyncChanges, SyncFilter},
+};
+
+const SETTINGS: &str = r#"
+credentials:
+  - id: sync-summary-credential
+    read_only: false
+    value:
+      type: text
+      value: val
+"#;
+
+const SCRIPT: &str = r#"
+id: sync-summary-script
+name: Sync Summary Script
+parameters: []
+steps:
+  - name: Echo
+    values:
+      - type: bash
+        code: echo "synced"
+"#;
+
+const JOB: &str = r#"
+id: sync-summary-job
+name: Sync Summary Job
+parameters: []
+triggers:
+  - type: manual
+script_id: sync-summary-script
+read_only: false
+"#;
+
+fn ids(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[tokio::test]
+async fn sync_summary() {
+    let directory = tempfile::tempdir().unwrap();
+    fs::write(directory.path().join("settings.yml"), SETTINGS).unwrap();
+    fs::create_dir(directory.path().join("scripts")).unwrap();
+    fs::write(directory.path().join("scripts").join("sync-summary-script.yml"), SCRIPT).unwrap();
+    fs::create_dir(directory.path().join("jobs")).unwrap();
+    fs::write(directory.path().join("jobs").join("sync-summary-job.yml"), JOB).unwrap();
+
+    // Leftovers of previous runs would be reported as unchanged.
+    if let Some(job) = Job::get("sync-summary-job").unwrap() {
+        job.delete().unwrap();
+    }
+    if let Some(credential) = Credential::get("sync-summary-credential", None).unwrap() {
+        credential.delete().unwrap();
+    }
+    let stale_script: Script =
+        serde_yaml::from_str(&SCRIPT.replace("sync-summary-script", "sync-summary-stale")).unwrap();
+    stale_script.sync(None).unwrap();
+    if let Some(script) = Script::get_all()
+        .unwrap()
+        .into_iter()
+        .find(|script| script.id == "sync-summary-script")
+    {
+        script.delete().unwrap();
+    }
+
+    let job = Job {
+        id: "sync-summary-runner".to_string(),
+        name: "Sync Summary Runner".to_string(),
+        parameters: vec![],
+        read_only: false,
+        script_id: stale_script.id.clone(),
+        triggers: vec![],
+        enabled: true,
+        cleanup: CleanupPolicy::Never,
+        log_level: LogLevel::Info,
+        concurrency: ConcurrencyPolicy::Allow,
+        clean_env: true,
+        tags: vec![],
+        git_default_branch: None,
+        notifications: vec![],
+    };
+    let mut job_result = JobResult::try_from((&job, &stale_script, false)).unwrap();
+    settings::sync(directory.path().to_path_buf(), &SyncFilter::default(), &mut job_result)
+        .await
+        .unwrap();
+
+    let summary = job_result.sync_summary.clone().unwrap();
+    assert_eq!(summary.credentials.created, ids(&["sync-summary-credential"]));
+    assert!(summary.credentials.updated.is_empty());
+    assert_eq!(summary.scripts.created, ids(&["sync-summary-script"]));
+    assert!(summary.scripts.deleted.contains(&"sync-summary-stale".to_string()));
+    assert_eq!(summary.jobs.created, ids(&["sync-summary-job"]));
+
+    settings::sync(directory.path().to_path_buf(), &SyncFilter::default(), &mut job_result)
+        .await
+        .unwrap();
+    let summary = job_result.sync_summary.unwrap();
+    let unchanged = |id: &str| SyncChanges {
+        unchanged: ids(&[id]),
+        ..Default::default()
+    };
+    assert_eq!(summary.credentials, unchanged("sync-summary-credential"));
+    assert_eq!(summary.scripts, unchanged("sync-summary-script"));
+    assert_eq!(summary.jobs, unchanged("sync-summary-job"));
+}